@@ -14,6 +14,8 @@ pub struct Article {
     // pub id: ArticleId,
     pub name: String,
     pub price: f32,
+    /// Sales tax rate in percent (if given by Vereinsflieger)
+    pub salestax: Option<f32>,
 }
 
 /// Article lookup table
@@ -21,11 +23,14 @@ pub struct Article {
 /// list of article ids is given on initialization (from static system configuration), while
 /// article information is fetched later from Vereinsflieger.
 #[derive(Debug)]
+#[allow(clippy::struct_field_names)]
 pub struct Articles {
     /// Look up index to article id
     ids: Vec<ArticleId>,
     /// Look up article id to article information
     articles: BTreeMap<ArticleId, Article>,
+    /// Whether article information changed since it was last marked clean
+    dirty: bool,
 }
 
 impl Articles {
@@ -34,21 +39,43 @@ impl Articles {
         Self {
             ids,
             articles: BTreeMap::new(),
+            dirty: false,
         }
     }
 
     /// Clear all article information
     pub fn clear(&mut self) {
-        self.articles.clear();
+        if !self.articles.is_empty() {
+            self.articles.clear();
+            self.dirty = true;
+        }
     }
 
     /// Update article with given article id. Ignores article ids not in list.
-    pub fn update(&mut self, id: &ArticleId, name: String, price: f32) {
+    pub fn update(&mut self, id: &ArticleId, name: String, price: f32, salestax: Option<f32>) {
         if self.ids.contains(id) {
-            self.articles.insert(id.clone(), Article { name, price });
+            let article = Article {
+                name,
+                price,
+                salestax,
+            };
+            if self.articles.get(id) != Some(&article) {
+                self.articles.insert(id.clone(), article);
+                self.dirty = true;
+            }
         }
     }
 
+    /// Returns true if article information changed since it was last marked clean
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Mark article information as clean, e.g. after storing it
+    pub fn mark_clean(&mut self) {
+        self.dirty = false;
+    }
+
     /// Number of ids
     pub fn count_ids(&self) -> usize {
         self.ids.len()
@@ -59,7 +86,9 @@ impl Articles {
         self.articles.len()
     }
 
-    /// Iterate over articles in order given on initialization
+    /// Iterate over articles in order given on initialization. Yields the index of each article
+    /// together with its id, i.e. the index is the one that `id` maps back to the article id.
+    /// Articles without information are skipped, but don't shift the index of other articles.
     pub fn iter(&self) -> impl Iterator<Item = (usize, &ArticleId, &Article)> {
         self.ids
             .iter()
@@ -67,6 +96,22 @@ impl Articles {
             .filter_map(|(idx, id)| self.get(id).map(|article| (idx, id, article)))
     }
 
+    /// Iterate over articles sorted by name. Like `iter`, yields the original index of each
+    /// article, so that a selection still maps to the correct article id. Articles with the same
+    /// name are yielded in order given on initialization.
+    #[allow(dead_code)]
+    pub fn iter_by_name(&self) -> impl Iterator<Item = (usize, &ArticleId, &Article)> {
+        let mut articles: Vec<_> = self.iter().collect();
+        articles.sort_by(|(_, _, a), (_, _, b)| a.name.cmp(&b.name));
+        articles.into_iter()
+    }
+
+    /// Iterate over article ids in order given on initialization (including ids of articles
+    /// without information)
+    pub fn ids(&self) -> impl Iterator<Item = &ArticleId> {
+        self.ids.iter()
+    }
+
     /// Look up id of article at given index
     pub fn id(&self, index: usize) -> Option<&ArticleId> {
         self.ids.get(index)
@@ -81,3 +126,94 @@ impl Articles {
         self.articles.get(id)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn articles() -> Articles {
+        let mut articles =
+            Articles::new(["1", "2", "3", "4"].into_iter().map(String::from).collect());
+        articles.update(&"1".into(), "Water".into(), 1.0, None);
+        articles.update(&"2".into(), "Coffee".into(), 1.5, None);
+        articles.update(&"4".into(), "Beer".into(), 2.0, Some(19.0));
+        articles
+    }
+
+    #[test]
+    fn iter() {
+        let articles = articles();
+        let names: Vec<_> = articles
+            .iter()
+            .map(|(idx, id, article)| (idx, id.as_str(), article.name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            [(0, "1", "Water"), (1, "2", "Coffee"), (3, "4", "Beer")]
+        );
+    }
+
+    #[test]
+    fn iter_by_name() {
+        let articles = articles();
+        let names: Vec<_> = articles
+            .iter_by_name()
+            .map(|(idx, id, article)| (idx, id.as_str(), article.name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            [(3, "4", "Beer"), (1, "2", "Coffee"), (0, "1", "Water")]
+        );
+    }
+
+    #[test]
+    fn iter_by_name_index_maps_to_id() {
+        let articles = articles();
+        for (idx, id, article) in articles.iter_by_name() {
+            assert_eq!(articles.id(idx), Some(id));
+            assert_eq!(articles.get(id), Some(article));
+        }
+    }
+
+    #[test]
+    fn iter_by_name_same_name() {
+        let mut articles = articles();
+        articles.update(&"3".into(), "Coffee".into(), 2.5, None);
+        let ids: Vec<_> = articles
+            .iter_by_name()
+            .map(|(idx, _id, article)| (idx, article.price))
+            .collect();
+        assert_eq!(ids, [(3, 2.0), (1, 1.5), (2, 2.5), (0, 1.0)]);
+    }
+
+    #[test]
+    fn update_marks_dirty() {
+        let mut articles = articles();
+        assert!(articles.is_dirty());
+        articles.mark_clean();
+        assert!(!articles.is_dirty());
+        articles.update(&"1".into(), "Water".into(), 1.2, None);
+        assert!(articles.is_dirty());
+    }
+
+    #[test]
+    fn update_unchanged_keeps_clean() {
+        let mut articles = articles();
+        articles.mark_clean();
+        articles.update(&"1".into(), "Water".into(), 1.0, None);
+        // Ignored article id doesn't change anything either
+        articles.update(&"5".into(), "Juice".into(), 1.0, None);
+        assert!(!articles.is_dirty());
+    }
+
+    #[test]
+    fn clear_marks_dirty() {
+        let mut articles = articles();
+        articles.mark_clean();
+        articles.clear();
+        assert!(articles.is_dirty());
+        articles.mark_clean();
+        articles.clear();
+        assert!(!articles.is_dirty());
+    }
+}
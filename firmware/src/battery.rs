@@ -0,0 +1,198 @@
+use core::sync::atomic::{AtomicU16, Ordering};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+#[cfg(feature = "battery")]
+use {
+    embassy_executor::{task, Spawner},
+    embassy_time::{Duration, Timer},
+    esp_hal::analog::adc::{Adc, AdcConfig, AdcPin, Attenuation},
+    esp_hal::gpio::GpioPin,
+    esp_hal::peripherals::ADC1,
+    log::{debug, warn},
+};
+
+/// Input voltage in millivolts at max ADC reading with 11 dB attenuation
+const ADC_FULL_SCALE_MV: u32 = 2500;
+
+/// Max ADC reading (12 bit)
+const ADC_MAX_COUNT: u16 = 4095;
+
+/// Ratio of the voltage divider between battery and ADC input (two equal resistors)
+const DIVIDER_RATIO: u32 = 2;
+
+/// Battery voltage in millivolts that is considered empty (single lithium cell)
+const EMPTY_MV: u16 = 3300;
+
+/// Battery voltage in millivolts that is considered full (single lithium cell)
+const FULL_MV: u16 = 4200;
+
+/// Battery voltage in millivolts below which the battery is low
+const LOW_MV: u16 = 3500;
+
+/// Battery voltage needs to rise this much above the low threshold before a low battery is
+/// reported again. Prevents repeated reports while the voltage wobbles around the threshold.
+const LOW_HYSTERESIS_MV: u16 = 100;
+
+/// Number of ADC readings that are averaged per measurement
+#[cfg(feature = "battery")]
+const SAMPLES: u32 = 16;
+
+/// How often to measure battery voltage
+#[cfg(feature = "battery")]
+const MEASURE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Battery voltage (in millivolts) of given ADC reading
+#[cfg_attr(not(feature = "battery"), allow(dead_code))]
+fn millivolts(count: u16) -> u16 {
+    let mv = u32::from(count.min(ADC_MAX_COUNT)) * ADC_FULL_SCALE_MV * DIVIDER_RATIO
+        / u32::from(ADC_MAX_COUNT);
+    // Can't overflow, max value is full scale voltage times divider ratio
+    u16::try_from(mv).unwrap_or(u16::MAX)
+}
+
+/// Estimated charge (percentage, 0-100) at given battery voltage (in millivolts)
+fn charge(millivolts: u16) -> u8 {
+    let mv = millivolts.clamp(EMPTY_MV, FULL_MV) - EMPTY_MV;
+    // Can't overflow, value is at most 100
+    u8::try_from(u32::from(mv) * 100 / u32::from(FULL_MV - EMPTY_MV)).unwrap_or(100)
+}
+
+/// Low battery detection
+#[derive(Debug, Default)]
+struct LowBattery {
+    low: bool,
+}
+
+#[cfg_attr(not(feature = "battery"), allow(dead_code))]
+impl LowBattery {
+    /// Update with measured battery voltage (in millivolts). Returns true if the battery just
+    /// became low.
+    fn update(&mut self, millivolts: u16) -> bool {
+        if self.low {
+            if millivolts >= LOW_MV + LOW_HYSTERESIS_MV {
+                self.low = false;
+            }
+            false
+        } else if millivolts < LOW_MV {
+            self.low = true;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Latest measured battery voltage in millivolts (0 if not measured)
+static MILLIVOLTS: AtomicU16 = AtomicU16::new(0);
+
+/// Battery voltage (in millivolts) when the battery became low, until taken for reporting
+static LOW: Signal<CriticalSectionRawMutex, u16> = Signal::new();
+
+/// Latest measured battery voltage in millivolts. Returns `None` if battery monitoring isn't
+/// enabled.
+pub fn voltage() -> Option<u16> {
+    match MILLIVOLTS.load(Ordering::Relaxed) {
+        0 => None,
+        mv => Some(mv),
+    }
+}
+
+/// Estimated charge (percentage, 0-100) of latest measured battery voltage. Returns `None` if
+/// battery monitoring isn't enabled.
+pub fn level() -> Option<u8> {
+    voltage().map(charge)
+}
+
+/// Battery voltage (in millivolts) if the battery became low since the last call
+pub fn take_low() -> Option<u16> {
+    LOW.try_take()
+}
+
+/// Start monitoring battery voltage with a voltage divider connected to the given pin
+#[cfg(feature = "battery")]
+pub fn start(adc: ADC1, pin: GpioPin<4>, spawner: Spawner) {
+    debug!("Battery: Spawning monitor task");
+    let mut config = AdcConfig::new();
+    let pin = config.enable_pin(pin, Attenuation::_11dB);
+    let adc = Adc::new(adc, config);
+    spawner
+        .spawn(monitor(adc, pin))
+        // Panic on failure since failing to spawn a task indicates a serious error
+        .expect("Failed to spawn battery monitor task");
+}
+
+/// Task for periodically measuring battery voltage
+#[cfg(feature = "battery")]
+#[task]
+async fn monitor(mut adc: Adc<'static, ADC1>, mut pin: AdcPin<GpioPin<4>, ADC1>) {
+    let mut low_battery = LowBattery::default();
+    loop {
+        let mut sum = 0;
+        for _ in 0..SAMPLES {
+            // A one-shot conversion only takes a few microseconds
+            let count = loop {
+                if let Ok(count) = adc.read_oneshot(&mut pin) {
+                    break count;
+                }
+            };
+            sum += u32::from(count);
+        }
+        // Can't overflow, average of 12 bit readings
+        let count = u16::try_from(sum / SAMPLES).unwrap_or(ADC_MAX_COUNT);
+        let mv = millivolts(count);
+        debug!("Battery: {}mV ({}%)", mv, charge(mv));
+        // Never store 0, which means not measured
+        MILLIVOLTS.store(mv.max(1), Ordering::Relaxed);
+        if low_battery.update(mv) {
+            warn!("Battery: Low battery, {}mV", mv);
+            LOW.signal(mv);
+        }
+        Timer::after(MEASURE_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adc_count_to_millivolts() {
+        assert_eq!(millivolts(0), 0);
+        assert_eq!(millivolts(2048), 2500);
+        assert_eq!(millivolts(1638), 2000);
+        assert_eq!(millivolts(ADC_MAX_COUNT), 5000);
+        // Out of range readings are clamped
+        assert_eq!(millivolts(u16::MAX), 5000);
+    }
+
+    #[test]
+    fn charge_estimate() {
+        assert_eq!(charge(0), 0);
+        assert_eq!(charge(EMPTY_MV), 0);
+        assert_eq!(charge(3750), 50);
+        assert_eq!(charge(FULL_MV), 100);
+        assert_eq!(charge(5000), 100);
+    }
+
+    #[test]
+    fn low_battery_reported_once() {
+        let mut low_battery = LowBattery::default();
+        assert!(!low_battery.update(3900));
+        assert!(!low_battery.update(LOW_MV));
+        assert!(low_battery.update(LOW_MV - 1));
+        assert!(!low_battery.update(3400));
+        assert!(!low_battery.update(3300));
+    }
+
+    #[test]
+    fn low_battery_hysteresis() {
+        let mut low_battery = LowBattery::default();
+        assert!(low_battery.update(3450));
+        // Wobbling around the threshold doesn't report again
+        assert!(!low_battery.update(3550));
+        assert!(!low_battery.update(3450));
+        // Recovered (e.g. charged), reports again when dropping below threshold
+        assert!(!low_battery.update(3600));
+        assert!(low_battery.update(3450));
+    }
+}
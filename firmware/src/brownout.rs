@@ -0,0 +1,68 @@
+use crate::{display, screen};
+use esp_hal::i2c::master::{Config as I2cConfig, I2c};
+use esp_hal::interrupt::InterruptConfigurable;
+use esp_hal::peripherals::{Peripherals, LPWR};
+use esp_hal::rtc_cntl::Rtc;
+use esp_hal::{handler, reset};
+use esp_println::println;
+use log::debug;
+
+/// Enable brown-out detector
+///
+/// If the supply voltage drops below the (default) threshold, the detector powers down flash
+/// (aborting any write in progress) and the RF circuits to save power. The interrupt handler
+/// then tries to tell the user and restarts the system right away, so code that was interrupted
+/// never continues. Data stored in flash must therefore survive an interrupted write (see the
+/// purchase queue, which alternates between two sectors). Must be called before the RTC watchdog
+/// is moved out of the given `Rtc`.
+pub fn enable(rtc: &mut Rtc) {
+    debug!("Brownout: Enabling brown-out detector");
+    rtc.set_interrupt_handler(interrupt_handler);
+
+    // SAFETY: The brown-out registers aren't used by esp-hal, and the interrupt registers are
+    // only modified for the brown-out bit
+    let rtc_cntl = unsafe { &*LPWR::PTR };
+    rtc_cntl.brown_out().write(|w| unsafe {
+        w.brown_out_int_wait()
+            .bits(0x2)
+            .brown_out_close_flash_ena()
+            .set_bit()
+            .brown_out_pd_rf_ena()
+            .set_bit()
+            .brown_out_cnt_clr()
+            .set_bit()
+            .brown_out_ena()
+            .set_bit()
+    });
+    rtc_cntl
+        .brown_out()
+        .modify(|_, w| w.brown_out_cnt_clr().clear_bit());
+    rtc_cntl
+        .int_clr()
+        .write(|w| w.brown_out().clear_bit_by_one());
+    rtc_cntl.int_ena().modify(|_, w| w.brown_out().set_bit());
+}
+
+/// Brown-out interrupt handler. Shows a message (if power is still sufficient to do so) and
+/// restarts the system.
+#[handler]
+fn interrupt_handler() {
+    // SAFETY: See `enable`. Interrupts are disabled, so that the handler doesn't run again.
+    let rtc_cntl = unsafe { &*LPWR::PTR };
+    rtc_cntl.int_ena().modify(|_, w| w.brown_out().clear_bit());
+    rtc_cntl
+        .int_clr()
+        .write(|w| w.brown_out().clear_bit_by_one());
+    println!("Brown-out detected, restarting...");
+
+    // Like on panic, the display is driven by a new blocking driver since the async one is owned
+    // by the (now interrupted) executor. Any error is ignored since we're restarting anyway.
+    // SAFETY: The system restarts right after, the stolen peripherals are never given back.
+    let peripherals = unsafe { Peripherals::steal() };
+    if let Ok(i2c) = I2c::new(peripherals.I2C0, I2cConfig::default()) {
+        let i2c = i2c.with_sda(peripherals.GPIO9).with_scl(peripherals.GPIO10);
+        display::show_blocking(i2c, &screen::BrownOut);
+    }
+
+    reset::software_reset();
+}
@@ -9,13 +9,27 @@ use esp_hal::peripherals;
 use esp_hal::time::RateExtU32;
 use log::{debug, info};
 
-/// PWM duty cycle to use for tones (percentage, 0-100)
-/// For an active low buzzer, 75% duty cycle means 25% active time.
-/// 50% produces max volume, 100% is off
+/// Default volume to use for tones (percentage, 0-100)
 #[cfg(not(debug_assertions))]
-const TONE_DUTY_CYCLE: u8 = 75;
+pub const DEFAULT_VOLUME: u8 = 50;
 #[cfg(debug_assertions)]
-const TONE_DUTY_CYCLE: u8 = 95;
+pub const DEFAULT_VOLUME: u8 = 10;
+
+/// PWM duty cycle to use for tones of given volume (percentage, 0-100)
+/// For an active low buzzer, 75% duty cycle means 25% active time.
+/// 50% produces max volume, 100% is off
+fn duty_cycle(volume: u8) -> u8 {
+    100 - volume.min(100) / 2
+}
+
+/// Pause between notes of a melody
+const NOTE_GAP: Duration = Duration::from_millis(10);
+
+/// Startup tone, also used for testing the buzzer
+const STARTUP: [(u32, Duration); 1] = [(3136, Duration::from_millis(1000))]; // G7
+
+/// Very short tick played as feedback on keypresses. Short enough to not delay input noticeably.
+const KEY_TICK: [(u32, Duration); 1] = [(3136, Duration::from_millis(15))]; // G7
 
 /// Buzzer error
 #[derive(Debug)]
@@ -47,10 +61,67 @@ impl fmt::Display for Error {
     }
 }
 
+/// Output for playing tones
+trait ToneOutput {
+    /// Start outputting a tone of given frequency
+    fn start(&mut self, frequency: u32) -> Result<(), Error>;
+
+    /// Stop outputting a tone
+    fn stop(&mut self) -> Result<(), Error>;
+
+    /// Wait for given duration
+    async fn wait(&mut self, duration: Duration);
+}
+
+/// Play melody of given notes (frequency and duration) with short gaps in between. A frequency of
+/// 0 is a rest (silence for the given duration). If muted, no tones are output, but the melody's
+/// duration is still waited for, so that timing stays the same.
+async fn play_melody<O: ToneOutput>(
+    output: &mut O,
+    notes: &[(u32, Duration)],
+    muted: bool,
+) -> Result<(), Error> {
+    for (idx, &(frequency, duration)) in notes.iter().enumerate() {
+        if idx > 0 {
+            output.wait(NOTE_GAP).await;
+        }
+        if frequency == 0 || muted {
+            output.wait(duration).await;
+        } else {
+            output.start(frequency)?;
+            output.wait(duration).await;
+            output.stop()?;
+        }
+    }
+    Ok(())
+}
+
+/// Play startup tone. Turns the output off before, which configures PWM timer and channel
+/// without making a sound, so that a failing output is detected even if muted.
+async fn play_startup<O: ToneOutput>(output: &mut O, muted: bool) -> Result<(), Error> {
+    output.stop()?;
+    play_melody(output, &STARTUP, muted).await
+}
+
+/// Play key feedback tick (if enabled)
+async fn play_key_tick<O: ToneOutput>(
+    output: &mut O,
+    enabled: bool,
+    muted: bool,
+) -> Result<(), Error> {
+    if !enabled {
+        return Ok(());
+    }
+    play_melody(output, &KEY_TICK, muted).await
+}
+
 /// Passive buzzer (driven by PWM signal on GPIO)
 pub struct Buzzer<'a> {
     ledc: Ledc<'a>,
     pin: AnyPin,
+    volume: u8,
+    muted: bool,
+    key_feedback: bool,
 }
 
 impl<'a> Buzzer<'a> {
@@ -65,9 +136,34 @@ impl<'a> Buzzer<'a> {
         Self {
             ledc,
             pin: pin.degrade(),
+            volume: DEFAULT_VOLUME,
+            muted: false,
+            key_feedback: false,
         }
     }
 
+    /// Set volume of tones (percentage, 0-100)
+    pub fn set_volume(&mut self, volume: u8) {
+        debug!("Buzzer: Setting volume to {}%", volume);
+        self.volume = volume.min(100);
+    }
+
+    /// Mute or unmute tones. A muted buzzer outputs no tones, but still takes the same time to
+    /// play them.
+    pub fn set_muted(&mut self, muted: bool) {
+        debug!("Buzzer: {}", if muted { "Muted" } else { "Unmuted" });
+        self.muted = muted;
+    }
+
+    /// Enable or disable feedback ticks on keypresses
+    pub fn set_key_feedback(&mut self, key_feedback: bool) {
+        debug!(
+            "Buzzer: Key feedback {}",
+            if key_feedback { "enabled" } else { "disabled" }
+        );
+        self.key_feedback = key_feedback;
+    }
+
     /// Drive the buzzer with a PWM signal of given frequency and duty cycle
     pub fn drive(&mut self, frequency: u32, duty_pct: u8) -> Result<(), Error> {
         // debug!("Buzzer: driving {} Hz at {}%", frequency, duty_pct);
@@ -94,43 +190,269 @@ impl<'a> Buzzer<'a> {
     }
 
     /// Output the given tone for given duration
+    #[allow(dead_code)]
     pub async fn tone(&mut self, frequency: u32, duration: Duration) -> Result<(), Error> {
-        // debug!("Buzzer: playing {} Hz for {}", frequency, duration);
-        self.drive(frequency, TONE_DUTY_CYCLE)?;
-        Timer::after(duration).await;
-        self.off()?;
-        Ok(())
+        self.play(&[(frequency, duration)]).await
+    }
+
+    /// Play melody of given notes (frequency and duration). A frequency of 0 is a rest.
+    pub async fn play(&mut self, notes: &[(u32, Duration)]) -> Result<(), Error> {
+        // debug!("Buzzer: playing {:?}", notes);
+        let muted = self.muted;
+        play_melody(self, notes, muted).await
     }
 
-    /// Output startup/testing tone
+    /// Output startup/testing tone. Fails if the PWM controller can't be configured (also if
+    /// muted).
     pub async fn startup(&mut self) -> Result<(), Error> {
         debug!("Buzzer: Playing startup tone");
-        self.tone(3136, Duration::from_millis(1000)).await // G7
+        let muted = self.muted;
+        play_startup(self, muted).await
     }
 
     /// Output a short confirmation tone
     pub async fn confirm(&mut self) -> Result<(), Error> {
         debug!("Buzzer: Playing confirm tone");
-        self.tone(3136, Duration::from_millis(100)).await // G7
+        self.play(&[
+            (3136, Duration::from_millis(100)), // G7
+        ])
+        .await
+    }
+
+    /// Output a very short tick as feedback for an accepted keypress (if key feedback is enabled)
+    pub async fn key_tick(&mut self) -> Result<(), Error> {
+        let (enabled, muted) = (self.key_feedback, self.muted);
+        play_key_tick(self, enabled, muted).await
     }
 
     /// Output a long denying tone
     pub async fn deny(&mut self) -> Result<(), Error> {
         debug!("Buzzer: Playing deny tone");
-        self.tone(392, Duration::from_millis(500)).await?; // G4
-        Timer::after(Duration::from_millis(1000)).await;
-        Ok(())
+        self.play(&[
+            (392, Duration::from_millis(500)), // G4
+            (0, Duration::from_millis(1000)),
+        ])
+        .await
     }
 
     /// Output an error tone
     pub async fn error(&mut self) -> Result<(), Error> {
         debug!("Buzzer: Playing error tone");
-        self.tone(784, Duration::from_millis(200)).await?; // G5
-        Timer::after(Duration::from_millis(10)).await;
-        self.tone(587, Duration::from_millis(200)).await?; // D5
-        Timer::after(Duration::from_millis(10)).await;
-        self.tone(392, Duration::from_millis(500)).await?; // G4
-        Timer::after(Duration::from_millis(1000)).await;
-        Ok(())
+        self.play(&[
+            (784, Duration::from_millis(200)), // G5
+            (587, Duration::from_millis(200)), // D5
+            (392, Duration::from_millis(500)), // G4
+            (0, Duration::from_millis(1000)),
+        ])
+        .await
+    }
+}
+
+impl ToneOutput for Buzzer<'_> {
+    fn start(&mut self, frequency: u32) -> Result<(), Error> {
+        self.drive(frequency, duty_cycle(self.volume))
+    }
+
+    fn stop(&mut self) -> Result<(), Error> {
+        self.off()
+    }
+
+    async fn wait(&mut self, duration: Duration) {
+        Timer::after(duration).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// Tone output event
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        Start(u32),
+        Stop,
+        Wait(u64),
+    }
+
+    /// Tone output that records a trace of events instead of driving hardware
+    #[derive(Debug, Default)]
+    struct TraceOutput(Vec<Event>);
+
+    /// Tone output that fails like an unconfigurable PWM channel
+    #[derive(Debug, Default)]
+    struct FailingOutput(TraceOutput);
+
+    impl ToneOutput for FailingOutput {
+        fn start(&mut self, frequency: u32) -> Result<(), Error> {
+            self.0.start(frequency)?;
+            Err(Error::Channel(channel::Error::Channel))
+        }
+
+        fn stop(&mut self) -> Result<(), Error> {
+            self.0.stop()?;
+            Err(Error::Channel(channel::Error::Channel))
+        }
+
+        async fn wait(&mut self, duration: Duration) {
+            self.0.wait(duration).await;
+        }
+    }
+
+    impl ToneOutput for TraceOutput {
+        fn start(&mut self, frequency: u32) -> Result<(), Error> {
+            self.0.push(Event::Start(frequency));
+            Ok(())
+        }
+
+        fn stop(&mut self) -> Result<(), Error> {
+            self.0.push(Event::Stop);
+            Ok(())
+        }
+
+        async fn wait(&mut self, duration: Duration) {
+            self.0.push(Event::Wait(duration.as_millis()));
+        }
+    }
+
+    #[async_std::test]
+    async fn play_single_note() {
+        let mut output = TraceOutput::default();
+        play_melody(&mut output, &[(440, Duration::from_millis(100))], false)
+            .await
+            .unwrap();
+        assert_eq!(output.0, [Event::Start(440), Event::Wait(100), Event::Stop]);
+    }
+
+    #[async_std::test]
+    async fn play_notes_in_order() {
+        let mut output = TraceOutput::default();
+        let notes = [
+            (784, Duration::from_millis(200)),
+            (0, Duration::from_millis(50)),
+            (392, Duration::from_millis(500)),
+        ];
+        play_melody(&mut output, &notes, false).await.unwrap();
+        assert_eq!(
+            output.0,
+            [
+                Event::Start(784),
+                Event::Wait(200),
+                Event::Stop,
+                Event::Wait(10),
+                Event::Wait(50),
+                Event::Wait(10),
+                Event::Start(392),
+                Event::Wait(500),
+                Event::Stop,
+            ]
+        );
+    }
+
+    #[async_std::test]
+    async fn play_nothing() {
+        let mut output = TraceOutput::default();
+        play_melody(&mut output, &[], false).await.unwrap();
+        assert!(output.0.is_empty());
+    }
+
+    #[async_std::test]
+    async fn play_muted() {
+        let mut output = TraceOutput::default();
+        let notes = [
+            (784, Duration::from_millis(200)),
+            (0, Duration::from_millis(50)),
+            (392, Duration::from_millis(500)),
+        ];
+        play_melody(&mut output, &notes, true).await.unwrap();
+        assert!(!output
+            .0
+            .iter()
+            .any(|event| matches!(event, Event::Start(_) | Event::Stop)));
+        assert_eq!(
+            output.0,
+            [
+                Event::Wait(200),
+                Event::Wait(10),
+                Event::Wait(50),
+                Event::Wait(10),
+                Event::Wait(500),
+            ]
+        );
+    }
+
+    #[async_std::test]
+    async fn startup() {
+        let mut output = TraceOutput::default();
+        play_startup(&mut output, false).await.unwrap();
+        assert_eq!(
+            output.0,
+            [
+                Event::Stop,
+                Event::Start(3136),
+                Event::Wait(1000),
+                Event::Stop
+            ]
+        );
+    }
+
+    #[async_std::test]
+    async fn startup_muted_checks_output() {
+        let mut output = TraceOutput::default();
+        play_startup(&mut output, true).await.unwrap();
+        assert_eq!(output.0, [Event::Stop, Event::Wait(1000)]);
+    }
+
+    #[async_std::test]
+    async fn startup_fails() {
+        let mut output = FailingOutput::default();
+        let res = play_startup(&mut output, true).await;
+        assert!(matches!(res, Err(Error::Channel(channel::Error::Channel))));
+        // No tone is played after failing
+        assert_eq!(output.0 .0, [Event::Stop]);
+    }
+
+    #[async_std::test]
+    async fn key_tick_once_per_call() {
+        let mut output = TraceOutput::default();
+        for _ in 0..3 {
+            play_key_tick(&mut output, true, false).await.unwrap();
+        }
+        assert_eq!(
+            output.0,
+            [
+                Event::Start(3136),
+                Event::Wait(15),
+                Event::Stop,
+                Event::Start(3136),
+                Event::Wait(15),
+                Event::Stop,
+                Event::Start(3136),
+                Event::Wait(15),
+                Event::Stop,
+            ]
+        );
+    }
+
+    #[async_std::test]
+    async fn key_tick_disabled() {
+        let mut output = TraceOutput::default();
+        play_key_tick(&mut output, false, false).await.unwrap();
+        assert!(output.0.is_empty());
+    }
+
+    #[test]
+    fn volume_to_duty_cycle() {
+        // Active low: 100% duty cycle is off, 50% duty cycle is max volume
+        assert_eq!(duty_cycle(0), 100);
+        assert_eq!(duty_cycle(10), 95);
+        assert_eq!(duty_cycle(50), 75);
+        assert_eq!(duty_cycle(100), 50);
+    }
+
+    #[test]
+    fn volume_to_duty_cycle_clamped() {
+        assert_eq!(duty_cycle(150), 50);
+        assert_eq!(duty_cycle(255), 50);
     }
 }
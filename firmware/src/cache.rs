@@ -0,0 +1,642 @@
+use crate::article::{Article, Articles};
+use crate::flash;
+use crate::json::{self, FromJsonObject, ToJson};
+use crate::nfc::Uid;
+use crate::time;
+use crate::user::{UserId, Users};
+use alloc::string::{String, ToString};
+use chrono::{DateTime, TimeDelta, Utc};
+use core::cell::RefCell;
+use core::str::FromStr;
+use embedded_io_async::{BufRead, Write};
+use log::{debug, info, warn};
+
+/// Subtype of cache data partition
+const PARTITION_SUBTYPE: u8 = 0x43;
+
+/// Default max age of cached data
+pub const DEFAULT_MAX_AGE: TimeDelta = TimeDelta::days(7);
+
+/// Flash-backed cache of article and user information
+///
+/// After a successful refresh, article and user information is stored to the `cache` flash data
+/// partition. On startup, the cached information is loaded, so the device can be used right away
+/// (even without network) while data is refreshed. Cached data older than the max age is
+/// discarded (which can only be detected if the current time is known). To reduce flash wear,
+/// data is only written if it changed (or if the stored data is about to expire).
+#[derive(Debug)]
+pub struct Cache {
+    /// Flash data partition to store cache to
+    partition: Option<flash::Partition>,
+    /// Max age of cached data
+    max_age: TimeDelta,
+    /// Checksum of stored data
+    checksum: Option<u32>,
+    /// Time when data was stored
+    time: Option<DateTime<Utc>>,
+}
+
+impl Cache {
+    /// Create cache using the `cache` flash data partition. If there is no valid `cache` data
+    /// partition, nothing is cached.
+    pub fn new(max_age: TimeDelta) -> Self {
+        // Look up cache data partition (custom partition type 0x54, subtype 0x43)
+        let partition = match flash::Partition::find(PARTITION_SUBTYPE) {
+            Ok(partition) => Some(partition),
+            Err(err) => {
+                warn!("Cache: Unable to find cache partition: {}", err);
+                None
+            }
+        };
+
+        Self {
+            partition,
+            max_age,
+            checksum: None,
+            time: None,
+        }
+    }
+
+    /// Returns true if cached data was loaded or stored
+    pub fn is_valid(&self) -> bool {
+        self.checksum.is_some()
+    }
+
+    /// Load cached article and user information. Returns true if valid data was loaded.
+    pub async fn load(&mut self, articles: &mut Articles, users: &mut Users) -> bool {
+        let Some(partition) = self.partition.as_mut() else {
+            return false;
+        };
+
+        // Parse JSON cache. An erased partition (all 0xff) fails to parse, which is expected.
+        let tables = Tables {
+            articles: RefCell::new(articles),
+            users: RefCell::new(users),
+        };
+        let res: Result<CacheHeader, _> = json::Reader::new(partition.reader())
+            .read_object_with_context(&tables)
+            .await;
+        let (articles, users) = (tables.articles.into_inner(), tables.users.into_inner());
+
+        let header = match res {
+            Ok(header) => header,
+            Err(err) => {
+                debug!("Cache: No valid data in cache partition: {}", err);
+                articles.clear();
+                users.clear();
+                return false;
+            }
+        };
+
+        let time = header.time.and_then(|t| DateTime::from_timestamp(t, 0));
+        if is_expired(time, time::now(), self.max_age) {
+            info!("Cache: Discarding expired data from cache partition");
+            articles.clear();
+            users.clear();
+            return false;
+        }
+
+        self.checksum = header.checksum;
+        self.time = time;
+        articles.mark_clean();
+        users.mark_clean();
+        info!(
+            "Cache: Loaded {} articles, {} uids of {} users from cache partition",
+            articles.count(),
+            users.count_uids(),
+            users.count()
+        );
+        true
+    }
+
+    /// Store article and user information, if it changed. Both tables are marked clean after
+    /// storing. Errors are logged, but otherwise ignored, since caching is optional.
+    pub async fn save(&mut self, articles: &mut Articles, users: &mut Users) {
+        if self.partition.is_none() {
+            return;
+        }
+
+        let now = time::now();
+        let renew = needs_renewal(self.time, now, self.max_age);
+        if !articles.is_dirty() && !users.is_dirty() && !renew {
+            debug!("Cache: Data not modified, skipping write");
+            return;
+        }
+        let checksum = checksum(articles, users).await;
+        if self.checksum == Some(checksum) && !renew {
+            debug!("Cache: Data unchanged, skipping write");
+            articles.mark_clean();
+            users.mark_clean();
+            return;
+        }
+
+        // Stream JSON to flash sector by sector, so the whole data never needs to be in memory
+        let Some(partition) = self.partition.as_mut() else {
+            return;
+        };
+        let mut json = json::Writer::new(partition.writer());
+        let res = json
+            .write(&CacheData {
+                checksum: Some(checksum),
+                time: now.map(|t| t.timestamp()),
+                articles,
+                users,
+            })
+            .await;
+        let mut writer = json.into_inner();
+        let res = match res {
+            Ok(()) => writer.flush().await.map_err(json::Error::from),
+            Err(err) => Err(err),
+        };
+        match res {
+            Ok(()) => {
+                debug!(
+                    "Cache: Stored {} bytes to cache partition",
+                    writer.position()
+                );
+                self.checksum = Some(checksum);
+                self.time = now;
+                articles.mark_clean();
+                users.mark_clean();
+            }
+            Err(err) => warn!("Cache: Unable to write cache partition: {}", err),
+        }
+    }
+}
+
+/// Returns true if data stored at the given time is expired. If any time is unknown, data is
+/// assumed to be valid.
+fn is_expired(time: Option<DateTime<Utc>>, now: Option<DateTime<Utc>>, max_age: TimeDelta) -> bool {
+    match (time, now) {
+        (Some(time), Some(now)) => now - time > max_age,
+        _ => false,
+    }
+}
+
+/// Returns true if data stored at the given time should be stored again (even if unchanged) to
+/// keep it from expiring
+fn needs_renewal(
+    time: Option<DateTime<Utc>>,
+    now: Option<DateTime<Utc>>,
+    max_age: TimeDelta,
+) -> bool {
+    match (time, now) {
+        (Some(time), Some(now)) => now - time > max_age / 2,
+        (None, Some(_now)) => true,
+        (_, None) => false,
+    }
+}
+
+/// Checksum of article and user information (FNV-1a of its JSON representation)
+async fn checksum(articles: &Articles, users: &Users) -> u32 {
+    let mut json = json::Writer::new(flash::Checksum::new());
+    // Writing to checksum can't fail
+    let _ = json
+        .write(&CacheData {
+            checksum: None,
+            time: None,
+            articles,
+            users,
+        })
+        .await;
+    json.into_inner().value()
+}
+
+/// Cached data (for writing)
+struct CacheData<'a> {
+    checksum: Option<u32>,
+    time: Option<i64>,
+    articles: &'a Articles,
+    users: &'a Users,
+}
+
+impl ToJson for CacheData<'_> {
+    async fn to_json<W: Write>(
+        &self,
+        json: &mut json::Writer<W>,
+    ) -> Result<(), json::Error<W::Error>> {
+        let mut object = json.write_object().await?;
+        if let Some(checksum) = self.checksum {
+            object.field("checksum", checksum).await?;
+        }
+        if let Some(time) = self.time {
+            object.field("time", time).await?;
+        }
+        object
+            .field("articles", CachedArticles(self.articles))
+            .await?
+            .field("users", CachedUsers(self.users))
+            .await?
+            .finish()
+            .await
+    }
+}
+
+/// Helper for writing cached articles as JSON object (article id to article information)
+struct CachedArticles<'a>(&'a Articles);
+
+impl ToJson for CachedArticles<'_> {
+    async fn to_json<W: Write>(
+        &self,
+        json: &mut json::Writer<W>,
+    ) -> Result<(), json::Error<W::Error>> {
+        let mut object = json.write_object().await?;
+        for (_idx, id, article) in self.0.iter() {
+            object.field(id, CachedArticleRef(article)).await?;
+        }
+        object.finish().await
+    }
+}
+
+/// Helper for writing cached article information
+struct CachedArticleRef<'a>(&'a Article);
+
+impl ToJson for CachedArticleRef<'_> {
+    async fn to_json<W: Write>(
+        &self,
+        json: &mut json::Writer<W>,
+    ) -> Result<(), json::Error<W::Error>> {
+        let mut object = json.write_object().await?;
+        object
+            .field("name", &self.0.name)
+            .await?
+            .field("price", self.0.price)
+            .await?;
+        if let Some(salestax) = self.0.salestax {
+            object.field("salestax", salestax).await?;
+        }
+        object.finish().await
+    }
+}
+
+/// Cached article information (for reading)
+#[derive(Debug, Default)]
+struct CachedArticle {
+    name: String,
+    price: f32,
+    salestax: Option<f32>,
+}
+
+impl FromJsonObject for CachedArticle {
+    type Context<'ctx> = ();
+
+    async fn read_next<R: BufRead>(
+        &mut self,
+        key: String,
+        json: &mut json::Reader<R>,
+        _context: &Self::Context<'_>,
+    ) -> Result<(), json::Error<R::Error>> {
+        match &*key {
+            "name" => self.name = json.read().await?,
+            "price" => self.price = json.read().await?,
+            "salestax" => self.salestax = Some(json.read().await?),
+            _ => json.skip_any().await?,
+        }
+        Ok(())
+    }
+}
+
+/// Helper for writing cached users as JSON object
+struct CachedUsers<'a>(&'a Users);
+
+impl ToJson for CachedUsers<'_> {
+    async fn to_json<W: Write>(
+        &self,
+        json: &mut json::Writer<W>,
+    ) -> Result<(), json::Error<W::Error>> {
+        json.write_object()
+            .await?
+            .field("uids", CachedUids(self.0))
+            .await?
+            .field("names", CachedUserNames(self.0))
+            .await?
+            .field("pins", CachedPins(self.0))
+            .await?
+            .finish()
+            .await
+    }
+}
+
+/// Helper for writing NFC uids as JSON object (uid to user id)
+struct CachedUids<'a>(&'a Users);
+
+impl ToJson for CachedUids<'_> {
+    async fn to_json<W: Write>(
+        &self,
+        json: &mut json::Writer<W>,
+    ) -> Result<(), json::Error<W::Error>> {
+        let mut object = json.write_object().await?;
+        for (uid, id) in self.0.iter_uids() {
+            object.field(&uid.to_string(), id).await?;
+        }
+        object.finish().await
+    }
+}
+
+/// Helper for writing user names as JSON object (user id to name)
+struct CachedUserNames<'a>(&'a Users);
+
+impl ToJson for CachedUserNames<'_> {
+    async fn to_json<W: Write>(
+        &self,
+        json: &mut json::Writer<W>,
+    ) -> Result<(), json::Error<W::Error>> {
+        let mut object = json.write_object().await?;
+        for (id, user) in self.0.iter() {
+            object.field(&id.to_string(), &user.name).await?;
+        }
+        object.finish().await
+    }
+}
+
+/// Helper for writing user PINs as JSON object (user id to PIN)
+struct CachedPins<'a>(&'a Users);
+
+impl ToJson for CachedPins<'_> {
+    async fn to_json<W: Write>(
+        &self,
+        json: &mut json::Writer<W>,
+    ) -> Result<(), json::Error<W::Error>> {
+        let mut object = json.write_object().await?;
+        for (id, pin) in self.0.iter_pins() {
+            object.field(&id.to_string(), pin).await?;
+        }
+        object.finish().await
+    }
+}
+
+/// Lookup tables to read cached data into
+struct Tables<'a> {
+    articles: RefCell<&'a mut Articles>,
+    users: RefCell<&'a mut Users>,
+}
+
+/// Cached data (for reading). Article and user information is stored directly to the lookup
+/// tables given as context.
+#[derive(Debug, Default)]
+struct CacheHeader {
+    checksum: Option<u32>,
+    time: Option<i64>,
+}
+
+impl FromJsonObject for CacheHeader {
+    type Context<'ctx> = Tables<'ctx>;
+
+    async fn read_next<R: BufRead>(
+        &mut self,
+        key: String,
+        json: &mut json::Reader<R>,
+        context: &Self::Context<'_>,
+    ) -> Result<(), json::Error<R::Error>> {
+        match &*key {
+            "checksum" => self.checksum = Some(json.read().await?),
+            "time" => self.time = Some(json.read().await?),
+            "articles" => {
+                let _: ArticlesReader = json.read_object_with_context(context).await?;
+            }
+            "users" => {
+                let _: UsersReader = json.read_object_with_context(context).await?;
+            }
+            _ => json.skip_any().await?,
+        }
+        Ok(())
+    }
+}
+
+/// Reader for cached articles
+#[derive(Default)]
+struct ArticlesReader;
+
+impl FromJsonObject for ArticlesReader {
+    type Context<'ctx> = Tables<'ctx>;
+
+    async fn read_next<R: BufRead>(
+        &mut self,
+        key: String,
+        json: &mut json::Reader<R>,
+        context: &Self::Context<'_>,
+    ) -> Result<(), json::Error<R::Error>> {
+        let article: CachedArticle = json.read().await?;
+        context
+            .articles
+            .borrow_mut()
+            .update(&key, article.name, article.price, article.salestax);
+        Ok(())
+    }
+}
+
+/// Reader for cached users
+#[derive(Default)]
+struct UsersReader;
+
+impl FromJsonObject for UsersReader {
+    type Context<'ctx> = Tables<'ctx>;
+
+    async fn read_next<R: BufRead>(
+        &mut self,
+        key: String,
+        json: &mut json::Reader<R>,
+        context: &Self::Context<'_>,
+    ) -> Result<(), json::Error<R::Error>> {
+        match &*key {
+            "uids" => {
+                let _: UidsReader = json.read_object_with_context(context).await?;
+            }
+            "names" => {
+                let _: UserNamesReader = json.read_object_with_context(context).await?;
+            }
+            "pins" => {
+                let _: PinsReader = json.read_object_with_context(context).await?;
+            }
+            _ => json.skip_any().await?,
+        }
+        Ok(())
+    }
+}
+
+/// Reader for cached NFC uids
+#[derive(Default)]
+struct UidsReader;
+
+impl FromJsonObject for UidsReader {
+    type Context<'ctx> = Tables<'ctx>;
+
+    async fn read_next<R: BufRead>(
+        &mut self,
+        key: String,
+        json: &mut json::Reader<R>,
+        context: &Self::Context<'_>,
+    ) -> Result<(), json::Error<R::Error>> {
+        let uid = Uid::from_str(&key).map_err(|_| json::Error::InvalidType)?;
+        let id: UserId = json.read().await?;
+        context.users.borrow_mut().update_uid(uid, id);
+        Ok(())
+    }
+}
+
+/// Reader for cached user names
+#[derive(Default)]
+struct UserNamesReader;
+
+impl FromJsonObject for UserNamesReader {
+    type Context<'ctx> = Tables<'ctx>;
+
+    async fn read_next<R: BufRead>(
+        &mut self,
+        key: String,
+        json: &mut json::Reader<R>,
+        context: &Self::Context<'_>,
+    ) -> Result<(), json::Error<R::Error>> {
+        let id = UserId::from_str(&key).map_err(|_| json::Error::InvalidType)?;
+        let name: String = json.read().await?;
+        context.users.borrow_mut().update_user(id, name);
+        Ok(())
+    }
+}
+
+/// Reader for cached user PINs
+#[derive(Default)]
+struct PinsReader;
+
+impl FromJsonObject for PinsReader {
+    type Context<'ctx> = Tables<'ctx>;
+
+    async fn read_next<R: BufRead>(
+        &mut self,
+        key: String,
+        json: &mut json::Reader<R>,
+        context: &Self::Context<'_>,
+    ) -> Result<(), json::Error<R::Error>> {
+        let id = UserId::from_str(&key).map_err(|_| json::Error::InvalidType)?;
+        let pin: String = json.read().await?;
+        context.users.borrow_mut().update_pin(id, pin);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    fn tables() -> (Articles, Users) {
+        let mut articles = Articles::new(vec!["1".to_string(), "2".to_string()]);
+        articles.update(&"1".to_string(), "Wasser".to_string(), 1.0, None);
+        articles.update(
+            &"2".to_string(),
+            "Apfelschorle".to_string(),
+            1.5,
+            Some(19.0),
+        );
+        let mut users = Users::new();
+        users.update_uid(Uid::Single([0x12, 0x34, 0x56, 0x78]), 1234);
+        users.update_uid(Uid::Double([1, 2, 3, 4, 5, 6, 7]), 1234);
+        users.update_user(1234, "Fred Flintstone".to_string());
+        users.update_pin(1234, "0815".to_string());
+        (articles, users)
+    }
+
+    async fn to_json(articles: &Articles, users: &Users, time: Option<i64>) -> Vec<u8> {
+        let mut json = json::Writer::new(Vec::new());
+        json.write(&CacheData {
+            checksum: Some(42),
+            time,
+            articles,
+            users,
+        })
+        .await
+        .unwrap();
+        json.into_inner()
+    }
+
+    #[async_std::test]
+    async fn roundtrip() {
+        let (articles, users) = tables();
+        let bytes = to_json(&articles, &users, Some(1_737_000_000)).await;
+
+        let mut read_articles = Articles::new(vec!["1".to_string(), "2".to_string()]);
+        let mut read_users = Users::new();
+        let tables = Tables {
+            articles: RefCell::new(&mut read_articles),
+            users: RefCell::new(&mut read_users),
+        };
+        let header: CacheHeader = json::Reader::new(&bytes[..])
+            .read_object_with_context(&tables)
+            .await
+            .unwrap();
+        assert_eq!(header.checksum, Some(42));
+        assert_eq!(header.time, Some(1_737_000_000));
+
+        assert_eq!(read_articles.count(), 2);
+        assert_eq!(read_articles.get("1"), articles.get("1"));
+        assert_eq!(read_articles.get("2"), articles.get("2"));
+        assert_eq!(read_users.count_uids(), users.count_uids());
+        assert_eq!(read_users.count(), users.count());
+        for (uid, id) in users.iter_uids() {
+            assert_eq!(read_users.id(uid), Some(id));
+        }
+        assert_eq!(read_users.get(1234), users.get(1234));
+        assert_eq!(read_users.pin(1234), Some("0815"));
+    }
+
+    #[async_std::test]
+    async fn roundtrip_ignores_unknown_articles() {
+        let (articles, users) = tables();
+        let bytes = to_json(&articles, &users, None).await;
+
+        // Article ids configured differently since data was stored
+        let mut read_articles = Articles::new(vec!["2".to_string(), "3".to_string()]);
+        let mut read_users = Users::new();
+        let tables = Tables {
+            articles: RefCell::new(&mut read_articles),
+            users: RefCell::new(&mut read_users),
+        };
+        let header: CacheHeader = json::Reader::new(&bytes[..])
+            .read_object_with_context(&tables)
+            .await
+            .unwrap();
+        assert_eq!(header.time, None);
+        assert_eq!(read_articles.count(), 1);
+        assert_eq!(read_articles.get("2"), articles.get("2"));
+    }
+
+    #[async_std::test]
+    async fn checksum_changes_with_data() {
+        let (mut articles, users) = tables();
+        let checksum1 = checksum(&articles, &users).await;
+        assert_eq!(checksum(&articles, &users).await, checksum1);
+        articles.update(&"1".to_string(), "Wasser".to_string(), 1.2, None);
+        assert_ne!(checksum(&articles, &users).await, checksum1);
+    }
+
+    #[test]
+    fn expiry() {
+        let t0 = DateTime::from_timestamp(1_737_000_000, 0);
+        let t1 = DateTime::from_timestamp(1_737_000_000 + 4 * 86400, 0);
+        let t2 = DateTime::from_timestamp(1_737_000_000 + 8 * 86400, 0);
+        let max_age = TimeDelta::days(7);
+        assert!(!is_expired(t0, t1, max_age));
+        assert!(is_expired(t0, t2, max_age));
+        assert!(!is_expired(t0, None, max_age));
+        assert!(!is_expired(None, t2, max_age));
+        assert!(!needs_renewal(t0, t0, max_age));
+        assert!(needs_renewal(t0, t1, max_age));
+        assert!(needs_renewal(None, t1, max_age));
+        assert!(!needs_renewal(t0, None, max_age));
+    }
+
+    #[async_std::test]
+    async fn read_erased() {
+        let bytes = [0xff; 16];
+        let (mut articles, mut users) = tables();
+        let tables = Tables {
+            articles: RefCell::new(&mut articles),
+            users: RefCell::new(&mut users),
+        };
+        let res: Result<CacheHeader, _> = json::Reader::new(&bytes[..])
+            .read_object_with_context(&tables)
+            .await;
+        assert!(res.is_err());
+    }
+}
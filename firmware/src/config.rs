@@ -1,16 +1,21 @@
 use crate::article::ArticleId;
+use crate::flash;
 use crate::json::{self, FromJson, FromJsonObject};
+use crate::language::Language;
+use crate::mixpanel;
 use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
+use core::convert::Infallible;
 use core::fmt;
 use core::ops::Deref;
 use embedded_io_async::BufRead;
-use embedded_storage::ReadStorage;
-use esp_partition_table::{PartitionTable, PartitionType};
 use esp_storage::FlashStorage;
 use log::{debug, info, warn};
 
+/// Subtype of config data partition
+const PARTITION_SUBTYPE: u8 = 0x44;
+
 /// String with sensitive content (debug and display output redacted)
 #[derive(Default)]
 pub struct SensitiveString(String);
@@ -23,6 +28,25 @@ impl FromJson for SensitiveString {
     }
 }
 
+/// List of article ids, given either as a single string or as an array of strings
+struct ArticleIds(Vec<ArticleId>);
+
+impl FromJson for ArticleIds {
+    async fn from_json<R: BufRead>(
+        json: &mut json::Reader<R>,
+    ) -> Result<Self, json::Error<R::Error>> {
+        match json.read_any().await? {
+            json::Value::String(id) => Ok(Self(vec![id])),
+            json::Value::Array(ids) => Ok(Self(
+                ids.into_iter()
+                    .map(ArticleId::try_from)
+                    .collect::<Result<_, _>>()?,
+            )),
+            _ => Err(json::Error::InvalidType),
+        }
+    }
+}
+
 impl fmt::Debug for SensitiveString {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if self.0.is_empty() {
@@ -57,7 +81,8 @@ impl Deref for SensitiveString {
 /// firmware updates via USB or OTA. Currently, configuration is read-only at runtime, i.e. it
 /// needs to be flashed manually once per device. To make this easier, it is expected to be stored
 /// in JSON format in the `config` data partition. See README.md for details on how to flash the
-/// configuration.
+/// configuration. After flashing, the configuration can be reloaded from the admin menu, which
+/// applies some settings (see `Settings`) immediately. Other settings need a restart.
 ///
 /// If there is no valid JSON or no valid `config` data partition, a default configuration is
 /// provided (which isn't very useful, but at least doesn't prevent the device from starting).
@@ -69,6 +94,12 @@ pub struct Config {
     pub wifi_password: SensitiveString,
     /// Mixpanel project token for analytics (optional)
     pub mp_token: Option<String>,
+    /// Mixpanel data residency region
+    pub mp_region: mixpanel::Region,
+    /// Mixpanel flush interval in seconds (optional)
+    pub mp_flush_interval: Option<u32>,
+    /// Mixpanel max number of events per batch (optional)
+    pub mp_batch_size: Option<usize>,
     /// Vereinsflieger API username
     pub vf_username: String,
     /// MD5 (hex) of Vereinsflieger API password
@@ -77,8 +108,68 @@ pub struct Config {
     pub vf_appkey: SensitiveString,
     /// Vereinsflieger API cid (optional)
     pub vf_cid: Option<u32>,
+    /// Vereinsflieger page size for fetching article and user lists (optional)
+    pub vf_page_size: Option<u32>,
+    /// Vereinsflieger two-factor auth shared secret (base32, optional)
+    pub vf_auth_secret: Option<SensitiveString>,
     /// Vereinsflieger article ids for purchase
     pub vf_article_ids: Vec<ArticleId>,
+    /// Vereinsflieger callsign to book purchases on (optional)
+    pub vf_callsign: Option<String>,
+    /// Vereinsflieger comment for purchases (optional, defaults to device id)
+    pub vf_sale_comment: Option<String>,
+    /// Vereinsflieger label prefix of user keys that are NFC uids (optional)
+    pub vf_nfc_key_prefix: Option<String>,
+    /// Allow users to authenticate by entering their member number (optional)
+    pub member_id_entry: Option<bool>,
+    /// PIN to enter the admin menu (digits, optional, admin menu disabled if unset)
+    pub admin_pin: Option<SensitiveString>,
+    /// Demo mode without network: only use locally known users and articles and only log
+    /// purchases locally (optional)
+    pub demo_mode: Option<bool>,
+    /// Name or location of this machine, shown on screen and reported via telemetry to tell
+    /// multiple machines apart (optional)
+    pub machine_name: Option<String>,
+    /// Total price in EUR above which a purchase needs an extra confirmation (optional)
+    pub large_purchase_threshold: Option<f32>,
+    /// Currency symbol shown with total prices (optional)
+    pub currency: Option<String>,
+    /// Decimal separator of prices (optional)
+    pub decimal_separator: Option<String>,
+    /// User greetings, replacing the built-in greetings of the language (optional)
+    pub greetings: Option<Vec<String>>,
+    /// Buzzer volume (percentage, 0-100, optional)
+    pub buzzer_volume: Option<u8>,
+    /// Mute buzzer (optional)
+    pub buzzer_muted: Option<bool>,
+    /// Short buzzer tick on every accepted keypress (optional)
+    pub key_feedback: Option<bool>,
+    /// Display rotation in degrees (0 or 180, optional)
+    pub display_rotation: Option<u16>,
+    /// Display brightness (percentage, 0-100, optional)
+    pub display_brightness: Option<u8>,
+    /// Flash display when showing an error (optional)
+    pub error_flash: Option<bool>,
+    /// Idle time in seconds until display is dimmed (optional)
+    pub idle_dim_timeout: Option<u32>,
+    /// Idle time in seconds until power saving is activated (optional)
+    pub idle_power_save_timeout: Option<u32>,
+    /// Idle time in seconds until the device goes to sleep (optional, no sleep if unset)
+    pub idle_sleep_timeout: Option<u32>,
+    /// Time in seconds to wait for user input before cancelling an action (optional)
+    pub user_timeout: Option<u32>,
+    /// Time in seconds to show the splash screen at startup (optional)
+    pub splash_timeout: Option<u32>,
+    /// Language of on-screen texts
+    pub language: Language,
+    /// Local time of day for daily data refresh ("HH:MM", optional)
+    pub schedule_time: Option<String>,
+    /// Local time offset to UTC in minutes (optional)
+    pub utc_offset: Option<i32>,
+    /// Max age in hours of cached article and user information (optional)
+    pub cache_max_age: Option<u32>,
+    /// Monitor battery voltage, disable if externally powered (optional, needs battery feature)
+    pub battery_monitor: Option<bool>,
 }
 
 impl FromJsonObject for Config {
@@ -98,50 +189,187 @@ impl FromJsonObject for Config {
             "mp-token" => self.mp_token = Some(json.read().await?),
             #[cfg(debug_assertions)]
             "mp-token-debug" => self.mp_token = Some(json.read().await?),
+            "mp-region" => self.mp_region = json.read().await?,
+            "mp-flush-interval" => self.mp_flush_interval = Some(json.read().await?),
+            "mp-batch-size" => self.mp_batch_size = Some(json.read().await?),
             "vf-username" => self.vf_username = json.read().await?,
             "vf-password-md5" => self.vf_password_md5 = json.read().await?,
             "vf-appkey" => self.vf_appkey = json.read().await?,
             "vf-cid" => self.vf_cid = Some(json.read().await?),
-            "vf-article-id" => self.vf_article_ids = vec![json.read().await?],
-            "vf-article-ids" => self.vf_article_ids = json.read().await?,
+            "vf-page-size" => self.vf_page_size = Some(json.read().await?),
+            "vf-auth-secret" => self.vf_auth_secret = Some(json.read().await?),
+            "vf-article-id" | "vf-article-ids" => {
+                self.vf_article_ids = json.read::<ArticleIds>().await?.0;
+            }
+            "vf-callsign" => self.vf_callsign = Some(json.read().await?),
+            "vf-sale-comment" => self.vf_sale_comment = Some(json.read().await?),
+            "vf-nfc-key-prefix" => self.vf_nfc_key_prefix = Some(json.read().await?),
+            "member-id-entry" => self.member_id_entry = Some(json.read().await?),
+            "admin-pin" => self.admin_pin = Some(json.read().await?),
+            "demo-mode" => self.demo_mode = Some(json.read().await?),
+            "machine-name" => self.machine_name = Some(json.read().await?),
+            "large-purchase-threshold" => {
+                self.large_purchase_threshold = Some(json.read().await?);
+            }
+            "currency" => self.currency = Some(json.read().await?),
+            "decimal-separator" => self.decimal_separator = Some(json.read().await?),
+            "greetings" => self.greetings = Some(json.read().await?),
+            "buzzer-volume" => self.buzzer_volume = Some(json.read().await?),
+            "buzzer-muted" => self.buzzer_muted = Some(json.read().await?),
+            "key-feedback" => self.key_feedback = Some(json.read().await?),
+            "display-rotation" => self.display_rotation = Some(json.read().await?),
+            "display-brightness" => self.display_brightness = Some(json.read().await?),
+            "error-flash" => self.error_flash = Some(json.read().await?),
+            "idle-dim-timeout" => self.idle_dim_timeout = Some(json.read().await?),
+            "idle-power-save-timeout" => self.idle_power_save_timeout = Some(json.read().await?),
+            "idle-sleep-timeout" => self.idle_sleep_timeout = Some(json.read().await?),
+            "user-timeout" => self.user_timeout = Some(json.read().await?),
+            "splash-timeout" => self.splash_timeout = Some(json.read().await?),
+            "language" => self.language = json.read().await?,
+            "schedule-time" => self.schedule_time = Some(json.read().await?),
+            "utc-offset" => self.utc_offset = Some(json.read().await?),
+            "cache-max-age" => self.cache_max_age = Some(json.read().await?),
+            "battery-monitor" => self.battery_monitor = Some(json.read().await?),
             _ => json.skip_any().await?,
         }
         Ok(())
     }
 }
 
+/// Settings that can be applied at runtime without restarting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    /// Language of on-screen texts
+    pub language: Language,
+    /// Buzzer volume (percentage, 0-100, optional)
+    pub buzzer_volume: Option<u8>,
+    /// Mute buzzer (optional)
+    pub buzzer_muted: Option<bool>,
+    /// Short buzzer tick on every accepted keypress (optional)
+    pub key_feedback: Option<bool>,
+    /// Display brightness (percentage, 0-100, optional)
+    pub display_brightness: Option<u8>,
+    /// Flash display when showing an error (optional)
+    pub error_flash: Option<bool>,
+    /// Idle time in seconds until display is dimmed (optional)
+    pub idle_dim_timeout: Option<u32>,
+    /// Idle time in seconds until power saving is activated (optional)
+    pub idle_power_save_timeout: Option<u32>,
+    /// Idle time in seconds until the device goes to sleep (optional, no sleep if unset)
+    pub idle_sleep_timeout: Option<u32>,
+    /// Time in seconds to wait for user input before cancelling an action (optional)
+    pub user_timeout: Option<u32>,
+    /// Time in seconds to show the splash screen at startup (optional)
+    pub splash_timeout: Option<u32>,
+}
+
 impl Config {
-    /// Read configuration from `config` flash data partition
-    pub async fn read() -> Self {
-        let mut storage = FlashStorage::new();
+    /// Settings that can be applied at runtime
+    pub fn settings(&self) -> Settings {
+        Settings {
+            language: self.language,
+            buzzer_volume: self.buzzer_volume,
+            buzzer_muted: self.buzzer_muted,
+            key_feedback: self.key_feedback,
+            display_brightness: self.display_brightness,
+            error_flash: self.error_flash,
+            idle_dim_timeout: self.idle_dim_timeout,
+            idle_power_save_timeout: self.idle_power_save_timeout,
+            idle_sleep_timeout: self.idle_sleep_timeout,
+            user_timeout: self.user_timeout,
+            splash_timeout: self.splash_timeout,
+        }
+    }
+
+    /// Keys of required settings that are missing (or empty)
+    pub fn missing_keys(&self) -> Vec<&'static str> {
+        // Network settings aren't needed in demo mode
+        let online = !self.demo_mode.unwrap_or(false);
+        [
+            ("wifi-ssid", online && self.wifi_ssid.is_empty()),
+            ("vf-username", online && self.vf_username.is_empty()),
+            ("vf-password-md5", online && self.vf_password_md5.is_empty()),
+            ("vf-appkey", online && self.vf_appkey.is_empty()),
+            ("vf-article-id", self.vf_article_ids.is_empty()),
+        ]
+        .into_iter()
+        .filter_map(|(key, missing)| missing.then_some(key))
+        .collect()
+    }
+
+    /// Returns true if all required settings are set. A missing or invalid config partition
+    /// results in an empty configuration, which isn't valid.
+    pub fn is_valid(&self) -> bool {
+        self.missing_keys().is_empty()
+    }
 
-        // Read partition table (at 0x8000 by default)
-        let table = PartitionTable::default();
-        debug!("Config: Reading partition table at 0x{:x}", table.addr);
+    /// Returns true if the given (reloaded) configuration differs from this one in any setting
+    /// that is only applied at startup, i.e. needs a restart to take effect
+    pub fn requires_restart(&self, other: &Self) -> bool {
+        *self.wifi_ssid != *other.wifi_ssid
+            || *self.wifi_password != *other.wifi_password
+            || self.mp_token != other.mp_token
+            || self.mp_region != other.mp_region
+            || self.mp_flush_interval != other.mp_flush_interval
+            || self.mp_batch_size != other.mp_batch_size
+            || self.vf_username != other.vf_username
+            || *self.vf_password_md5 != *other.vf_password_md5
+            || *self.vf_appkey != *other.vf_appkey
+            || self.vf_cid != other.vf_cid
+            || self.vf_page_size != other.vf_page_size
+            || self.vf_auth_secret.as_deref() != other.vf_auth_secret.as_deref()
+            || self.vf_article_ids != other.vf_article_ids
+            || self.vf_callsign != other.vf_callsign
+            || self.vf_sale_comment != other.vf_sale_comment
+            || self.vf_nfc_key_prefix != other.vf_nfc_key_prefix
+            || self.member_id_entry != other.member_id_entry
+            || self.admin_pin.as_deref() != other.admin_pin.as_deref()
+            || self.demo_mode != other.demo_mode
+            || self.machine_name != other.machine_name
+            || self.large_purchase_threshold != other.large_purchase_threshold
+            || self.currency != other.currency
+            || self.decimal_separator != other.decimal_separator
+            || self.greetings != other.greetings
+            || self.display_rotation != other.display_rotation
+            || self.schedule_time != other.schedule_time
+            || self.utc_offset != other.utc_offset
+            || self.cache_max_age != other.cache_max_age
+            || self.battery_monitor != other.battery_monitor
+    }
+
+    /// Parse configuration from JSON. Parsing is lenient about trailing commas and allows
+    /// comments, since the configuration is typically edited by hand.
+    async fn parse(bytes: &[u8]) -> Result<Self, json::Error<Infallible>> {
+        let mut json = json::Reader::new(bytes);
+        json.set_allow_trailing_comma(true);
+        json.set_allow_comments(true);
+        // Skip leading whitespace and comments
+        if json.is_end().await? {
+            return Err(json::Error::Eof);
+        }
+        json.read().await
+    }
 
+    /// Read configuration from `config` flash data partition
+    pub async fn read() -> Self {
         // Look up config data partition (custom partition type 0x54, subtype 0x44)
-        let config_offset = if let Some(offset) = table
-            .iter_storage(&mut storage, false)
-            .flatten()
-            .find(|partition| partition.type_ == PartitionType::User(0x54, 0x44))
-            .map(|partition| partition.offset)
-        {
-            debug!("Config: Found config partition at offset 0x{:x}", offset);
-            offset
-        } else {
-            warn!("Config: Unable to find config partition");
-            return Self::default();
+        let mut partition = match flash::Partition::find(PARTITION_SUBTYPE) {
+            Ok(partition) => partition,
+            Err(err) => {
+                warn!("Config: Unable to find config partition: {}", err);
+                return Self::default();
+            }
         };
 
         // Read first sector (4 kb) of config data partition
         let mut bytes = [0; FlashStorage::SECTOR_SIZE as usize];
-        if let Err(_err) = storage.read(config_offset, &mut bytes) {
-            warn!("Config: Unable to read config partition");
+        if let Err(err) = partition.read(&mut bytes) {
+            warn!("Config: Unable to read config partition: {}", err);
             return Self::default();
         }
 
         // Parse JSON config
-        let config = match json::Reader::new(&bytes[..]).read().await {
+        let config = match Self::parse(&bytes).await {
             Ok(config) => config,
             Err(err) => {
                 warn!(
@@ -154,6 +382,281 @@ impl Config {
 
         debug!("Config: System configuration: {:?}", config);
         info!("Config: Configuration loaded from config partition");
+        let missing_keys = config.missing_keys();
+        if !missing_keys.is_empty() {
+            warn!(
+                "Config: Missing required settings: {}",
+                missing_keys.join(", ")
+            );
+        }
         config
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read(s: &str) -> Config {
+        Config::parse(s.as_bytes()).await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn trailing_comma() {
+        let config = read(
+            r#"{"wifi-ssid": "My Wifi", "vf-article-id": ["1", "2",], "vf-username": "user",}"#,
+        )
+        .await;
+        assert_eq!(config.wifi_ssid, "My Wifi");
+        assert_eq!(config.vf_article_ids, ["1", "2"]);
+        assert_eq!(config.vf_username, "user");
+    }
+
+    #[async_std::test]
+    async fn comments() {
+        let config = read(
+            r#"// Configuration
+            {
+                // Clubhouse network
+                "wifi-ssid": "My Wifi", /* "wifi-password": "secret", */
+                "vf-article-id": ["1"] // Drinks
+            }"#,
+        )
+        .await;
+        assert_eq!(config.wifi_ssid, "My Wifi");
+        assert!(config.wifi_password.is_empty());
+        assert_eq!(config.vf_article_ids, ["1"]);
+    }
+
+    #[async_std::test]
+    async fn valid() {
+        let config = read(
+            r#"{"wifi-ssid": "My Wifi", "vf-username": "user", "vf-password-md5": "0123",
+                "vf-appkey": "cafe", "vf-article-id": ["1", "2"]}"#,
+        )
+        .await;
+        assert!(config.is_valid());
+    }
+
+    #[async_std::test]
+    async fn partially_set_invalid() {
+        let config = read(
+            r#"{"wifi-ssid": "My Wifi", "vf-username": "user", "vf-password-md5": "0123",
+                "vf-appkey": "cafe"}"#,
+        )
+        .await;
+        assert!(!config.is_valid());
+        let config = read(r#"{"wifi-ssid": "My Wifi", "vf-article-id": ["1"]}"#).await;
+        assert!(!config.is_valid());
+    }
+
+    #[test]
+    fn empty_invalid() {
+        let config = Config::default();
+        assert!(!config.is_valid());
+        assert_eq!(
+            config.missing_keys(),
+            [
+                "wifi-ssid",
+                "vf-username",
+                "vf-password-md5",
+                "vf-appkey",
+                "vf-article-id"
+            ]
+        );
+    }
+
+    #[async_std::test]
+    async fn missing_keys() {
+        let config = read(
+            r#"{"wifi-ssid": "My Wifi", "vf-username": "user", "vf-password-md5": "0123",
+                "vf-appkey": "cafe", "vf-article-id": ["1"]}"#,
+        )
+        .await;
+        assert!(config.missing_keys().is_empty());
+        let config = read(
+            r#"{"wifi-ssid": "My Wifi", "vf-username": "user", "vf-password-md5": "0123",
+                "vf-appkey": "cafe"}"#,
+        )
+        .await;
+        assert_eq!(config.missing_keys(), ["vf-article-id"]);
+        let config = read(r#"{"wifi-ssid": "", "vf-appkey": "cafe", "language": "en"}"#).await;
+        assert_eq!(
+            config.missing_keys(),
+            [
+                "wifi-ssid",
+                "vf-username",
+                "vf-password-md5",
+                "vf-article-id"
+            ]
+        );
+    }
+
+    #[async_std::test]
+    async fn read_display_rotation() {
+        let config = read(r#"{"wifi-ssid": "My Wifi", "display-rotation": 180}"#).await;
+        assert_eq!(config.wifi_ssid, "My Wifi");
+        assert_eq!(config.display_rotation, Some(180));
+    }
+
+    #[async_std::test]
+    async fn read_display_rotation_unset() {
+        let config = read(r#"{"wifi-ssid": "My Wifi"}"#).await;
+        assert_eq!(config.display_rotation, None);
+    }
+
+    #[async_std::test]
+    async fn read_error_flash() {
+        let config = read(r#"{"wifi-ssid": "My Wifi", "error-flash": true}"#).await;
+        assert_eq!(config.error_flash, Some(true));
+        assert_eq!(config.settings().error_flash, Some(true));
+    }
+
+    #[async_std::test]
+    async fn read_machine_name() {
+        let config = read(r#"{"machine-name": "Clubheim"}"#).await;
+        assert_eq!(config.machine_name.as_deref(), Some("Clubheim"));
+        let reloaded = read(r#"{"machine-name": "Werkstatt"}"#).await;
+        assert!(config.requires_restart(&reloaded));
+        let config = read("{}").await;
+        assert_eq!(config.machine_name, None);
+    }
+
+    #[async_std::test]
+    async fn read_idle_sleep_timeout() {
+        let config = read(r#"{"idle-sleep-timeout": 3600}"#).await;
+        assert_eq!(config.idle_sleep_timeout, Some(3600));
+        assert_eq!(config.settings().idle_sleep_timeout, Some(3600));
+        let config = read("{}").await;
+        assert_eq!(config.idle_sleep_timeout, None);
+    }
+
+    #[async_std::test]
+    async fn read_single_article_id() {
+        let config = read(r#"{"vf-article-id": "1234"}"#).await;
+        assert_eq!(config.vf_article_ids, ["1234"]);
+    }
+
+    #[async_std::test]
+    async fn read_article_id_array() {
+        let config = read(r#"{"vf-article-id": ["1234", "2345"]}"#).await;
+        assert_eq!(config.vf_article_ids, ["1234", "2345"]);
+        let config = read(r#"{"vf-article-ids": ["1234", "2345", "3456"]}"#).await;
+        assert_eq!(config.vf_article_ids, ["1234", "2345", "3456"]);
+        let config = read(r#"{"vf-article-ids": "1234"}"#).await;
+        assert_eq!(config.vf_article_ids, ["1234"]);
+    }
+
+    #[async_std::test]
+    async fn read_invalid_article_ids() {
+        let res = json::Reader::new(r#"{"vf-article-id": 1234}"#.as_bytes())
+            .read::<Config>()
+            .await;
+        assert!(res.is_err());
+    }
+
+    #[async_std::test]
+    async fn read_greetings() {
+        let config = read(r#"{"greetings": ["Servus", "Moin"]}"#).await;
+        assert_eq!(
+            config.greetings.as_deref(),
+            Some(&["Servus".to_string(), "Moin".to_string()][..])
+        );
+        let config = read(r#"{"wifi-ssid": "My Wifi"}"#).await;
+        assert_eq!(config.greetings, None);
+    }
+
+    #[async_std::test]
+    async fn read_battery_monitor() {
+        let config = read(r#"{"battery-monitor": true}"#).await;
+        assert_eq!(config.battery_monitor, Some(true));
+        let reloaded = read(r#"{"battery-monitor": false}"#).await;
+        assert!(config.requires_restart(&reloaded));
+    }
+
+    #[async_std::test]
+    async fn read_nfc_key_prefix() {
+        let config = read(r#"{"vf-nfc-key-prefix": "RFID"}"#).await;
+        assert_eq!(config.vf_nfc_key_prefix.as_deref(), Some("RFID"));
+        let reloaded = read(r#"{"wifi-ssid": "My Wifi"}"#).await;
+        assert_eq!(reloaded.vf_nfc_key_prefix, None);
+        assert!(config.requires_restart(&reloaded));
+    }
+
+    #[async_std::test]
+    async fn demo_mode() {
+        let config = read(r#"{"demo-mode": true, "vf-article-id": ["1"]}"#).await;
+        assert_eq!(config.demo_mode, Some(true));
+        assert!(config.is_valid());
+        let config = read(r#"{"demo-mode": true}"#).await;
+        assert_eq!(config.missing_keys(), ["vf-article-id"]);
+        let config = read(r#"{"demo-mode": false, "vf-article-id": ["1"]}"#).await;
+        assert!(!config.is_valid());
+        let reloaded = read(r#"{"vf-article-id": ["1"]}"#).await;
+        assert!(config.requires_restart(&reloaded));
+    }
+
+    #[async_std::test]
+    async fn read_member_id_entry() {
+        let config = read(r#"{"member-id-entry": true}"#).await;
+        assert_eq!(config.member_id_entry, Some(true));
+        let reloaded = read(r#"{"wifi-ssid": "My Wifi"}"#).await;
+        assert_eq!(reloaded.member_id_entry, None);
+        assert!(config.requires_restart(&reloaded));
+    }
+
+    #[async_std::test]
+    async fn read_admin_pin() {
+        let config = read(r#"{"admin-pin": "0815"}"#).await;
+        assert_eq!(config.admin_pin.as_deref(), Some("0815"));
+        assert_eq!(format!("{:?}", config.admin_pin), "Some(\"<redacted>\")");
+        let reloaded = read(r#"{"admin-pin": "4711"}"#).await;
+        assert!(config.requires_restart(&reloaded));
+    }
+
+    #[async_std::test]
+    async fn read_invalid_greetings() {
+        let res = json::Reader::new(r#"{"greetings": "Moin"}"#.as_bytes())
+            .read::<Config>()
+            .await;
+        assert!(res.is_err());
+    }
+
+    #[async_std::test]
+    async fn reload_settings() {
+        let config =
+            read(r#"{"wifi-ssid": "My Wifi", "language": "de", "buzzer-volume": 50}"#).await;
+        let reloaded = read(
+            r#"{"wifi-ssid": "My Wifi", "language": "en", "buzzer-muted": true,
+                "key-feedback": true, "display-brightness": 80, "idle-dim-timeout": 60,
+                "user-timeout": 30}"#,
+        )
+        .await;
+        assert_eq!(
+            reloaded.settings(),
+            Settings {
+                language: Language::English,
+                buzzer_volume: None,
+                buzzer_muted: Some(true),
+                key_feedback: Some(true),
+                display_brightness: Some(80),
+                error_flash: None,
+                idle_dim_timeout: Some(60),
+                idle_power_save_timeout: None,
+                idle_sleep_timeout: None,
+                user_timeout: Some(30),
+                splash_timeout: None,
+            }
+        );
+        assert_ne!(config.settings(), reloaded.settings());
+        assert!(!config.requires_restart(&reloaded));
+    }
+
+    #[async_std::test]
+    async fn reload_requires_restart() {
+        let config = read(r#"{"wifi-ssid": "My Wifi", "language": "de"}"#).await;
+        let reloaded = read(r#"{"wifi-ssid": "Other Wifi", "language": "de"}"#).await;
+        assert_eq!(config.settings(), reloaded.settings());
+        assert!(config.requires_restart(&reloaded));
+    }
+}
@@ -0,0 +1,105 @@
+use alloc::vec::Vec;
+use embassy_time::{Duration, Instant};
+
+/// Default duration during which repeated events for the same key are suppressed
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(30);
+
+/// Max number of keys to keep track of. If exceeded, the oldest key is forgotten.
+const MAX_KEYS: usize = 8;
+
+/// Cooldown tracker
+/// Keeps track of recent events per key (e.g. denied NFC uids) and determines whether an event
+/// should be reported or suppressed, because the same key already had an event within the cooldown
+/// window. Time is passed in by the caller, so it can be tested without a real clock.
+#[derive(Debug)]
+pub struct Cooldown<K> {
+    window: Duration,
+    recent: Vec<(K, Instant)>,
+}
+
+impl<K: Clone + PartialEq> Cooldown<K> {
+    /// Create new cooldown tracker with given window
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            recent: Vec::new(),
+        }
+    }
+
+    /// Register an event for the given key at the given time. Returns true if the event should be
+    /// reported, false if it should be suppressed since the key already had a reported event
+    /// within the window.
+    pub fn check(&mut self, key: &K, now: Instant) -> bool {
+        // Forget keys whose window has passed
+        let window = self.window;
+        self.recent
+            .retain(|(_key, since)| now.saturating_duration_since(*since) < window);
+        if self.recent.iter().any(|(k, _since)| k == key) {
+            return false;
+        }
+        if self.recent.len() >= MAX_KEYS {
+            self.recent.remove(0);
+        }
+        self.recent.push((key.clone(), now));
+        true
+    }
+
+    /// Forget all keys, e.g. after a successful action
+    pub fn reset(&mut self) {
+        self.recent.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cooldown() -> Cooldown<u32> {
+        Cooldown::new(Duration::from_secs(10))
+    }
+
+    #[test]
+    fn first_event_reported() {
+        let mut cooldown = cooldown();
+        assert!(cooldown.check(&1, Instant::from_secs(0)));
+        assert!(cooldown.check(&2, Instant::from_secs(1)));
+    }
+
+    #[test]
+    fn repeated_events_suppressed() {
+        let mut cooldown = cooldown();
+        assert!(cooldown.check(&1, Instant::from_secs(0)));
+        assert!(!cooldown.check(&1, Instant::from_secs(1)));
+        assert!(!cooldown.check(&1, Instant::from_secs(9)));
+    }
+
+    #[test]
+    fn reported_again_after_window() {
+        let mut cooldown = cooldown();
+        assert!(cooldown.check(&1, Instant::from_secs(0)));
+        assert!(!cooldown.check(&1, Instant::from_secs(5)));
+        // Window counts from the reported event, suppressed events don't extend it
+        assert!(cooldown.check(&1, Instant::from_secs(10)));
+        assert!(!cooldown.check(&1, Instant::from_secs(11)));
+    }
+
+    #[test]
+    fn reset_forgets_keys() {
+        let mut cooldown = cooldown();
+        assert!(cooldown.check(&1, Instant::from_secs(0)));
+        cooldown.reset();
+        assert!(cooldown.check(&1, Instant::from_secs(1)));
+    }
+
+    #[test]
+    fn oldest_key_forgotten_if_full() {
+        let mut cooldown = cooldown();
+        let max = u32::try_from(MAX_KEYS).unwrap();
+        for key in 0..=max {
+            assert!(cooldown.check(&key, Instant::from_secs(0)));
+        }
+        // Key 0 was dropped to make room, others are still tracked
+        assert!(cooldown.check(&0, Instant::from_secs(1)));
+        assert!(!cooldown.check(&max, Instant::from_secs(1)));
+    }
+}
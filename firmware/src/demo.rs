@@ -0,0 +1,176 @@
+use crate::article::{ArticleId, Articles};
+use crate::queue::Purchase;
+use crate::time;
+use crate::user::{UserId, Users};
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use log::info;
+
+/// Max number of purchases to keep in the demo log. If exceeded, the oldest purchases are dropped.
+const MAX_PURCHASES: usize = 50;
+
+/// Price of placeholder articles
+const PLACEHOLDER_PRICE: f32 = 1.0;
+
+/// User id of the demo user that any unknown id card authenticates as in demo mode. Purchases in
+/// demo mode are never submitted, so it doesn't matter if a real member has this id.
+pub const DEMO_USER_ID: UserId = 0;
+
+/// Add placeholder information for all articles without information (e.g. if articles were never
+/// fetched from Vereinsflieger), so that every configured article can be purchased in demo mode.
+/// Returns the number of placeholder articles added.
+pub fn add_placeholder_articles(articles: &mut Articles) -> usize {
+    let missing: Vec<(usize, ArticleId)> = articles
+        .ids()
+        .enumerate()
+        .filter(|(_idx, id)| articles.get(*id).is_none())
+        .map(|(idx, id)| (idx, id.clone()))
+        .collect();
+    for (idx, id) in &missing {
+        articles.update(id, format!("Demo {}", idx + 1), PLACEHOLDER_PRICE, None);
+    }
+    missing.len()
+}
+
+/// Add the demo user, so that anybody can purchase in demo mode, even without users ever
+/// fetched from Vereinsflieger (e.g. in release builds, which don't have built-in test cards)
+pub fn add_demo_user(users: &mut Users) {
+    users.update_user(DEMO_USER_ID, String::from("Demo"));
+}
+
+/// Log of purchases made in demo mode. Purchases are only kept in memory and never submitted.
+#[derive(Debug, Default)]
+pub struct DemoLog {
+    purchases: VecDeque<Purchase>,
+    next_id: u32,
+}
+
+impl DemoLog {
+    /// Create new empty demo log
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of logged purchases
+    #[allow(dead_code)]
+    pub fn len(&self) -> usize {
+        self.purchases.len()
+    }
+
+    /// Log a purchase
+    pub fn push(
+        &mut self,
+        user_id: UserId,
+        article_id: ArticleId,
+        amount: f32,
+        total_price: f32,
+        salestax: Option<f32>,
+        callsign: Option<String>,
+    ) -> Purchase {
+        let purchase = Purchase {
+            id: self.next_id,
+            user_id,
+            article_id,
+            amount,
+            total_price,
+            salestax,
+            callsign,
+            time: time::now(),
+        };
+        info!(
+            "Demo: Logged purchase {}: {}x {}, {:.02} for user {}",
+            purchase.id, purchase.amount, purchase.article_id, purchase.total_price, user_id
+        );
+        self.next_id = self.next_id.wrapping_add(1);
+        if self.purchases.len() >= MAX_PURCHASES {
+            self.purchases.pop_front();
+        }
+        self.purchases.push_back(purchase.clone());
+        purchase
+    }
+
+    /// Remove purchase with given id. Returns false if there was no such purchase.
+    pub fn remove(&mut self, id: u32) -> bool {
+        let len = self.purchases.len();
+        self.purchases.retain(|purchase| purchase.id != id);
+        self.purchases.len() != len
+    }
+
+    /// Iterate over logged purchases (oldest first)
+    #[allow(dead_code)]
+    pub fn iter(&self) -> impl Iterator<Item = &Purchase> {
+        self.purchases.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn articles() -> Articles {
+        let mut articles = Articles::new(["1", "2", "3"].map(String::from).to_vec());
+        articles.update(&"2".to_string(), "Coffee".into(), 1.5, None);
+        articles
+    }
+
+    #[test]
+    fn placeholder_articles() {
+        let mut articles = articles();
+        assert_eq!(add_placeholder_articles(&mut articles), 2);
+        let names: Vec<_> = articles
+            .iter()
+            .map(|(idx, _id, article)| (idx, article.name.as_str(), article.price))
+            .collect();
+        assert_eq!(
+            names,
+            [(0, "Demo 1", 1.0), (1, "Coffee", 1.5), (2, "Demo 3", 1.0)]
+        );
+        // Nothing left to add
+        assert_eq!(add_placeholder_articles(&mut articles), 0);
+    }
+
+    #[test]
+    fn demo_user() {
+        let mut users = Users::new();
+        add_demo_user(&mut users);
+        assert_eq!(
+            users.get(DEMO_USER_ID).map(|u| u.name.as_str()),
+            Some("Demo")
+        );
+    }
+
+    #[test]
+    fn log_purchases() {
+        let mut log = DemoLog::new();
+        let p1 = log.push(1234, "1".into(), 2.0, 3.0, None, None);
+        let p2 = log.push(5678, "2".into(), 1.0, 1.5, Some(19.0), None);
+        assert_ne!(p1.id, p2.id);
+        assert_eq!(log.len(), 2);
+        let purchases: Vec<_> = log.iter().cloned().collect();
+        assert_eq!(purchases, [p1, p2]);
+    }
+
+    #[test]
+    fn remove_purchase() {
+        let mut log = DemoLog::new();
+        let p1 = log.push(1234, "1".into(), 2.0, 3.0, None, None);
+        let p2 = log.push(1234, "1".into(), 2.0, 3.0, None, None);
+        assert!(log.remove(p1.id));
+        assert!(!log.remove(p1.id));
+        let ids: Vec<_> = log.iter().map(|purchase| purchase.id).collect();
+        assert_eq!(ids, [p2.id]);
+    }
+
+    #[test]
+    fn log_drops_oldest() {
+        let mut log = DemoLog::new();
+        for _ in 0..MAX_PURCHASES + 5 {
+            log.push(1234, "1".into(), 1.0, 1.0, None, None);
+        }
+        assert_eq!(log.len(), MAX_PURCHASES);
+        assert_eq!(log.iter().next().map(|purchase| purchase.id), Some(5));
+    }
+}
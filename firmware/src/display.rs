@@ -1,14 +1,168 @@
+use crate::flash::Checksum;
+use crate::language::Language;
 use crate::screen::{self, Screen};
 use core::fmt;
+use embassy_time::{Duration, Timer};
+use embedded_graphics::image::{Image, ImageRaw};
 use embedded_graphics::pixelcolor::BinaryColor;
 use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use embedded_hal::i2c::I2c as BlockingI2c;
 use embedded_hal_async::i2c::I2c;
-use log::{debug, info};
-use ssd1306::mode::{BufferedGraphicsModeAsync, DisplayConfigAsync};
-use ssd1306::prelude::I2CInterface;
+use log::{debug, info, warn};
+use ssd1306::mode::{BufferedGraphicsModeAsync, DisplayConfig, DisplayConfigAsync};
+use ssd1306::prelude::{Brightness, I2CInterface};
 use ssd1306::rotation::DisplayRotation;
-use ssd1306::size::DisplaySize128x64;
-use ssd1306::Ssd1306Async;
+#[cfg(feature = "display-128x32")]
+use ssd1306::size::DisplaySize128x32 as PanelSize;
+#[cfg(not(feature = "display-128x32"))]
+use ssd1306::size::DisplaySize128x64 as PanelSize;
+use ssd1306::{Ssd1306, Ssd1306Async};
+
+/// Display brightness to use while dimmed (percentage, 0-100)
+const DIM_BRIGHTNESS: u8 = 0;
+
+/// How long the display stays inverted (and normal) per flash
+const FLASH_INTERVAL: Duration = Duration::from_millis(150);
+
+/// Display contrast to use for given brightness level (percentage, 0-100)
+fn contrast(level: u8) -> u8 {
+    #[allow(clippy::cast_possible_truncation)]
+    let contrast = (u16::from(level.min(100)) * 255 / 100) as u8;
+    contrast
+}
+
+/// Display brightness for given brightness level (percentage, 0-100). Without a level, the
+/// display's default brightness is used.
+fn brightness(level: Option<u8>) -> Brightness {
+    level.map_or_else(Brightness::default, |level| {
+        Brightness::custom(2, contrast(level))
+    })
+}
+
+/// Map rotation in degrees to display rotation. Only landscape orientations (0 and 180 degrees)
+/// are supported, since screens are laid out for a 128x64 (or 128x32) display.
+fn rotation(degrees: u16) -> Option<DisplayRotation> {
+    match degrees {
+        0 => Some(DisplayRotation::Rotate0),
+        180 => Some(DisplayRotation::Rotate180),
+        _ => None,
+    }
+}
+
+/// Invert states to step through for flashing the display the given number of times. Always ends
+/// with a non-inverted display.
+fn flash_steps(count: usize) -> impl Iterator<Item = bool> {
+    (0..count).flat_map(|_| [true, false])
+}
+
+/// Draw target that passes everything to a wrapped draw target and calculates a fingerprint
+/// (checksum) of everything drawn. Drawing the same content the same way results in the same
+/// fingerprint, so it can be used to detect whether content has changed.
+struct Fingerprint<'a, D> {
+    target: &'a mut D,
+    checksum: Checksum,
+}
+
+impl<'a, D> Fingerprint<'a, D> {
+    /// Create new fingerprinting draw target wrapping the given draw target
+    fn new(target: &'a mut D) -> Self {
+        Self {
+            target,
+            checksum: Checksum::new(),
+        }
+    }
+
+    /// Fingerprint of everything drawn so far
+    fn finish(&self) -> u32 {
+        self.checksum.value()
+    }
+
+    /// Add point and color to fingerprint
+    fn add_pixel(checksum: &mut Checksum, point: Point, color: BinaryColor) {
+        checksum.update(&point.x.to_le_bytes());
+        checksum.update(&point.y.to_le_bytes());
+        checksum.update(&[u8::from(color.is_on())]);
+    }
+}
+
+impl<D: Dimensions> Dimensions for Fingerprint<'_, D> {
+    fn bounding_box(&self) -> Rectangle {
+        self.target.bounding_box()
+    }
+}
+
+impl<D: DrawTarget<Color = BinaryColor>> DrawTarget for Fingerprint<'_, D> {
+    type Color = BinaryColor;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let checksum = &mut self.checksum;
+        self.target
+            .draw_iter(pixels.into_iter().inspect(|Pixel(point, color)| {
+                Self::add_pixel(checksum, *point, *color);
+            }))
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        let checksum = &mut self.checksum;
+        Self::add_pixel(checksum, area.top_left, BinaryColor::Off);
+        Self::add_pixel(checksum, Point::zero() + area.size, BinaryColor::Off);
+        self.target.fill_contiguous(
+            area,
+            colors.into_iter().inspect(|color| {
+                checksum.update(&[u8::from(color.is_on())]);
+            }),
+        )
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        Self::add_pixel(&mut self.checksum, area.top_left, color);
+        Self::add_pixel(&mut self.checksum, Point::zero() + area.size, color);
+        self.target.fill_solid(area, color)
+    }
+}
+
+/// Keeps track of the content currently shown on the display (by its fingerprint)
+#[derive(Debug, Default)]
+struct Shown(Option<u32>);
+
+impl Shown {
+    /// Set fingerprint of content to be shown. Returns true if it differs from the content
+    /// currently shown (or if the shown content is unknown).
+    fn update(&mut self, fingerprint: u32) -> bool {
+        self.0.replace(fingerprint) != Some(fingerprint)
+    }
+
+    /// Forget the shown content, e.g. because the display was cleared or turned off
+    fn invalidate(&mut self) {
+        self.0 = None;
+    }
+}
+
+/// Show given screen using a blocking display driver with default settings. This is meant to be
+/// used after a panic, when the system is in an undefined state and the async display driver
+/// can't be used anymore. Any error is ignored.
+pub fn show_blocking<I2C: BlockingI2c, S: Screen>(i2c: I2C, screen: &S) {
+    let mut driver = Ssd1306::new(
+        I2CInterface::new(i2c, 0x3c, 0x40),
+        PanelSize,
+        DisplayRotation::Rotate0,
+    )
+    .into_buffered_graphics_mode();
+    if driver.init().is_err() {
+        return;
+    }
+    let _ = driver.clear(BinaryColor::Off);
+    let _ = screen.draw(Language::default(), &mut driver);
+    let _ = driver.flush();
+}
 
 /// Display error
 #[derive(Debug)]
@@ -61,38 +215,112 @@ impl fmt::Display for Error {
 
 /// Convenient hardware-agnostic display driver
 pub struct Display<I2C> {
-    driver: Ssd1306Async<
-        I2CInterface<I2C>,
-        DisplaySize128x64,
-        BufferedGraphicsModeAsync<DisplaySize128x64>,
-    >,
+    driver: Ssd1306Async<I2CInterface<I2C>, PanelSize, BufferedGraphicsModeAsync<PanelSize>>,
+    brightness: Option<u8>,
+    inverted: bool,
+    language: Language,
+    shown: Shown,
 }
 
 impl<I2C: I2c> Display<I2C> {
-    /// Create display driver and initialize display hardware
-    pub async fn new(i2c: I2C) -> Result<Self, Error> {
+    /// Create display driver and initialize display hardware with given rotation (in degrees)
+    pub async fn new(i2c: I2C, rotation_degrees: u16) -> Result<Self, Error> {
         debug!("Display: Initializing SSD1306...");
 
+        let rotation = rotation(rotation_degrees).unwrap_or_else(|| {
+            warn!(
+                "Display: Unsupported rotation {}°, using 0°",
+                rotation_degrees
+            );
+            DisplayRotation::Rotate0
+        });
+
         // Build SSD1306 driver and switch to buffered graphics mode
-        let mut driver = Ssd1306Async::new(
-            I2CInterface::new(i2c, 0x3c, 0x40),
-            DisplaySize128x64,
-            DisplayRotation::Rotate0,
-        )
-        .into_buffered_graphics_mode();
+        let mut driver = Ssd1306Async::new(I2CInterface::new(i2c, 0x3c, 0x40), PanelSize, rotation)
+            .into_buffered_graphics_mode();
 
-        // Initialize and clear display
+        // Initialize and clear display (initializing sets default brightness)
         driver.init().await?;
         driver.clear(BinaryColor::Off)?;
         driver.flush().await?;
 
         info!("Display: SSD1306 initialized");
-        Ok(Self { driver })
+        Ok(Self {
+            driver,
+            brightness: None,
+            inverted: false,
+            language: Language::default(),
+            shown: Shown::default(),
+        })
+    }
+
+    /// Set language of screen texts
+    pub fn set_language(&mut self, language: Language) {
+        debug!("Display: Setting language to {:?}", language);
+        self.language = language;
+    }
+
+    /// Set display brightness (percentage, 0-100, or the display's default brightness if `None`)
+    pub async fn set_brightness(&mut self, level: Option<u8>) -> Result<(), Error> {
+        if let Some(level) = level {
+            debug!("Display: Setting brightness to {}%", level);
+        } else {
+            debug!("Display: Setting default brightness");
+        }
+        self.brightness = level.map(|level| level.min(100));
+        self.apply_brightness(self.brightness).await
+    }
+
+    /// Dim display (or restore brightness)
+    pub async fn set_dimmed(&mut self, dimmed: bool) -> Result<(), Error> {
+        debug!("Display: {}", if dimmed { "Dimming" } else { "Undimming" });
+        let level = if dimmed {
+            Some(DIM_BRIGHTNESS)
+        } else {
+            self.brightness
+        };
+        self.apply_brightness(level).await
+    }
+
+    /// Send brightness of given level to display
+    async fn apply_brightness(&mut self, level: Option<u8>) -> Result<(), Error> {
+        self.driver.set_brightness(brightness(level)).await?;
+        Ok(())
+    }
+
+    /// Invert display (or restore normal display). Inverting doesn't change the display buffer,
+    /// so it applies to whatever is currently shown.
+    pub async fn set_invert(&mut self, invert: bool) -> Result<(), Error> {
+        if invert == self.inverted {
+            return Ok(());
+        }
+        debug!(
+            "Display: {}",
+            if invert { "Inverting" } else { "Uninverting" }
+        );
+        self.driver.set_invert(invert).await?;
+        self.inverted = invert;
+        Ok(())
+    }
+
+    /// Flash display by briefly inverting it the given number of times. The display is always
+    /// left non-inverted, even if flashing fails.
+    pub async fn flash(&mut self, count: usize) -> Result<(), Error> {
+        for invert in flash_steps(count) {
+            if let Err(err) = self.set_invert(invert).await {
+                // Try to restore normal display in any case
+                let _ = self.set_invert(false).await;
+                return Err(err);
+            }
+            Timer::after(FLASH_INTERVAL).await;
+        }
+        Ok(())
     }
 
     /// Turn display off
     pub async fn turn_off(&mut self) -> Result<(), Error> {
         debug!("Display: Power off");
+        self.shown.invalidate();
         self.driver.set_display_on(false).await?;
         Ok(())
     }
@@ -100,18 +328,222 @@ impl<I2C: I2c> Display<I2C> {
     /// Clear display
     #[allow(dead_code)]
     pub async fn clear(&mut self) -> Result<(), Error> {
+        self.shown.invalidate();
         self.driver.clear(BinaryColor::Off)?;
         self.driver.flush().await?;
         self.driver.set_display_on(true).await?;
         Ok(())
     }
 
-    /// Show screen
+    /// Show screen. Skips sending to the display if the screen looks the same as the one shown.
     pub async fn screen<S: Screen>(&mut self, screen: &S) -> Result<(), Error> {
         self.driver.clear(BinaryColor::Off)?;
-        screen.draw(&mut self.driver)?;
+        let mut target = Fingerprint::new(&mut self.driver);
+        screen.draw(self.language, &mut target)?;
+        let fingerprint = target.finish();
+        if !self.shown.update(fingerprint) {
+            return Ok(());
+        }
+        if let Err(err) = self.flush_and_turn_on().await {
+            // Display content is unknown if sending failed
+            self.shown.invalidate();
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Show raw image at given position, e.g. a frame of a boot animation. Unlike screens, this
+    /// doesn't need to implement the `Screen` trait and is always sent to the display.
+    #[allow(dead_code)]
+    pub async fn draw_raw_framebuffer(
+        &mut self,
+        image: &ImageRaw<'_, BinaryColor>,
+        position: Point,
+    ) -> Result<(), Error> {
+        self.shown.invalidate();
+        self.driver.clear(BinaryColor::Off)?;
+        Image::new(image, position).draw(&mut self.driver)?;
+        self.flush_and_turn_on().await
+    }
+
+    /// Send display buffer to display and turn it on
+    async fn flush_and_turn_on(&mut self) -> Result<(), Error> {
         self.driver.flush().await?;
         self.driver.set_display_on(true).await?;
         Ok(())
     }
+
+    /// Advance animation of screen and show it again if needed
+    pub async fn animate<S: Screen>(&mut self, screen: &mut S) -> Result<(), Error> {
+        if screen.tick(self.language) {
+            self.screen(screen).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contrast_levels() {
+        assert_eq!(contrast(0), 0);
+        assert_eq!(contrast(50), 127);
+        assert_eq!(contrast(100), 255);
+    }
+
+    #[test]
+    fn contrast_clamped() {
+        assert_eq!(contrast(101), 255);
+        assert_eq!(contrast(255), 255);
+    }
+
+    #[test]
+    fn brightness_default() {
+        assert_eq!(brightness(None), Brightness::NORMAL);
+        assert_eq!(brightness(Some(100)), Brightness::BRIGHTEST);
+    }
+
+    #[test]
+    fn flash_steps_alternate() {
+        assert_eq!(
+            flash_steps(2).collect::<Vec<_>>(),
+            [true, false, true, false]
+        );
+    }
+
+    #[test]
+    fn flash_steps_end_non_inverted() {
+        assert_eq!(flash_steps(0).count(), 0);
+        for count in 1..5 {
+            assert_eq!(flash_steps(count).last(), Some(false));
+        }
+    }
+
+    /// I2C bus that accepts everything and records all written bytes
+    #[derive(Debug, Default)]
+    struct RecordingI2c(Vec<Vec<u8>>);
+
+    impl embedded_hal_async::i2c::ErrorType for RecordingI2c {
+        type Error = core::convert::Infallible;
+    }
+
+    impl I2c for RecordingI2c {
+        async fn transaction(
+            &mut self,
+            _address: u8,
+            operations: &mut [embedded_hal_async::i2c::Operation<'_>],
+        ) -> Result<(), Self::Error> {
+            for operation in operations {
+                if let embedded_hal_async::i2c::Operation::Write(bytes) = operation {
+                    self.0.push(bytes.to_vec());
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[async_std::test]
+    async fn invert_command_sequence() {
+        let mut display = Display::new(RecordingI2c::default(), 0).await.unwrap();
+        display.set_invert(true).await.unwrap();
+        // Already inverted, nothing sent
+        display.set_invert(true).await.unwrap();
+        display.set_invert(false).await.unwrap();
+        let i2c = display.driver.release().release();
+        // Commands sent after initialization (0xa7 inverted, 0xa6 normal)
+        let commands: Vec<_> = i2c
+            .0
+            .iter()
+            .skip_while(|bytes| bytes.as_slice() != [0x00, 0xa7])
+            .cloned()
+            .collect();
+        assert_eq!(commands, [[0x00, 0xa7], [0x00, 0xa6]]);
+    }
+
+    use embedded_graphics::mock_display::MockDisplay;
+    use embedded_graphics::primitives::{PrimitiveStyle, StyledDrawable};
+
+    /// Draw a small rectangle at the given position and return its fingerprint
+    fn fingerprint(x: i32, fill: bool) -> u32 {
+        let mut display = MockDisplay::<BinaryColor>::new();
+        let mut target = Fingerprint::new(&mut display);
+        let style = if fill {
+            PrimitiveStyle::with_fill(BinaryColor::On)
+        } else {
+            PrimitiveStyle::with_stroke(BinaryColor::On, 1)
+        };
+        Rectangle::new(Point::new(x, 1), Size::new(4, 3))
+            .draw_styled(&style, &mut target)
+            .unwrap();
+        target.finish()
+    }
+
+    #[test]
+    fn fingerprint_same_content() {
+        assert_eq!(fingerprint(1, true), fingerprint(1, true));
+        assert_eq!(fingerprint(1, false), fingerprint(1, false));
+    }
+
+    #[test]
+    fn fingerprint_different_content() {
+        assert_ne!(fingerprint(1, true), fingerprint(2, true));
+        assert_ne!(fingerprint(1, true), fingerprint(1, false));
+        assert_ne!(fingerprint(1, false), fingerprint(2, false));
+    }
+
+    #[test]
+    fn fingerprint_nothing_drawn() {
+        let mut display = MockDisplay::<BinaryColor>::new();
+        let target = Fingerprint::new(&mut display);
+        assert_eq!(target.finish(), Checksum::new().value());
+    }
+
+    #[test]
+    fn fingerprint_draws_to_target() {
+        let mut display = MockDisplay::<BinaryColor>::new();
+        let mut target = Fingerprint::new(&mut display);
+        Rectangle::new(Point::new(1, 1), Size::new(2, 2))
+            .draw_styled(&PrimitiveStyle::with_fill(BinaryColor::On), &mut target)
+            .unwrap();
+        display.assert_pattern(&[
+            "   ", //
+            " ##", //
+            " ##", //
+        ]);
+    }
+
+    #[test]
+    fn shown_skips_unchanged() {
+        let mut shown = Shown::default();
+        assert!(shown.update(1));
+        assert!(!shown.update(1));
+        assert!(shown.update(2));
+        assert!(!shown.update(2));
+        assert!(shown.update(1));
+    }
+
+    #[test]
+    fn shown_invalidated() {
+        let mut shown = Shown::default();
+        assert!(shown.update(1));
+        shown.invalidate();
+        assert!(shown.update(1));
+        assert!(!shown.update(1));
+    }
+
+    #[test]
+    fn rotation_supported() {
+        assert!(matches!(rotation(0), Some(DisplayRotation::Rotate0)));
+        assert!(matches!(rotation(180), Some(DisplayRotation::Rotate180)));
+    }
+
+    #[test]
+    fn rotation_unsupported() {
+        assert!(rotation(90).is_none());
+        assert!(rotation(270).is_none());
+        assert!(rotation(45).is_none());
+        assert!(rotation(360).is_none());
+    }
 }
@@ -1,6 +1,6 @@
 use crate::json::{self, ToJson};
 use crate::user::UserId;
-use crate::{display, nfc, vereinsflieger};
+use crate::{display, nfc, queue, vereinsflieger};
 use alloc::string::ToString;
 use core::fmt;
 use core::future::Future;
@@ -47,6 +47,11 @@ impl Error {
         matches!(self.kind, ErrorKind::UserTimeout)
     }
 
+    /// Stable numeric error code (see `ErrorKind::code`)
+    pub fn code(&self) -> u8 {
+        self.kind.code()
+    }
+
     /// User whose action caused the error, if any
     pub fn user_id(&self) -> Option<UserId> {
         self.user_id
@@ -88,6 +93,8 @@ pub enum ErrorKind {
     NFCError(nfc::Error),
     /// Vereinsflieger API error
     VereinsfliegerError(vereinsflieger::Error),
+    /// Purchase queue error
+    QueueError(queue::Error),
     /// User cancel request
     Cancel,
     /// User interaction timeout
@@ -96,6 +103,27 @@ pub enum ErrorKind {
     NoNetwork,
     /// The specified article was not found
     ArticleNotFound,
+    /// No articles are configured
+    NoArticles,
+}
+
+impl ErrorKind {
+    /// Stable numeric error code, e.g. for telemetry or to be shown on screen. The upper nibble
+    /// denotes the failing subsystem (0 for general errors). Codes must never change or be
+    /// reused, so that operators can rely on them.
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::Cancel => 0x01,
+            Self::UserTimeout => 0x02,
+            Self::NoNetwork => 0x03,
+            Self::ArticleNotFound => 0x04,
+            Self::NoArticles => 0x05,
+            Self::DisplayError(_) => 0x10,
+            Self::NFCError(_) => 0x20,
+            Self::VereinsfliegerError(_) => 0x30,
+            Self::QueueError(_) => 0x40,
+        }
+    }
 }
 
 impl From<display::Error> for ErrorKind {
@@ -116,16 +144,24 @@ impl From<vereinsflieger::Error> for ErrorKind {
     }
 }
 
+impl From<queue::Error> for ErrorKind {
+    fn from(err: queue::Error) -> Self {
+        Self::QueueError(err)
+    }
+}
+
 impl fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::DisplayError(err) => write!(f, "Display: {err}"),
             Self::NFCError(err) => write!(f, "NFC: {err}"),
             Self::VereinsfliegerError(err) => write!(f, "Vereinsflieger: {err}"),
+            Self::QueueError(err) => write!(f, "Queue: {err}"),
             Self::Cancel => write!(f, "User cancelled"),
             Self::UserTimeout => write!(f, "Timeout waiting for input"),
             Self::NoNetwork => write!(f, "No network connection"),
             Self::ArticleNotFound => write!(f, "Article not found"),
+            Self::NoArticles => write!(f, "No articles configured"),
         }
     }
 }
@@ -138,3 +174,36 @@ impl ToJson for ErrorKind {
         json.write(self.to_string()).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn general_codes() {
+        assert_eq!(ErrorKind::Cancel.code(), 0x01);
+        assert_eq!(ErrorKind::UserTimeout.code(), 0x02);
+        assert_eq!(ErrorKind::NoNetwork.code(), 0x03);
+        assert_eq!(ErrorKind::ArticleNotFound.code(), 0x04);
+        assert_eq!(ErrorKind::NoArticles.code(), 0x05);
+    }
+
+    #[test]
+    fn subsystem_codes() {
+        let err = display::Error::InterfaceError(display_interface::DisplayError::BusWriteError);
+        assert_eq!(ErrorKind::DisplayError(err).code(), 0x10);
+        assert_eq!(ErrorKind::NFCError(nfc::Error::Timeout).code(), 0x20);
+        let err = vereinsflieger::Error::Timeout;
+        assert_eq!(ErrorKind::VereinsfliegerError(err).code(), 0x30);
+        assert_eq!(ErrorKind::QueueError(queue::Error::Full).code(), 0x40);
+    }
+
+    #[test]
+    fn error_code() {
+        let err = Error::from(queue::Error::Storage);
+        assert_eq!(err.code(), 0x40);
+        let err = Error::try_with(1234, || Err::<(), _>(ErrorKind::NoArticles.into())).unwrap_err();
+        assert_eq!(err.code(), 0x05);
+        assert_eq!(err.user_id(), Some(1234));
+    }
+}
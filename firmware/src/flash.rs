@@ -0,0 +1,291 @@
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use core::fmt;
+use core::future::{self, Future};
+use embedded_io_async::{BufRead, ErrorKind, ErrorType, Read, Write};
+use embedded_storage::{ReadStorage, Storage};
+use esp_partition_table::{PartitionTable, PartitionType};
+use esp_storage::{FlashStorage, FlashStorageError};
+use log::debug;
+
+/// Custom partition type of data partitions used by this firmware
+const PARTITION_TYPE: u8 = 0x54;
+
+/// Size of chunks to read when streaming data from a partition
+const READ_CHUNK_SIZE: usize = 256;
+
+/// Size of chunks to write when streaming data to a partition (one flash sector)
+const WRITE_CHUNK_SIZE: usize = FlashStorage::SECTOR_SIZE as usize;
+
+/// Flash storage error
+#[derive(Debug)]
+pub enum Error {
+    /// Data partition not found in partition table
+    PartitionNotFound,
+    /// Data doesn't fit into partition
+    TooLarge,
+    /// Flash storage error
+    Storage(FlashStorageError),
+}
+
+impl From<FlashStorageError> for Error {
+    fn from(err: FlashStorageError) -> Self {
+        Self::Storage(err)
+    }
+}
+
+impl embedded_io_async::Error for Error {
+    fn kind(&self) -> ErrorKind {
+        ErrorKind::Other
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PartitionNotFound => write!(f, "Partition not found"),
+            Self::TooLarge => write!(f, "Data too large"),
+            Self::Storage(_err) => write!(f, "Flash storage error"),
+        }
+    }
+}
+
+/// Flash data partition
+///
+/// Data partitions used by this firmware use the custom partition type 0x54 and a subtype to
+/// tell them apart (see `partitions.csv`).
+#[derive(Debug)]
+pub struct Partition {
+    storage: FlashStorage,
+    offset: u32,
+    size: usize,
+}
+
+impl Partition {
+    /// Look up data partition with given subtype in partition table
+    pub fn find(subtype: u8) -> Result<Self, Error> {
+        let mut storage = FlashStorage::new();
+
+        // Read partition table (at 0x8000 by default)
+        let table = PartitionTable::default();
+        debug!(
+            "Flash: Reading partition table at 0x{:x} for partition 0x{:02x}",
+            table.addr, subtype
+        );
+
+        // Look up data partition by custom partition type and given subtype
+        let (offset, size) = table
+            .iter_storage(&mut storage, false)
+            .flatten()
+            .find(|partition| partition.type_ == PartitionType::User(PARTITION_TYPE, subtype))
+            .map(|partition| (partition.offset, partition.size))
+            .ok_or(Error::PartitionNotFound)?;
+        debug!(
+            "Flash: Found partition 0x{:02x} at offset 0x{:x}, size 0x{:x}",
+            subtype, offset, size
+        );
+
+        Ok(Self {
+            storage,
+            offset,
+            size,
+        })
+    }
+
+    /// Read from start of partition to fill the given buffer
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<(), Error> {
+        self.read_at(0, buf)
+    }
+
+    /// Read from given offset in partition to fill the given buffer
+    pub fn read_at(&mut self, offset: usize, buf: &mut [u8]) -> Result<(), Error> {
+        if offset + buf.len() > self.size {
+            return Err(Error::TooLarge);
+        }
+        let offset = u32::try_from(offset).map_err(|_| Error::TooLarge)?;
+        self.storage.read(self.offset + offset, buf)?;
+        Ok(())
+    }
+
+    /// Stream data from start of partition. Reads in small chunks, so data larger than the
+    /// available memory can be parsed.
+    pub fn reader(&mut self) -> Reader<'_> {
+        Reader {
+            partition: self,
+            offset: 0,
+            buf: [0; READ_CHUNK_SIZE],
+            pos: 0,
+            len: 0,
+        }
+    }
+
+    /// Stream data to start of partition. Writes sector by sector, so data larger than the
+    /// available memory can be stored. Remaining bytes of the last written sector are left
+    /// untouched, so data should be self-delimiting (like JSON). `flush` needs to be called to
+    /// write the last sector.
+    pub fn writer(&mut self) -> Writer<'_> {
+        Writer {
+            partition: self,
+            offset: 0,
+            buf: Vec::with_capacity(WRITE_CHUNK_SIZE),
+        }
+    }
+
+    /// Write given data to start of partition. Remaining bytes of the last written sector are
+    /// left untouched, so data should be self-delimiting (like JSON) or padded by the caller.
+    #[allow(dead_code)]
+    pub fn write(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.write_at(0, bytes)
+    }
+
+    /// Write given data to given offset in partition. Data is written sector by sector. If power
+    /// fails while writing (brown-out), the written sectors may be left in an inconsistent state,
+    /// so data should carry a checksum if it needs to be verified.
+    pub fn write_at(&mut self, offset: usize, bytes: &[u8]) -> Result<(), Error> {
+        if offset + bytes.len() > self.size {
+            return Err(Error::TooLarge);
+        }
+        let mut offset = self.offset + u32::try_from(offset).map_err(|_| Error::TooLarge)?;
+        for chunk in bytes.chunks(FlashStorage::SECTOR_SIZE as usize) {
+            self.storage.write(offset, chunk)?;
+            // Chunks are at most a sector in size, conversion can't fail
+            offset += u32::try_from(chunk.len()).unwrap_or(FlashStorage::SECTOR_SIZE);
+        }
+        Ok(())
+    }
+}
+
+/// Writer that calculates a FNV-1a checksum of written data, e.g. to tell if stored data changed
+/// or to verify stored data
+#[derive(Debug)]
+pub struct Checksum(u32);
+
+impl Checksum {
+    pub fn new() -> Self {
+        Self(0x811c_9dc5)
+    }
+
+    /// Add given bytes to checksum
+    pub fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u32::from(*byte)).wrapping_mul(0x0100_0193);
+        }
+    }
+
+    /// Checksum of data written so far
+    pub fn value(&self) -> u32 {
+        self.0
+    }
+}
+
+impl ErrorType for Checksum {
+    type Error = Infallible;
+}
+
+impl Write for Checksum {
+    fn write(&mut self, buf: &[u8]) -> impl Future<Output = Result<usize, Self::Error>> {
+        self.update(buf);
+        future::ready(Ok(buf.len()))
+    }
+}
+
+/// Buffered writer for streaming data to a partition
+#[derive(Debug)]
+pub struct Writer<'a> {
+    partition: &'a mut Partition,
+    offset: usize,
+    buf: Vec<u8>,
+}
+
+impl Writer<'_> {
+    /// Number of bytes written so far (including buffered bytes)
+    pub fn position(&self) -> usize {
+        self.offset + self.buf.len()
+    }
+
+    /// Write buffered bytes to partition
+    fn write_buf(&mut self) -> Result<(), Error> {
+        self.partition.write_at(self.offset, &self.buf)?;
+        self.offset += self.buf.len();
+        self.buf.clear();
+        Ok(())
+    }
+}
+
+impl ErrorType for Writer<'_> {
+    type Error = Error;
+}
+
+impl Write for Writer<'_> {
+    fn write(&mut self, buf: &[u8]) -> impl Future<Output = Result<usize, Self::Error>> {
+        // Buffer up to a sector and write it once it's full. Writing flash is blocking, so the
+        // write is always completed right away.
+        let len = buf.len().min(WRITE_CHUNK_SIZE - self.buf.len());
+        self.buf.extend_from_slice(&buf[..len]);
+        let res = if self.buf.len() >= WRITE_CHUNK_SIZE {
+            self.write_buf()
+        } else {
+            Ok(())
+        };
+        future::ready(res.map(|()| len))
+    }
+
+    fn flush(&mut self) -> impl Future<Output = Result<(), Self::Error>> {
+        let res = if self.buf.is_empty() {
+            Ok(())
+        } else {
+            self.write_buf()
+        };
+        future::ready(res)
+    }
+}
+
+/// Buffered reader for streaming data from a partition
+#[derive(Debug)]
+pub struct Reader<'a> {
+    partition: &'a mut Partition,
+    offset: usize,
+    buf: [u8; READ_CHUNK_SIZE],
+    pos: usize,
+    len: usize,
+}
+
+impl Reader<'_> {
+    /// Read next chunk if buffer is exhausted. At end of partition, the buffer stays empty.
+    fn fill_chunk(&mut self) -> Result<(), Error> {
+        if self.pos >= self.len {
+            let len = READ_CHUNK_SIZE.min(self.partition.size - self.offset);
+            self.partition.read_at(self.offset, &mut self.buf[..len])?;
+            self.offset += len;
+            self.pos = 0;
+            self.len = len;
+        }
+        Ok(())
+    }
+}
+
+impl ErrorType for Reader<'_> {
+    type Error = Error;
+}
+
+impl Read for Reader<'_> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let data = self.fill_buf().await?;
+        let len = data.len().min(buf.len());
+        buf[..len].copy_from_slice(&data[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl BufRead for Reader<'_> {
+    fn fill_buf(&mut self) -> impl Future<Output = Result<&[u8], Self::Error>> {
+        // Reading flash is blocking, so data is always ready
+        let res = self.fill_chunk();
+        future::ready(res.map(|()| &self.buf[self.pos..self.len]))
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.len);
+    }
+}
@@ -0,0 +1,41 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+use esp_alloc::HEAP;
+
+/// Size of the heap in bytes
+pub const SIZE: usize = 150 * 1024;
+
+/// Highest heap usage seen when sampling
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+/// Heap usage statistics (in bytes)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// Currently used
+    pub used: usize,
+    /// Currently free
+    pub free: usize,
+    /// Highest usage seen when sampling. Usage is only sampled at points where memory usage is
+    /// expected to be high, so the actual peak may be higher.
+    pub peak: usize,
+}
+
+/// Sample current heap usage to keep track of peak usage
+pub fn sample() -> usize {
+    let used = HEAP.used();
+    // Compare and swap isn't available on this target, but there's no concurrent access since
+    // everything runs on a single executor
+    if used > PEAK.load(Ordering::Relaxed) {
+        PEAK.store(used, Ordering::Relaxed);
+    }
+    used
+}
+
+/// Current heap usage statistics
+pub fn stats() -> Stats {
+    let used = sample();
+    Stats {
+        used,
+        free: HEAP.free(),
+        peak: PEAK.load(Ordering::Relaxed),
+    }
+}
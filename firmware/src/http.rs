@@ -6,16 +6,18 @@ use alloc::vec::Vec;
 use chrono::DateTime;
 use core::convert::Infallible;
 use core::{fmt, str};
-use embedded_io_async::{BufRead, Read};
-use log::debug;
+use embassy_time::{Duration, Instant};
+use embedded_io_async::{BufRead, ErrorType, Read};
+use log::{debug, warn};
 use reqwless::client::{HttpClient, HttpResource, HttpResourceRequestBuilder};
 use reqwless::client::{TlsConfig, TlsVerify};
-use reqwless::headers::ContentType;
+use reqwless::headers::{ContentType, TransferEncoding};
 use reqwless::request::{RequestBody, RequestBuilder};
 use reqwless::response::{BodyReader, StatusCode};
 
-/// Maximum size of response from server
-const MAX_RESPONSE_SIZE: usize = 4096;
+/// Default size of receive buffer. Response headers must fit into it, and responses that are
+/// read as a whole (not streamed) must fit into it as well.
+pub const DEFAULT_RX_BUFFER_SIZE: usize = 4096;
 
 /// TLS read buffer size
 const READ_BUFFER_SIZE: usize = 16640;
@@ -39,6 +41,10 @@ pub enum Error {
     ServerError(StatusCode),
     /// Response could not be parsed
     MalformedResponse(json::Error<reqwless::Error>),
+    /// Response header doesn't fit into receive buffer
+    HeaderTooLarge,
+    /// Response body doesn't fit into buffer
+    BodyBufferTooSmall,
 }
 
 impl From<reqwless::Error> for Error {
@@ -47,6 +53,37 @@ impl From<reqwless::Error> for Error {
     }
 }
 
+impl From<json::Error<reqwless::Error>> for Error {
+    fn from(err: json::Error<reqwless::Error>) -> Self {
+        match err {
+            json::Error::Io(reqwless::Error::BufferTooSmall) => Self::BodyBufferTooSmall,
+            err => Self::MalformedResponse(err),
+        }
+    }
+}
+
+impl Error {
+    /// Error for an unsuccessful HTTP status
+    pub fn from_status(status: StatusCode) -> Self {
+        if status.0 == 401 {
+            Self::Unauthorized
+        } else if status.is_server_error() {
+            Self::ServerError(status)
+        } else {
+            Self::BadRequest(status)
+        }
+    }
+
+    /// Error of sending a request. Since the response header is buffered in the receive buffer,
+    /// a buffer that is too small means that the response header is too large.
+    fn from_send(err: reqwless::Error) -> Self {
+        match err {
+            reqwless::Error::BufferTooSmall => Self::HeaderTooLarge,
+            err => Self::Network(err),
+        }
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -56,22 +93,75 @@ impl fmt::Display for Error {
             Self::BadRequest(status) => write!(f, "Bad request ({})", status.0),
             Self::ServerError(status) => write!(f, "Server error ({})", status.0),
             Self::MalformedResponse(_err) => write!(f, "Malformed response"),
+            Self::HeaderTooLarge => write!(f, "Response header too large"),
+            Self::BodyBufferTooSmall => write!(f, "Response too large"),
         }
     }
 }
 
+/// Bookkeeping of requests sent over a connection, to decide whether the connection can be
+/// reused for further requests
+#[derive(Debug)]
+struct Reuse {
+    /// Time it took to establish the connection (DNS, TCP and TLS handshake)
+    connect_time: Duration,
+    /// Number of requests sent over the connection
+    requests: u32,
+    /// Whether the connection can be used for further requests
+    reusable: bool,
+}
+
+impl Reuse {
+    /// Bookkeeping of a new connection that took the given time to establish
+    fn new(connect_time: Duration) -> Self {
+        Self {
+            connect_time,
+            requests: 0,
+            reusable: true,
+        }
+    }
+
+    /// Begin sending a request. Fails if the connection can't be used anymore (callers should
+    /// check `Connection::is_reusable` and reconnect instead).
+    fn begin(&mut self) -> Result<(), Error> {
+        if !self.reusable {
+            return Err(Error::Network(reqwless::Error::ConnectionAborted));
+        }
+        self.requests += 1;
+        Ok(())
+    }
+
+    /// Response received. The connection can only be reused if the server keeps it open.
+    fn received(&mut self, keep_alive: bool) {
+        self.reusable &= keep_alive;
+    }
+
+    /// Request failed, state of the connection is unknown
+    fn failed(&mut self) {
+        self.reusable = false;
+    }
+
+    /// Estimated time saved by not reconnecting for every request
+    fn saved_time(&self) -> Duration {
+        self.connect_time * self.requests.saturating_sub(1)
+    }
+}
+
 /// HTTP client resources
+#[allow(clippy::struct_field_names)]
 pub struct Resources {
     read_buffer: Vec<u8>,
     write_buffer: Vec<u8>,
+    rx_buffer: Vec<u8>,
 }
 
 impl Resources {
-    /// Create new HTTP client resources
-    pub fn new() -> Self {
+    /// Create new HTTP client resources with given receive buffer size
+    pub fn new(rx_buffer_size: usize) -> Self {
         Self {
             read_buffer: vec![0; READ_BUFFER_SIZE],
             write_buffer: vec![0; WRITE_BUFFER_SIZE],
+            rx_buffer: vec![0; rx_buffer_size],
         }
     }
 }
@@ -79,6 +169,7 @@ impl Resources {
 /// HTTP client
 pub struct Http<'a> {
     client: HttpClient<'a, TcpClient<'a>, DnsSocket<'a>>,
+    rx_buffer: &'a mut [u8],
 }
 
 impl fmt::Debug for Http<'_> {
@@ -101,7 +192,10 @@ impl<'a> Http<'a> {
         );
         let client = HttpClient::new_with_tls(wifi.tcp(), wifi.dns(), tls_config);
 
-        Self { client }
+        Self {
+            client,
+            rx_buffer: &mut resources.rx_buffer,
+        }
     }
 
     /// Connect to HTTP server
@@ -109,16 +203,30 @@ impl<'a> Http<'a> {
         &'conn mut self,
         base_url: &'conn str,
     ) -> Result<Connection<'conn>, Error> {
+        let start = Instant::now();
         let resource = self.client.resource(base_url).await?;
-        debug!("HTTP: Connected {}", base_url);
+        let connect_time = start.elapsed();
+        debug!(
+            "HTTP: Connected {} ({}ms)",
+            base_url,
+            connect_time.as_millis()
+        );
 
-        Ok(Connection { resource })
+        Ok(Connection {
+            resource,
+            rx_buf: self.rx_buffer,
+            reuse: Reuse::new(connect_time),
+        })
     }
 }
 
-/// HTTP client connection
+/// HTTP client connection. Multiple requests can be sent over the same connection (keep-alive),
+/// saving the time of a new TLS handshake for every request. The connection is closed when
+/// dropped.
 pub struct Connection<'a> {
     resource: HttpResource<'a, TcpConnection<'a>>,
+    rx_buf: &'a mut [u8],
+    reuse: Reuse,
 }
 
 impl fmt::Debug for Connection<'_> {
@@ -126,24 +234,39 @@ impl fmt::Debug for Connection<'_> {
         f.debug_struct("Connection")
             .field("host", &self.resource.host)
             .field("base_path", &self.resource.base_path)
+            .field("reuse", &self.reuse)
             .finish()
     }
 }
 
+impl Drop for Connection<'_> {
+    fn drop(&mut self) {
+        debug!(
+            "HTTP: Closing connection after {} requests (saved {}ms)",
+            self.reuse.requests,
+            self.reuse.saved_time().as_millis()
+        );
+    }
+}
+
 impl<'a> Connection<'a> {
+    /// Returns true if further requests can be sent over this connection. Otherwise, the server
+    /// closed the connection or a request failed, and a new connection needs to be established.
+    pub fn is_reusable(&self) -> bool {
+        self.reuse.reusable
+    }
+
     /// Send GET request, deserialize JSON response
     pub async fn get<T: FromJson>(&mut self, path: &str) -> Result<T, Error> {
-        let mut rx_buf = [0; MAX_RESPONSE_SIZE];
-        let mut json = self.get_json(path, &mut rx_buf).await?;
-        json.read().await.map_err(Error::MalformedResponse)
+        let json = self.get_json(path).await?;
+        Self::read_to_end(json).await
     }
 
     /// Send GET request, return response body JSON reader
     pub async fn get_json<'req>(
         &'req mut self,
         path: &'req str,
-        rx_buf: &'req mut [u8],
-    ) -> Result<json::Reader<BodyReader<impl Read + BufRead + use<'a, 'req>>>, Error> {
+    ) -> Result<json::Reader<Body<BodyReader<impl Read + BufRead + use<'a, 'req>>>>, Error> {
         // FIXME: Return type of this function shouldn't be generic, but reqwless hides the
         // inner type `BufferingReader` so we can't use the full type signature for now
 
@@ -153,15 +276,14 @@ impl<'a> Connection<'a> {
             .get(path)
             .headers(&[("Accept", "application/json")]);
 
-        Self::send_request(request, rx_buf).await
+        Self::send_request(&mut self.reuse, request, self.rx_buf).await
     }
 
     /// Serialize data to JSON, send POST request, deserialize JSON response
     pub async fn post<T: ToJson, U: FromJson>(&mut self, path: &str, data: &T) -> Result<U, Error> {
         let body = Self::prepare_body(data).await?;
-        let mut rx_buf = [0; MAX_RESPONSE_SIZE];
-        let mut json = self.post_json(path, &body, &mut rx_buf).await?;
-        json.read().await.map_err(Error::MalformedResponse)
+        let json = self.post_json(path, &body).await?;
+        Self::read_to_end(json).await
     }
 
     /// Serialize data to JSON, send POST request, return response body JSON reader
@@ -169,8 +291,7 @@ impl<'a> Connection<'a> {
         &'req mut self,
         path: &'req str,
         data: &'req [u8],
-        rx_buf: &'req mut [u8],
-    ) -> Result<json::Reader<BodyReader<impl Read + BufRead + use<'a, 'req>>>, Error> {
+    ) -> Result<json::Reader<Body<BodyReader<impl Read + BufRead + use<'a, 'req>>>>, Error> {
         // FIXME: Return type of this function shouldn't be generic, but reqwless hides the
         // inner type `BufferingReader` so we can't use the full type signature for now
 
@@ -187,7 +308,7 @@ impl<'a> Connection<'a> {
             .headers(&[("Accept", "application/json")])
             .body(data);
 
-        Self::send_request(request, rx_buf).await
+        Self::send_request(&mut self.reuse, request, self.rx_buf).await
     }
 
     /// Serialize data to JSON for request body
@@ -201,20 +322,51 @@ impl<'a> Connection<'a> {
 }
 
 impl Connection<'_> {
+    /// Deserialize JSON response and discard the remaining body, so that the next request can
+    /// be sent over the same connection
+    async fn read_to_end<T: FromJson, R: BufRead<Error = reqwless::Error>>(
+        mut json: json::Reader<R>,
+    ) -> Result<T, Error> {
+        let value = json.read().await?;
+        json.discard_to_end().await?;
+        Ok(value)
+    }
+
     /// Send request, check response status and return response body JSON reader
     async fn send_request<'req, 'conn, B: RequestBody>(
+        reuse: &mut Reuse,
         request: HttpResourceRequestBuilder<'req, 'conn, TcpConnection<'conn>, B>,
         rx_buf: &'req mut [u8],
-    ) -> Result<json::Reader<BodyReader<impl Read + BufRead + use<'req, 'conn, B>>>, Error> {
+    ) -> Result<json::Reader<Body<BodyReader<impl Read + BufRead + use<'req, 'conn, B>>>>, Error>
+    {
         // FIXME: Return type of this function shouldn't be generic, but reqwless hides the
         // inner type `BufferingReader` so we can't use the full type signature for now
 
+        reuse.begin()?;
+
         // rx_buf is used to buffer response headers. The response body reader uses this only for
         // non-TLS connections. Body reader of TLS connections will use the TLS read_buffer for
         // buffering parts of the body. However, read_to_end will again always use this buffer.
-        let response = request.send(rx_buf).await?;
+        let response = match request.send(rx_buf).await {
+            Ok(response) => response,
+            Err(err) => {
+                reuse.failed();
+                return Err(Error::from_send(err));
+            }
+        };
         debug!("HTTP: Status {}", response.status.0);
 
+        // Connection can be reused if the server doesn't close it and the end of the body can be
+        // determined without reading until the connection is closed
+        let connection_close = response
+            .headers()
+            .any(|(k, v)| k.eq_ignore_ascii_case("Connection") && v.eq_ignore_ascii_case(b"close"));
+        let delimited_body = response.content_length.is_some()
+            || response
+                .transfer_encoding
+                .contains(&TransferEncoding::Chunked);
+        reuse.received(!connection_close && delimited_body);
+
         // Extract current date and time from response
         let time = response
             .headers()
@@ -226,12 +378,15 @@ impl Connection<'_> {
         }
 
         // Check HTTP response status
-        if response.status.0 == 401 {
-            return Err(Error::Unauthorized);
-        } else if response.status.is_server_error() {
-            return Err(Error::ServerError(response.status));
-        } else if !response.status.is_successful() {
-            return Err(Error::BadRequest(response.status));
+        let status = response.status;
+        if !status.is_successful() {
+            // Discard body of error response, so that the next request can be sent over the
+            // same connection
+            if let Err(err) = response.body().discard().await {
+                warn!("HTTP: Failed to discard error response: {:?}", err);
+                reuse.failed();
+            }
+            return Err(Error::from_status(status));
         }
 
         // Reqwless' content-type parsing is unreliable, so parse the body in any case. Parsing
@@ -240,6 +395,252 @@ impl Connection<'_> {
         //     return Err(Error::InvalidResponse);
         // }
 
-        Ok(json::Reader::new(response.body().reader()))
+        let content_length = response.content_length;
+        Ok(json::Reader::new(Body::new(
+            response.body().reader(),
+            content_length,
+        )))
+    }
+}
+
+/// Response body reader
+/// Wraps the body reader of the HTTP client and keeps track of the number of bytes read. If the
+/// server announced the body length (`Content-Length` header), a truncated body is detected and
+/// logged when reaching its end early.
+#[derive(Debug)]
+pub struct Body<R> {
+    reader: R,
+    content_length: Option<usize>,
+    read: usize,
+}
+
+impl<R> Body<R> {
+    /// Create body reader with given announced body length (if any)
+    fn new(reader: R, content_length: Option<usize>) -> Self {
+        Self {
+            reader,
+            content_length,
+            read: 0,
+        }
+    }
+
+    /// Body length announced by the server (if any)
+    #[allow(dead_code)]
+    pub fn content_length(&self) -> Option<usize> {
+        self.content_length
+    }
+
+    /// Number of body bytes read so far
+    #[allow(dead_code)]
+    pub fn bytes_read(&self) -> usize {
+        self.read
+    }
+}
+
+impl<R: ErrorType> ErrorType for Body<R> {
+    type Error = R::Error;
+}
+
+impl<R: Read> Read for Body<R> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let missing = missing_bytes(self.content_length, self.read);
+        let res = self.reader.read(buf).await;
+        match res {
+            Ok(len) if len > 0 || buf.is_empty() => self.read += len,
+            _ => warn_truncated(missing, self.content_length),
+        }
+        res
+    }
+}
+
+impl<R: BufRead> BufRead for Body<R> {
+    async fn fill_buf(&mut self) -> Result<&[u8], Self::Error> {
+        let content_length = self.content_length;
+        let missing = missing_bytes(content_length, self.read);
+        let res = self.reader.fill_buf().await;
+        if matches!(res, Ok([]) | Err(_)) {
+            warn_truncated(missing, content_length);
+        }
+        res
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.read += amt;
+        self.reader.consume(amt);
+    }
+}
+
+/// Number of bytes missing from a body with the given announced length after reading the given
+/// number of bytes. Always zero if the length wasn't announced.
+fn missing_bytes(content_length: Option<usize>, read: usize) -> usize {
+    content_length.map_or(0, |len| len.saturating_sub(read))
+}
+
+/// Log a truncated body if bytes are missing when reaching its end (or failing to read further)
+fn warn_truncated(missing: usize, content_length: Option<usize>) {
+    if let (true, Some(len)) = (missing > 0, content_length) {
+        warn!(
+            "HTTP: Response body truncated, got {} of {} bytes",
+            len - missing,
+            len
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwless::request::Method;
+    use reqwless::response::Response;
+    use reqwless::TryBufRead;
+
+    /// Mock response with a large header
+    const RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\n\
+        Content-Type: application/json\r\n\
+        Content-Length: 2\r\n\
+        X-Padding: 0123456789012345678901234567890123456789012345678901234567890123456789\r\n\
+        \r\n\
+        {}";
+
+    /// Read mock response headers using a receive buffer of given size
+    async fn read_response(rx_buffer_size: usize) -> Result<StatusCode, Error> {
+        let mut conn = RESPONSE;
+        let mut rx_buf = vec![0; rx_buffer_size];
+        let response = Response::read(&mut conn, Method::GET, &mut rx_buf)
+            .await
+            .map_err(Error::from_send)?;
+        Ok(response.status)
+    }
+
+    #[async_std::test]
+    async fn response_header_fits() {
+        let status = read_response(256).await.unwrap();
+        assert_eq!(status.0, 200);
+    }
+
+    #[async_std::test]
+    async fn response_header_too_large() {
+        let res = read_response(64).await;
+        assert!(matches!(res, Err(Error::HeaderTooLarge)));
+    }
+
+    /// Mock connection to read a response from
+    struct MockConnection<'a>(&'a [u8]);
+
+    impl ErrorType for MockConnection<'_> {
+        type Error = Infallible;
+    }
+
+    impl Read for MockConnection<'_> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            self.0.read(buf).await
+        }
+    }
+
+    impl TryBufRead for MockConnection<'_> {}
+
+    /// Read mock response and discard its body. Returns announced and read body length, and the
+    /// result of reading.
+    async fn read_body(response: &[u8]) -> (Option<usize>, usize, Result<(), Error>) {
+        let mut conn = MockConnection(response);
+        let mut rx_buf = vec![0; 256];
+        let response = Response::read(&mut conn, Method::GET, &mut rx_buf)
+            .await
+            .unwrap();
+        let content_length = response.content_length;
+        let mut json = json::Reader::new(Body::new(response.body().reader(), content_length));
+        let res = json.discard_to_end().await.map_err(Error::from);
+        let body = json.get_ref();
+        (body.content_length(), body.bytes_read(), res)
+    }
+
+    #[async_std::test]
+    async fn body_content_length() {
+        let (content_length, read, res) = read_body(RESPONSE).await;
+        assert_eq!(content_length, Some(2));
+        assert_eq!(read, 2);
+        assert!(res.is_ok());
+    }
+
+    #[async_std::test]
+    async fn body_without_content_length() {
+        let response = b"HTTP/1.1 200 OK\r\n\r\n{\"a\":1}";
+        let (content_length, read, res) = read_body(response).await;
+        assert_eq!(content_length, None);
+        assert_eq!(read, 7);
+        assert!(res.is_ok());
+    }
+
+    #[async_std::test]
+    async fn body_truncated() {
+        let response = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n{\"a\":";
+        let (content_length, read, res) = read_body(response).await;
+        assert_eq!(content_length, Some(10));
+        assert_eq!(read, 5);
+        assert!(matches!(
+            res,
+            Err(Error::MalformedResponse(json::Error::Io(
+                reqwless::Error::ConnectionAborted
+            )))
+        ));
+    }
+
+    #[test]
+    fn missing_body_bytes() {
+        assert_eq!(missing_bytes(Some(10), 0), 10);
+        assert_eq!(missing_bytes(Some(10), 4), 6);
+        assert_eq!(missing_bytes(Some(10), 10), 0);
+        assert_eq!(missing_bytes(Some(10), 12), 0);
+        assert_eq!(missing_bytes(None, 4), 0);
+    }
+
+    #[test]
+    fn body_buffer_too_small() {
+        let err = Error::from(json::Error::Io(reqwless::Error::BufferTooSmall));
+        assert!(matches!(err, Error::BodyBufferTooSmall));
+        let err = Error::from(json::Error::<reqwless::Error>::Eof);
+        assert!(matches!(err, Error::MalformedResponse(json::Error::Eof)));
+    }
+
+    #[test]
+    fn network_error() {
+        let err = Error::from_send(reqwless::Error::ConnectionAborted);
+        assert!(matches!(
+            err,
+            Error::Network(reqwless::Error::ConnectionAborted)
+        ));
+    }
+
+    #[test]
+    fn reuse_connection() {
+        let mut reuse = Reuse::new(Duration::from_millis(1500));
+        assert_eq!(reuse.saved_time(), Duration::from_millis(0));
+        for _ in 0..3 {
+            assert!(reuse.begin().is_ok());
+            reuse.received(true);
+        }
+        assert_eq!(reuse.requests, 3);
+        assert_eq!(reuse.saved_time(), Duration::from_millis(3000));
+    }
+
+    #[test]
+    fn reuse_closed_connection() {
+        let mut reuse = Reuse::new(Duration::from_millis(1500));
+        assert!(reuse.begin().is_ok());
+        reuse.received(false);
+        assert!(reuse.begin().is_err());
+        assert_eq!(reuse.requests, 1);
+        // Keep-alive of a later response doesn't reopen the connection
+        reuse.received(true);
+        assert!(reuse.begin().is_err());
+    }
+
+    #[test]
+    fn reuse_failed_connection() {
+        let mut reuse = Reuse::new(Duration::from_millis(1500));
+        assert!(reuse.begin().is_ok());
+        reuse.failed();
+        assert!(reuse.begin().is_err());
+        assert_eq!(reuse.saved_time(), Duration::from_millis(0));
     }
 }
@@ -0,0 +1,214 @@
+use embassy_time::{Duration, Instant};
+use log::debug;
+
+/// Default duration of inactivity after which the display is dimmed
+#[cfg(not(debug_assertions))]
+pub const DEFAULT_DIM_TIMEOUT: Duration = Duration::from_secs(300);
+#[cfg(debug_assertions)]
+pub const DEFAULT_DIM_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default duration of inactivity after which power saving is activated
+#[cfg(not(debug_assertions))]
+pub const DEFAULT_POWER_SAVE_TIMEOUT: Duration = Duration::from_secs(360);
+#[cfg(debug_assertions)]
+pub const DEFAULT_POWER_SAVE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Idle state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// Recently active, display at full brightness
+    Active,
+    /// Idle for a short time, display dimmed
+    Dimmed,
+    /// Idle for a long time, display turned off and NFC reader polling at a low rate
+    PowerSave,
+}
+
+/// Idle tracker
+/// Keeps track of the time since the last activity and determines when to dim the display and
+/// when to enter power saving. Time is passed in by the caller, so transitions can be tested
+/// without a real clock.
+#[derive(Debug)]
+pub struct Idle {
+    dim_timeout: Duration,
+    power_save_timeout: Duration,
+    sleep_timeout: Option<Duration>,
+    since: Instant,
+    state: State,
+}
+
+impl Idle {
+    /// Create new idle tracker with default timeouts, starting as active at the given time
+    pub fn new(now: Instant) -> Self {
+        Self {
+            dim_timeout: DEFAULT_DIM_TIMEOUT,
+            power_save_timeout: DEFAULT_POWER_SAVE_TIMEOUT,
+            sleep_timeout: None,
+            since: now,
+            state: State::Active,
+        }
+    }
+
+    /// Set timeouts for dimming and power saving (both counted from the last activity). Power
+    /// saving timeout is raised to the dimming timeout if it is shorter.
+    pub fn set_timeouts(&mut self, dim_timeout: Duration, power_save_timeout: Duration) {
+        debug!(
+            "Idle: Setting timeouts to {}s (dim), {}s (power save)",
+            dim_timeout.as_secs(),
+            power_save_timeout.as_secs()
+        );
+        self.dim_timeout = dim_timeout;
+        self.power_save_timeout = power_save_timeout.max(dim_timeout);
+    }
+
+    /// Set timeout for going to sleep (counted from the last activity), or `None` to never sleep.
+    /// Going to sleep is a stage of power saving, so the timeout is raised to the power saving
+    /// timeout if it is shorter.
+    pub fn set_sleep_timeout(&mut self, sleep_timeout: Option<Duration>) {
+        if let Some(timeout) = sleep_timeout {
+            debug!("Idle: Setting sleep timeout to {}s", timeout.as_secs());
+        }
+        self.sleep_timeout = sleep_timeout;
+    }
+
+    /// Current idle state
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Time since the last activity
+    pub fn idle_time(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.since)
+    }
+
+    /// Register activity at the given time. Returns to active state.
+    pub fn reset(&mut self, now: Instant) {
+        self.since = now;
+        self.state = State::Active;
+    }
+
+    /// Time of the next state transition if no activity happens
+    pub fn deadline(&self) -> Instant {
+        match self.state {
+            State::Active => self.since + self.dim_timeout,
+            State::Dimmed | State::PowerSave => self.since + self.power_save_timeout,
+        }
+    }
+
+    /// Time to go to sleep if no activity happens (if sleep is enabled)
+    pub fn sleep_deadline(&self) -> Option<Instant> {
+        self.sleep_timeout
+            .map(|timeout| self.since + timeout.max(self.power_save_timeout))
+    }
+
+    /// Update idle state for the given time. Returns the new state if it changed.
+    pub fn update(&mut self, now: Instant) -> Option<State> {
+        let idle = self.idle_time(now);
+        let state = if idle >= self.power_save_timeout {
+            State::PowerSave
+        } else if idle >= self.dim_timeout {
+            State::Dimmed
+        } else {
+            State::Active
+        };
+        if state == self.state {
+            None
+        } else {
+            self.state = state;
+            Some(state)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn idle() -> Idle {
+        let mut idle = Idle::new(Instant::from_secs(0));
+        idle.set_timeouts(Duration::from_secs(10), Duration::from_secs(30));
+        idle
+    }
+
+    #[test]
+    fn starts_active() {
+        let mut idle = idle();
+        assert_eq!(idle.state(), State::Active);
+        assert_eq!(idle.deadline(), Instant::from_secs(10));
+        assert_eq!(idle.update(Instant::from_secs(9)), None);
+        assert_eq!(idle.state(), State::Active);
+    }
+
+    #[test]
+    fn idle_time() {
+        let mut idle = idle();
+        assert_eq!(
+            idle.idle_time(Instant::from_secs(25)),
+            Duration::from_secs(25)
+        );
+        idle.reset(Instant::from_secs(30));
+        assert_eq!(
+            idle.idle_time(Instant::from_secs(25)),
+            Duration::from_secs(0)
+        );
+        assert_eq!(
+            idle.idle_time(Instant::from_secs(42)),
+            Duration::from_secs(12)
+        );
+    }
+
+    #[test]
+    fn dims_then_powers_down() {
+        let mut idle = idle();
+        assert_eq!(idle.update(Instant::from_secs(10)), Some(State::Dimmed));
+        assert_eq!(idle.deadline(), Instant::from_secs(30));
+        assert_eq!(idle.update(Instant::from_secs(20)), None);
+        assert_eq!(idle.update(Instant::from_secs(30)), Some(State::PowerSave));
+        assert_eq!(idle.update(Instant::from_secs(40)), None);
+        assert_eq!(idle.state(), State::PowerSave);
+    }
+
+    #[test]
+    fn skips_dimming_if_late() {
+        let mut idle = idle();
+        assert_eq!(idle.update(Instant::from_secs(35)), Some(State::PowerSave));
+    }
+
+    #[test]
+    fn activity_wakes_up() {
+        let mut idle = idle();
+        idle.update(Instant::from_secs(30));
+        idle.reset(Instant::from_secs(50));
+        assert_eq!(idle.state(), State::Active);
+        assert_eq!(idle.deadline(), Instant::from_secs(60));
+        assert_eq!(idle.update(Instant::from_secs(55)), None);
+        assert_eq!(idle.update(Instant::from_secs(60)), Some(State::Dimmed));
+    }
+
+    #[test]
+    fn sleep_deadline() {
+        let mut idle = idle();
+        assert_eq!(idle.sleep_deadline(), None);
+        idle.set_sleep_timeout(Some(Duration::from_secs(60)));
+        assert_eq!(idle.sleep_deadline(), Some(Instant::from_secs(60)));
+        idle.reset(Instant::from_secs(100));
+        assert_eq!(idle.sleep_deadline(), Some(Instant::from_secs(160)));
+        idle.set_sleep_timeout(None);
+        assert_eq!(idle.sleep_deadline(), None);
+    }
+
+    #[test]
+    fn sleep_not_before_power_save() {
+        let mut idle = idle();
+        idle.set_sleep_timeout(Some(Duration::from_secs(20)));
+        assert_eq!(idle.sleep_deadline(), Some(Instant::from_secs(30)));
+    }
+
+    #[test]
+    fn power_save_timeout_not_before_dimming() {
+        let mut idle = Idle::new(Instant::from_secs(0));
+        idle.set_timeouts(Duration::from_secs(10), Duration::from_secs(5));
+        assert_eq!(idle.update(Instant::from_secs(5)), None);
+        assert_eq!(idle.update(Instant::from_secs(10)), Some(State::PowerSave));
+    }
+}
@@ -1,4 +1,5 @@
 use super::value::TryFromValueError;
+use alloc::string::String;
 use core::fmt;
 
 /// JSON reader/writer error
@@ -8,7 +9,9 @@ pub enum Error<E> {
     Eof,
     Unexpected(char),
     NumberTooLarge,
+    StringTooLong,
     InvalidType,
+    DuplicateKey(String),
 }
 
 impl<E: embedded_io_async::Error> From<E> for Error<E> {
@@ -30,7 +33,9 @@ impl<E: fmt::Display> fmt::Display for Error<E> {
             Self::Eof => write!(f, "Premature EOF"),
             Self::Unexpected(ch) => write!(f, "Unexpected `{ch}`"),
             Self::NumberTooLarge => write!(f, "Number too large"),
+            Self::StringTooLong => write!(f, "String too long"),
             Self::InvalidType => write!(f, "Invalid type"),
+            Self::DuplicateKey(key) => write!(f, "Duplicate key `{key}`"),
         }
     }
 }
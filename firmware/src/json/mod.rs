@@ -6,6 +6,9 @@ pub use self::error::Error;
 mod reader;
 pub use self::reader::{FromJson, FromJsonArray, FromJsonObject, Reader};
 
+mod timestamp;
+pub use self::timestamp::{SpaceSeparated, Timestamp, TimestampFormat};
+
 mod value;
 pub use self::value::{TryFromValueError, Value};
 
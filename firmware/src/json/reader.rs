@@ -2,27 +2,77 @@ use super::error::Error;
 use super::value::Value;
 use alloc::borrow::Cow;
 use alloc::boxed::Box;
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::string::String;
 use alloc::vec::Vec;
 use core::iter::Extend;
 use core::str::FromStr;
+use embassy_futures::yield_now;
 use embedded_io_async::BufRead;
 
+/// Number of object fields and array elements to read before yielding to other tasks. Parsing
+/// large documents from a fast source can otherwise block other tasks (like the watchdog feeder)
+/// for too long.
+const YIELD_INTERVAL: usize = 64;
+
 /// Asynchronous streaming JSON reader
 ///
 /// This JSON reader reads from a wrapped asynchronous byte reader and parses JSON without storing
 /// any JSON source in memory (though the underlying byte reader typically has a memory buffer).
 #[derive(Debug)]
+#[allow(clippy::struct_field_names)]
 pub struct Reader<R> {
     reader: R,
     pos: usize,
+    strict: bool,
+    trailing_comma: bool,
+    comments: bool,
+    max_string_len: Option<usize>,
+    elements: usize,
 }
 
 impl<R: BufRead> Reader<R> {
     /// Create JSON reader
     pub fn new(reader: R) -> Self {
-        Self { reader, pos: 0 }
+        Self {
+            reader,
+            pos: 0,
+            strict: false,
+            trailing_comma: false,
+            comments: false,
+            max_string_len: None,
+            elements: 0,
+        }
+    }
+
+    /// Enable or disable strict mode. In strict mode, reading an object fails if a key repeats
+    /// within the object. Otherwise (default), every value of a repeated key is passed to the
+    /// object, which typically means that the last value wins.
+    #[allow(dead_code)]
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Allow or disallow a trailing comma. If allowed, a single trailing comma after the last
+    /// element of an object or array is accepted, which is useful for lenient parsing of
+    /// hand-edited JSON. Disallowed by default, as required by the JSON spec.
+    pub fn set_allow_trailing_comma(&mut self, allow: bool) {
+        self.trailing_comma = allow;
+    }
+
+    /// Allow or disallow comments. If allowed, `//` line comments and `/* */` block comments are
+    /// skipped wherever whitespace is allowed (JSONC), which is useful for lenient parsing of
+    /// hand-edited JSON. Disallowed by default, as required by the JSON spec.
+    pub fn set_allow_comments(&mut self, allow: bool) {
+        self.comments = allow;
+    }
+
+    /// Set maximum length (in bytes) of strings. Reading a longer string fails instead of
+    /// buffering it, which protects the heap from pathologically large strings. Strings are
+    /// unlimited by default.
+    #[allow(dead_code)]
+    pub fn set_max_string_len(&mut self, max_len: Option<usize>) {
+        self.max_string_len = max_len;
     }
 
     /// Returns a reference to the inner reader wrapped by this reader
@@ -106,24 +156,35 @@ impl<R: BufRead> Reader<R> {
         context: &T::Context<'_>,
     ) -> Result<T, Error<R::Error>> {
         let mut obj = T::default();
+        // Keys seen so far, only kept in strict mode
+        let mut keys = BTreeSet::new();
         self.expect(b'{').await?;
+        let mut after_comma = false;
         loop {
             self.trim().await?;
             let key = match self.peek().await? {
+                b'}' if after_comma && !self.trailing_comma => break Err(Error::unexpected(b'}')),
                 b'}' => {
                     self.consume();
                     break Ok(obj);
                 }
                 _ => self.read_string().await?,
             };
+            if self.strict && !keys.insert(key.clone()) {
+                break Err(Error::DuplicateKey(key));
+            }
             self.trim().await?;
             self.expect(b':').await?;
             self.trim().await?;
             obj.read_next(key, self, context).await?;
+            self.element_read().await;
             self.trim().await?;
             match self.peek().await? {
-                b',' => self.consume(),
-                b'}' => (),
+                b',' => {
+                    self.consume();
+                    after_comma = true;
+                }
+                b'}' => after_comma = false,
                 ch => break Err(Error::unexpected(ch)),
             }
         }
@@ -132,9 +193,11 @@ impl<R: BufRead> Reader<R> {
     /// Skip JSON object
     pub async fn skip_object(&mut self) -> Result<(), Error<R::Error>> {
         self.expect(b'{').await?;
+        let mut after_comma = false;
         loop {
             self.trim().await?;
             match self.peek().await? {
+                b'}' if after_comma && !self.trailing_comma => break Err(Error::unexpected(b'}')),
                 b'}' => {
                     self.consume();
                     break Ok(());
@@ -147,8 +210,11 @@ impl<R: BufRead> Reader<R> {
             self.skip_any().await?;
             self.trim().await?;
             match self.peek().await? {
-                b',' => self.consume(),
-                b'}' => (),
+                b',' => {
+                    self.consume();
+                    after_comma = true;
+                }
+                b'}' => after_comma = false,
                 ch => break Err(Error::unexpected(ch)),
             }
         }
@@ -173,20 +239,44 @@ impl<R: BufRead> Reader<R> {
         context: &T::Context<'_>,
     ) -> Result<T, Error<R::Error>> {
         let mut vec = T::default();
+        self.read_array_streaming(async |json| vec.read_next(json, context).await)
+            .await?;
+        Ok(vec)
+    }
+
+    /// Read JSON array element by element
+    /// The given function is called for every element with the reader positioned at the element
+    /// and must read (or skip) exactly one value. This allows to process elements inline without
+    /// a dedicated type (any context can be captured by the function). Returns the number of
+    /// elements.
+    pub async fn read_array_streaming(
+        &mut self,
+        mut f: impl AsyncFnMut(&mut Self) -> Result<(), Error<R::Error>>,
+    ) -> Result<usize, Error<R::Error>> {
         self.expect(b'[').await?;
+        let mut count = 0;
+        let mut after_comma = false;
         loop {
             self.trim().await?;
             match self.peek().await? {
+                b']' if after_comma && !self.trailing_comma => break Err(Error::unexpected(b']')),
                 b']' => {
                     self.consume();
-                    break Ok(vec);
+                    break Ok(count);
+                }
+                _ => {
+                    f(self).await?;
+                    self.element_read().await;
+                    count += 1;
                 }
-                _ => vec.read_next(self, context).await?,
             }
             self.trim().await?;
             match self.peek().await? {
-                b',' => self.consume(),
-                b']' => (),
+                b',' => {
+                    self.consume();
+                    after_comma = true;
+                }
+                b']' => after_comma = false,
                 ch => break Err(Error::unexpected(ch)),
             }
         }
@@ -195,9 +285,11 @@ impl<R: BufRead> Reader<R> {
     /// Skip JSON array
     pub async fn skip_array(&mut self) -> Result<(), Error<R::Error>> {
         self.expect(b'[').await?;
+        let mut after_comma = false;
         loop {
             self.trim().await?;
             match self.peek().await? {
+                b']' if after_comma && !self.trailing_comma => break Err(Error::unexpected(b']')),
                 b']' => {
                     self.consume();
                     break Ok(());
@@ -206,8 +298,11 @@ impl<R: BufRead> Reader<R> {
             }
             self.trim().await?;
             match self.peek().await? {
-                b',' => self.consume(),
-                b']' => (),
+                b',' => {
+                    self.consume();
+                    after_comma = true;
+                }
+                b']' => after_comma = false,
                 ch => break Err(Error::unexpected(ch)),
             }
         }
@@ -218,6 +313,12 @@ impl<R: BufRead> Reader<R> {
         self.expect(b'"').await?;
         let mut buf = Vec::new();
         loop {
+            if self
+                .max_string_len
+                .is_some_and(|max_len| buf.len() > max_len)
+            {
+                break Err(Error::StringTooLong);
+            }
             match self.peek().await? {
                 // This is safe to check, even in the middle of a UTF-8 character since UTF-8
                 // guarantees that no character encoding is a substring of any other character
@@ -390,6 +491,15 @@ impl<R: BufRead> Reader<R> {
         Ok(())
     }
 
+    /// Returns true if there's nothing but whitespace left to read
+    pub async fn is_end(&mut self) -> Result<bool, Error<R::Error>> {
+        match self.trim().await {
+            Ok(()) => Ok(false),
+            Err(Error::Eof) => Ok(true),
+            Err(err) => Err(err),
+        }
+    }
+
     /// Read and discard any remaining data
     pub async fn discard_to_end(&mut self) -> Result<(), Error<R::Error>> {
         loop {
@@ -403,6 +513,15 @@ impl<R: BufRead> Reader<R> {
 }
 
 impl<R: BufRead> Reader<R> {
+    /// Count an object field or array element that was read and periodically yield to other
+    /// tasks
+    async fn element_read(&mut self) {
+        self.elements = self.elements.wrapping_add(1);
+        if self.elements.is_multiple_of(YIELD_INTERVAL) {
+            yield_now().await;
+        }
+    }
+
     /// Peek next character from reader
     async fn peek(&mut self) -> Result<u8, Error<R::Error>> {
         // OPTIMIZE: Minimize calls to fill_buf by keeping a local reference (but: lifetime issues)
@@ -427,16 +546,45 @@ impl<R: BufRead> Reader<R> {
         self.pos += 1;
     }
 
-    /// Skip whitespace and peek next character from reader
+    /// Skip whitespace (and comments if allowed) and peek next character from reader
     async fn trim(&mut self) -> Result<(), Error<R::Error>> {
         loop {
             match self.peek().await? {
                 ch if ch.is_ascii_whitespace() => self.consume(),
+                b'/' if self.comments => self.skip_comment().await?,
                 _ => break Ok(()),
             }
         }
     }
 
+    /// Skip line or block comment
+    async fn skip_comment(&mut self) -> Result<(), Error<R::Error>> {
+        self.expect(b'/').await?;
+        match self.peek().await? {
+            b'/' => loop {
+                self.consume();
+                match self.peek().await {
+                    // Line comment ends at end of line or end of input
+                    Ok(b'\n') | Err(Error::Eof) => break Ok(()),
+                    Ok(_) => (),
+                    Err(err) => break Err(err),
+                }
+            },
+            b'*' => {
+                self.consume();
+                loop {
+                    let ch = self.peek().await?;
+                    self.consume();
+                    if ch == b'*' && self.peek().await? == b'/' {
+                        self.consume();
+                        break Ok(());
+                    }
+                }
+            }
+            ch => Err(Error::unexpected(ch)),
+        }
+    }
+
     /// Expect the given character
     async fn expect(&mut self, expected: u8) -> Result<(), Error<R::Error>> {
         match self.peek().await? {
@@ -629,7 +777,9 @@ impl<T: FromJson> FromJsonArray for Vec<T> {
 
 /// Deserialize from streaming JSON object
 /// The given method is called for every field and gets a reader that MUST be used to read the
-/// next value.
+/// next value. Nested objects that need the context too can be read by passing it down, e.g.
+/// using `json.read_object_with_context(context)` (for nested arrays, use `read_array_streaming`
+/// and read each element with the context).
 pub trait FromJsonObject: Sized + Default {
     /// Additional context information passed to deserialization
     type Context<'ctx>: ?Sized;
@@ -662,6 +812,9 @@ impl<T: FromJson> FromJsonObject for BTreeMap<String, T> {
 mod tests {
     use super::*;
     use alloc::vec;
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll, Waker};
 
     fn reader(s: &str) -> Reader<&[u8]> {
         Reader::new(s.as_bytes())
@@ -756,12 +909,367 @@ mod tests {
         );
     }
 
+    #[async_std::test]
+    async fn read_object_duplicate_key() {
+        let json = r#"{"foo": 1, "bar": 2, "foo": 3}"#;
+        // Lenient by default, last value wins
+        assert_read_eq!(
+            json,
+            read_object,
+            Ok(BTreeMap::from([
+                ("bar".to_string(), 2),
+                ("foo".to_string(), 3),
+            ]))
+        );
+        // Strict mode rejects repeated key
+        let mut strict_reader = reader(json);
+        strict_reader.set_strict(true);
+        let res: Result<BTreeMap<String, u32>, _> = strict_reader.read_object().await;
+        assert_eq!(res, Err(Error::DuplicateKey("foo".into())));
+    }
+
+    #[async_std::test]
+    async fn read_object_strict_nested() {
+        // Same key in different objects is no duplicate
+        let mut strict_reader = reader(r#"{"foo": {"foo": 1}, "bar": {"foo": 2}}"#);
+        strict_reader.set_strict(true);
+        let res: Result<BTreeMap<String, BTreeMap<String, u32>>, _> =
+            strict_reader.read_object().await;
+        assert_eq!(
+            res,
+            Ok(BTreeMap::from([
+                ("bar".to_string(), BTreeMap::from([("foo".to_string(), 2)])),
+                ("foo".to_string(), BTreeMap::from([("foo".to_string(), 1)])),
+            ]))
+        );
+        // Duplicates in nested objects are detected
+        let mut strict_reader = reader(r#"{"foo": {"bar": 1, "bar": 2}}"#);
+        strict_reader.set_strict(true);
+        let res: Result<BTreeMap<String, BTreeMap<String, u32>>, _> =
+            strict_reader.read_object().await;
+        assert_eq!(res, Err(Error::DuplicateKey("bar".into())));
+    }
+
+    #[async_std::test]
+    async fn read_object_nested_with_context() {
+        // Nested objects look up prices from the shared context by name
+        type Prices = BTreeMap<String, u32>;
+
+        #[derive(Debug, Default, PartialEq)]
+        struct Item {
+            name: String,
+            price: Option<u32>,
+        }
+
+        impl FromJsonObject for Item {
+            type Context<'ctx> = Prices;
+
+            async fn read_next<R: BufRead>(
+                &mut self,
+                key: String,
+                json: &mut Reader<R>,
+                context: &Self::Context<'_>,
+            ) -> Result<(), Error<R::Error>> {
+                match &*key {
+                    "name" => {
+                        self.name = json.read().await?;
+                        self.price = context.get(&self.name).copied();
+                    }
+                    _ => json.skip_any().await?,
+                }
+                Ok(())
+            }
+        }
+
+        #[derive(Debug, Default, PartialEq)]
+        struct Order {
+            first: Item,
+            more: Vec<Item>,
+        }
+
+        impl FromJsonObject for Order {
+            type Context<'ctx> = Prices;
+
+            async fn read_next<R: BufRead>(
+                &mut self,
+                key: String,
+                json: &mut Reader<R>,
+                context: &Self::Context<'_>,
+            ) -> Result<(), Error<R::Error>> {
+                match &*key {
+                    "first" => self.first = json.read_object_with_context(context).await?,
+                    "more" => {
+                        json.read_array_streaming(async |json| {
+                            self.more
+                                .push(json.read_object_with_context(context).await?);
+                            Ok(())
+                        })
+                        .await?;
+                    }
+                    _ => json.skip_any().await?,
+                }
+                Ok(())
+            }
+        }
+
+        let prices = Prices::from([("coffee".to_string(), 150), ("water".to_string(), 100)]);
+        let json = r#"{"first": {"name": "coffee"}, "more": [{"name": "tea"}, {"name": "water"}]}"#;
+        let res: Result<Order, _> = reader(json).read_object_with_context(&prices).await;
+        assert_eq!(
+            res,
+            Ok(Order {
+                first: Item {
+                    name: "coffee".into(),
+                    price: Some(150),
+                },
+                more: vec![
+                    Item {
+                        name: "tea".into(),
+                        price: None,
+                    },
+                    Item {
+                        name: "water".into(),
+                        price: Some(100),
+                    },
+                ],
+            })
+        );
+    }
+
+    #[async_std::test]
+    async fn read_object_trailing_comma() {
+        let json = r#"{"foo": 1, "bar": 2,}"#;
+        // Disallowed by default
+        let res: Result<BTreeMap<String, u32>, _> = reader(json).read_object().await;
+        assert_eq!(res, Err(Error::Unexpected('}')));
+        assert_read_eq!(json, skip_object, Err(Error::Unexpected('}')));
+        // Single trailing comma is accepted if allowed
+        let mut lenient_reader = reader(json);
+        lenient_reader.set_allow_trailing_comma(true);
+        let res: Result<BTreeMap<String, u32>, _> = lenient_reader.read_object().await;
+        assert_eq!(
+            res,
+            Ok(BTreeMap::from([
+                ("bar".to_string(), 2),
+                ("foo".to_string(), 1),
+            ]))
+        );
+        let mut lenient_reader = reader(json);
+        lenient_reader.set_allow_trailing_comma(true);
+        assert_eq!(lenient_reader.skip_object().await, Ok(()));
+        // Multiple or leading commas are never accepted
+        for json in [r#"{"foo": 1,,}"#, "{,}"] {
+            let mut lenient_reader = reader(json);
+            lenient_reader.set_allow_trailing_comma(true);
+            let res: Result<BTreeMap<String, u32>, _> = lenient_reader.read_object().await;
+            assert_eq!(res, Err(Error::Unexpected(',')));
+        }
+    }
+
+    fn reader_with_comments(s: &str) -> Reader<&[u8]> {
+        let mut reader = reader(s);
+        reader.set_allow_comments(true);
+        reader
+    }
+
+    #[async_std::test]
+    async fn read_object_comments() {
+        let json = r#"{ // Greeting
+            "foo": /* inline */ "hi", /* Answer */ "bar": 42 // Trailing
+            /* Multiple
+               lines ** */ }"#;
+        // Disallowed by default
+        let res: Result<BTreeMap<String, Value>, _> = reader(json).read_object().await;
+        assert_eq!(res, Err(Error::Unexpected('/')));
+        // Comments are skipped if allowed
+        let expected = BTreeMap::from([
+            ("foo".to_string(), Value::String("hi".into())),
+            ("bar".to_string(), Value::Integer(42)),
+        ]);
+        assert_eq!(reader_with_comments(json).read_object().await, Ok(expected));
+        assert_eq!(reader_with_comments(json).skip_object().await, Ok(()));
+    }
+
+    #[async_std::test]
+    async fn read_array_comments() {
+        let json = "[1, // one\n 2 /* two */]";
+        assert_eq!(
+            reader_with_comments(json).read_array().await,
+            Ok(vec![1, 2])
+        );
+        assert_eq!(reader_with_comments(json).skip_array().await, Ok(()));
+    }
+
+    #[async_std::test]
+    async fn read_string_comments() {
+        // Comments inside strings are part of the string
+        let json = r#"{"url": "http://example.com/*", "note": "// not a comment */"}"#;
+        assert_eq!(
+            reader_with_comments(json).read_object().await,
+            Ok(BTreeMap::from([
+                ("url".to_string(), "http://example.com/*".to_string()),
+                ("note".to_string(), "// not a comment */".to_string()),
+            ]))
+        );
+    }
+
+    #[async_std::test]
+    async fn invalid_comments() {
+        let res: Result<Vec<u32>, _> = reader_with_comments("[1, / 2]").read_array().await;
+        assert_eq!(res, Err(Error::Unexpected(' ')));
+        let res: Result<Vec<u32>, _> = reader_with_comments("[1, /* 2]").read_array().await;
+        assert_eq!(res, Err(Error::Eof));
+        assert_read_eq!("// comment", is_end, Ok(false));
+        assert_eq!(reader_with_comments("// comment").is_end().await, Ok(true));
+    }
+
+    #[async_std::test]
+    async fn is_end() {
+        assert_read_eq!("", is_end, Ok(true));
+        assert_read_eq!(" \r\n", is_end, Ok(true));
+        assert_read_eq!("{}", is_end, Ok(false));
+        assert_read_eq!("  null", is_end, Ok(false));
+    }
+
     #[async_std::test]
     async fn read_array() {
         assert_read_eq!("[]", read_array, Ok(Vec::<u32>::new()));
         assert_read_eq!("[1, 2, 3, 4]", read_array, Ok(vec![1, 2, 3, 4]));
     }
 
+    #[async_std::test]
+    async fn read_array_streaming() {
+        let mut sum = 0;
+        let res = reader("[1, 2, 3, 4]")
+            .read_array_streaming(async |json| {
+                sum += json.read_integer().await?;
+                Ok(())
+            })
+            .await;
+        assert_eq!(res, Ok(4));
+        assert_eq!(sum, 10);
+    }
+
+    /// Poll future to completion, returns its output and how often it was pending (which is
+    /// only caused by yielding, since reading from a slice is never pending)
+    fn poll_counting_yields<F: Future>(future: F) -> (F::Output, usize) {
+        let mut future = pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut yields = 0;
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => break (output, yields),
+                Poll::Pending => yields += 1,
+            }
+        }
+    }
+
+    #[test]
+    fn read_array_yields_periodically() {
+        let elements: Vec<_> = (1..=3 * YIELD_INTERVAL + 1)
+            .map(|i| i.to_string())
+            .collect();
+        let json = format!("[{}]", elements.join(", "));
+        let mut json = reader(&json);
+        let (res, yields) = poll_counting_yields(json.read::<Vec<usize>>());
+        let expected: Vec<_> = (1..=3 * YIELD_INTERVAL + 1).collect();
+        assert_eq!(res, Ok(expected));
+        assert_eq!(yields, 3);
+    }
+
+    #[test]
+    fn read_object_yields_periodically() {
+        // Nested array elements and object fields both count
+        let elements = vec!["0"; YIELD_INTERVAL - 1].join(", ");
+        let json = format!(r#"{{"foo": [{elements}], "bar": 1}}"#);
+        let mut json = reader(&json);
+        let (res, yields) = poll_counting_yields(json.read::<BTreeMap<String, Value>>());
+        assert_eq!(res.map(|map| map.len()), Ok(2));
+        assert_eq!(yields, 1);
+    }
+
+    #[async_std::test]
+    async fn read_array_streaming_empty() {
+        let mut calls = 0;
+        let res = reader("[ ]")
+            .read_array_streaming(async |_json| {
+                calls += 1;
+                Ok(())
+            })
+            .await;
+        assert_eq!(res, Ok(0));
+        assert_eq!(calls, 0);
+    }
+
+    #[async_std::test]
+    async fn read_array_streaming_nested() {
+        // Sum integers of nested arrays
+        let mut sum = 0;
+        let res = reader("[[1, 2], [], [3]]")
+            .read_array_streaming(async |json| {
+                json.read_array_streaming(async |json| {
+                    sum += json.read_integer().await?;
+                    Ok(())
+                })
+                .await?;
+                Ok(())
+            })
+            .await;
+        assert_eq!(res, Ok(3));
+        assert_eq!(sum, 6);
+        // Skip nested structures
+        let mut sum = 0;
+        let res = reader(r#"[{"foo": [1, 2]}, 3, [4, {}], 5]"#)
+            .read_array_streaming(async |json| match json.peek().await? {
+                b'{' | b'[' => json.skip_any().await,
+                _ => {
+                    sum += json.read_integer().await?;
+                    Ok(())
+                }
+            })
+            .await;
+        assert_eq!(res, Ok(4));
+        assert_eq!(sum, 8);
+    }
+
+    #[async_std::test]
+    async fn read_array_streaming_error() {
+        let res = reader("[1, true, 3]")
+            .read_array_streaming(async |json| json.read_integer().await.map(|_| ()))
+            .await;
+        assert_eq!(res, Err(Error::Unexpected('t')));
+        let res = reader("{}")
+            .read_array_streaming(async |json| json.skip_any().await)
+            .await;
+        assert_eq!(res, Err(Error::Unexpected('{')));
+    }
+
+    #[async_std::test]
+    async fn read_array_trailing_comma() {
+        // Disallowed by default
+        let res: Result<Vec<u32>, _> = reader("[1, 2,]").read_array().await;
+        assert_eq!(res, Err(Error::Unexpected(']')));
+        assert_read_eq!("[1, 2,]", skip_array, Err(Error::Unexpected(']')));
+        // Single trailing comma is accepted if allowed
+        let mut lenient_reader = reader("[1, 2,]");
+        lenient_reader.set_allow_trailing_comma(true);
+        assert_eq!(lenient_reader.read_array().await, Ok(vec![1, 2]));
+        let mut lenient_reader = reader("[1, 2,]");
+        lenient_reader.set_allow_trailing_comma(true);
+        assert_eq!(lenient_reader.skip_array().await, Ok(()));
+        // Multiple or leading commas are never accepted
+        let mut lenient_reader = reader("[1, 2,,]");
+        lenient_reader.set_allow_trailing_comma(true);
+        let res: Result<Vec<u32>, _> = lenient_reader.read_array().await;
+        assert_eq!(res, Err(Error::Unexpected(',')));
+        let mut lenient_reader = reader("[,]");
+        lenient_reader.set_allow_trailing_comma(true);
+        assert_eq!(
+            lenient_reader.skip_array().await,
+            Err(Error::Unexpected(','))
+        );
+    }
+
     #[async_std::test]
     async fn read_string() {
         assert_read_eq!("\"\"", read_string, Ok("".into()));
@@ -776,6 +1284,30 @@ mod tests {
         assert_read_eq!("\"hello", read_string, Err(Error::Eof));
     }
 
+    #[async_std::test]
+    async fn read_string_max_len() {
+        let mut json = reader(r#""hello""#);
+        json.set_max_string_len(Some(5));
+        assert_eq!(json.read_string().await, Ok("hello".into()));
+        let mut json = reader(r#""hello!""#);
+        json.set_max_string_len(Some(5));
+        assert_eq!(json.read_string().await, Err(Error::StringTooLong));
+        let mut json = reader(r#""hello!""#);
+        json.set_max_string_len(None);
+        assert_eq!(json.read_string().await, Ok("hello!".into()));
+    }
+
+    #[async_std::test]
+    async fn read_string_max_len_multibyte() {
+        // Limit is in bytes, not characters
+        let mut json = reader(r#""héllö""#);
+        json.set_max_string_len(Some(7));
+        assert_eq!(json.read_string().await, Ok("héllö".into()));
+        let mut json = reader(r#""héllö""#);
+        json.set_max_string_len(Some(6));
+        assert_eq!(json.read_string().await, Err(Error::StringTooLong));
+    }
+
     #[async_std::test]
     async fn read_decimal() {
         assert_read_eq!("0", read_decimal, Ok(0.0));
@@ -0,0 +1,127 @@
+use super::{Error, FromJson, Reader};
+use alloc::string::String;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use core::fmt;
+use core::marker::PhantomData;
+use embedded_io_async::BufRead;
+
+/// Format of timestamp strings, used by `Timestamp`
+#[allow(dead_code)]
+pub trait TimestampFormat {
+    /// Format string as used by `chrono::format::strftime`
+    const FORMAT: &'static str;
+}
+
+/// Timestamp format "yyyy-mm-dd hh:mm:ss" (e.g. as used by Vereinsflieger)
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpaceSeparated;
+
+impl TimestampFormat for SpaceSeparated {
+    const FORMAT: &'static str = "%Y-%m-%d %H:%M:%S";
+}
+
+/// Timestamp read from a JSON string in the format given by `F`
+///
+/// Timestamp strings usually don't include a timezone, so the parsed date and time is naive.
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Timestamp<F = SpaceSeparated> {
+    datetime: NaiveDateTime,
+    format: PhantomData<F>,
+}
+
+impl<F> fmt::Debug for Timestamp<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Timestamp").field(&self.datetime).finish()
+    }
+}
+
+#[allow(dead_code)]
+impl<F: TimestampFormat> Timestamp<F> {
+    /// Parse timestamp from given string. Returns `None` if the string doesn't match the format.
+    pub fn parse(s: &str) -> Option<Self> {
+        let datetime = NaiveDateTime::parse_from_str(s, F::FORMAT).ok()?;
+        Some(Self {
+            datetime,
+            format: PhantomData,
+        })
+    }
+}
+
+#[allow(dead_code)]
+impl<F> Timestamp<F> {
+    /// Naive date and time of this timestamp
+    pub fn naive(&self) -> NaiveDateTime {
+        self.datetime
+    }
+
+    /// Date and time of this timestamp, assuming it is given in UTC
+    pub fn and_utc(&self) -> DateTime<Utc> {
+        self.datetime.and_utc()
+    }
+}
+
+impl<F: TimestampFormat> FromJson for Timestamp<F> {
+    async fn from_json<R: BufRead>(json: &mut Reader<R>) -> Result<Self, Error<R::Error>> {
+        let s: String = json.read().await?;
+        Self::parse(&s).ok_or(Error::InvalidType)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{NaiveDate, NaiveTime};
+    use core::convert::Infallible;
+
+    async fn read<T: FromJson>(s: &str) -> Result<T, Error<Infallible>> {
+        Reader::new(s.as_bytes()).read().await
+    }
+
+    fn datetime(y: i32, m: u32, d: u32, hh: u32, mm: u32, ss: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_time(NaiveTime::from_hms_opt(hh, mm, ss).unwrap())
+    }
+
+    #[async_std::test]
+    async fn read_timestamp() {
+        let timestamp: Timestamp = read(r#""2025-01-16 03:59:59""#).await.unwrap();
+        assert_eq!(timestamp.naive(), datetime(2025, 1, 16, 3, 59, 59));
+        assert_eq!(timestamp.and_utc().timestamp(), 1_736_999_999);
+    }
+
+    #[async_std::test]
+    async fn read_timestamp_custom_format() {
+        struct GermanFormat;
+
+        impl TimestampFormat for GermanFormat {
+            const FORMAT: &'static str = "%d.%m.%Y %H:%M";
+        }
+
+        let timestamp: Timestamp<GermanFormat> = read(r#""16.01.2025 00:00""#).await.unwrap();
+        assert_eq!(timestamp.naive(), datetime(2025, 1, 16, 0, 0, 0));
+    }
+
+    #[async_std::test]
+    async fn read_invalid_timestamp() {
+        for json in [
+            r#""2025-01-16""#,
+            r#""2025-01-16T03:59:59""#,
+            r#""2025-02-30 12:00:00""#,
+            r#""2025-01-16 25:00:00""#,
+            r#""""#,
+        ] {
+            assert_eq!(read::<Timestamp>(json).await, Err(Error::InvalidType));
+        }
+    }
+
+    #[async_std::test]
+    async fn read_timestamp_not_a_string() {
+        assert_eq!(
+            read::<Timestamp>("1736999999").await,
+            Err(Error::Unexpected('1'))
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use alloc::collections::BTreeMap;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
+use core::convert::Infallible;
 use core::str::FromStr;
 use core::{fmt, num};
 
@@ -26,6 +27,12 @@ impl From<num::ParseFloatError> for TryFromValueError {
     }
 }
 
+impl From<Infallible> for TryFromValueError {
+    fn from(err: Infallible) -> Self {
+        match err {}
+    }
+}
+
 impl fmt::Display for TryFromValueError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "JSON value conversion error")
@@ -44,6 +51,20 @@ pub enum Value {
     Object(BTreeMap<String, Value>),
 }
 
+impl Value {
+    /// Look up nested value by the given path. Each path segment is an object key or an array
+    /// index (e.g. `["data", "0", "price"]`). Returns `None` if the path doesn't exist, or if a
+    /// segment doesn't fit the type of the value it's applied to.
+    #[allow(dead_code)]
+    pub fn get_path(&self, path: &[&str]) -> Option<&Value> {
+        path.iter().try_fold(self, |value, segment| match value {
+            Value::Object(object) => object.get(*segment),
+            Value::Array(array) => array.get(segment.parse::<usize>().ok()?),
+            _ => None,
+        })
+    }
+}
+
 impl From<()> for Value {
     fn from(_value: ()) -> Self {
         Self::Null
@@ -361,24 +382,150 @@ impl TryFrom<Value> for String {
     }
 }
 
-impl TryFrom<Value> for Vec<Value> {
+impl<T> TryFrom<Value> for Vec<T>
+where
+    T: TryFrom<Value>,
+    TryFromValueError: From<T::Error>,
+{
     type Error = TryFromValueError;
 
     fn try_from(value: Value) -> Result<Self, Self::Error> {
         match value {
-            Value::Array(array) => Ok(array),
+            Value::Array(array) => Ok(array
+                .into_iter()
+                .map(T::try_from)
+                .collect::<Result<_, _>>()?),
             _ => Err(TryFromValueError),
         }
     }
 }
 
-impl TryFrom<Value> for BTreeMap<String, Value> {
+impl<T> TryFrom<Value> for BTreeMap<String, T>
+where
+    T: TryFrom<Value>,
+    TryFromValueError: From<T::Error>,
+{
     type Error = TryFromValueError;
 
     fn try_from(value: Value) -> Result<Self, Self::Error> {
         match value {
-            Value::Object(object) => Ok(object),
+            Value::Object(object) => Ok(object
+                .into_iter()
+                .map(|(key, value)| Ok((key, T::try_from(value)?)))
+                .collect::<Result<_, T::Error>>()?),
             _ => Err(TryFromValueError),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn value() -> Value {
+        Value::from([
+            (
+                "data",
+                Value::from(vec![
+                    Value::from([("price", Value::from(1.5)), ("name", Value::from("Beer"))]),
+                    Value::from([("price", Value::from(2.5))]),
+                ]),
+            ),
+            ("count", Value::from(2)),
+        ])
+    }
+
+    #[test]
+    fn get_path_hit() {
+        let value = value();
+        assert_eq!(value.get_path(&[]), Some(&value));
+        assert_eq!(value.get_path(&["count"]), Some(&Value::Integer(2)));
+        assert_eq!(
+            value.get_path(&["data", "0", "price"]),
+            Some(&Value::Decimal(1.5))
+        );
+        assert_eq!(
+            value.get_path(&["data", "1", "price"]),
+            Some(&Value::Decimal(2.5))
+        );
+    }
+
+    #[test]
+    fn get_path_miss() {
+        let value = value();
+        assert_eq!(value.get_path(&["foo"]), None);
+        assert_eq!(value.get_path(&["data", "2"]), None);
+        assert_eq!(value.get_path(&["data", "1", "name"]), None);
+    }
+
+    #[test]
+    fn get_path_type_mismatch() {
+        let value = value();
+        // Key applied to an array
+        assert_eq!(value.get_path(&["data", "price"]), None);
+        // Index applied to an object
+        assert_eq!(value.get_path(&["0"]), None);
+        // Path continues beyond a scalar value
+        assert_eq!(value.get_path(&["count", "0"]), None);
+        assert_eq!(value.get_path(&["data", "0", "name", "foo"]), None);
+        // Negative index
+        assert_eq!(value.get_path(&["data", "-1"]), None);
+    }
+
+    #[test]
+    fn try_into_vec() {
+        let value = Value::from(vec![Value::from(1), Value::from("2"), Value::from(3)]);
+        assert_eq!(Vec::<u32>::try_from(value.clone()), Ok(vec![1, 2, 3]));
+        assert_eq!(
+            Vec::<f32>::try_from(Value::from(vec![Value::from(1), Value::from(2.5)])),
+            Ok(vec![1.0, 2.5])
+        );
+        assert_eq!(
+            Vec::<Value>::try_from(value),
+            Ok(vec![Value::from(1), Value::from("2"), Value::from(3)])
+        );
+        assert_eq!(Vec::<u32>::try_from(Value::from(vec![])), Ok(vec![]));
+    }
+
+    #[test]
+    fn try_into_vec_failing() {
+        let value = Value::from(vec![Value::from(1), Value::from("foo")]);
+        assert_eq!(Vec::<u32>::try_from(value), Err(TryFromValueError));
+        let value = Value::from(vec![Value::from(1), Value::from(-1)]);
+        assert_eq!(Vec::<u32>::try_from(value), Err(TryFromValueError));
+        assert_eq!(
+            Vec::<u32>::try_from(Value::from([("a", Value::from(1))])),
+            Err(TryFromValueError)
+        );
+    }
+
+    #[test]
+    fn try_into_map() {
+        let value = Value::from([("a", Value::from(1)), ("b", Value::from("2"))]);
+        assert_eq!(
+            BTreeMap::<String, u32>::try_from(value.clone()),
+            Ok(BTreeMap::from([("a".to_string(), 1), ("b".to_string(), 2)]))
+        );
+        assert_eq!(
+            BTreeMap::<String, Value>::try_from(value),
+            Ok(BTreeMap::from([
+                ("a".to_string(), Value::from(1)),
+                ("b".to_string(), Value::from("2"))
+            ]))
+        );
+    }
+
+    #[test]
+    fn try_into_map_failing() {
+        let value = Value::from([("a", Value::from(1)), ("b", Value::Null)]);
+        assert_eq!(
+            BTreeMap::<String, u32>::try_from(value),
+            Err(TryFromValueError)
+        );
+        assert_eq!(
+            BTreeMap::<String, u32>::try_from(Value::from(vec![Value::from(1)])),
+            Err(TryFromValueError)
+        );
+    }
+}
@@ -59,7 +59,7 @@ impl<W: Write> Writer<W> {
     }
 
     /// Write JSON object
-    pub async fn write_object(&mut self) -> Result<ObjectWriter<W>, Error<W::Error>> {
+    pub async fn write_object(&mut self) -> Result<ObjectWriter<'_, W>, Error<W::Error>> {
         ObjectWriter::new(self).await
     }
 
@@ -80,6 +80,30 @@ impl<W: Write> Writer<W> {
         Ok(())
     }
 
+    /// Write JSON array of objects. For every item, an object is started and the given function
+    /// is called to write its fields. This allows to stream objects without building an
+    /// intermediate value for each of them.
+    pub async fn write_array_of_objects<T, I>(
+        &mut self,
+        iter: I,
+        mut f: impl AsyncFnMut(&mut ObjectWriter<'_, W>, T) -> Result<(), Error<W::Error>>,
+    ) -> Result<(), Error<W::Error>>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        self.write_raw(b"[").await?;
+        for (i, elem) in iter.into_iter().enumerate() {
+            if i > 0 {
+                self.write_raw(b", ").await?;
+            }
+            let mut object = self.write_object().await?;
+            f(&mut object, elem).await?;
+            object.finish().await?;
+        }
+        self.write_raw(b"]").await?;
+        Ok(())
+    }
+
     /// Write JSON string
     pub async fn write_string(&mut self, value: &str) -> Result<(), Error<W::Error>> {
         self.write_raw(b"\"").await?;
@@ -149,13 +173,34 @@ impl<'w, W: Write> ObjectWriter<'w, W> {
         key: &str,
         value: T,
     ) -> Result<&mut Self, Error<W::Error>> {
-        if self.has_fields {
-            self.json.write_raw(b", ").await?;
-        }
-        self.json.write_string(key).await?;
-        self.json.write_raw(b": ").await?;
+        self.write_key(key).await?;
         self.json.write(value).await?;
-        self.has_fields = true;
+        Ok(self)
+    }
+
+    /// Write object field if a value is given, omit the field otherwise
+    pub async fn field_opt<T: ToJson>(
+        &mut self,
+        key: &str,
+        value: Option<T>,
+    ) -> Result<&mut Self, Error<W::Error>> {
+        match value {
+            Some(value) => self.field(key, value).await,
+            None => Ok(self),
+        }
+    }
+
+    /// Write object field with a pre-serialized JSON value. The value bytes are written verbatim
+    /// without any validation, so the caller must make sure they are a single valid JSON value.
+    /// Otherwise the resulting JSON is malformed.
+    #[allow(dead_code)]
+    pub async fn field_raw(
+        &mut self,
+        key: &str,
+        value: &[u8],
+    ) -> Result<&mut Self, Error<W::Error>> {
+        self.write_key(key).await?;
+        self.json.write_raw(value).await?;
         Ok(self)
     }
 
@@ -179,6 +224,19 @@ impl<'w, W: Write> ObjectWriter<'w, W> {
     }
 }
 
+impl<W: Write> ObjectWriter<'_, W> {
+    /// Write separator (if needed) and key of next field
+    async fn write_key(&mut self, key: &str) -> Result<(), Error<W::Error>> {
+        if self.has_fields {
+            self.json.write_raw(b", ").await?;
+        }
+        self.json.write_string(key).await?;
+        self.json.write_raw(b": ").await?;
+        self.has_fields = true;
+        Ok(())
+    }
+}
+
 /// Serialize to streaming JSON
 pub trait ToJson {
     /// Serialize this type using the given JSON writer
@@ -450,6 +508,72 @@ mod tests {
         );
     }
 
+    #[async_std::test]
+    async fn write_object_optional_fields() {
+        let mut json = writer();
+        let res = json
+            .write_object()
+            .await
+            .unwrap()
+            .field_opt("foo", None::<&str>)
+            .await
+            .unwrap()
+            .field_opt("bar", Some(42))
+            .await
+            .unwrap()
+            .field_opt("baz", None::<bool>)
+            .await
+            .unwrap()
+            .field_opt("qux", Some("hi"))
+            .await
+            .unwrap()
+            .finish()
+            .await;
+        let json = String::from_utf8(json.into_inner()).unwrap();
+        assert_eq!(res.map(|()| &*json), Ok(r#"{"bar": 42, "qux": "hi"}"#));
+    }
+
+    #[async_std::test]
+    async fn write_object_no_optional_fields() {
+        let mut json = writer();
+        let res = json
+            .write_object()
+            .await
+            .unwrap()
+            .field_opt("foo", None::<u32>)
+            .await
+            .unwrap()
+            .finish()
+            .await;
+        let json = String::from_utf8(json.into_inner()).unwrap();
+        assert_eq!(res.map(|()| &*json), Ok("{}"));
+    }
+
+    #[async_std::test]
+    async fn write_object_raw_field() {
+        let mut json = writer();
+        let res = json
+            .write_object()
+            .await
+            .unwrap()
+            .field("foo", "hi")
+            .await
+            .unwrap()
+            .field_raw("bar", br#"{"baz": [1, 2, 3], "qux": null}"#)
+            .await
+            .unwrap()
+            .field_raw("quux", b"42")
+            .await
+            .unwrap()
+            .finish()
+            .await;
+        let json = String::from_utf8(json.into_inner()).unwrap();
+        assert_eq!(
+            res.map(|()| &*json),
+            Ok(r#"{"foo": "hi", "bar": {"baz": [1, 2, 3], "qux": null}, "quux": 42}"#)
+        );
+    }
+
     #[async_std::test]
     async fn write_array() {
         assert_write_eq!(write_array, Vec::<u32>::new(), Ok("[]"));
@@ -468,6 +592,67 @@ mod tests {
         );
     }
 
+    #[async_std::test]
+    async fn write_array_of_objects() {
+        struct Test {
+            foo: u32,
+            bar: Option<&'static str>,
+        }
+
+        impl ToJson for Test {
+            async fn to_json<W: Write>(&self, json: &mut Writer<W>) -> Result<(), Error<W::Error>> {
+                json.write_object()
+                    .await?
+                    .field("foo", self.foo)
+                    .await?
+                    .field_opt("bar", self.bar)
+                    .await?
+                    .finish()
+                    .await
+            }
+        }
+
+        let items = [(1, Some("a")), (2, None), (3, Some("c"))];
+
+        let mut json = writer();
+        json.write_array(items.iter().map(|&(foo, bar)| Test { foo, bar }))
+            .await
+            .unwrap();
+        let expected = String::from_utf8(json.into_inner()).unwrap();
+
+        let mut json = writer();
+        json.write_array_of_objects(items, async |object, (foo, bar)| {
+            object
+                .field("foo", foo)
+                .await?
+                .field_opt("bar", bar)
+                .await?;
+            Ok(())
+        })
+        .await
+        .unwrap();
+        let json = String::from_utf8(json.into_inner()).unwrap();
+
+        assert_eq!(json, expected);
+        assert_eq!(
+            json,
+            r#"[{"foo": 1, "bar": "a"}, {"foo": 2}, {"foo": 3, "bar": "c"}]"#
+        );
+    }
+
+    #[async_std::test]
+    async fn write_array_of_objects_empty() {
+        let mut json = writer();
+        let res = json
+            .write_array_of_objects(Vec::<u32>::new(), async |object, value| {
+                object.field("value", value).await?;
+                Ok(())
+            })
+            .await;
+        let json = String::from_utf8(json.into_inner()).unwrap();
+        assert_eq!(res.map(|()| &*json), Ok("[]"));
+    }
+
     #[async_std::test]
     async fn write_string() {
         assert_write_eq!(write_string, "", Ok("\"\""));
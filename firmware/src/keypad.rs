@@ -1,5 +1,8 @@
+use crate::sleep::WakeupPin;
+use alloc::string::String;
 use embassy_futures::select::select_array;
-use embassy_time::{Duration, Timer};
+use embassy_time::{Duration, Instant, Timer};
+use embedded_hal::digital::OutputPin;
 use esp_hal::gpio::{Input, OutputOpenDrain};
 use log::{debug, info};
 
@@ -9,6 +12,18 @@ const OUTPUT_SETTLE_TIME: Duration = Duration::from_micros(1);
 /// Time to wait for debounce after detected keypress
 const INPUT_DEBOUNCE_TIME: Duration = Duration::from_millis(10);
 
+/// How often to scan a held key while waiting for it to be released
+const RELEASE_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Number of keypad columns (depending on keypad variant)
+#[cfg(not(feature = "keypad-4x4"))]
+pub const COLS: usize = 3;
+#[cfg(feature = "keypad-4x4")]
+pub const COLS: usize = 4;
+
+/// Number of keypad rows
+pub const ROWS: usize = 4;
+
 /// Key that can be pressed
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Key {
@@ -52,21 +67,234 @@ impl Key {
     }
 }
 
+/// Key press, distinguished by how long the key was held
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyPress {
+    /// Key released before the long press threshold
+    Short(Key),
+    /// Key held beyond the long press threshold (may still be held)
+    Long(Key),
+}
+
+/// Detector for a key held down longer than a threshold
+#[derive(Debug)]
+pub struct LongPress {
+    threshold: Duration,
+    pressed_at: Option<Instant>,
+    fired: bool,
+}
+
+impl LongPress {
+    /// Create detector for given threshold
+    pub const fn new(threshold: Duration) -> Self {
+        Self {
+            threshold,
+            pressed_at: None,
+            fired: false,
+        }
+    }
+
+    /// Key was pressed at given time
+    pub fn press(&mut self, now: Instant) {
+        self.pressed_at = Some(now);
+        self.fired = false;
+    }
+
+    /// Key was released
+    pub fn release(&mut self) {
+        self.pressed_at = None;
+        self.fired = false;
+    }
+
+    /// Returns true once if the key is held beyond the threshold at given time
+    pub fn update(&mut self, now: Instant) -> bool {
+        match self.pressed_at {
+            Some(pressed_at) if !self.fired && now >= pressed_at + self.threshold => {
+                self.fired = true;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Detector for a sequence of keys pressed in a row
+#[derive(Debug)]
+pub struct KeySequence<const N: usize> {
+    keys: [Key; N],
+    pos: usize,
+}
+
+impl<const N: usize> KeySequence<N> {
+    /// Create detector for given sequence of keys
+    pub const fn new(keys: [Key; N]) -> Self {
+        Self { keys, pos: 0 }
+    }
+
+    /// Returns true if no key of the sequence has been pressed yet
+    pub fn is_idle(&self) -> bool {
+        self.pos == 0
+    }
+
+    /// Feed a pressed key. Returns true if the sequence was completed with this key. Any key not
+    /// matching the sequence restarts detection.
+    pub fn feed(&mut self, key: Key) -> bool {
+        if self.keys.get(self.pos) == Some(&key) {
+            self.pos += 1;
+        } else if self.keys.first() == Some(&key) {
+            self.pos = 1;
+        } else {
+            self.pos = 0;
+        }
+        if self.pos >= N {
+            self.pos = 0;
+            return true;
+        }
+        false
+    }
+}
+
+/// State of a number entry after feeding a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry {
+    /// Number is still being entered
+    Pending,
+    /// Number was entered and confirmed
+    Done(u32),
+    /// Entry was cancelled
+    Cancelled,
+}
+
+/// Entry of a number with digit keys. The enter key confirms the number, the cancel key deletes
+/// the last digit or cancels the entry if there are no digits.
+#[derive(Debug)]
+pub struct NumberEntry {
+    digits: String,
+    max_digits: usize,
+}
+
+impl NumberEntry {
+    /// Create number entry accepting up to the given number of digits (at most 9, so that the
+    /// number always fits)
+    pub fn new(max_digits: usize) -> Self {
+        Self {
+            digits: String::new(),
+            max_digits: max_digits.min(9),
+        }
+    }
+
+    /// Digits entered so far
+    pub fn digits(&self) -> &str {
+        &self.digits
+    }
+
+    /// Feed a pressed key. Digits beyond the maximum number of digits and other keys are ignored.
+    pub fn feed(&mut self, key: Key) -> Entry {
+        match key {
+            Key::Digit(n) if n < 10 && self.digits.len() < self.max_digits => {
+                self.digits.push(key.as_char());
+                Entry::Pending
+            }
+            Key::Enter => match self.digits.parse() {
+                Ok(number) => Entry::Done(number),
+                // No digits entered yet
+                Err(_) => Entry::Pending,
+            },
+            Key::Cancel => match self.digits.pop() {
+                Some(_) => Entry::Pending,
+                None => Entry::Cancelled,
+            },
+            _ => Entry::Pending,
+        }
+    }
+
+    /// Feed a key press. Holding the cancel key clears all digits at once (or cancels if no
+    /// digits were entered yet), any other key press is fed like a short one.
+    pub fn feed_press(&mut self, press: KeyPress) -> Entry {
+        match press {
+            KeyPress::Long(Key::Cancel) if self.digits.is_empty() => Entry::Cancelled,
+            KeyPress::Long(Key::Cancel) => {
+                self.digits.clear();
+                Entry::Pending
+            }
+            KeyPress::Short(key) | KeyPress::Long(key) => self.feed(key),
+        }
+    }
+}
+
+/// Scancode of pressed key in scanned key states. Returns `None` if no key is pressed or if the
+/// pressed keys are ambiguous.
+///
+/// Without diodes, pressing three keys that form corners of a rectangle in the key matrix makes
+/// the fourth corner appear pressed as well ("ghosting"). Since it can't be told which of these
+/// keys are actually pressed, such states are ignored.
+fn pressed_scancode<const COLS: usize, const ROWS: usize>(
+    states: &[[bool; COLS]; ROWS],
+) -> Option<usize> {
+    if may_ghost(states) {
+        return None;
+    }
+    states.iter().flatten().position(|state| *state)
+}
+
+/// Returns true if scanned key states may contain ghost keys, i.e. if pressed keys form a
+/// rectangle in the key matrix (two rows sharing at least two pressed columns)
+fn may_ghost<const COLS: usize, const ROWS: usize>(states: &[[bool; COLS]; ROWS]) -> bool {
+    for (y, row1) in states.iter().enumerate() {
+        for row2 in &states[y + 1..] {
+            let shared = row1
+                .iter()
+                .zip(row2)
+                .filter(|(state1, state2)| **state1 && **state2)
+                .count();
+            if shared >= 2 {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Configure key matrix for waking up from sleep by pressing any key. Like waiting for a
+/// keypress, all rows are pulled low so that any key pulls its column low.
+fn configure_wakeup<O: OutputPin, I: WakeupPin>(rows: &mut [O], cols: &mut [I], enable: bool) {
+    for out in rows {
+        // Setting a GPIO output is infallible
+        let _ = out.set_low();
+    }
+    for input in cols {
+        input.set_wakeup(enable);
+    }
+}
+
 /// Matrix keypad driver
 pub struct Keypad<'a, const COLS: usize, const ROWS: usize> {
     cols: [Input<'a>; COLS],
     rows: [OutputOpenDrain<'a>; ROWS],
+    /// Scancode of key reported as pressed but not released yet
+    held: Option<usize>,
 }
 
 impl<'a, const COLS: usize, const ROWS: usize> Keypad<'a, COLS, ROWS> {
     /// Create matrix keypad from given input columns and output rows
     pub fn new(cols: [Input<'a>; COLS], rows: [OutputOpenDrain<'a>; ROWS]) -> Self {
         info!("Keypad: {ROWS}x{COLS} matrix initialized");
-        Self { cols, rows }
+        Self {
+            cols,
+            rows,
+            held: None,
+        }
+    }
+
+    /// Enable or disable waking up from sleep by pressing any key
+    pub fn set_wakeup(&mut self, enable: bool) {
+        configure_wakeup(&mut self.rows, &mut self.cols, enable);
     }
 
     /// Wait for keypress and return scancode of pressed key
     pub async fn read_scancode(&mut self) -> usize {
+        // Reading plain keypresses doesn't track releases, forget about any key held before
+        self.held = None;
         loop {
             // Wait for any key pressed
             self.wait_for_keypress().await;
@@ -75,14 +303,52 @@ impl<'a, const COLS: usize, const ROWS: usize> Keypad<'a, COLS, ROWS> {
             // Scan keypad for pressed keys
             let states = self.scan().await;
             // TODO: Use better algorithm to detect pressed key? (e.g. compare to previous states)
-            for (y, row) in states.iter().enumerate() {
-                for (x, state) in row.iter().enumerate() {
-                    if *state {
-                        return y * COLS + x;
-                    }
-                }
+            if let Some(scancode) = pressed_scancode(&states) {
+                return scancode;
+            }
+            // Keypress detected, but no pressed key scanned. Happens when contacts bounce on release
+            // or when multiple pressed keys are ambiguous (wait for a clean keypress then).
+            if may_ghost(&states) {
+                debug!("Keypad: Ignoring ambiguous multi-key press");
+            }
+        }
+    }
+
+    /// Wait for key press or release and return scancode of key and whether it was pressed (true)
+    /// or released (false). If a key is held, waits for its release before reading the next
+    /// keypress.
+    async fn read_scancode_event(&mut self) -> (usize, bool) {
+        if let Some(scancode) = self.held.take() {
+            self.wait_for_release(scancode).await;
+            return (scancode, false);
+        }
+        let scancode = self.read_scancode().await;
+        self.held = Some(scancode);
+        (scancode, true)
+    }
+
+    /// Wait for keypress and return scancode of pressed key and whether it was held beyond the
+    /// given threshold (true). Returns as soon as the key is released or the threshold exceeded,
+    /// so a long press is reported while the key is still held.
+    pub async fn read_long_press_scancode(&mut self, threshold: Duration) -> (usize, bool) {
+        let scancode = loop {
+            if let (scancode, true) = self.read_scancode_event().await {
+                break scancode;
+            }
+        };
+        let mut long_press = LongPress::new(threshold);
+        long_press.press(Instant::now());
+        loop {
+            Timer::after(RELEASE_POLL_INTERVAL).await;
+            if !self.is_pressed(scancode).await {
+                long_press.release();
+                self.held = None;
+                return (scancode, false);
+            }
+            if long_press.update(Instant::now()) {
+                // Key is still held, release will be reported by the next read of key events
+                return (scancode, true);
             }
-            // Keypress detected, but no pressed key scanned. Happens when contacts bounce on release.
         }
     }
 }
@@ -98,6 +364,21 @@ impl<const COLS: usize, const ROWS: usize> Keypad<'_, COLS, ROWS> {
         select_array(self.cols.each_mut().map(Input::wait_for_falling_edge)).await;
     }
 
+    /// Wait for the key with given scancode to be released
+    async fn wait_for_release(&mut self, scancode: usize) {
+        while self.is_pressed(scancode).await {
+            Timer::after(RELEASE_POLL_INTERVAL).await;
+        }
+        // Wait for bounced contacts to settle
+        Timer::after(INPUT_DEBOUNCE_TIME).await;
+    }
+
+    /// Scan keys and return true if the key with given scancode is pressed
+    async fn is_pressed(&mut self, scancode: usize) -> bool {
+        let states = self.scan().await;
+        states[scancode / COLS][scancode % COLS]
+    }
+
     /// Scan all keys and return array of pressed false/true states
     async fn scan(&mut self) -> [[bool; COLS]; ROWS] {
         // Assuming inputs have pull up resistors, so keys will pull low when pressed
@@ -119,12 +400,18 @@ impl<const COLS: usize, const ROWS: usize> Keypad<'_, COLS, ROWS> {
     }
 }
 
-impl Keypad<'_, 3, 4> {
+/// Key layout of a keypad variant, mapping scancodes to keys
+pub trait KeyLayout {
+    /// Keys in scancode order (row by row)
+    const KEYS: &'static [Key];
+}
+
+impl KeyLayout for Keypad<'_, 3, 4> {
     // 1 2 3
     // 4 5 6
     // 7 8 9
     // * 0 #
-    const KEYS: [Key; 12] = [
+    const KEYS: &'static [Key] = &[
         Key::Digit(1),
         Key::Digit(2),
         Key::Digit(3),
@@ -138,23 +425,14 @@ impl Keypad<'_, 3, 4> {
         Key::Digit(0),
         Key::Enter,
     ];
-
-    /// Wait for keypress and return pressed key
-    pub async fn read(&mut self) -> Key {
-        let scancode = self.read_scancode().await;
-        let key = Self::KEYS[scancode];
-        debug!("Keypad: {:?} pressed", key);
-        key
-    }
 }
 
-#[allow(dead_code)]
-impl Keypad<'_, 4, 4> {
+impl KeyLayout for Keypad<'_, 4, 4> {
     // 1 2 3 A
     // 4 5 6 B
     // 7 8 9 C
     // * 0 # D
-    const KEYS: [Key; 16] = [
+    const KEYS: &'static [Key] = &[
         Key::Digit(1),
         Key::Digit(2),
         Key::Digit(3),
@@ -172,7 +450,12 @@ impl Keypad<'_, 4, 4> {
         Key::Enter,
         Key::Other('D'),
     ];
+}
 
+impl<const COLS: usize, const ROWS: usize> Keypad<'_, COLS, ROWS>
+where
+    Self: KeyLayout,
+{
     /// Wait for keypress and return pressed key
     pub async fn read(&mut self) -> Key {
         let scancode = self.read_scancode().await;
@@ -180,4 +463,325 @@ impl Keypad<'_, 4, 4> {
         debug!("Keypad: {:?} pressed", key);
         key
     }
+
+    /// Wait for keypress and return pressed key, distinguishing short and long presses
+    pub async fn read_long_press(&mut self, threshold: Duration) -> KeyPress {
+        let (scancode, long) = self.read_long_press_scancode(threshold).await;
+        let key = Self::KEYS[scancode];
+        debug!("Keypad: {:?} pressed (long: {})", key, long);
+        if long {
+            KeyPress::Long(key)
+        } else {
+            KeyPress::Short(key)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEQUENCE: [Key; 4] = [Key::Cancel, Key::Enter, Key::Cancel, Key::Enter];
+
+    const NONE: [[bool; 3]; 4] = [[false; 3]; 4];
+
+    const THRESHOLD: Duration = Duration::from_millis(800);
+
+    #[test]
+    fn keys_cover_matrix() {
+        assert_eq!(Keypad::<'static, 3, 4>::KEYS.len(), 3 * 4);
+        assert_eq!(Keypad::<'static, 4, 4>::KEYS.len(), 4 * 4);
+    }
+
+    #[test]
+    fn keys_3x4() {
+        let keys = Keypad::<'static, 3, 4>::KEYS;
+        assert_eq!(keys[0], Key::Digit(1));
+        assert_eq!(keys[2], Key::Digit(3));
+        assert_eq!(keys[3], Key::Digit(4));
+        assert_eq!(keys[9], Key::Cancel);
+        assert_eq!(keys[10], Key::Digit(0));
+        assert_eq!(keys[11], Key::Enter);
+    }
+
+    #[test]
+    fn keys_4x4() {
+        let keys = Keypad::<'static, 4, 4>::KEYS;
+        assert_eq!(keys[0], Key::Digit(1));
+        assert_eq!(keys[2], Key::Digit(3));
+        assert_eq!(keys[3], Key::Other('A'));
+        assert_eq!(keys[4], Key::Digit(4));
+        assert_eq!(keys[7], Key::Other('B'));
+        assert_eq!(keys[11], Key::Other('C'));
+        assert_eq!(keys[12], Key::Cancel);
+        assert_eq!(keys[13], Key::Digit(0));
+        assert_eq!(keys[14], Key::Enter);
+        assert_eq!(keys[15], Key::Other('D'));
+        // Digits are at the same positions as on the 3x4 keypad
+        for (scancode, key) in Keypad::<'static, 3, 4>::KEYS.iter().enumerate() {
+            assert_eq!(keys[scancode / 3 * 4 + scancode % 3], *key);
+        }
+    }
+
+    #[test]
+    fn long_press_not_pressed() {
+        let mut long_press = LongPress::new(THRESHOLD);
+        assert!(!long_press.update(Instant::from_millis(5000)));
+    }
+
+    #[test]
+    fn long_press_released_early() {
+        let mut long_press = LongPress::new(THRESHOLD);
+        long_press.press(Instant::from_millis(1000));
+        assert!(!long_press.update(Instant::from_millis(1500)));
+        long_press.release();
+        assert!(!long_press.update(Instant::from_millis(2000)));
+    }
+
+    #[test]
+    fn long_press_fires_once() {
+        let mut long_press = LongPress::new(THRESHOLD);
+        long_press.press(Instant::from_millis(1000));
+        assert!(!long_press.update(Instant::from_millis(1799)));
+        assert!(long_press.update(Instant::from_millis(1800)));
+        assert!(!long_press.update(Instant::from_millis(2500)));
+    }
+
+    #[test]
+    fn long_press_pressed_again() {
+        let mut long_press = LongPress::new(THRESHOLD);
+        long_press.press(Instant::from_millis(1000));
+        assert!(long_press.update(Instant::from_millis(2000)));
+        long_press.release();
+        long_press.press(Instant::from_millis(3000));
+        assert!(!long_press.update(Instant::from_millis(3500)));
+        assert!(long_press.update(Instant::from_millis(3900)));
+    }
+
+    #[test]
+    fn scan_no_key() {
+        assert_eq!(pressed_scancode(&NONE), None);
+    }
+
+    #[test]
+    fn scan_single_key() {
+        let mut states = NONE;
+        states[0][0] = true;
+        assert_eq!(pressed_scancode(&states), Some(0));
+        let mut states = NONE;
+        states[2][1] = true;
+        assert_eq!(pressed_scancode(&states), Some(7));
+        let mut states = NONE;
+        states[3][2] = true;
+        assert_eq!(pressed_scancode(&states), Some(11));
+    }
+
+    #[test]
+    fn scan_two_keys() {
+        // Two keys can't ghost, first pressed key wins
+        let mut states = NONE;
+        states[1][0] = true;
+        states[2][2] = true;
+        assert!(!may_ghost(&states));
+        assert_eq!(pressed_scancode(&states), Some(3));
+        let mut states = NONE;
+        states[1][0] = true;
+        states[1][2] = true;
+        assert!(!may_ghost(&states));
+        assert_eq!(pressed_scancode(&states), Some(3));
+    }
+
+    #[test]
+    fn scan_ghosting() {
+        // Keys 1, 2 and 4 pressed make key 5 appear pressed as well
+        let states = [
+            [true, true, false],
+            [true, true, false],
+            [false, false, false],
+            [false, false, false],
+        ];
+        assert!(may_ghost(&states));
+        assert_eq!(pressed_scancode(&states), None);
+        // Rectangle across non-adjacent rows and columns
+        let states = [
+            [false, false, false],
+            [true, false, true],
+            [false, true, false],
+            [true, false, true],
+        ];
+        assert!(may_ghost(&states));
+        assert_eq!(pressed_scancode(&states), None);
+    }
+
+    #[test]
+    fn scan_three_keys_without_rectangle() {
+        let states = [
+            [true, false, false],
+            [false, true, false],
+            [false, false, true],
+            [false, false, false],
+        ];
+        assert!(!may_ghost(&states));
+        assert_eq!(pressed_scancode(&states), Some(0));
+    }
+
+    #[test]
+    fn sequence_complete() {
+        let mut seq = KeySequence::new(SEQUENCE);
+        assert!(seq.is_idle());
+        assert!(!seq.feed(Key::Cancel));
+        assert!(!seq.is_idle());
+        assert!(!seq.feed(Key::Enter));
+        assert!(!seq.feed(Key::Cancel));
+        assert!(seq.feed(Key::Enter));
+        assert!(seq.is_idle());
+    }
+
+    #[test]
+    fn sequence_interrupted() {
+        let mut seq = KeySequence::new(SEQUENCE);
+        assert!(!seq.feed(Key::Cancel));
+        assert!(!seq.feed(Key::Enter));
+        assert!(!seq.feed(Key::Digit(1)));
+        assert!(seq.is_idle());
+        assert!(!seq.feed(Key::Enter));
+        assert!(seq.is_idle());
+    }
+
+    #[test]
+    fn sequence_restarted() {
+        let mut seq = KeySequence::new(SEQUENCE);
+        assert!(!seq.feed(Key::Cancel));
+        assert!(!seq.feed(Key::Cancel));
+        assert!(!seq.feed(Key::Enter));
+        assert!(!seq.feed(Key::Cancel));
+        assert!(seq.feed(Key::Enter));
+    }
+
+    #[test]
+    fn sequence_repeated() {
+        let mut seq = KeySequence::new(SEQUENCE);
+        for key in SEQUENCE {
+            seq.feed(key);
+        }
+        assert!(seq.is_idle());
+        for key in &SEQUENCE[..3] {
+            assert!(!seq.feed(*key));
+        }
+        assert!(seq.feed(Key::Enter));
+    }
+
+    #[test]
+    fn number_entry() {
+        let mut entry = NumberEntry::new(5);
+        assert_eq!(entry.feed(Key::Digit(4)), Entry::Pending);
+        assert_eq!(entry.feed(Key::Digit(0)), Entry::Pending);
+        assert_eq!(entry.feed(Key::Digit(2)), Entry::Pending);
+        assert_eq!(entry.digits(), "402");
+        assert_eq!(entry.feed(Key::Enter), Entry::Done(402));
+    }
+
+    #[test]
+    fn number_entry_empty() {
+        let mut entry = NumberEntry::new(5);
+        assert_eq!(entry.feed(Key::Enter), Entry::Pending);
+        assert_eq!(entry.feed(Key::Other('A')), Entry::Pending);
+        assert_eq!(entry.digits(), "");
+        assert_eq!(entry.feed(Key::Cancel), Entry::Cancelled);
+    }
+
+    #[test]
+    fn number_entry_delete() {
+        let mut entry = NumberEntry::new(5);
+        entry.feed(Key::Digit(1));
+        entry.feed(Key::Digit(2));
+        assert_eq!(entry.feed(Key::Cancel), Entry::Pending);
+        assert_eq!(entry.digits(), "1");
+        assert_eq!(entry.feed(Key::Cancel), Entry::Pending);
+        assert_eq!(entry.feed(Key::Cancel), Entry::Cancelled);
+    }
+
+    #[test]
+    fn number_entry_max_digits() {
+        let mut entry = NumberEntry::new(3);
+        for n in 1..=5 {
+            entry.feed(Key::Digit(n));
+        }
+        assert_eq!(entry.digits(), "123");
+        assert_eq!(entry.feed(Key::Enter), Entry::Done(123));
+        // Number of digits is limited so that any entered number fits
+        let mut entry = NumberEntry::new(20);
+        for _ in 0..20 {
+            entry.feed(Key::Digit(9));
+        }
+        assert_eq!(entry.feed(Key::Enter), Entry::Done(999_999_999));
+    }
+
+    #[test]
+    fn number_entry_clear_on_hold() {
+        let mut entry = NumberEntry::new(5);
+        entry.feed_press(KeyPress::Short(Key::Digit(1)));
+        entry.feed_press(KeyPress::Long(Key::Digit(2)));
+        assert_eq!(entry.digits(), "12");
+        assert_eq!(
+            entry.feed_press(KeyPress::Long(Key::Cancel)),
+            Entry::Pending
+        );
+        assert_eq!(entry.digits(), "");
+        assert_eq!(
+            entry.feed_press(KeyPress::Long(Key::Cancel)),
+            Entry::Cancelled
+        );
+    }
+
+    #[derive(Debug, Default, Clone, Copy)]
+    struct RowStub {
+        low: bool,
+    }
+
+    impl embedded_hal::digital::ErrorType for RowStub {
+        type Error = core::convert::Infallible;
+    }
+
+    impl OutputPin for RowStub {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.low = true;
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.low = false;
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Default, Clone, Copy)]
+    struct ColStub {
+        wakeup: bool,
+    }
+
+    impl WakeupPin for ColStub {
+        fn set_wakeup(&mut self, enable: bool) {
+            self.wakeup = enable;
+        }
+    }
+
+    #[test]
+    fn wakeup_enable() {
+        let mut rows = [RowStub::default(); 4];
+        let mut cols = [ColStub::default(); 3];
+        configure_wakeup(&mut rows, &mut cols, true);
+        // Any key pulls its column low
+        assert!(rows.iter().all(|row| row.low));
+        assert!(cols.iter().all(|col| col.wakeup));
+    }
+
+    #[test]
+    fn wakeup_disable() {
+        let mut rows = [RowStub::default(); 4];
+        let mut cols = [ColStub { wakeup: true }; 3];
+        configure_wakeup(&mut rows, &mut cols, false);
+        assert!(rows.iter().all(|row| row.low));
+        assert!(cols.iter().all(|col| !col.wakeup));
+    }
 }
@@ -0,0 +1,220 @@
+use crate::json::{self, FromJson};
+use alloc::string::String;
+use embedded_io_async::BufRead;
+use log::warn;
+
+/// Language of on-screen texts
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    /// German (default)
+    #[default]
+    German,
+    /// English
+    English,
+}
+
+impl Language {
+    /// Look up language by its ISO 639-1 code (e.g. "de" or "en")
+    pub fn from_code(code: &str) -> Option<Self> {
+        match code {
+            "de" => Some(Self::German),
+            "en" => Some(Self::English),
+            _ => None,
+        }
+    }
+
+    /// On-screen texts in this language
+    pub fn strings(self) -> &'static Strings {
+        match self {
+            Self::German => &GERMAN,
+            Self::English => &ENGLISH,
+        }
+    }
+}
+
+impl FromJson for Language {
+    async fn from_json<R: BufRead>(
+        json: &mut json::Reader<R>,
+    ) -> Result<Self, json::Error<R::Error>> {
+        let code: String = json.read().await?;
+        Ok(Self::from_code(&code).unwrap_or_else(|| {
+            warn!("Language: Unknown language {}, using default", code);
+            Self::default()
+        }))
+    }
+}
+
+/// Table of on-screen texts
+#[derive(Debug)]
+pub struct Strings {
+    /// User greetings (chosen randomly)
+    pub greetings: &'static [&'static str],
+    pub error: &'static str,
+    pub cancel: &'static str,
+    pub back: &'static str,
+    pub next: &'static str,
+    pub page: &'static str,
+    pub ok: &'static str,
+    pub stand_by: &'static str,
+    pub wifi_connecting: &'static str,
+    pub updating_data: &'static str,
+    pub fetching_balance: &'static str,
+    pub purchasing: &'static str,
+    pub submitting_telemetry: &'static str,
+    pub admin_menu: &'static str,
+    pub admin_pin: &'static str,
+    pub pin: &'static str,
+    pub refresh: &'static str,
+    pub device_id: &'static str,
+    pub clear_queue: &'static str,
+    pub reload_config: &'static str,
+    pub config_reloaded: &'static str,
+    pub restart_required: &'static str,
+    pub scan_id: &'static str,
+    pub member_id: &'static str,
+    pub enter_member_id: &'static str,
+    pub demo_mode: &'static str,
+    pub select_amount: &'static str,
+    pub pay: &'static str,
+    pub drinks_approved: &'static str,
+    pub undo: &'static str,
+    pub undone: &'static str,
+    pub drinks_cancelled: &'static str,
+    pub really: &'static str,
+    pub panic: &'static str,
+    pub restarting: &'static str,
+    pub brown_out: &'static str,
+    pub low_voltage: &'static str,
+    pub not_configured: &'static str,
+    pub flash_config: &'static str,
+}
+
+/// German texts
+static GERMAN: Strings = Strings {
+    greetings: &[
+        "Hi", "Hallo", "Hey", "Tach", "Servus", "Moin", "Hej", "Olá", "Ciao",
+    ],
+    error: "FEHLER!",
+    cancel: "* Abbruch",
+    back: "* Zurück",
+    next: "Weiter",
+    page: "Seite",
+    ok: "# Ok",
+    stand_by: "Stand By...",
+    wifi_connecting: "WLAN Verbindung\nwird aufgebaut",
+    updating_data: "Daten-Aktualisierung",
+    fetching_balance: "Kontostand wird\nabgefragt",
+    purchasing: "Zahlung wird\nbearbeitet",
+    submitting_telemetry: "Daten-Übertragung",
+    admin_menu: "Admin-Menü",
+    admin_pin: "Admin-PIN",
+    pin: "PIN",
+    refresh: "Aktualisieren",
+    device_id: "Geräte-ID",
+    clear_queue: "Queue leeren",
+    reload_config: "Konfig. laden",
+    config_reloaded: "Konfiguration\ngeladen",
+    restart_required: "Neustart erforderlich",
+    scan_id: "Mitgliedsausweis\nscannen",
+    member_id: "Mitgliedsnummer",
+    enter_member_id: "# Nummer",
+    demo_mode: "Demo-Modus",
+    select_amount: "Anzahl wählen",
+    pay: "# BEZAHLEN",
+    drinks_approved: "Getränke genehmigt",
+    undo: "* Rückgängig",
+    undone: "Storniert!",
+    drinks_cancelled: "Getränke storniert",
+    really: "Wirklich",
+    panic: "SYSTEMFEHLER!",
+    restarting: "Neustart in",
+    brown_out: "STROMAUSFALL!",
+    low_voltage: "Spannung zu niedrig",
+    not_configured: "KEINE KONFIG!",
+    flash_config: "Konfiguration flashen",
+};
+
+/// English texts
+static ENGLISH: Strings = Strings {
+    greetings: &[
+        "Hi", "Hello", "Hey", "Howdy", "Cheers", "Welcome", "Hej", "Olá", "Ciao",
+    ],
+    error: "ERROR!",
+    cancel: "* Cancel",
+    back: "* Back",
+    next: "Next",
+    page: "Page",
+    ok: "# Ok",
+    stand_by: "Stand By...",
+    wifi_connecting: "Connecting\nto WiFi",
+    updating_data: "Updating data",
+    fetching_balance: "Fetching\naccount balance",
+    purchasing: "Processing\npayment",
+    submitting_telemetry: "Sending data",
+    admin_menu: "Admin Menu",
+    admin_pin: "Admin PIN",
+    pin: "PIN",
+    refresh: "Refresh",
+    device_id: "Device ID",
+    clear_queue: "Clear queue",
+    reload_config: "Reload config",
+    config_reloaded: "Configuration\nreloaded",
+    restart_required: "Restart required",
+    scan_id: "Scan\nmember card",
+    member_id: "Member number",
+    enter_member_id: "# Number",
+    demo_mode: "Demo mode",
+    select_amount: "Select amount",
+    pay: "# PAY",
+    drinks_approved: "drinks approved",
+    undo: "* Undo",
+    undone: "Undone!",
+    drinks_cancelled: "drinks cancelled",
+    really: "Really",
+    panic: "PANIC!",
+    restarting: "Restarting in",
+    brown_out: "POWER FAILURE!",
+    low_voltage: "Supply voltage too low",
+    not_configured: "NOT CONFIGURED!",
+    flash_config: "Please flash config",
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_code() {
+        assert_eq!(Language::from_code("de"), Some(Language::German));
+        assert_eq!(Language::from_code("en"), Some(Language::English));
+        assert_eq!(Language::from_code("xx"), None);
+    }
+
+    #[test]
+    fn default_is_german() {
+        assert_eq!(Language::default(), Language::German);
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(Language::German.strings().error, "FEHLER!");
+        assert_eq!(Language::English.strings().error, "ERROR!");
+        assert_eq!(Language::German.strings().pay, "# BEZAHLEN");
+        assert_eq!(Language::English.strings().pay, "# PAY");
+        assert_eq!(Language::German.strings().greetings[1], "Hallo");
+        assert_eq!(Language::English.strings().greetings[1], "Hello");
+    }
+
+    #[async_std::test]
+    async fn read_language() {
+        let read = |s: &'static str| async move {
+            json::Reader::new(s.as_bytes())
+                .read::<Language>()
+                .await
+                .unwrap()
+        };
+        assert_eq!(read(r#""en""#).await, Language::English);
+        assert_eq!(read(r#""de""#).await, Language::German);
+        assert_eq!(read(r#""xx""#).await, Language::German);
+    }
+}
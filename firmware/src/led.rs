@@ -0,0 +1,189 @@
+use embassy_executor::{task, Spawner};
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+use esp_hal::gpio::{Level, Output};
+use log::debug;
+
+/// System state to indicate with the status LED
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// System is starting up
+    Booting,
+    /// Waiting for network to become available
+    Connecting,
+    /// Ready and waiting for a user
+    Ready,
+    /// User transaction (or other lengthy action) in progress
+    Busy,
+    /// Showing an error
+    Error,
+}
+
+impl State {
+    /// Blink pattern of this state. Steps of LED level (on/off) and duration in milliseconds,
+    /// repeated endlessly.
+    fn pattern(self) -> &'static [(bool, u64)] {
+        match self {
+            // Fast blinking
+            Self::Booting => &[(true, 100), (false, 100)],
+            // Slow blinking
+            Self::Connecting => &[(true, 500), (false, 500)],
+            // Short flash every few seconds
+            Self::Ready => &[(true, 50), (false, 2950)],
+            // Steady on
+            Self::Busy => &[(true, 1000)],
+            // Double flash
+            Self::Error => &[(true, 100), (false, 100), (true, 100), (false, 700)],
+        }
+    }
+}
+
+/// Blink pattern state machine
+#[derive(Debug)]
+struct Blinker {
+    state: State,
+    step: usize,
+}
+
+impl Blinker {
+    /// Create new state machine, starting with the pattern of the given state
+    fn new(state: State) -> Self {
+        Self { state, step: 0 }
+    }
+
+    /// Switch to pattern of given state. Returns false if the state didn't change, in which case
+    /// the current pattern continues.
+    fn set_state(&mut self, state: State) -> bool {
+        if state == self.state {
+            return false;
+        }
+        self.state = state;
+        self.step = 0;
+        true
+    }
+
+    /// Next step of the current pattern: LED level (on/off) and duration to keep it
+    fn next_step(&mut self) -> (bool, Duration) {
+        let pattern = self.state.pattern();
+        let (on, millis) = pattern[self.step];
+        self.step = (self.step + 1) % pattern.len();
+        (on, Duration::from_millis(millis))
+    }
+}
+
+/// Requested state, passed to the blink task
+static STATE: Signal<CriticalSectionRawMutex, State> = Signal::new();
+
+/// Indicate given system state with the status LED
+pub fn set(state: State) {
+    STATE.signal(state);
+}
+
+/// Start blinking the status LED. The LED is active low, i.e. it lights up if the output is low.
+pub fn start(led: Output<'static>, spawner: Spawner) {
+    debug!("LED: Spawning blink task");
+    spawner
+        .spawn(blink(led))
+        // Panic on failure since failing to spawn a task indicates a serious error
+        .expect("Failed to spawn LED blink task");
+}
+
+/// Task for blinking the status LED according to the current system state
+#[task]
+async fn blink(mut led: Output<'static>) {
+    let mut blinker = Blinker::new(State::Booting);
+    loop {
+        let (on, duration) = blinker.next_step();
+        led.set_level(if on { Level::Low } else { Level::High });
+        let deadline = Instant::now() + duration;
+        // Keep current step until its time is up or the state changes
+        while let Either::Second(state) = select(Timer::at(deadline), STATE.wait()).await {
+            if blinker.set_state(state) {
+                debug!("LED: Indicating {:?}", state);
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    /// Timeline of the next given number of steps, in milliseconds
+    fn timeline(blinker: &mut Blinker, steps: usize) -> Vec<(bool, u64)> {
+        (0..steps)
+            .map(|_| {
+                let (on, duration) = blinker.next_step();
+                (on, duration.as_millis())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn booting() {
+        let mut blinker = Blinker::new(State::Booting);
+        assert_eq!(
+            timeline(&mut blinker, 4),
+            [(true, 100), (false, 100), (true, 100), (false, 100)]
+        );
+    }
+
+    #[test]
+    fn connecting() {
+        let mut blinker = Blinker::new(State::Connecting);
+        assert_eq!(
+            timeline(&mut blinker, 4),
+            [(true, 500), (false, 500), (true, 500), (false, 500)]
+        );
+    }
+
+    #[test]
+    fn ready() {
+        let mut blinker = Blinker::new(State::Ready);
+        assert_eq!(
+            timeline(&mut blinker, 4),
+            [(true, 50), (false, 2950), (true, 50), (false, 2950)]
+        );
+    }
+
+    #[test]
+    fn busy() {
+        let mut blinker = Blinker::new(State::Busy);
+        assert_eq!(timeline(&mut blinker, 2), [(true, 1000), (true, 1000)]);
+    }
+
+    #[test]
+    fn error() {
+        let mut blinker = Blinker::new(State::Error);
+        assert_eq!(
+            timeline(&mut blinker, 5),
+            [
+                (true, 100),
+                (false, 100),
+                (true, 100),
+                (false, 700),
+                (true, 100)
+            ]
+        );
+    }
+
+    #[test]
+    fn state_change_restarts_pattern() {
+        let mut blinker = Blinker::new(State::Error);
+        timeline(&mut blinker, 3);
+        assert!(blinker.set_state(State::Ready));
+        assert_eq!(timeline(&mut blinker, 2), [(true, 50), (false, 2950)]);
+    }
+
+    #[test]
+    fn same_state_continues_pattern() {
+        let mut blinker = Blinker::new(State::Error);
+        timeline(&mut blinker, 3);
+        assert!(!blinker.set_state(State::Error));
+        assert_eq!(timeline(&mut blinker, 2), [(false, 700), (true, 100)]);
+    }
+}
@@ -10,7 +10,12 @@
 //!     (I2C SDA)     SCL/GPIO9 -  9 | 3  - GPIO3/A3     (Keypad Row4)
 //!     (I2C SCL)        GPIO10 - 10 | 2  - GPIO2/A2     (Keypad Row3)
 //!     (NFC IRQ)     RX/GPIO20 - 20 | 1  - GPIO1/A1     (Keypad Row2)
-//!                   TX/GPIO21 - 21 | 0  - GPIO0/A0     (Keypad Row1)
+//! (Keypad Col4*)    TX/GPIO21 - 21 | 0  - GPIO0/A0     (Keypad Row1)
+//!
+//! * Only with 4x4 matrix keypad (feature `keypad-4x4`)
+//!
+//! With battery monitoring (feature `battery`), a battery voltage divider is connected to GPIO4
+//! and the buzzer to GPIO21 instead.
 //!
 //! Pinout OLED 2.42" Display
 //!
@@ -39,33 +44,53 @@
 #![no_std]
 #![no_main]
 
+#[cfg(all(feature = "battery", feature = "keypad-4x4"))]
+compile_error!("Features `battery` and `keypad-4x4` can't be combined since both use GPIO21");
+
 mod article;
+mod battery;
+mod brownout;
 mod buzzer;
+mod cache;
 mod config;
+mod cooldown;
+mod demo;
 mod display;
 mod error;
+mod flash;
+mod heap;
 mod http;
+mod idle;
 mod json;
 mod keypad;
+mod language;
+mod led;
 mod mixpanel;
+mod ndef;
 mod nfc;
 mod pn532;
+mod queue;
 mod schedule;
 mod screen;
+mod sleep;
 mod telemetry;
 mod time;
+mod totp;
 mod ui;
+mod undo;
 mod user;
 mod vereinsflieger;
+mod watchdog;
 mod wifi;
 
+use alloc::format;
 use embassy_embedded_hal::shared_bus::asynch::i2c::I2cDevice;
 use embassy_executor::Spawner;
 use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
 use esp_alloc as _;
 use esp_backtrace as _;
 use esp_hal::clock::CpuClock;
-use esp_hal::config::WatchdogConfig;
+use esp_hal::config::{WatchdogConfig, WatchdogStatus};
 use esp_hal::efuse::Efuse;
 use esp_hal::gpio::{Input, Level, Output, OutputOpenDrain, Pull};
 use esp_hal::i2c::master::{BusTimeout, Config as I2cConfig, I2c};
@@ -76,7 +101,7 @@ use esp_hal::time::{Duration, RateExtU32};
 use esp_hal::timer::systimer::SystemTimer;
 use esp_hal::timer::timg::TimerGroup;
 use esp_println::println;
-use log::{error, info};
+use log::{error, info, warn};
 use rand_core::RngCore;
 
 extern crate alloc;
@@ -99,7 +124,12 @@ unsafe fn halt() -> ! {
     // restart the system. Any error should be ignored.
     let peripherals = Peripherals::steal();
 
-    // TODO: Steal display driver and show a panic message to the user
+    // Show a panic message to the user. The display is driven by a new blocking driver since
+    // the async one is owned by the (now halted) executor.
+    if let Ok(i2c) = I2c::new(peripherals.I2C0, I2cConfig::default()) {
+        let i2c = i2c.with_sda(peripherals.GPIO9).with_scl(peripherals.GPIO10);
+        display::show_blocking(i2c, &screen::Panic::new(PANIC_RESTART_DELAY.to_secs()));
+    }
 
     // Restart automatically after a delay
     println!("Restarting in {} seconds...", PANIC_RESTART_DELAY.to_secs());
@@ -116,14 +146,15 @@ unsafe fn halt() -> ! {
 async fn main(spawner: Spawner) {
     let esp_config = esp_hal::Config::default()
         .with_cpu_clock(CpuClock::max())
-        // TODO: Enable watchdog
-        .with_watchdog(WatchdogConfig::default());
+        .with_watchdog(WatchdogConfig::default().with_rwdt(WatchdogStatus::Enabled(
+            Duration::micros(watchdog::TIMEOUT.as_micros()),
+        )));
     let peripherals = esp_hal::init(esp_config);
     let mut rng = Rng::new(peripherals.RNG);
-    let _led = Output::new(peripherals.GPIO8, Level::High);
+    let led = Output::new(peripherals.GPIO8, Level::High);
 
     // Initialize global allocator
-    esp_alloc::heap_allocator!(150 * 1024);
+    esp_alloc::heap_allocator!(heap::SIZE);
 
     // Initialize async executor
     let systimer = SystemTimer::new(peripherals.SYSTIMER);
@@ -133,13 +164,36 @@ async fn main(spawner: Spawner) {
     esp_println::logger::init_logger_from_env();
     info!("Touch 'n Drink v{VERSION_STR} ({GIT_SHA_STR})");
 
+    // Detect brown-outs to shut down gracefully if the supply voltage drops
+    let mut rtc = Rtc::new(peripherals.LPWR);
+    brownout::enable(&mut rtc);
+
+    // Feed watchdog periodically. Resets the system if the executor gets stuck.
+    watchdog::start(core::mem::take(&mut rtc.rwdt), spawner);
+
+    // Indicate system state with status LED
+    led::start(led, spawner);
+
     // Read system configuration
     let config = config::Config::read().await;
 
     // Initialize article and user look up tables
-    let mut articles = article::Articles::new(config.vf_article_ids);
+    let mut articles = article::Articles::new(config.vf_article_ids.clone());
     let mut users = user::Users::new();
 
+    // Load cached articles and users (if any)
+    let mut cache = cache::Cache::new(
+        config
+            .cache_max_age
+            .map_or(cache::DEFAULT_MAX_AGE, |hours| {
+                chrono::TimeDelta::hours(hours.into())
+            }),
+    );
+    cache.load(&mut articles, &mut users).await;
+
+    // Load queue of pending purchases
+    let queue = queue::Queue::load().await;
+
     // Initialize I2C controller
     let i2c_config = I2cConfig::default()
         // Standard-Mode: 100 kHz, Fast-Mode: 400 kHz
@@ -158,11 +212,40 @@ async fn main(spawner: Spawner) {
     let i2c: Mutex<NoopRawMutex, _> = Mutex::new(i2c);
 
     // Initialize display
-    let mut display = display::Display::new(I2cDevice::new(&i2c))
-        .await
-        // Panic on failure since without a display there's no reasonable way to tell the user
-        .expect("Display initialization failed");
-    let _ = display.screen(&screen::Splash).await;
+    let mut display =
+        display::Display::new(I2cDevice::new(&i2c), config.display_rotation.unwrap_or(0))
+            .await
+            // Panic on failure since without a display there's no reasonable way to tell the user
+            .expect("Display initialization failed");
+    display.set_language(config.language);
+    if config.display_brightness.is_some() {
+        let _ = display.set_brightness(config.display_brightness).await;
+    }
+    let machine_name = config.machine_name.as_deref().unwrap_or_default();
+    let _ = display.screen(&screen::Splash::new(machine_name)).await;
+
+    // Without required settings (e.g. if the config partition is missing), the device can't
+    // work. Tell the user instead of endlessly failing with network errors.
+    if !config.is_valid() {
+        error!("Missing or incomplete configuration, halting");
+        led::set(led::State::Error);
+        let _ = display
+            .screen(&screen::NotConfigured::new(&config.missing_keys()))
+            .await;
+        return core::future::pending().await;
+    }
+
+    // If pending purchases can't be loaded, refuse to work instead of discarding them. Stored
+    // purchases can still be read out of the queue partition to book them manually.
+    let mut queue = match queue {
+        Ok(queue) => queue,
+        Err(err) => {
+            error!("Unable to load pending purchases, halting");
+            led::set(led::State::Error);
+            let _ = display.screen(&screen::Failure::new(err, None)).await;
+            return core::future::pending().await;
+        }
+    };
 
     // Initialize keypad
     let mut keypad = keypad::Keypad::new(
@@ -170,6 +253,8 @@ async fn main(spawner: Spawner) {
             Input::new(peripherals.GPIO5, Pull::Up),
             Input::new(peripherals.GPIO6, Pull::Up),
             Input::new(peripherals.GPIO7, Pull::Up),
+            #[cfg(feature = "keypad-4x4")]
+            Input::new(peripherals.GPIO21, Pull::Up),
         ],
         [
             OutputOpenDrain::new(peripherals.GPIO0, Level::High, Pull::None),
@@ -200,32 +285,114 @@ async fn main(spawner: Spawner) {
     // Panic on failure since an initialization error indicates a static configuration error
     .expect("Wifi initialization failed");
 
+    // Demo mode works without network, so don't even try to connect
+    if config.demo_mode.unwrap_or(false) {
+        wifi.stop();
+    }
+
     // Initialize HTTP client
     // As this allocates quite a bit of memory (e.g. for TLS buffers), only a single http client
     // is created that can be passed to an API client whenever a connection needs to be established
-    let mut http_resources = http::Resources::new();
+    let mut http_resources = http::Resources::new(http::DEFAULT_RX_BUFFER_SIZE);
     let mut http = http::Http::new(&wifi, rng.next_u64(), &mut http_resources);
 
+    // Device id (from base MAC address)
+    let device_id: const_hex::Buffer<6, false> =
+        const_hex::Buffer::new().const_format(&Efuse::read_base_mac_address());
+
     // Initialize Vereinsflieger API client
     let mut vereinsflieger = vereinsflieger::Vereinsflieger::new(
         &config.vf_username,
         &config.vf_password_md5,
         &config.vf_appkey,
         config.vf_cid,
+        device_id.as_str(),
     );
+    if let Some(ref comment) = config.vf_sale_comment {
+        vereinsflieger.set_sale_comment(comment.clone());
+    }
+    if let Some(ref prefix) = config.vf_nfc_key_prefix {
+        vereinsflieger.set_nfc_key_prefix(prefix.clone());
+    }
+    if let Some(page_size) = config.vf_page_size {
+        vereinsflieger.set_page_size(page_size);
+    }
+    if let Some(ref secret) = config.vf_auth_secret {
+        if let Some(totp) = totp::Totp::from_base32(secret) {
+            vereinsflieger.set_auth_secret(totp);
+        } else {
+            warn!("Invalid Vereinsflieger two-factor auth secret");
+        }
+    }
 
     // Initialize telemetry
-    let device_id: const_hex::Buffer<6, false> =
-        const_hex::Buffer::new().const_format(&Efuse::read_base_mac_address());
-    let mut telemetry = telemetry::Telemetry::new(config.mp_token.as_deref(), device_id.as_str());
+    let mut telemetry = telemetry::Telemetry::new(
+        config.mp_token.as_deref(),
+        config.mp_region,
+        device_id.as_str(),
+    );
+    if !machine_name.is_empty() {
+        telemetry.set_machine_name(machine_name);
+    }
+    telemetry.set_flush_limits(
+        config
+            .mp_flush_interval
+            .map_or(telemetry::MAX_BUFFER_DURATION, |secs| {
+                embassy_time::Duration::from_secs(secs.into())
+            }),
+        config.mp_batch_size.unwrap_or(telemetry::MAX_BUFFER_EVENTS),
+    );
     telemetry.track(telemetry::Event::SystemStart);
+    if let Some(version) = nfc.firmware_version() {
+        telemetry.track(telemetry::Event::ReaderInitialized(version));
+    }
+
+    // Monitor battery voltage (if running on battery)
+    #[cfg(feature = "battery")]
+    if config.battery_monitor.unwrap_or(true) {
+        battery::start(peripherals.ADC1, peripherals.GPIO4, spawner);
+    } else {
+        info!("Battery: Monitoring disabled, assuming external power");
+    }
 
     // Initialize buzzer
+    #[cfg(not(feature = "battery"))]
     let mut buzzer = buzzer::Buzzer::new(peripherals.LEDC, peripherals.GPIO4);
-    let _ = buzzer.startup().await;
+    #[cfg(feature = "battery")]
+    let mut buzzer = buzzer::Buzzer::new(peripherals.LEDC, peripherals.GPIO21);
+    if let Some(volume) = config.buzzer_volume {
+        buzzer.set_volume(volume);
+    }
+    if let Some(muted) = config.buzzer_muted {
+        buzzer.set_muted(muted);
+    }
+    if let Err(err) = buzzer.startup().await {
+        // Not fatal, but a silent buzzer should be noticed
+        error!("Buzzer: Startup failed: {}", err);
+        telemetry.track(telemetry::Event::Error(
+            None,
+            None,
+            format!("Buzzer: {err}"),
+        ));
+    }
 
     // Initialize scheduler
-    let mut schedule = schedule::Daily::new();
+    let mut refresh = schedule::Recurring::daily();
+    if let Some(ref time) = config.schedule_time {
+        let offset =
+            chrono::FixedOffset::east_opt(config.utc_offset.unwrap_or(0).saturating_mul(60));
+        if let (Some(time), Some(offset)) = (schedule::parse_time_of_day(time), offset) {
+            refresh.set_time_of_day(time, offset);
+        } else {
+            warn!("Invalid schedule time {} or UTC offset", time);
+        }
+    }
+    let mut schedule = schedule::Scheduler::new();
+    schedule.add(schedule::Job::RefreshData, refresh);
+    schedule.add(schedule::Job::FlushTelemetry, schedule::Recurring::hourly());
+
+    // RTC is used to put the system into sleep after extended idle
+    let mut sleep = sleep::Sleep::new(rtc);
 
     // Create UI
     let mut ui = ui::Ui::new(
@@ -239,11 +406,18 @@ async fn main(spawner: Spawner) {
         &mut vereinsflieger,
         &mut articles,
         &mut users,
+        &mut queue,
+        &mut cache,
         &mut telemetry,
         &mut schedule,
+        &mut sleep,
+        &config,
+        device_id.as_str(),
     );
+    let _ = ui.apply_settings(&config.settings()).await;
 
     loop {
+        #[allow(clippy::large_futures)]
         match ui.init().await {
             // Success: continue
             Ok(()) => break,
@@ -1,18 +1,76 @@
+mod proto_engage;
 mod proto_event;
 
 use crate::http::{self, Http};
+use crate::json::{self, FromJson};
 use crate::telemetry::Event;
 use crate::time;
+use crate::user::UserId;
+use alloc::string::String;
 use core::fmt;
 use embassy_time::{with_timeout, Duration, Instant};
+use embedded_io_async::BufRead;
 use log::{debug, warn};
 
-/// Mixpanel API base URL
-const BASE_URL: &str = "https://api-eu.mixpanel.com";
-
 /// How long to wait for a server response
 const TIMEOUT: Duration = Duration::from_secs(10);
 
+/// Mixpanel data residency region
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    /// EU data residency (default)
+    #[default]
+    Eu,
+    /// US data residency
+    Us,
+    /// India data residency
+    In,
+}
+
+impl Region {
+    /// Look up region by its name (e.g. "eu" or "us")
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "eu" => Some(Self::Eu),
+            "us" => Some(Self::Us),
+            "in" => Some(Self::In),
+            _ => None,
+        }
+    }
+
+    /// Mixpanel API base URL for this region
+    pub fn base_url(self) -> &'static str {
+        match self {
+            Self::Eu => "https://api-eu.mixpanel.com",
+            Self::Us => "https://api.mixpanel.com",
+            Self::In => "https://api-in.mixpanel.com",
+        }
+    }
+}
+
+impl FromJson for Region {
+    async fn from_json<R: BufRead>(
+        json: &mut json::Reader<R>,
+    ) -> Result<Self, json::Error<R::Error>> {
+        let name: String = json.read().await?;
+        Ok(Self::from_name(&name).unwrap_or_else(|| {
+            warn!("Mixpanel: Unknown region {}, using default", name);
+            Self::default()
+        }))
+    }
+}
+
+/// Read API response. Mixpanel returns an empty body on success (unless asked for a verbose
+/// response), which isn't valid JSON, so an empty body results in a default response.
+async fn read_response<T: FromJson + Default, R: BufRead>(
+    json: &mut json::Reader<R>,
+) -> Result<T, json::Error<R::Error>> {
+    if json.is_end().await? {
+        return Ok(T::default());
+    }
+    json.read().await
+}
+
 /// Mixpanel API error
 #[derive(Debug)]
 pub enum Error {
@@ -22,6 +80,8 @@ pub enum Error {
     Connect(http::Error),
     /// Failed to submit events to API server
     Submit(http::Error),
+    /// Failed to set user profile
+    SetProfile(http::Error),
     /// Timeout waiting for response from API server
     Timeout,
 }
@@ -38,6 +98,7 @@ impl fmt::Display for Error {
             Self::CurrentTimeNotSet => write!(f, "Unknown current time"),
             Self::Connect(err) => write!(f, "Connect failed ({err})"),
             Self::Submit(err) => write!(f, "Submit failed ({err})"),
+            Self::SetProfile(err) => write!(f, "Set profile failed ({err})"),
             Self::Timeout => write!(f, "Timeout"),
         }
     }
@@ -47,13 +108,25 @@ impl fmt::Display for Error {
 #[derive(Debug)]
 pub struct Mixpanel<'a> {
     token: &'a str,
+    region: Region,
     device_id: &'a str,
+    machine_name: Option<&'a str>,
 }
 
 impl<'a> Mixpanel<'a> {
-    /// Create new Mixpanel API client using the given project token
-    pub fn new(token: &'a str, device_id: &'a str) -> Self {
-        Self { token, device_id }
+    /// Create new Mixpanel API client using the given project token and region
+    pub fn new(token: &'a str, region: Region, device_id: &'a str) -> Self {
+        Self {
+            token,
+            region,
+            device_id,
+            machine_name: None,
+        }
+    }
+
+    /// Set name of this machine, which is sent as a property of every event
+    pub fn set_machine_name(&mut self, name: &'a str) {
+        self.machine_name = Some(name);
     }
 
     /// Connect to API server
@@ -71,9 +144,16 @@ pub struct Connection<'a> {
     http: http::Connection<'a>,
     token: &'a str,
     device_id: &'a str,
+    machine_name: Option<&'a str>,
 }
 
 impl Connection<'_> {
+    /// Returns true if further requests can be sent over this connection. Otherwise, the
+    /// connection was closed and the caller needs to connect again.
+    pub fn is_reusable(&self) -> bool {
+        self.http.is_reusable()
+    }
+
     /// Submit tracked events
     pub async fn submit(&mut self, events: &[(Instant, Event)]) -> Result<(), Error> {
         use proto_event::{TrackRequest, TrackResponse};
@@ -84,32 +164,66 @@ impl Connection<'_> {
         }
 
         debug!("Mixpanel: Submitting {} events...", events.len());
-        let response: TrackResponse = with_timeout(
-            TIMEOUT,
-            self.http.post(
-                "track?verbose=1",
-                &TrackRequest {
-                    token: self.token,
-                    device_id: self.device_id,
-                    events,
-                },
-            ),
-        )
-        .await?
+        let request_body = http::Connection::prepare_body(&TrackRequest {
+            token: self.token,
+            device_id: self.device_id,
+            machine_name: self.machine_name,
+            events,
+        })
+        .await
         .map_err(Error::Submit)?;
+        let response: TrackResponse =
+            with_timeout(TIMEOUT, self.post("track?verbose=1", &request_body))
+                .await?
+                .map_err(Error::Submit)?;
         debug!(
             "Mixpanel: Submit successul, status {} {}",
             response.status, response.error
         );
         Ok(())
     }
+
+    /// Set profile properties of given user
+    pub async fn set_profile(&mut self, user_id: UserId, name: &str) -> Result<(), Error> {
+        use proto_engage::{EngageRequest, EngageResponse};
+
+        debug!("Mixpanel: Setting profile of user {}...", user_id);
+        let request_body = http::Connection::prepare_body(&EngageRequest {
+            token: self.token,
+            user_id,
+            name,
+        })
+        .await
+        .map_err(Error::SetProfile)?;
+        let response: EngageResponse =
+            with_timeout(TIMEOUT, self.post("engage?verbose=1", &request_body))
+                .await?
+                .map_err(Error::SetProfile)?;
+        debug!(
+            "Mixpanel: Set profile successful, status {} {}",
+            response.status, response.error
+        );
+        Ok(())
+    }
+}
+
+impl Connection<'_> {
+    /// Send POST request with given JSON body, deserialize response (if any)
+    async fn post<T: FromJson + Default>(
+        &mut self,
+        path: &str,
+        body: &[u8],
+    ) -> Result<T, http::Error> {
+        let mut json = self.http.post_json(path, body).await?;
+        read_response(&mut json).await.map_err(http::Error::from)
+    }
 }
 
 impl<'a> Connection<'a> {
     /// Connect to API server
     async fn new(mp: &'a Mixpanel<'_>, http: &'a mut Http<'_>) -> Result<Self, Error> {
         // Connect to API server
-        let connection = with_timeout(TIMEOUT, http.connect(BASE_URL))
+        let connection = with_timeout(TIMEOUT, http.connect(mp.region.base_url()))
             .await?
             .map_err(Error::Connect)?;
 
@@ -117,6 +231,52 @@ impl<'a> Connection<'a> {
             http: connection,
             token: mp.token,
             device_id: mp.device_id,
+            machine_name: mp.machine_name,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use proto_event::TrackResponse;
+
+    #[async_std::test]
+    async fn read_empty_response() {
+        let mut json = json::Reader::new(&b""[..]);
+        let response: TrackResponse = read_response(&mut json).await.unwrap();
+        assert_eq!(response.status, 0);
+        assert_eq!(response.error, "");
+    }
+
+    #[async_std::test]
+    async fn read_verbose_response() {
+        let mut json = json::Reader::new(&br#"{"error": null, "status": 1}"#[..]);
+        let response: TrackResponse = read_response(&mut json).await.unwrap();
+        assert_eq!(response.status, 1);
+        assert_eq!(response.error, "");
+
+        let mut json = json::Reader::new(&br#"{"error": "invalid token", "status": 0}"#[..]);
+        let response: TrackResponse = read_response(&mut json).await.unwrap();
+        assert_eq!(response.status, 0);
+        assert_eq!(response.error, "invalid token");
+    }
+
+    #[test]
+    fn region_from_name() {
+        assert_eq!(Region::from_name("eu"), Some(Region::Eu));
+        assert_eq!(Region::from_name("us"), Some(Region::Us));
+        assert_eq!(Region::from_name("in"), Some(Region::In));
+        assert_eq!(Region::from_name("EU"), None);
+        assert_eq!(Region::from_name("mars"), None);
+    }
+
+    #[test]
+    fn region_base_url() {
+        assert_eq!(Region::default().base_url(), "https://api-eu.mixpanel.com");
+        assert_eq!(Region::Eu.base_url(), "https://api-eu.mixpanel.com");
+        assert_eq!(Region::Us.base_url(), "https://api.mixpanel.com");
+        assert_eq!(Region::In.base_url(), "https://api-in.mixpanel.com");
+    }
+}
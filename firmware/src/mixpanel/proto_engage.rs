@@ -0,0 +1,126 @@
+use crate::json::{self, FromJsonObject, ToJson};
+use crate::user::UserId;
+use alloc::string::String;
+use embedded_io_async::{BufRead, Write};
+
+/// `engage` request (profile set)
+#[derive(Debug)]
+pub struct EngageRequest<'a> {
+    pub token: &'a str,
+    pub user_id: UserId,
+    pub name: &'a str,
+}
+
+impl ToJson for EngageRequest<'_> {
+    async fn to_json<W: Write>(
+        &self,
+        json: &mut json::Writer<W>,
+    ) -> Result<(), json::Error<W::Error>> {
+        json.write_array([ProfileUpdate { request: self }]).await
+    }
+}
+
+/// `engage` response
+#[derive(Debug, Default)]
+pub struct EngageResponse {
+    pub error: String,
+    pub status: u32,
+}
+
+impl FromJsonObject for EngageResponse {
+    type Context<'ctx> = ();
+
+    async fn read_next<R: BufRead>(
+        &mut self,
+        key: String,
+        json: &mut json::Reader<R>,
+        _context: &Self::Context<'_>,
+    ) -> Result<(), json::Error<R::Error>> {
+        match &*key {
+            // Error is null on success
+            "error" => {
+                if let json::Value::String(error) = json.read_any().await? {
+                    self.error = error;
+                }
+            }
+            "status" => self.status = json.read().await?,
+            _ => json.skip_any().await?,
+        }
+        Ok(())
+    }
+}
+
+/// Profile update
+#[derive(Debug)]
+struct ProfileUpdate<'a> {
+    request: &'a EngageRequest<'a>,
+}
+
+impl ToJson for ProfileUpdate<'_> {
+    async fn to_json<W: Write>(
+        &self,
+        json: &mut json::Writer<W>,
+    ) -> Result<(), json::Error<W::Error>> {
+        // Reserved properties, see https://docs.mixpanel.com/docs/data-structure/user-profiles
+        json.write_object()
+            .await?
+            .field("$token", self.request.token)
+            .await?
+            .field("$distinct_id", self.request.user_id)
+            .await?
+            .field(
+                "$set",
+                ProfileProperties {
+                    request: self.request,
+                },
+            )
+            .await?
+            .finish()
+            .await
+    }
+}
+
+/// Profile properties
+#[derive(Debug)]
+struct ProfileProperties<'a> {
+    request: &'a EngageRequest<'a>,
+}
+
+impl ToJson for ProfileProperties<'_> {
+    async fn to_json<W: Write>(
+        &self,
+        json: &mut json::Writer<W>,
+    ) -> Result<(), json::Error<W::Error>> {
+        json.write_object()
+            .await?
+            .field("$name", self.request.name)
+            .await?
+            .finish()
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    async fn to_json<T: ToJson>(value: &T) -> String {
+        let mut json = json::Writer::new(Vec::new());
+        json.write(value).await.unwrap();
+        String::from_utf8(json.into_inner()).unwrap()
+    }
+
+    #[async_std::test]
+    async fn write_request() {
+        let request = EngageRequest {
+            token: "abcdef",
+            user_id: 12345,
+            name: "Fred Flintstone",
+        };
+        assert_eq!(
+            to_json(&request).await,
+            r#"[{"$token": "abcdef", "$distinct_id": 12345, "$set": {"$name": "Fred Flintstone"}}]"#
+        );
+    }
+}
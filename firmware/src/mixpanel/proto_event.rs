@@ -10,6 +10,7 @@ use embedded_io_async::{BufRead, Write};
 pub struct TrackRequest<'a> {
     pub token: &'a str,
     pub device_id: &'a str,
+    pub machine_name: Option<&'a str>,
     pub events: &'a [(Instant, telemetry::Event)],
 }
 
@@ -18,12 +19,22 @@ impl ToJson for TrackRequest<'_> {
         &self,
         json: &mut json::Writer<W>,
     ) -> Result<(), json::Error<W::Error>> {
-        json.write_array(self.events.iter().map(|(time, event)| Event {
-            token: self.token,
-            device_id: self.device_id,
-            time,
-            telemetry: event,
-        }))
+        // Stream events, so that memory usage stays flat even for many events
+        json.write_array_of_objects(self.events, async |object, (time, event)| {
+            let properties = EventProperties {
+                token: self.token,
+                device_id: self.device_id,
+                machine_name: self.machine_name,
+                time,
+                telemetry: event,
+            };
+            object
+                .field("event", event.event_name())
+                .await?
+                .field("properties", properties)
+                .await?;
+            Ok(())
+        })
         .await
     }
 }
@@ -40,44 +51,38 @@ impl FromJsonObject for TrackResponse {
 
     async fn read_next<R: BufRead>(
         &mut self,
-        _key: String,
+        key: String,
         json: &mut json::Reader<R>,
         _context: &Self::Context<'_>,
     ) -> Result<(), json::Error<R::Error>> {
-        // FIXME: Mixpanel returns an empty body on success, which is not a valid JSON object
-        json.skip_any().await
+        match &*key {
+            // Error is null on success
+            "error" => {
+                if let json::Value::String(error) = json.read_any().await? {
+                    self.error = error;
+                }
+            }
+            "status" => self.status = json.read().await?,
+            _ => json.skip_any().await?,
+        }
+        Ok(())
     }
 }
 
-/// Event
-#[derive(Debug)]
-struct Event<'a> {
-    token: &'a str,
-    device_id: &'a str,
-    time: &'a Instant,
-    telemetry: &'a telemetry::Event,
-}
-
-impl ToJson for Event<'_> {
-    async fn to_json<W: Write>(
-        &self,
-        json: &mut json::Writer<W>,
-    ) -> Result<(), json::Error<W::Error>> {
-        json.write_object()
-            .await?
-            .field("event", self.telemetry.event_name())
-            .await?
-            .field("properties", EventProperties { event: self })
-            .await?
-            .finish()
-            .await
-    }
+/// System uptime in seconds at the given time (which is relative to system start). Unlike the
+/// event time, this doesn't need the current time to be known.
+fn uptime_secs(time: &Instant) -> u64 {
+    time.as_secs()
 }
 
 /// Event properties
 #[derive(Debug)]
 struct EventProperties<'a> {
-    event: &'a Event<'a>,
+    token: &'a str,
+    device_id: &'a str,
+    machine_name: Option<&'a str>,
+    time: &'a Instant,
+    telemetry: &'a telemetry::Event,
 }
 
 impl ToJson for EventProperties<'_> {
@@ -86,24 +91,20 @@ impl ToJson for EventProperties<'_> {
         json: &mut json::Writer<W>,
     ) -> Result<(), json::Error<W::Error>> {
         // Convert relative `Instant` time to absolute `DateTime` (needs current time set)
-        let time = self
-            .event
-            .time
-            .to_datetime()
-            .ok_or(json::Error::InvalidType)?;
+        let time = self.time.to_datetime().ok_or(json::Error::InvalidType)?;
 
         let mut object = json.write_object().await?;
 
         // Reserved properties, see https://docs.mixpanel.com/docs/data-structure/property-reference/reserved-properties
         object
-            .field("token", self.event.token)
+            .field("token", self.token)
             .await?
             .field("time", time.timestamp_millis())
             .await?;
         // Use user id as distinct id if event is associated with a user, use device id otherwise
-        match self.event.telemetry.user_id() {
+        match self.telemetry.user_id() {
             Some(user_id) => object.field("distinct_id", user_id).await?,
-            None => object.field("distinct_id", self.event.device_id).await?,
+            None => object.field("distinct_id", self.device_id).await?,
         };
 
         // Global custom properties
@@ -112,14 +113,85 @@ impl ToJson for EventProperties<'_> {
             .await?
             .field("firmware_git_sha", crate::GIT_SHA_STR)
             .await?
-            .field("device_id", self.event.device_id)
+            .field("device_id", self.device_id)
+            .await?
+            .field_opt("machine_name", self.machine_name)
+            .await?
+            .field("uptime", uptime_secs(self.time))
             .await?;
         // Event-specific custom properties
-        self.event
-            .telemetry
-            .add_event_attributes(&mut object)
-            .await?;
+        self.telemetry.add_event_attributes(&mut object).await?;
 
         object.finish().await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use chrono::DateTime;
+
+    async fn to_json<T: ToJson>(value: &T) -> String {
+        let mut json = json::Writer::new(Vec::new());
+        json.write(value).await.unwrap();
+        String::from_utf8(json.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn uptime() {
+        assert_eq!(uptime_secs(&Instant::from_millis(0)), 0);
+        assert_eq!(uptime_secs(&Instant::from_millis(1999)), 1);
+        assert_eq!(uptime_secs(&Instant::from_secs(86_400)), 86_400);
+    }
+
+    #[async_std::test]
+    async fn write_event_uptime() {
+        crate::time::set(&DateTime::from_timestamp(1_737_000_000, 0).unwrap());
+        let request = TrackRequest {
+            token: "abcdef",
+            device_id: "0123456789ab",
+            machine_name: None,
+            events: &[(Instant::from_secs(3600), telemetry::Event::SystemStart)],
+        };
+        let json = to_json(&request).await;
+        assert!(json.starts_with(r#"[{"event": "system_start", "properties": {"token": "abcdef""#));
+        assert!(json.ends_with(r#""device_id": "0123456789ab", "uptime": 3600}}]"#));
+    }
+
+    #[async_std::test]
+    async fn write_event_machine_name() {
+        crate::time::set(&DateTime::from_timestamp(1_737_000_000, 0).unwrap());
+        let request = TrackRequest {
+            token: "abcdef",
+            device_id: "0123456789ab",
+            machine_name: Some("Clubheim"),
+            events: &[(Instant::from_secs(3600), telemetry::Event::SystemStart)],
+        };
+        let json = to_json(&request).await;
+        assert!(json.contains(
+            r#""device_id": "0123456789ab", "machine_name": "Clubheim", "uptime": 3600}"#
+        ));
+    }
+
+    #[async_std::test]
+    async fn write_multiple_events() {
+        crate::time::set(&DateTime::from_timestamp(1_737_000_000, 0).unwrap());
+        let request = TrackRequest {
+            token: "abcdef",
+            device_id: "0123456789ab",
+            machine_name: None,
+            events: &[
+                (Instant::from_secs(10), telemetry::Event::SystemStart),
+                (
+                    Instant::from_secs(20),
+                    telemetry::Event::DataRefreshed(3, 4, 5),
+                ),
+            ],
+        };
+        let json = to_json(&request).await;
+        assert!(json.starts_with(r#"[{"event": "system_start", "#));
+        assert!(json.contains(r#""uptime": 10}}, {"event": "data_refreshed", "#));
+        assert!(json.ends_with("}}]"));
+    }
+}
@@ -0,0 +1,286 @@
+use alloc::format;
+use alloc::string::String;
+use core::str;
+
+/// TLV block: NULL (NFC Forum Type 2 Tag §2.3)
+const TLV_NULL: u8 = 0x00;
+/// TLV block: NDEF message (NFC Forum Type 2 Tag §2.3)
+const TLV_NDEF_MESSAGE: u8 = 0x03;
+/// TLV block: terminator (NFC Forum Type 2 Tag §2.3)
+const TLV_TERMINATOR: u8 = 0xfe;
+
+/// NDEF record header flag: message end (NDEF §3.2.2)
+const FLAG_ME: u8 = 0x40;
+/// NDEF record header flag: chunked record (NDEF §3.2.3)
+const FLAG_CF: u8 = 0x20;
+/// NDEF record header flag: short record (NDEF §3.2.4)
+const FLAG_SR: u8 = 0x10;
+/// NDEF record header flag: id length present (NDEF §3.2.5)
+const FLAG_IL: u8 = 0x08;
+/// NDEF record header: type name format mask (NDEF §3.2.6)
+const TNF_MASK: u8 = 0x07;
+/// NDEF type name format: NFC Forum well-known type (NDEF §3.2.6)
+const TNF_WELL_KNOWN: u8 = 0x01;
+
+/// URI identifier codes (NFC Forum URI RTD §3.2.2)
+static URI_PREFIXES: [&str; 36] = [
+    "",
+    "http://www.",
+    "https://www.",
+    "http://",
+    "https://",
+    "tel:",
+    "mailto:",
+    "ftp://anonymous:anonymous@",
+    "ftp://ftp.",
+    "ftps://",
+    "sftp://",
+    "smb://",
+    "nfs://",
+    "ftp://",
+    "dav://",
+    "news:",
+    "telnet://",
+    "imap:",
+    "rtsp://",
+    "urn:",
+    "pop:",
+    "sip:",
+    "sips:",
+    "tftp:",
+    "btspp://",
+    "btl2cap://",
+    "btgoep://",
+    "tcpobex://",
+    "irdaobex://",
+    "file://",
+    "urn:epc:id:",
+    "urn:epc:tag:",
+    "urn:epc:pat:",
+    "urn:epc:raw:",
+    "urn:epc:",
+    "urn:nfc:",
+];
+
+/// Result of looking up the NDEF message in TLV blocks
+#[derive(Debug, PartialEq, Eq)]
+pub enum Tlv<'a> {
+    /// NDEF message found
+    Message(&'a [u8]),
+    /// No NDEF message present
+    NoMessage,
+    /// More data needed to find the NDEF message
+    Incomplete,
+}
+
+/// Look up NDEF message in TLV blocks read from a Type 2 tag's data area
+pub fn find_message(mut data: &[u8]) -> Tlv<'_> {
+    loop {
+        let Some((&tag, rest)) = data.split_first() else {
+            return Tlv::Incomplete;
+        };
+        match tag {
+            TLV_NULL => {
+                data = rest;
+                continue;
+            }
+            TLV_TERMINATOR => return Tlv::NoMessage,
+            _ => (),
+        }
+
+        // Length is 1 byte, or 3 bytes if first byte is 0xff
+        let (len, rest) = match rest {
+            [0xff, hi, lo, rest @ ..] => (usize::from(u16::from_be_bytes([*hi, *lo])), rest),
+            [0xff, ..] | [] => return Tlv::Incomplete,
+            [len, rest @ ..] => (usize::from(*len), rest),
+        };
+        if rest.len() < len {
+            return Tlv::Incomplete;
+        }
+        if tag == TLV_NDEF_MESSAGE {
+            return Tlv::Message(&rest[..len]);
+        }
+        data = &rest[len..];
+    }
+}
+
+/// NDEF record
+#[derive(Debug, PartialEq, Eq)]
+struct Record<'a> {
+    /// Header flags and type name format
+    header: u8,
+    /// Record type
+    type_: &'a [u8],
+    /// Record payload
+    payload: &'a [u8],
+}
+
+impl<'a> Record<'a> {
+    /// Parse record at start of given data. Returns record and remaining data.
+    fn parse(data: &'a [u8]) -> Option<(Self, &'a [u8])> {
+        let (&header, data) = data.split_first()?;
+        let (&type_len, data) = data.split_first()?;
+        let (payload_len, data) = if header & FLAG_SR != 0 {
+            let (&len, data) = data.split_first()?;
+            (usize::from(len), data)
+        } else {
+            let (len, data) = data.split_at_checked(4)?;
+            (
+                usize::try_from(u32::from_be_bytes(len.try_into().ok()?)).ok()?,
+                data,
+            )
+        };
+        let (id_len, data) = if header & FLAG_IL != 0 {
+            let (&len, data) = data.split_first()?;
+            (usize::from(len), data)
+        } else {
+            (0, data)
+        };
+        let (type_, data) = data.split_at_checked(usize::from(type_len))?;
+        let (_id, data) = data.split_at_checked(id_len)?;
+        let (payload, data) = data.split_at_checked(payload_len)?;
+        Some((
+            Self {
+                header,
+                type_,
+                payload,
+            },
+            data,
+        ))
+    }
+
+    /// Returns true if record is the last record of the message
+    fn is_last(&self) -> bool {
+        self.header & FLAG_ME != 0
+    }
+
+    /// Returns true if record is of the given NFC Forum well-known type
+    fn is_well_known(&self, type_: &[u8]) -> bool {
+        self.header & TNF_MASK == TNF_WELL_KNOWN && self.type_ == type_
+    }
+
+    /// Decode text record payload (NFC Forum Text RTD §3.2.1)
+    fn text(&self) -> Option<String> {
+        let (&status, rest) = self.payload.split_first()?;
+        // UTF-16 encoded text isn't supported
+        if status & 0x80 != 0 {
+            return None;
+        }
+        let lang_len = usize::from(status & 0x3f);
+        let text = rest.get(lang_len..)?;
+        str::from_utf8(text).ok().map(String::from)
+    }
+
+    /// Decode URI record payload (NFC Forum URI RTD §3.2)
+    fn uri(&self) -> Option<String> {
+        let (&code, rest) = self.payload.split_first()?;
+        let prefix = URI_PREFIXES.get(usize::from(code)).copied().unwrap_or("");
+        let uri = str::from_utf8(rest).ok()?;
+        Some(format!("{prefix}{uri}"))
+    }
+}
+
+/// Parse NDEF message and return decoded payload of first text or URI record
+pub fn parse_message(mut data: &[u8]) -> Option<String> {
+    while let Some((record, rest)) = Record::parse(data) {
+        // Chunked records aren't supported
+        if record.header & FLAG_CF == 0 {
+            if record.is_well_known(b"T") {
+                return record.text();
+            }
+            if record.is_well_known(b"U") {
+                return record.uri();
+            }
+        }
+        if record.is_last() {
+            break;
+        }
+        data = rest;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    // NDEF message with single text record "Hello" (language "en")
+    const TEXT_MESSAGE: [u8; 12] = [
+        0xd1, 0x01, 0x08, b'T', 0x02, b'e', b'n', b'H', b'e', b'l', b'l', b'o',
+    ];
+
+    // NDEF message with single URI record "https://example.com"
+    const URI_MESSAGE: [u8; 16] = [
+        0xd1, 0x01, 0x0c, b'U', 0x04, b'e', b'x', b'a', b'm', b'p', b'l', b'e', b'.', b'c', b'o',
+        b'm',
+    ];
+
+    #[test]
+    fn find_message_in_tlv() {
+        let mut data = vec![0x00, 0x01, 0x03, 0xa0, 0x0c, 0x34, 0x03, 12];
+        data.extend_from_slice(&TEXT_MESSAGE);
+        data.extend_from_slice(&[0xfe, 0x00, 0x00]);
+        assert_eq!(find_message(&data), Tlv::Message(&TEXT_MESSAGE));
+    }
+
+    #[test]
+    fn find_message_long_length() {
+        let mut data = vec![0x03, 0xff, 0x00, 12];
+        data.extend_from_slice(&TEXT_MESSAGE);
+        assert_eq!(find_message(&data), Tlv::Message(&TEXT_MESSAGE));
+    }
+
+    #[test]
+    fn find_message_missing() {
+        assert_eq!(find_message(&[0x00, 0x00, 0xfe, 0x03]), Tlv::NoMessage);
+    }
+
+    #[test]
+    fn find_message_incomplete() {
+        assert_eq!(find_message(&[]), Tlv::Incomplete);
+        assert_eq!(find_message(&[0x00, 0x00]), Tlv::Incomplete);
+        assert_eq!(find_message(&[0x03, 0x0c, 0xd1, 0x01]), Tlv::Incomplete);
+        assert_eq!(find_message(&[0x03, 0xff, 0x00]), Tlv::Incomplete);
+    }
+
+    #[test]
+    fn parse_text_record() {
+        assert_eq!(parse_message(&TEXT_MESSAGE), Some(String::from("Hello")));
+    }
+
+    #[test]
+    fn parse_uri_record() {
+        assert_eq!(
+            parse_message(&URI_MESSAGE),
+            Some(String::from("https://example.com"))
+        );
+    }
+
+    #[test]
+    fn parse_skips_other_records() {
+        // Message with a MIME record (TNF 2, type "a/b") followed by a text record
+        let mut data = vec![0x92, 0x03, 0x02, b'a', b'/', b'b', 0x12, 0x34];
+        let mut text = TEXT_MESSAGE;
+        text[0] = 0x51; // ME, SR, TNF 1
+        data.extend_from_slice(&text);
+        assert_eq!(parse_message(&data), Some(String::from("Hello")));
+    }
+
+    #[test]
+    fn parse_long_record() {
+        let data = [
+            0xc1, 0x01, 0x00, 0x00, 0x00, 0x08, b'T', 0x02, b'e', b'n', b'H', b'e', b'l', b'l',
+            b'o',
+        ];
+        assert_eq!(parse_message(&data), Some(String::from("Hello")));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert_eq!(parse_message(&[]), None);
+        assert_eq!(parse_message(&[0xd1, 0x01, 0x08, b'T', 0x02]), None);
+        // Empty record (TNF 0)
+        assert_eq!(parse_message(&[0xd0, 0x00, 0x00]), None);
+    }
+}
@@ -1,58 +1,233 @@
 // Use custom pn532 driver instead of pn532 crate
 use crate::pn532;
 
-use crate::json::{self, ToJson};
-use alloc::string::ToString;
+use crate::json::{self, FromJson, ToJson};
+use crate::ndef;
+use crate::sleep::WakeupPin;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 use const_hex::FromHex;
 use core::convert::Infallible;
 use core::fmt::{self, Debug};
 use core::str::FromStr;
-use embassy_time::{Duration, Timer};
+use embassy_time::{Duration, Instant, Timer};
 use embedded_hal_async::digital::Wait;
 use embedded_hal_async::i2c::I2c;
-use embedded_io_async::Write;
+use embedded_io_async::{BufRead, Write};
 use log::{debug, info, warn};
-use pn532::{Error as Pn532Error, I2CInterfaceWithIrq, Pn532, Request, SAMMode};
+use pn532::{Command, Error as Pn532Error, I2CInterfaceWithIrq, Pn532, Request, SAMMode, Timeouts};
 
 /// NFC reader read loop timeout
 const READ_TIMEOUT: Duration = Duration::from_millis(100);
 
+/// Mifare authentication timeouts. Authentication involves a crypto handshake with the card,
+/// which takes longer than other commands.
+const AUTH_TIMEOUTS: Timeouts = Timeouts {
+    ack: Duration::from_millis(100),
+    response: Duration::from_millis(200),
+};
+
+/// Max number of attempts to detect a target if the response is corrupted (e.g. by RF noise)
+const READ_ATTEMPTS: usize = 3;
+
 /// NFC reader read loop sleep
 const READ_SLEEP: Duration = Duration::from_millis(400);
 
+/// NFC reader read loop sleep while polling at low power. The PN532 is powered down in between.
+const LOW_POWER_READ_SLEEP: Duration = Duration::from_millis(1500);
+
+/// Time for PN532 to wake up from power down (PN532 §7.2.11: ~1 ms, with some margin)
+const WAKEUP_TIME: Duration = Duration::from_millis(5);
+
+/// Wake up source for `PowerDown`: I2C (PN532 §7.2.11)
+const WAKEUP_I2C: u8 = 0x80;
+
+/// Wake up source for `PowerDown`: RF level detector (PN532 §7.2.11)
+const WAKEUP_RF: u8 = 0x08;
+
+/// `RFConfiguration` item: RF field (PN532 §7.3.1)
+const RF_CFG_ITEM_FIELD: u8 = 0x01;
+
+/// Time for cards to power up after the RF field was switched on
+const RF_SETTLE_TIME: Duration = Duration::from_millis(5);
+
+/// First page of NTAG data area (NTAG213 §8.5)
+const NTAG_DATA_START_PAGE: u8 = 4;
+
+/// Page after the largest supported NTAG data area (NTAG216 §8.5)
+const NTAG_DATA_END_PAGE: u8 = 226;
+
+/// How long a card needs to be absent before it is returned again. Must be larger than a read
+/// loop cycle.
+const SAME_CARD_WINDOW: Duration = Duration::from_secs(2);
+
+/// Mifare Classic authentication with key A (MF1S50 §10.1)
+const MIFARE_CMD_AUTH_A: u8 = 0x60;
+
+/// Mifare Classic authentication with key B (MF1S50 §10.1)
+const MIFARE_CMD_AUTH_B: u8 = 0x61;
+
+/// Mifare Classic block read (MF1S50 §10.2)
+const MIFARE_CMD_READ: u8 = 0x30;
+
+/// `InDataExchange` status: Mifare authentication error (PN532 §7.1)
+const STATUS_MIFARE_AUTH_ERROR: u8 = 0x14;
+
+/// `InDataExchange` status: Target timeout (PN532 §7.1)
+const STATUS_TIMEOUT: u8 = 0x01;
+
 /// NFC reader error
-// Basically a PN532 error with static interface error type to avoid generics in this type
 #[derive(Debug)]
-pub struct Error(Pn532Error<embedded_hal_async::i2c::ErrorKind>);
+pub enum Error {
+    /// PN532 error (with static interface error type to avoid generics in this type)
+    Pn532(Pn532Error<embedded_hal_async::i2c::ErrorKind>),
+    /// No target detected or target didn't respond in time
+    Timeout,
+    /// Mifare authentication failed (wrong key)
+    AuthFailed,
+    /// Target communication failed with given PN532 status code
+    Status(u8),
+}
 
 impl<E: embedded_hal_async::i2c::Error> From<Pn532Error<E>> for Error {
     fn from(err: Pn532Error<E>) -> Self {
         // Convert generic Pn532Error::InterfaceError(E: embedded_hal::i2c::Error) to non-generic
         // Pn532Error::InterfaceError(embedded_hal::i2c::ErrorKind) to avoid generics in this type
         match err {
-            Pn532Error::BadAck => Self(Pn532Error::BadAck),
-            Pn532Error::BadResponseFrame => Self(Pn532Error::BadResponseFrame),
-            Pn532Error::Syntax => Self(Pn532Error::Syntax),
-            Pn532Error::CrcError => Self(Pn532Error::CrcError),
-            Pn532Error::BufTooSmall => Self(Pn532Error::BufTooSmall),
-            Pn532Error::TimeoutAck => Self(Pn532Error::TimeoutAck),
-            Pn532Error::TimeoutResponse => Self(Pn532Error::TimeoutResponse),
-            Pn532Error::InterfaceError(e) => Self(Pn532Error::InterfaceError(e.kind())),
+            Pn532Error::BadAck => Self::Pn532(Pn532Error::BadAck),
+            Pn532Error::BadResponseFrame => Self::Pn532(Pn532Error::BadResponseFrame),
+            Pn532Error::Syntax => Self::Pn532(Pn532Error::Syntax),
+            Pn532Error::CrcError => Self::Pn532(Pn532Error::CrcError),
+            Pn532Error::BufTooSmall => Self::Pn532(Pn532Error::BufTooSmall),
+            Pn532Error::TimeoutAck => Self::Pn532(Pn532Error::TimeoutAck),
+            Pn532Error::TimeoutResponse => Self::Pn532(Pn532Error::TimeoutResponse),
+            Pn532Error::InterfaceError(e) => Self::Pn532(Pn532Error::InterfaceError(e.kind())),
         }
     }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self.0 {
-            Pn532Error::BadAck => write!(f, "Bad ACK"),
-            Pn532Error::BadResponseFrame => write!(f, "Bad response frame"),
-            Pn532Error::Syntax => write!(f, "Syntax error"),
-            Pn532Error::CrcError => write!(f, "CRC error"),
-            Pn532Error::BufTooSmall => write!(f, "Buffer too small"),
-            Pn532Error::TimeoutAck => write!(f, "ACK timeout"),
-            Pn532Error::TimeoutResponse => write!(f, "Response timeout"),
-            Pn532Error::InterfaceError(_err) => write!(f, "Bus error"),
+        match self {
+            Self::Pn532(Pn532Error::BadAck) => write!(f, "Bad ACK"),
+            Self::Pn532(Pn532Error::BadResponseFrame) => write!(f, "Bad response frame"),
+            Self::Pn532(Pn532Error::Syntax) => write!(f, "Syntax error"),
+            Self::Pn532(Pn532Error::CrcError) => write!(f, "CRC error"),
+            Self::Pn532(Pn532Error::BufTooSmall) => write!(f, "Buffer too small"),
+            Self::Pn532(Pn532Error::TimeoutAck) => write!(f, "ACK timeout"),
+            Self::Pn532(Pn532Error::TimeoutResponse) => write!(f, "Response timeout"),
+            Self::Pn532(Pn532Error::InterfaceError(_err)) => write!(f, "Bus error"),
+            Self::Timeout => write!(f, "Card timeout"),
+            Self::AuthFailed => write!(f, "Card authentication failed"),
+            Self::Status(status) => write!(f, "Card error 0x{status:02x}"),
+        }
+    }
+}
+
+/// Mifare Classic sector key
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MifareKey {
+    /// Key A
+    A([u8; 6]),
+    /// Key B
+    B([u8; 6]),
+}
+
+/// Build `SAMConfiguration` request to configure PN532 as initiator in normal mode
+/// (PN532 §7.2.10)
+fn sam_configuration_request() -> Request<3> {
+    Request::sam_configuration(SAMMode::Normal, true)
+}
+
+/// Build `PowerDown` request with wake up by I2C and optionally by RF field (PN532 §7.2.11)
+fn power_down_request(wakeup_rf: bool) -> Request<2> {
+    if wakeup_rf {
+        // Also wake up on external RF field and signal it on the IRQ line
+        Request::new(Command::PowerDown, [WAKEUP_I2C | WAKEUP_RF, 0x01])
+    } else {
+        Request::new(Command::PowerDown, [WAKEUP_I2C, 0x00])
+    }
+}
+
+/// Build `RFConfiguration` request to switch the RF field on or off (PN532 §7.3.1). Automatic RF
+/// collision avoidance is kept disabled.
+fn rf_field_request(on: bool) -> Request<2> {
+    Request::new(Command::RFConfiguration, [RF_CFG_ITEM_FIELD, u8::from(on)])
+}
+
+/// UID bytes used for Mifare Classic authentication. Single size UIDs are used as is, for longer
+/// UIDs the last 4 bytes (of the last cascade level) are used, like libnfc does.
+fn mifare_auth_uid(uid: &Uid) -> [u8; 4] {
+    let uid = uid.as_ref();
+    let mut uid4 = [0; 4];
+    uid4.copy_from_slice(&uid[uid.len() - 4..]);
+    uid4
+}
+
+/// Build `InDataExchange` request to authenticate the sector of the given block (PN532 §7.3.8,
+/// MF1S50 §10.1)
+fn mifare_auth_request(block: u8, key: &MifareKey, uid: [u8; 4]) -> Request<13> {
+    let (cmd, key) = match key {
+        MifareKey::A(key) => (MIFARE_CMD_AUTH_A, key),
+        MifareKey::B(key) => (MIFARE_CMD_AUTH_B, key),
+    };
+    Request::new(
+        Command::InDataExchange,
+        [
+            0x01, cmd, block, key[0], key[1], key[2], key[3], key[4], key[5], uid[0], uid[1],
+            uid[2], uid[3],
+        ],
+    )
+}
+
+/// Build `InDataExchange` request to read the given block (PN532 §7.3.8, MF1S50 §10.2)
+fn mifare_read_request(block: u8) -> Request<3> {
+    Request::new(Command::InDataExchange, [0x01, MIFARE_CMD_READ, block])
+}
+
+/// Check status byte of `InDataExchange` response (PN532 §7.3.8)
+fn check_status(response: &[u8]) -> Result<&[u8], Error> {
+    match response.split_first() {
+        Some((0x00, data)) => Ok(data),
+        Some((&status, _)) if status & 0x3f == STATUS_MIFARE_AUTH_ERROR => Err(Error::AuthFailed),
+        Some((&status, _)) if status & 0x3f == STATUS_TIMEOUT => Err(Error::Timeout),
+        Some((&status, _)) => Err(Error::Status(status)),
+        None => Err(Error::Pn532(Pn532Error::BadResponseFrame)),
+    }
+}
+
+/// Same-card suppression. Remembers the last returned card and when it was last seen, so that a
+/// card held on the reader isn't returned repeatedly.
+#[derive(Debug)]
+struct SameCardFilter {
+    window: Duration,
+    last: Option<(Uid, Instant)>,
+}
+
+impl SameCardFilter {
+    /// Create same-card suppression with given debounce window
+    fn new(window: Duration) -> Self {
+        Self { window, last: None }
+    }
+
+    /// Card with given UID was detected at given time. Returns true if it should be returned,
+    /// false if it's the last returned card and it wasn't absent for the debounce window.
+    fn accept(&mut self, uid: &Uid, now: Instant) -> bool {
+        if let Some((last_uid, last_seen)) = &mut self.last {
+            if last_uid == uid && now.saturating_duration_since(*last_seen) < self.window {
+                *last_seen = now;
+                return false;
+            }
+        }
+        self.last = Some((uid.clone(), now));
+        true
+    }
+
+    /// Consider the last returned card as seen at the given time
+    fn reset(&mut self, now: Instant) {
+        if let Some((_uid, last_seen)) = &mut self.last {
+            *last_seen = now;
         }
     }
 }
@@ -61,6 +236,9 @@ impl fmt::Display for Error {
 #[derive(Debug)]
 pub struct Nfc<I2C, IRQ> {
     driver: Pn532<I2CInterfaceWithIrq<I2C, IRQ>>,
+    firmware_version: Option<FirmwareVersion>,
+    same_card: Option<SameCardFilter>,
+    low_power: bool,
 }
 
 impl<I2C: I2c, IRQ: Wait<Error = Infallible>> Nfc<I2C, IRQ> {
@@ -75,11 +253,7 @@ impl<I2C: I2c, IRQ: Wait<Error = Infallible>> Nfc<I2C, IRQ> {
 
         // Configure PN532 as initiator (normal mode)
         driver
-            .process_async(
-                // SAMConfiguration request (PN532 §7.2.10)
-                &Request::sam_configuration(SAMMode::Normal, true),
-                0,
-            )
+            .process_async(&sam_configuration_request(), 0)
             .await?;
 
         // Query PN532 version and capabilities
@@ -90,40 +264,156 @@ impl<I2C: I2c, IRQ: Wait<Error = Infallible>> Nfc<I2C, IRQ> {
                 4,
             )
             .await?;
-        // GetFirmwareVersion response (PN532 §7.2.2)
-        // - 1 byte: IC version (0x32 for PN532)
-        // - 1 byte: Version of firmware
-        // - 1 byte: Revision of firmware
-        // - 1 byte: Supported functionality bitmask
-        //           - Bit 0: ISO/IEC 14443 Type A
-        //           - Bit 1: ISO/IEC 14443 Type B
-        //           - Bit 2: ISO 18092
-        debug!(
-            "NFC: PN532 IC 0x{:02x}, Firmware {}.{}, Support 0x{:02x}",
-            version_response[0], version_response[1], version_response[2], version_response[3]
-        );
+        let firmware_version = FirmwareVersion::from_response(version_response);
+        if let Some(version) = firmware_version {
+            debug!(
+                "NFC: PN532 IC 0x{:02x}, Firmware {}, Support 0x{:02x}",
+                version.ic, version, version.support
+            );
+        } else {
+            warn!("NFC: Invalid firmware version response");
+        }
 
         info!("NFC: PN532 initialized");
-        Ok(Self { driver })
+        Ok(Self {
+            driver,
+            firmware_version,
+            same_card: Some(SameCardFilter::new(SAME_CARD_WINDOW)),
+            low_power: false,
+        })
+    }
+
+    /// PN532 firmware version (if it could be determined during initialization)
+    pub fn firmware_version(&self) -> Option<FirmwareVersion> {
+        self.firmware_version
+    }
+
+    /// Put PN532 into power down mode to save energy while idle. Current consumption drops from
+    /// about 30-100 mA (idle/polling) to about 10 µA (PN532 datasheet §8.3). No cards can be
+    /// detected while powered down, `power_up` needs to be called before reading again.
+    #[allow(dead_code)]
+    pub async fn power_down(&mut self) -> Result<(), Error> {
+        debug!("NFC: Power down");
+        self.power_down_with(false).await
+    }
+
+    /// Like `power_down`, but the PN532 also wakes up if it detects an external RF field (e.g. a
+    /// phone) and signals it on the IRQ line, so it can wake up a sleeping host. Passive cards
+    /// can't be detected while powered down. `power_up` needs to be called after waking up.
+    pub async fn power_down_with_wakeup(&mut self) -> Result<(), Error> {
+        debug!("NFC: Power down (RF wakeup)");
+        self.power_down_with(true).await
+    }
+
+    async fn power_down_with(&mut self, wakeup_rf: bool) -> Result<(), Error> {
+        // Abort any currently running command, ignore any error
+        let _ = self.driver.abort().await;
+
+        // PowerDown response (PN532 §7.2.11)
+        // - 1 byte: status
+        let response = self
+            .driver
+            .process_async(&power_down_request(wakeup_rf), 1)
+            .await?;
+        if response.first().is_some_and(|status| *status != 0) {
+            warn!("NFC: Power down failed with status 0x{:02x}", response[0]);
+        }
+        Ok(())
+    }
+
+    /// Wake up PN532 from power down mode. Since the PN532 loses its configuration on power down,
+    /// it is configured again.
+    pub async fn power_up(&mut self) -> Result<(), Error> {
+        debug!("NFC: Power up");
+        self.wake_up().await
+    }
+
+    async fn wake_up(&mut self) -> Result<(), Error> {
+        // Any I2C communication wakes up the PN532. Send an ACK frame (abort) and give it time to
+        // wake up, ignore any error
+        let _ = self.driver.abort().await;
+        Timer::after(WAKEUP_TIME).await;
+
+        // Configure PN532 as initiator (normal mode) again
+        self.driver
+            .process_async(&sam_configuration_request(), 0)
+            .await?;
+        Ok(())
+    }
+
+    /// Switch off RF field. Reduces interference with nearby electronics and saves power while
+    /// not detecting cards. Any error is ignored, since the field is switched on again before
+    /// the next detection anyway.
+    async fn rf_field_off(&mut self) {
+        if let Err(err) = self.driver.process_async(&rf_field_request(false), 0).await {
+            warn!("NFC: Failed to switch off RF field: {:?}", err);
+        }
+    }
+
+    /// Switch on RF field and give cards in range time to power up
+    async fn rf_field_on(&mut self) -> Result<(), Error> {
+        self.driver
+            .process_timeout_retry_async(&rf_field_request(true), 0, READ_TIMEOUT, READ_ATTEMPTS)
+            .await?;
+        Timer::after(RF_SETTLE_TIME).await;
+        Ok(())
+    }
+
+    /// Enable or disable polling at low power. While enabled, reading polls for cards less often
+    /// and powers down the PN532 between detections, so cards can still be detected while saving
+    /// power. `power_up` needs to be called after disabling it.
+    pub fn set_low_power(&mut self, low_power: bool) {
+        debug!("NFC: Low power polling: {}", low_power);
+        self.low_power = low_power;
+    }
+
+    /// Set debounce window of same-card suppression (`None` disables suppression)
+    #[allow(dead_code)]
+    pub fn set_same_card_window(&mut self, window: Option<Duration>) {
+        self.same_card = window.map(SameCardFilter::new);
+    }
+
+    /// Restart the debounce window of the last returned card (e.g. after finishing a
+    /// transaction), so it isn't returned again unless it was removed in between
+    pub fn reset_same_card(&mut self) {
+        if let Some(ref mut same_card) = self.same_card {
+            same_card.reset(Instant::now());
+        }
     }
 
     /// Wait for NFC target and read identification
     pub async fn read(&mut self) -> Result<Uid, Error> {
+        let (uid, _info) = self.read_card().await?;
+        Ok(uid)
+    }
+
+    /// Wait for NFC target and read identification and card information
+    pub async fn read_card(&mut self) -> Result<(Uid, CardInfo), Error> {
         loop {
             // Abort any currently running command, ignore any error
             let _ = self.driver.abort().await;
 
-            // Sleep for some time before starting next detection
-            Timer::after(READ_SLEEP).await;
+            // Sleep for some time before starting next detection. The RF field is only switched
+            // on while detecting. At low power, the PN532 is powered down while sleeping.
+            if self.low_power {
+                self.power_down_with(false).await?;
+                Timer::after(LOW_POWER_READ_SLEEP).await;
+                self.wake_up().await?;
+            } else {
+                self.rf_field_off().await;
+                Timer::after(READ_SLEEP).await;
+            }
+            self.rf_field_on().await?;
 
-            // Detect any ISO/IEC14443 Type A target in passive mode
+            // Detect any ISO/IEC14443 Type A target in passive mode, retry on corrupted responses
             let list_response = match self
                 .driver
-                .process_timeout_async(
+                .process_timeout_retry_async(
                     // InListPassiveTarget request (PN532 §7.3.5)
                     &Request::INLIST_ONE_ISO_A_TARGET,
                     pn532::BUFFER_SIZE - 9, // max response length
                     READ_TIMEOUT,
+                    READ_ATTEMPTS,
                 )
                 .await
             {
@@ -157,12 +447,20 @@ impl<I2C: I2c, IRQ: Wait<Error = Infallible>> Nfc<I2C, IRQ> {
             }
             debug_assert_eq!(list_response[1], 1, "NFC: First target number must be 1");
 
+            // Extract card information (SENS_RES/ATQA and SEL_RES/SAK)
+            let info = CardInfo::from_target_list(list_response);
+
             // Extract and parse UID, truncate tail on short response
             let nfcid = &list_response[6..];
             let nfcid_len = (list_response[5] as usize).min(nfcid.len());
             let nfcid = &nfcid[..nfcid_len];
             let maybe_uid = match Uid::try_from(nfcid) {
-                Ok(uid) => Some(uid),
+                Ok(uid) => {
+                    if !matches!(nfcid.len(), 4 | 7 | 10) {
+                        info!("NFC: Normalized NFCID {:02x?} to {}", nfcid, uid);
+                    }
+                    Some(uid)
+                }
                 Err(_err) => {
                     warn!("NFC: Target has invalid NFCID: {:02x?}", nfcid);
                     None
@@ -184,19 +482,253 @@ impl<I2C: I2c, IRQ: Wait<Error = Infallible>> Nfc<I2C, IRQ> {
                 warn!("NFC: Failed to release target: {:?}", err);
             }
 
-            // Return UID if retrieved, continue looping otherwise
-            if let Some(uid) = maybe_uid {
-                debug!("NFC: Detected NFC card: {}", uid);
-                return Ok(uid);
+            // Return UID and card information if retrieved, continue looping otherwise
+            if let (Some(uid), Some(info)) = (maybe_uid, info) {
+                // Skip card if it's still held on the reader since it was last returned
+                if let Some(ref mut same_card) = self.same_card {
+                    if !same_card.accept(&uid, Instant::now()) {
+                        continue;
+                    }
+                }
+                debug!("NFC: Detected NFC card: {} ({})", uid, info);
+                return Ok((uid, info));
+            }
+        }
+    }
+
+    /// Read a 16-byte data block from a Mifare Classic card with the given UID (as previously
+    /// returned by `read`). The card needs to be still present. The sector containing the block
+    /// is authenticated with the given key first.
+    #[allow(dead_code)]
+    pub async fn read_mifare_block(
+        &mut self,
+        uid: &Uid,
+        block: u8,
+        key: &MifareKey,
+    ) -> Result<[u8; 16], Error> {
+        self.select_target().await?;
+        let res = self.exchange_mifare_block(uid, block, key).await;
+        self.release_target().await;
+        res
+    }
+
+    /// Read NDEF message from an NTAG card with the given UID (as previously returned by `read`)
+    /// and return the decoded payload of the first text or URI record. The card needs to be
+    /// still present. Returns `None` if the card isn't an NTAG or has no NDEF data.
+    #[allow(dead_code)]
+    pub async fn read_ndef(&mut self, uid: &Uid) -> Result<Option<String>, Error> {
+        // NTAG cards have double size UIDs
+        if !matches!(uid, Uid::Double(_)) {
+            return Ok(None);
+        }
+
+        self.select_target().await?;
+        let res = self.exchange_ndef().await;
+        self.release_target().await;
+        res
+    }
+}
+
+impl<I2C: I2c, IRQ: Wait<Error = Infallible> + WakeupPin> Nfc<I2C, IRQ> {
+    /// Enable or disable waking up the host from sleep by the PN532 IRQ line (see
+    /// `power_down_with_wakeup`)
+    pub fn set_wakeup(&mut self, enable: bool) {
+        self.driver.interface_mut().irq.set_wakeup(enable);
+    }
+}
+
+impl<I2C: I2c, IRQ: Wait<Error = Infallible>> Nfc<I2C, IRQ> {
+    /// Select previously detected target again, fail if it's not present anymore
+    async fn select_target(&mut self) -> Result<(), Error> {
+        // Abort any currently running command, ignore any error
+        let _ = self.driver.abort().await;
+
+        let list_response = match self
+            .driver
+            .process_timeout_async(
+                // InListPassiveTarget request (PN532 §7.3.5)
+                &Request::INLIST_ONE_ISO_A_TARGET,
+                pn532::BUFFER_SIZE - 9, // max response length
+                READ_TIMEOUT,
+            )
+            .await
+        {
+            Ok(bytes) => bytes,
+            Err(Pn532Error::TimeoutResponse) => return Err(Error::Timeout),
+            Err(err) => return Err(err.into()),
+        };
+        if list_response.first().copied().unwrap_or(0) < 1 {
+            return Err(Error::Timeout);
+        }
+        Ok(())
+    }
+
+    /// Release selected target, ignore any error
+    async fn release_target(&mut self) {
+        if let Err(err) = self
+            .driver
+            .process_async(
+                // InRelease request (PN532 §7.3.11)
+                &Request::RELEASE_TAG_1,
+                1,
+            )
+            .await
+        {
+            warn!("NFC: Failed to release target: {:?}", err);
+        }
+    }
+
+    /// Read NDEF message of currently selected NTAG target
+    async fn exchange_ndef(&mut self) -> Result<Option<String>, Error> {
+        let mut data = Vec::new();
+        // NDEF data area starts at page 4, a read returns 4 pages (16 bytes) (NTAG213 §8.5)
+        for page in (NTAG_DATA_START_PAGE..NTAG_DATA_END_PAGE).step_by(4) {
+            let read_response = self
+                .driver
+                .process_timeout_async(&Request::ntag_read(page), 17, READ_TIMEOUT)
+                .await?;
+            match check_status(read_response) {
+                Ok(bytes) => data.extend_from_slice(bytes),
+                // Reading beyond end of memory fails, use what has been read
+                Err(Error::Status(_)) => break,
+                Err(err) => return Err(err),
             }
+            match ndef::find_message(&data) {
+                ndef::Tlv::Message(message) => {
+                    let payload = ndef::parse_message(message);
+                    debug!("NFC: Read NDEF payload: {:?}", payload);
+                    return Ok(payload);
+                }
+                ndef::Tlv::NoMessage => return Ok(None),
+                ndef::Tlv::Incomplete => (),
+            }
+        }
+        debug!("NFC: No NDEF message found");
+        Ok(None)
+    }
+
+    /// Authenticate and read block of currently selected Mifare Classic target
+    async fn exchange_mifare_block(
+        &mut self,
+        uid: &Uid,
+        block: u8,
+        key: &MifareKey,
+    ) -> Result<[u8; 16], Error> {
+        // Authenticate sector
+        let uid4 = mifare_auth_uid(uid);
+        let auth_response = self
+            .driver
+            .process_timeouts_async(&mifare_auth_request(block, key, uid4), 1, AUTH_TIMEOUTS)
+            .await?;
+        check_status(auth_response)?;
+
+        // Read block
+        let read_response = self
+            .driver
+            .process_timeout_async(&mifare_read_request(block), 17, READ_TIMEOUT)
+            .await?;
+        let data = check_status(read_response)?;
+        let data: [u8; 16] = data
+            .get(..16)
+            .and_then(|data| data.try_into().ok())
+            .ok_or(Error::Pn532(Pn532Error::BadResponseFrame))?;
+        debug!("NFC: Read Mifare block {}: {:02x?}", block, data);
+        Ok(data)
+    }
+}
+
+/// NFC card information (ISO/IEC 14443 Type A)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CardInfo {
+    /// Answer to request (ATQA, `SENS_RES`)
+    pub atqa: [u8; 2],
+    /// Select acknowledge (SAK, `SEL_RES`)
+    pub sak: u8,
+}
+
+impl CardInfo {
+    /// Extract card information of first target from `InListPassiveTarget` response
+    /// (PN532 §7.3.5)
+    fn from_target_list(list_response: &[u8]) -> Option<Self> {
+        match list_response {
+            [_num_targets, _target_number, atqa0, atqa1, sak, ..] => Some(Self {
+                atqa: [*atqa0, *atqa1],
+                sak: *sak,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns true if card is a Mifare Classic card (NXP AN10833)
+    pub fn is_mifare_classic(self) -> bool {
+        matches!(self.sak, 0x08 | 0x09 | 0x18 | 0x88)
+    }
+
+    /// Returns true if card is a Mifare Ultralight or NTAG card (NXP AN10833)
+    pub fn is_ntag(self) -> bool {
+        self.sak == 0x00 && self.atqa == [0x00, 0x44]
+    }
+}
+
+impl fmt::Display for CardInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.is_mifare_classic() {
+            "Mifare Classic"
+        } else if self.is_ntag() {
+            "NTAG"
+        } else {
+            "Unknown"
+        };
+        write!(
+            f,
+            "{kind}, ATQA {:02x}{:02x}, SAK {:02x}",
+            self.atqa[0], self.atqa[1], self.sak
+        )
+    }
+}
+
+/// PN532 version and capabilities
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FirmwareVersion {
+    /// IC version (0x32 for PN532)
+    pub ic: u8,
+    /// Version of firmware
+    pub version: u8,
+    /// Revision of firmware
+    pub revision: u8,
+    /// Supported functionality bitmask (bit 0: ISO/IEC 14443 Type A, bit 1: ISO/IEC 14443
+    /// Type B, bit 2: ISO 18092)
+    pub support: u8,
+}
+
+impl FirmwareVersion {
+    /// Extract version information from `GetFirmwareVersion` response (PN532 §7.2.2)
+    fn from_response(version_response: &[u8]) -> Option<Self> {
+        match version_response {
+            [ic, version, revision, support] => Some(Self {
+                ic: *ic,
+                version: *version,
+                revision: *revision,
+                support: *support,
+            }),
+            _ => None,
         }
     }
 }
 
+impl fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.version, self.revision)
+    }
+}
+
 /// NFC UID Error
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct InvalidUid;
 
+/// Cascade tag (CT) that precedes partial UIDs during anticollision (ISO/IEC 14443-3 §6.5.4)
+const CASCADE_TAG: u8 = 0x88;
+
 /// NFC UID
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub enum Uid {
@@ -212,11 +744,19 @@ impl TryFrom<&[u8]> for Uid {
     type Error = InvalidUid;
 
     fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
-        match bytes.len() {
+        match bytes {
             // Note: Always safe to unwrap because of matching length check
-            4 => Ok(Self::Single(bytes.try_into().unwrap())),
-            7 => Ok(Self::Double(bytes.try_into().unwrap())),
-            10 => Ok(Self::Triple(bytes.try_into().unwrap())),
+            _ if bytes.len() == 4 => Ok(Self::Single(bytes.try_into().unwrap())),
+            _ if bytes.len() == 7 => Ok(Self::Double(bytes.try_into().unwrap())),
+            _ if bytes.len() == 10 => Ok(Self::Triple(bytes.try_into().unwrap())),
+            // Some cards and readers include the cascade tag of the first cascade level
+            [CASCADE_TAG, uid @ ..] if uid.len() == 4 || uid.len() == 7 => Self::try_from(uid),
+            // Cascade tags of first and second cascade level of a triple size UID
+            [CASCADE_TAG, uid0, uid1, uid2, CASCADE_TAG, rest @ ..] if rest.len() == 7 => {
+                let mut uid = [*uid0, *uid1, *uid2, 0, 0, 0, 0, 0, 0, 0];
+                uid[3..].copy_from_slice(rest);
+                Ok(Self::Triple(uid))
+            }
             _ => Err(InvalidUid),
         }
     }
@@ -279,3 +819,316 @@ impl ToJson for Uid {
         json.write(self.to_string()).await
     }
 }
+
+impl FromJson for Uid {
+    async fn from_json<R: BufRead>(
+        json: &mut json::Reader<R>,
+    ) -> Result<Self, json::Error<R::Error>> {
+        let s = json.read_string().await?;
+        Self::from_str(&s).map_err(|_| json::Error::InvalidType)
+    }
+}
+
+/// NFC UID in byte array JSON representation
+/// While UIDs are represented as hex strings in JSON by default (e.g. for telemetry), this
+/// wrapper represents them as an array of bytes instead.
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UidBytes(pub Uid);
+
+impl ToJson for UidBytes {
+    async fn to_json<W: Write>(
+        &self,
+        json: &mut json::Writer<W>,
+    ) -> Result<(), json::Error<W::Error>> {
+        json.write_array(self.0.as_ref()).await
+    }
+}
+
+impl FromJson for UidBytes {
+    async fn from_json<R: BufRead>(
+        json: &mut json::Reader<R>,
+    ) -> Result<Self, json::Error<R::Error>> {
+        let bytes: Vec<u8> = json.read().await?;
+        let uid = Uid::try_from(&bytes[..]).map_err(|_| json::Error::InvalidType)?;
+        Ok(Self(uid))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UID1: Uid = Uid::Single([0x12, 0x34, 0x56, 0x78]);
+    const UID2: Uid = Uid::Single([0xde, 0xad, 0xbe, 0xef]);
+
+    #[test]
+    fn same_card_suppressed() {
+        let mut filter = SameCardFilter::new(Duration::from_secs(2));
+        assert!(filter.accept(&UID1, Instant::from_millis(0)));
+        // Held on reader, seen in every read loop cycle
+        for ms in (500..10_000).step_by(500) {
+            assert!(!filter.accept(&UID1, Instant::from_millis(ms)));
+        }
+        // Removed and presented again after debounce window
+        assert!(filter.accept(&UID1, Instant::from_millis(12_000)));
+    }
+
+    #[test]
+    fn other_card_accepted() {
+        let mut filter = SameCardFilter::new(Duration::from_secs(2));
+        assert!(filter.accept(&UID1, Instant::from_millis(0)));
+        assert!(filter.accept(&UID2, Instant::from_millis(500)));
+        assert!(filter.accept(&UID1, Instant::from_millis(1000)));
+    }
+
+    #[test]
+    fn same_card_reset() {
+        let mut filter = SameCardFilter::new(Duration::from_secs(2));
+        assert!(filter.accept(&UID1, Instant::from_millis(0)));
+        // Transaction finished much later while card is still held on reader
+        filter.reset(Instant::from_millis(30_000));
+        assert!(!filter.accept(&UID1, Instant::from_millis(30_500)));
+        assert!(filter.accept(&UID1, Instant::from_millis(33_000)));
+    }
+
+    #[test]
+    fn uid_from_bytes() {
+        assert_eq!(
+            Uid::try_from(&[0x12, 0x34, 0x56, 0x78][..]),
+            Ok(Uid::Single([0x12, 0x34, 0x56, 0x78]))
+        );
+        assert_eq!(
+            Uid::try_from(&[0x04, 0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc][..]),
+            Ok(Uid::Double([0x04, 0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc]))
+        );
+        assert_eq!(Uid::try_from(&[0x12, 0x34, 0x56][..]), Err(InvalidUid));
+        assert_eq!(Uid::try_from(&[][..]), Err(InvalidUid));
+    }
+
+    #[test]
+    fn uid_from_bytes_starting_with_cascade_tag() {
+        // Valid lengths are never stripped, even if starting with the cascade tag value
+        assert_eq!(
+            Uid::try_from(&[0x88, 0x12, 0x34, 0x56][..]),
+            Ok(Uid::Single([0x88, 0x12, 0x34, 0x56]))
+        );
+    }
+
+    #[test]
+    fn uid_from_bytes_with_cascade_tag() {
+        assert_eq!(
+            Uid::try_from(&[0x88, 0x12, 0x34, 0x56, 0x78][..]),
+            Ok(Uid::Single([0x12, 0x34, 0x56, 0x78]))
+        );
+        assert_eq!(
+            Uid::try_from(&[0x88, 0x04, 0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc][..]),
+            Ok(Uid::Double([0x04, 0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc]))
+        );
+        assert_eq!(
+            Uid::try_from(
+                &[0x88, 0x01, 0x02, 0x03, 0x88, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a][..]
+            ),
+            Ok(Uid::Triple([
+                0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a
+            ]))
+        );
+    }
+
+    #[test]
+    fn uid_from_bytes_with_invalid_cascade_tag() {
+        // Cascade tag with unsupported length
+        assert_eq!(
+            Uid::try_from(&[0x88, 0x12, 0x34, 0x56, 0x78, 0x9a][..]),
+            Err(InvalidUid)
+        );
+        // Only a single cascade tag is stripped
+        assert_eq!(
+            Uid::try_from(&[0x88, 0x88, 0x12, 0x34, 0x56, 0x78][..]),
+            Err(InvalidUid)
+        );
+        // Second cascade tag missing
+        assert_eq!(
+            Uid::try_from(
+                &[0x88, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b][..]
+            ),
+            Err(InvalidUid)
+        );
+    }
+
+    fn uids() -> [Uid; 3] {
+        [
+            Uid::Single([0x12, 0x34, 0x56, 0x78]),
+            Uid::Double([0x04, 0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc]),
+            Uid::Triple([0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a]),
+        ]
+    }
+
+    async fn to_json<T: ToJson>(value: &T) -> String {
+        let mut json = json::Writer::new(Vec::new());
+        json.write(value).await.unwrap();
+        String::from_utf8(json.into_inner()).unwrap()
+    }
+
+    async fn from_json<T: FromJson>(s: &str) -> Result<T, json::Error<Infallible>> {
+        json::Reader::new(s.as_bytes()).read().await
+    }
+
+    #[async_std::test]
+    async fn uid_json() {
+        assert_eq!(to_json(&uids()[0]).await, r#""12345678""#);
+        for uid in uids() {
+            let json = to_json(&uid).await;
+            assert_eq!(from_json::<Uid>(&json).await.ok(), Some(uid));
+        }
+        assert!(from_json::<Uid>(r#""123456""#).await.is_err());
+        assert!(from_json::<Uid>("[18, 52, 86, 120]").await.is_err());
+    }
+
+    #[async_std::test]
+    async fn uid_bytes_json() {
+        assert_eq!(
+            to_json(&UidBytes(uids()[0].clone())).await,
+            "[18, 52, 86, 120]"
+        );
+        for uid in uids() {
+            let json = to_json(&UidBytes(uid.clone())).await;
+            assert_eq!(from_json::<UidBytes>(&json).await.ok(), Some(UidBytes(uid)));
+        }
+        assert!(from_json::<UidBytes>("[18, 52, 86]").await.is_err());
+        assert!(from_json::<UidBytes>("[18, 52, 86, 1200]").await.is_err());
+        assert!(from_json::<UidBytes>(r#""12345678""#).await.is_err());
+    }
+
+    #[test]
+    fn card_info_mifare_classic() {
+        let response = [0x01, 0x01, 0x00, 0x04, 0x08, 0x04, 0x12, 0x34, 0x56, 0x78];
+        let info = CardInfo::from_target_list(&response).unwrap();
+        assert_eq!(info.atqa, [0x00, 0x04]);
+        assert_eq!(info.sak, 0x08);
+        assert!(info.is_mifare_classic());
+        assert!(!info.is_ntag());
+    }
+
+    #[test]
+    fn card_info_ntag() {
+        let response = [
+            0x01, 0x01, 0x00, 0x44, 0x00, 0x07, 0x04, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66,
+        ];
+        let info = CardInfo::from_target_list(&response).unwrap();
+        assert_eq!(info.atqa, [0x00, 0x44]);
+        assert_eq!(info.sak, 0x00);
+        assert!(!info.is_mifare_classic());
+        assert!(info.is_ntag());
+    }
+
+    #[test]
+    fn card_info_short_response() {
+        assert_eq!(CardInfo::from_target_list(&[0x01, 0x01, 0x00, 0x04]), None);
+    }
+
+    #[test]
+    fn firmware_version() {
+        // GetFirmwareVersion response of a PN532 with firmware 1.6
+        let version = FirmwareVersion::from_response(&[0x32, 0x01, 0x06, 0x07]).unwrap();
+        assert_eq!(
+            version,
+            FirmwareVersion {
+                ic: 0x32,
+                version: 1,
+                revision: 6,
+                support: 0x07,
+            }
+        );
+        assert_eq!(version.to_string(), "1.6");
+    }
+
+    #[test]
+    fn firmware_version_invalid_response() {
+        assert_eq!(FirmwareVersion::from_response(&[0x32, 0x01, 0x06]), None);
+        assert_eq!(FirmwareVersion::from_response(&[]), None);
+    }
+
+    #[test]
+    fn power_down_request_frame() {
+        let request = power_down_request(false);
+        assert_eq!(request.command, Command::PowerDown);
+        assert_eq!(request.data, [0x80, 0x00]);
+    }
+
+    #[test]
+    fn power_down_request_frame_with_rf_wakeup() {
+        let request = power_down_request(true);
+        assert_eq!(request.command, Command::PowerDown);
+        assert_eq!(request.data, [0x88, 0x01]);
+    }
+
+    #[test]
+    fn rf_field_request_frame() {
+        let request = rf_field_request(false);
+        assert_eq!(request.command, Command::RFConfiguration);
+        assert_eq!(request.data, [0x01, 0x00]);
+        let request = rf_field_request(true);
+        assert_eq!(request.command, Command::RFConfiguration);
+        assert_eq!(request.data, [0x01, 0x01]);
+    }
+
+    #[test]
+    fn sam_configuration_request_frame() {
+        let request = sam_configuration_request();
+        assert_eq!(request.command, Command::SAMConfiguration);
+        assert_eq!(request.data, [0x01, 0x00, 0x01]);
+    }
+
+    #[test]
+    fn auth_uid_single() {
+        assert_eq!(mifare_auth_uid(&UID1), [0x12, 0x34, 0x56, 0x78]);
+    }
+
+    #[test]
+    fn auth_uid_double() {
+        let uid = Uid::Double([0x04, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66]);
+        assert_eq!(mifare_auth_uid(&uid), [0x33, 0x44, 0x55, 0x66]);
+    }
+
+    #[test]
+    fn auth_request_key_a() {
+        let key = MifareKey::A([0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        let request = mifare_auth_request(4, &key, [0x12, 0x34, 0x56, 0x78]);
+        assert_eq!(request.command, Command::InDataExchange);
+        assert_eq!(
+            request.data,
+            [0x01, 0x60, 0x04, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x12, 0x34, 0x56, 0x78]
+        );
+    }
+
+    #[test]
+    fn auth_request_key_b() {
+        let key = MifareKey::B([0xa0, 0xa1, 0xa2, 0xa3, 0xa4, 0xa5]);
+        let request = mifare_auth_request(7, &key, [0xde, 0xad, 0xbe, 0xef]);
+        assert_eq!(request.command, Command::InDataExchange);
+        assert_eq!(
+            request.data,
+            [0x01, 0x61, 0x07, 0xa0, 0xa1, 0xa2, 0xa3, 0xa4, 0xa5, 0xde, 0xad, 0xbe, 0xef]
+        );
+    }
+
+    #[test]
+    fn read_request() {
+        let request = mifare_read_request(5);
+        assert_eq!(request.command, Command::InDataExchange);
+        assert_eq!(request.data, [0x01, 0x30, 0x05]);
+    }
+
+    #[test]
+    fn response_status() {
+        assert_eq!(
+            check_status(&[0x00, 0x01, 0x02]).ok(),
+            Some(&[0x01, 0x02][..])
+        );
+        assert!(matches!(check_status(&[0x14]), Err(Error::AuthFailed)));
+        assert!(matches!(check_status(&[0x01]), Err(Error::Timeout)));
+        assert!(matches!(check_status(&[0x27]), Err(Error::Status(0x27))));
+        assert!(matches!(check_status(&[]), Err(Error::Pn532(_))));
+    }
+}
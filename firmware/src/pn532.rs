@@ -9,9 +9,10 @@
 
 use core::convert::Infallible;
 use core::fmt::Debug;
-use embassy_time::{with_timeout, Duration};
+use embassy_time::{with_timeout, Duration, Timer};
 use embedded_hal_async::digital::Wait;
 use embedded_hal_async::i2c::{I2c, Operation};
+use embedded_hal_async::spi::{self, SpiDevice};
 use log::warn;
 use pn532::i2c::{I2C_ADDRESS, PN532_I2C_READY};
 use pn532::requests::BorrowedRequest;
@@ -22,10 +23,10 @@ pub use pn532::{Error, Request};
 /// Response buffer size (32 is the PN532 default)
 pub const BUFFER_SIZE: usize = 64;
 
-/// Command ACK timeout
+/// Default command ACK timeout
 const ACK_TIMEOUT: Duration = Duration::from_millis(50);
 
-/// Command response timeout
+/// Default command response timeout
 const RESPONSE_TIMEOUT: Duration = Duration::from_millis(50);
 
 const PREAMBLE: [u8; 3] = [0x00, 0x00, 0xFF];
@@ -86,6 +87,98 @@ impl<I2C: I2c, IRQ: Wait<Error = Infallible>> Interface for I2CInterfaceWithIrq<
     }
 }
 
+/// PN532 SPI interface without ready status interrupt (status is polled instead)
+/// This is mostly a re-implementation of `pn532::spi::SPIInterface`, but with asynchronous
+/// handling. The SPI bus needs to be configured in mode 0 with LSB first bit order.
+// TODO: Switch to `pn532::spi::SPIInterface` once the pn532 crate supports async interfaces
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct SPIInterface<SPI> {
+    pub spi: SPI,
+}
+
+impl<SPI: SpiDevice> Interface for SPIInterface<SPI> {
+    type Error = SPI::Error;
+
+    async fn write(&mut self, frame: &[u8]) -> Result<(), Self::Error> {
+        self.spi
+            .transaction(&mut [
+                spi::Operation::Write(&[Self::DATA_WRITE]),
+                spi::Operation::Write(frame),
+            ])
+            .await
+    }
+
+    async fn wait_ready(&mut self) -> Result<(), Self::Error> {
+        // Without IRQ line, poll status until ready
+        while !self.is_ready().await? {
+            Timer::after(Self::STATUS_POLL_INTERVAL).await;
+        }
+        Ok(())
+    }
+
+    async fn read(&mut self, buf: &mut [u8]) -> Result<(), Self::Error> {
+        self.spi
+            .transaction(&mut [
+                spi::Operation::Write(&[Self::DATA_READ]),
+                spi::Operation::Read(buf),
+            ])
+            .await
+    }
+}
+
+#[allow(dead_code)]
+impl<SPI: SpiDevice> SPIInterface<SPI> {
+    /// Status polling interval while waiting for ready status
+    const STATUS_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+    /// SPI operation: data write (PN532 §6.2.5)
+    const DATA_WRITE: u8 = 0x01;
+    /// SPI operation: status read (PN532 §6.2.5)
+    const STATUS_READ: u8 = 0x02;
+    /// SPI operation: data read (PN532 §6.2.5)
+    const DATA_READ: u8 = 0x03;
+    /// SPI status: ready (PN532 §6.2.5)
+    const READY: u8 = 0x01;
+
+    /// Read status and return true if PN532 is ready
+    async fn is_ready(&mut self) -> Result<bool, SPI::Error> {
+        let mut status = [0];
+        self.spi
+            .transaction(&mut [
+                spi::Operation::Write(&[Self::STATUS_READ]),
+                spi::Operation::Read(&mut status),
+            ])
+            .await?;
+        Ok(status[0] & Self::READY != 0)
+    }
+}
+
+/// PN532 command timeouts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Time to wait for the PN532 to acknowledge a request
+    pub ack: Duration,
+    /// Time to wait for the PN532 to respond to a request
+    pub response: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            ack: ACK_TIMEOUT,
+            response: RESPONSE_TIMEOUT,
+        }
+    }
+}
+
+impl Timeouts {
+    /// Same timeouts, but with the given response timeout
+    pub fn with_response(self, response: Duration) -> Self {
+        Self { response, ..self }
+    }
+}
+
 /// PN532 driver
 /// This is mostly a re-implementation of `pn532::Pn532`, but for asynchronous interfaces
 // TODO: Switch to `pn532::Pn532` once the pn532 crate supports async interfaces
@@ -93,6 +186,7 @@ impl<I2C: I2c, IRQ: Wait<Error = Infallible>> Interface for I2CInterfaceWithIrq<
 pub struct Pn532<I, const N: usize = BUFFER_SIZE> {
     interface: I,
     buf: [u8; N],
+    timeouts: Timeouts,
 }
 
 impl<I: Interface, const N: usize> Pn532<I, N> {
@@ -102,9 +196,27 @@ impl<I: Interface, const N: usize> Pn532<I, N> {
         Self {
             interface,
             buf: [0; N],
+            timeouts: Timeouts::default(),
         }
     }
 
+    /// Underlying interface, e.g. to configure interrupt pins
+    pub fn interface_mut(&mut self) -> &mut I {
+        &mut self.interface
+    }
+
+    /// Default timeouts of commands
+    #[allow(dead_code)]
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
+    /// Set default timeouts of commands (50 ms each unless changed)
+    #[allow(dead_code)]
+    pub fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+
     /// Send PN532 request
     /// Like `pn532::Pn532::send`, but for asynchronous interfaces
     #[inline]
@@ -182,7 +294,7 @@ impl<I: Interface, const N: usize> Pn532<I, N> {
         response_len: usize,
     ) -> Result<&[u8], Error<I::Error>> {
         // codegen trampoline: https://github.com/rust-lang/rust/issues/77960
-        self._process_timeout_async(request.into(), response_len, RESPONSE_TIMEOUT)
+        self._process_timeout_async(request.into(), response_len, self.timeouts)
             .await
     }
 
@@ -196,18 +308,81 @@ impl<I: Interface, const N: usize> Pn532<I, N> {
         timeout: Duration,
     ) -> Result<&[u8], Error<I::Error>> {
         // codegen trampoline: https://github.com/rust-lang/rust/issues/77960
-        self._process_timeout_async(request.into(), response_len, timeout)
+        let timeouts = self.timeouts.with_response(timeout);
+        self._process_timeout_async(request.into(), response_len, timeouts)
             .await
     }
-    pub async fn _process_timeout_async(
+
+    /// Send PN532 request and wait for ack and response with the given ACK and response timeouts
+    #[inline]
+    pub async fn process_timeouts_async<'a>(
+        &mut self,
+        request: impl Into<BorrowedRequest<'a>>,
+        response_len: usize,
+        timeouts: Timeouts,
+    ) -> Result<&[u8], Error<I::Error>> {
+        // codegen trampoline: https://github.com/rust-lang/rust/issues/77960
+        self._process_timeout_async(request.into(), response_len, timeouts)
+            .await
+    }
+
+    /// Send PN532 request and wait for ack and response with timeout. If the response frame is
+    /// corrupted (e.g. by RF noise), the request is retried up to the given number of attempts in
+    /// total. Interface errors and timeouts aren't retried.
+    #[inline]
+    pub async fn process_timeout_retry_async<'a>(
+        &mut self,
+        request: impl Into<BorrowedRequest<'a>>,
+        response_len: usize,
+        timeout: Duration,
+        attempts: usize,
+    ) -> Result<&[u8], Error<I::Error>> {
+        // codegen trampoline: https://github.com/rust-lang/rust/issues/77960
+        self._process_timeout_retry_async(request.into(), response_len, timeout, attempts)
+            .await
+    }
+    async fn _process_timeout_retry_async(
         &mut self,
         request: BorrowedRequest<'_>,
         response_len: usize,
         timeout: Duration,
+        attempts: usize,
+    ) -> Result<&[u8], Error<I::Error>> {
+        let mut attempt = 1;
+        loop {
+            let res = self
+                ._process_timeout_async(
+                    BorrowedRequest::new(request.command, request.data),
+                    response_len,
+                    self.timeouts.with_response(timeout),
+                )
+                .await
+                // Only keep the response length to not hold a borrow of the buffer across retries
+                .map(<[u8]>::len);
+            match res {
+                // Response data is located right after the frame header (see `parse_response`)
+                Ok(len) => return Ok(&self.buf[7..7 + len]),
+                Err(err) if attempt < attempts && is_transient(&err) => {
+                    warn!(
+                        "PN532: Retrying after transient error (attempt {}): {:?}",
+                        attempt, err
+                    );
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    pub async fn _process_timeout_async(
+        &mut self,
+        request: BorrowedRequest<'_>,
+        response_len: usize,
+        timeouts: Timeouts,
     ) -> Result<&[u8], Error<I::Error>> {
         let sent_command = request.command;
 
-        with_timeout(ACK_TIMEOUT, async {
+        with_timeout(timeouts.ack, async {
             self.send(request).await?;
             self.interface.wait_ready().await?;
             self.receive_ack().await
@@ -215,7 +390,7 @@ impl<I: Interface, const N: usize> Pn532<I, N> {
         .await
         .map_err(|_| Error::TimeoutAck)??;
 
-        with_timeout(timeout, async {
+        with_timeout(timeouts.response, async {
             self.interface.wait_ready().await?;
             self.receive_response(sent_command, response_len).await
         })
@@ -224,6 +399,15 @@ impl<I: Interface, const N: usize> Pn532<I, N> {
     }
 }
 
+/// Returns true if the given error is caused by a corrupted response frame, which is likely
+/// transient (e.g. RF noise) and worth retrying
+fn is_transient<E: Debug>(err: &Error<E>) -> bool {
+    matches!(
+        err,
+        Error::CrcError | Error::BadResponseFrame | Error::Syntax
+    )
+}
+
 /// Parse PN532 response
 /// Like `pn532::protocol::parse_response`
 fn parse_response<E: Debug>(
@@ -267,3 +451,289 @@ fn parse_response<E: Debug>(
     // Adjust response buf and return it
     Ok(&response_buf[7..5 + frame_len as usize])
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::collections::VecDeque;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    /// Mock SPI device that records written bytes and returns given bytes on read
+    #[derive(Debug, Default)]
+    struct MockSpi {
+        /// Bytes written, one entry per transaction
+        written: Vec<Vec<u8>>,
+        /// Bytes to return on read, one entry per transaction
+        reads: VecDeque<Vec<u8>>,
+    }
+
+    impl spi::ErrorType for MockSpi {
+        type Error = Infallible;
+    }
+
+    impl SpiDevice for MockSpi {
+        async fn transaction(
+            &mut self,
+            operations: &mut [spi::Operation<'_, u8>],
+        ) -> Result<(), Self::Error> {
+            let mut written = Vec::new();
+            for operation in operations {
+                match operation {
+                    spi::Operation::Write(bytes) => written.extend_from_slice(bytes),
+                    spi::Operation::Read(buf) => {
+                        let bytes = self.reads.pop_front().unwrap();
+                        buf.copy_from_slice(&bytes);
+                    }
+                    _ => unimplemented!(),
+                }
+            }
+            self.written.push(written);
+            Ok(())
+        }
+    }
+
+    fn interface(reads: &[&[u8]]) -> SPIInterface<MockSpi> {
+        SPIInterface {
+            spi: MockSpi {
+                written: Vec::new(),
+                reads: reads.iter().map(|bytes| bytes.to_vec()).collect(),
+            },
+        }
+    }
+
+    #[async_std::test]
+    async fn spi_write() {
+        let mut spi = interface(&[]);
+        spi.write(&[0x00, 0x00, 0xff, 0x02]).await.unwrap();
+        assert_eq!(spi.spi.written, [vec![0x01, 0x00, 0x00, 0xff, 0x02]]);
+    }
+
+    #[async_std::test]
+    async fn spi_read() {
+        let mut spi = interface(&[&[0x00, 0x00, 0xff]]);
+        let mut buf = [0; 3];
+        spi.read(&mut buf).await.unwrap();
+        assert_eq!(buf, [0x00, 0x00, 0xff]);
+        assert_eq!(spi.spi.written, [vec![0x03]]);
+    }
+
+    #[async_std::test]
+    async fn spi_status() {
+        let mut spi = interface(&[&[0x00], &[0x01]]);
+        assert!(!spi.is_ready().await.unwrap());
+        assert!(spi.is_ready().await.unwrap());
+        assert_eq!(spi.spi.written, [vec![0x02], vec![0x02]]);
+    }
+
+    #[async_std::test]
+    async fn spi_wait_ready() {
+        let mut spi = interface(&[&[0x01]]);
+        spi.wait_ready().await.unwrap();
+        assert_eq!(spi.spi.written, [vec![0x02]]);
+    }
+
+    /// Mock PN532 interface that counts written frames and returns given frames (or errors) on read
+    #[derive(Debug, Default)]
+    struct MockInterface {
+        /// Number of frames written
+        writes: usize,
+        /// Frames to return on read, one entry per read
+        reads: VecDeque<Result<Vec<u8>, ()>>,
+        /// Number of times to get ready before not responding anymore (unlimited if `None`)
+        ready: Option<usize>,
+    }
+
+    impl Interface for MockInterface {
+        type Error = ();
+
+        async fn write(&mut self, _frame: &[u8]) -> Result<(), Self::Error> {
+            self.writes += 1;
+            Ok(())
+        }
+
+        async fn wait_ready(&mut self) -> Result<(), Self::Error> {
+            match self.ready {
+                Some(0) => core::future::pending().await,
+                Some(ref mut ready) => *ready -= 1,
+                None => (),
+            }
+            Ok(())
+        }
+
+        async fn read(&mut self, buf: &mut [u8]) -> Result<(), Self::Error> {
+            let bytes = self.reads.pop_front().unwrap()?;
+            buf[..bytes.len()].copy_from_slice(&bytes);
+            Ok(())
+        }
+    }
+
+    /// Response frame with given response command and data
+    fn response_frame(command: u8, data: &[u8]) -> Vec<u8> {
+        let len = 2 + data.len() as u8;
+        let sum = data
+            .iter()
+            .fold(PN532_TO_HOST.wrapping_add(command), |s, &b| {
+                s.wrapping_add(b)
+            });
+        let mut frame = vec![0x00, 0x00, 0xff, len, (!len).wrapping_add(1)];
+        frame.extend_from_slice(&[PN532_TO_HOST, command]);
+        frame.extend_from_slice(data);
+        frame.extend_from_slice(&[(!sum).wrapping_add(1), POSTAMBLE]);
+        frame
+    }
+
+    /// Response frame with wrong data checksum
+    fn corrupted_frame(command: u8, data: &[u8]) -> Vec<u8> {
+        let mut frame = response_frame(command, data);
+        let checksum = frame.len() - 2;
+        frame[checksum] = frame[checksum].wrapping_add(1);
+        frame
+    }
+
+    async fn process_retry(
+        reads: Vec<Result<Vec<u8>, ()>>,
+        attempts: usize,
+    ) -> (Result<Vec<u8>, Error<()>>, usize) {
+        let mut driver: Pn532<_> = Pn532::new_async(MockInterface {
+            reads: reads.into(),
+            ..MockInterface::default()
+        });
+        let res = driver
+            .process_timeout_retry_async(
+                &Request::GET_FIRMWARE_VERSION,
+                4,
+                RESPONSE_TIMEOUT,
+                attempts,
+            )
+            .await
+            .map(<[u8]>::to_vec);
+        (res, driver.interface.writes)
+    }
+
+    const VERSION: [u8; 4] = [0x32, 0x01, 0x06, 0x07];
+
+    #[test]
+    fn parse_corrupted_response() {
+        let frame = corrupted_frame(0x03, &VERSION);
+        assert_eq!(parse_response::<()>(&frame, 0x03), Err(Error::CrcError));
+        let frame = response_frame(0x03, &VERSION);
+        assert_eq!(parse_response::<()>(&frame, 0x03), Ok(&VERSION[..]));
+    }
+
+    #[async_std::test]
+    async fn retry_succeeds() {
+        let (res, writes) = process_retry(
+            vec![
+                Ok(ACK.to_vec()),
+                Ok(corrupted_frame(0x03, &VERSION)),
+                Ok(ACK.to_vec()),
+                Ok(response_frame(0x03, &VERSION)),
+            ],
+            3,
+        )
+        .await;
+        assert_eq!(res, Ok(VERSION.to_vec()));
+        assert_eq!(writes, 2);
+    }
+
+    #[async_std::test]
+    async fn retry_gives_up() {
+        let (res, writes) = process_retry(
+            vec![
+                Ok(ACK.to_vec()),
+                Ok(corrupted_frame(0x03, &VERSION)),
+                Ok(ACK.to_vec()),
+                Ok(corrupted_frame(0x03, &VERSION)),
+                Ok(ACK.to_vec()),
+                Ok(corrupted_frame(0x03, &VERSION)),
+            ],
+            3,
+        )
+        .await;
+        assert_eq!(res, Err(Error::CrcError));
+        assert_eq!(writes, 3);
+    }
+
+    #[async_std::test]
+    async fn no_retry_on_interface_error() {
+        let (res, writes) = process_retry(vec![Ok(ACK.to_vec()), Err(())], 3).await;
+        assert_eq!(res, Err(Error::InterfaceError(())));
+        assert_eq!(writes, 1);
+    }
+
+    /// Driver with a PN532 that gets ready the given number of times and doesn't respond anymore
+    /// afterwards
+    fn unresponsive_driver(ready: usize) -> Pn532<MockInterface> {
+        Pn532::new_async(MockInterface {
+            reads: VecDeque::from([Ok(ACK.to_vec())]),
+            ready: Some(ready),
+            ..MockInterface::default()
+        })
+    }
+
+    #[test]
+    fn default_timeouts() {
+        let driver = unresponsive_driver(0);
+        assert_eq!(
+            driver.timeouts(),
+            Timeouts {
+                ack: Duration::from_millis(50),
+                response: Duration::from_millis(50),
+            }
+        );
+        let timeouts = driver.timeouts().with_response(Duration::from_millis(200));
+        assert_eq!(timeouts.ack, Duration::from_millis(50));
+        assert_eq!(timeouts.response, Duration::from_millis(200));
+    }
+
+    #[async_std::test]
+    async fn ack_timeout() {
+        // Zero timeouts expire as soon as the PN532 doesn't respond immediately
+        let timeouts = Timeouts {
+            ack: Duration::from_ticks(0),
+            response: Duration::from_secs(3600),
+        };
+        let mut driver = unresponsive_driver(0);
+        let res = driver
+            .process_timeouts_async(&Request::GET_FIRMWARE_VERSION, 4, timeouts)
+            .await;
+        assert_eq!(res, Err(Error::TimeoutAck));
+
+        // Configured default timeouts are used if none are given
+        let mut driver = unresponsive_driver(0);
+        driver.set_timeouts(timeouts);
+        let res = driver
+            .process_async(&Request::GET_FIRMWARE_VERSION, 4)
+            .await;
+        assert_eq!(res, Err(Error::TimeoutAck));
+    }
+
+    #[async_std::test]
+    async fn response_timeout() {
+        let timeouts = Timeouts {
+            ack: Duration::from_secs(3600),
+            response: Duration::from_ticks(0),
+        };
+        let mut driver = unresponsive_driver(1);
+        let res = driver
+            .process_timeouts_async(&Request::GET_FIRMWARE_VERSION, 4, timeouts)
+            .await;
+        assert_eq!(res, Err(Error::TimeoutResponse));
+
+        // Configured ACK timeout is kept if only a response timeout is given
+        let mut driver = unresponsive_driver(1);
+        driver.set_timeouts(timeouts);
+        let res = driver
+            .process_timeout_async(&Request::GET_FIRMWARE_VERSION, 4, Duration::from_ticks(0))
+            .await;
+        assert_eq!(res, Err(Error::TimeoutResponse));
+    }
+
+    #[async_std::test]
+    async fn no_retry_on_bad_ack() {
+        let (res, writes) = process_retry(vec![Ok(vec![0x00; 6])], 3).await;
+        assert_eq!(res, Err(Error::BadAck));
+        assert_eq!(writes, 1);
+    }
+}
@@ -0,0 +1,799 @@
+use crate::article::ArticleId;
+use crate::flash;
+use crate::json::{self, FromJsonObject, ToJson};
+use crate::time;
+use crate::user::UserId;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use chrono::{DateTime, Utc};
+use core::fmt;
+use embedded_io_async::{BufRead, Write};
+use esp_storage::FlashStorage;
+use log::{debug, error, info, warn};
+
+/// Subtype of purchase queue data partition
+const PARTITION_SUBTYPE: u8 = 0x51;
+
+/// Size of a flash sector. The queue is stored in one of two sectors at the start of the
+/// partition.
+const SECTOR_SIZE: usize = FlashStorage::SECTOR_SIZE as usize;
+
+/// Max number of pending purchases. Since the whole queue is stored in a single flash sector, the
+/// queue may also be full earlier if purchases are large (e.g. with long article ids or
+/// callsigns).
+const MAX_PURCHASES: usize = 30;
+
+/// Purchase queue error
+#[derive(Debug)]
+pub enum Error {
+    /// Too many pending purchases
+    Full,
+    /// Purchase could not be stored to flash
+    Storage,
+    /// Stored purchases could not be read
+    Corrupted,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full => write!(f, "Too many pending purchases"),
+            Self::Storage => write!(f, "Unable to store purchase"),
+            Self::Corrupted => write!(f, "Stored purchases corrupted"),
+        }
+    }
+}
+
+/// Pending purchase
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Purchase {
+    /// Unique id of purchase (to tell apart otherwise identical purchases)
+    pub id: u32,
+    /// User id of purchaser
+    pub user_id: UserId,
+    /// Purchased article id
+    pub article_id: ArticleId,
+    /// Purchased amount
+    pub amount: f32,
+    /// Total price
+    pub total_price: f32,
+    /// Sales tax rate in percent (optional)
+    pub salestax: Option<f32>,
+    /// Callsign to book purchase on (optional)
+    pub callsign: Option<String>,
+    /// Time of purchase (if current time was known)
+    pub time: Option<DateTime<Utc>>,
+}
+
+impl FromJsonObject for Purchase {
+    type Context<'ctx> = ();
+
+    async fn read_next<R: BufRead>(
+        &mut self,
+        key: String,
+        json: &mut json::Reader<R>,
+        _context: &Self::Context<'_>,
+    ) -> Result<(), json::Error<R::Error>> {
+        match &*key {
+            "id" => self.id = json.read().await?,
+            "user_id" => self.user_id = json.read().await?,
+            "article_id" => self.article_id = json.read().await?,
+            "amount" => self.amount = json.read().await?,
+            "total_price" => self.total_price = json.read().await?,
+            "salestax" => self.salestax = Some(json.read().await?),
+            "callsign" => self.callsign = Some(json.read().await?),
+            "time" => {
+                let timestamp = json.read().await?;
+                self.time =
+                    Some(DateTime::from_timestamp(timestamp, 0).ok_or(json::Error::InvalidType)?);
+            }
+            _ => json.skip_any().await?,
+        }
+        Ok(())
+    }
+}
+
+impl ToJson for Purchase {
+    async fn to_json<W: Write>(
+        &self,
+        json: &mut json::Writer<W>,
+    ) -> Result<(), json::Error<W::Error>> {
+        let mut object = json.write_object().await?;
+        let mut object = object
+            .field("id", self.id)
+            .await?
+            .field("user_id", self.user_id)
+            .await?
+            .field("article_id", &self.article_id)
+            .await?
+            .field("amount", self.amount)
+            .await?
+            .field("total_price", self.total_price)
+            .await?;
+        if let Some(salestax) = self.salestax {
+            object = object.field("salestax", salestax).await?;
+        }
+        if let Some(ref callsign) = self.callsign {
+            object = object.field("callsign", callsign).await?;
+        }
+        if let Some(time) = self.time {
+            object = object.field("time", time.timestamp()).await?;
+        }
+        object.finish().await
+    }
+}
+
+/// Store-and-forward queue of pending purchases
+///
+/// Purchases are added to this queue and immediately stored to the `queue` flash data partition,
+/// so they survive a restart. Pending purchases are submitted to Vereinsflieger once the network
+/// is available. Every purchase gets a unique id and is removed (by id) right after it has been
+/// submitted successfully. This way, a purchase is never submitted twice, even if submitting
+/// subsequent purchases fails and is retried later.
+///
+/// Adding a purchase is atomic: the queue is stored alternately to one of two flash sectors,
+/// together with a sequence number and a checksum. The previously stored queue is kept until the
+/// newly stored queue was read back successfully. If storing is interrupted (e.g. by a brown-out
+/// that restarts the system), the previous queue (without the new purchase) is loaded on the next
+/// start. If storing fails otherwise, the purchase is discarded and an error is returned, so that
+/// a sale is never half-recorded.
+#[derive(Debug, Default)]
+pub struct Queue {
+    /// Flash data partition to store queue to
+    partition: Option<flash::Partition>,
+    /// Sector that holds the latest stored queue (if any)
+    sector: Option<usize>,
+    /// Sequence number of latest stored queue
+    seq: u32,
+    /// Id of next purchase
+    next_id: u32,
+    /// Pending purchases
+    purchases: VecDeque<Purchase>,
+}
+
+impl FromJsonObject for Queue {
+    type Context<'ctx> = ();
+
+    async fn read_next<R: BufRead>(
+        &mut self,
+        key: String,
+        json: &mut json::Reader<R>,
+        _context: &Self::Context<'_>,
+    ) -> Result<(), json::Error<R::Error>> {
+        match &*key {
+            "next_id" => self.next_id = json.read().await?,
+            "purchases" => self.purchases = json.read::<Vec<_>>().await?.into(),
+            _ => json.skip_any().await?,
+        }
+        Ok(())
+    }
+}
+
+impl ToJson for Queue {
+    async fn to_json<W: Write>(
+        &self,
+        json: &mut json::Writer<W>,
+    ) -> Result<(), json::Error<W::Error>> {
+        json.write_object()
+            .await?
+            .field("next_id", self.next_id)
+            .await?
+            .field("purchases", Purchases(&self.purchases))
+            .await?
+            .finish()
+            .await
+    }
+}
+
+/// Helper for writing pending purchases as JSON array
+struct Purchases<'a>(&'a VecDeque<Purchase>);
+
+impl ToJson for Purchases<'_> {
+    async fn to_json<W: Write>(
+        &self,
+        json: &mut json::Writer<W>,
+    ) -> Result<(), json::Error<W::Error>> {
+        json.write_array(self.0).await
+    }
+}
+
+/// Stored queue, as found in a flash sector
+#[derive(Debug, Default)]
+struct Record {
+    /// Sequence number (incremented every time the queue is stored)
+    seq: u32,
+    /// Checksum of queue
+    checksum: Option<u32>,
+    /// Stored queue
+    queue: Queue,
+}
+
+impl FromJsonObject for Record {
+    type Context<'ctx> = ();
+
+    async fn read_next<R: BufRead>(
+        &mut self,
+        key: String,
+        json: &mut json::Reader<R>,
+        context: &Self::Context<'_>,
+    ) -> Result<(), json::Error<R::Error>> {
+        match &*key {
+            "seq" => self.seq = json.read().await?,
+            "checksum" => self.checksum = Some(json.read().await?),
+            _ => self.queue.read_next(key, json, context).await?,
+        }
+        Ok(())
+    }
+}
+
+/// Queue to store in a flash sector
+struct RecordRef<'a> {
+    seq: u32,
+    checksum: u32,
+    queue: &'a Queue,
+}
+
+impl ToJson for RecordRef<'_> {
+    async fn to_json<W: Write>(
+        &self,
+        json: &mut json::Writer<W>,
+    ) -> Result<(), json::Error<W::Error>> {
+        json.write_object()
+            .await?
+            .field("seq", self.seq)
+            .await?
+            .field("checksum", self.checksum)
+            .await?
+            .field("next_id", self.queue.next_id)
+            .await?
+            .field("purchases", Purchases(&self.queue.purchases))
+            .await?
+            .finish()
+            .await
+    }
+}
+
+impl Record {
+    /// Parse queue record from sector data. Returns `None` if the sector is erased (all 0xff) and
+    /// an error if it contains anything but a valid record (e.g. if writing was interrupted).
+    async fn parse(bytes: &[u8]) -> Result<Option<Self>, Error> {
+        if bytes.iter().all(|b| *b == 0xff) {
+            return Ok(None);
+        }
+        let record: Self = json::Reader::new(bytes).read().await.map_err(|err| {
+            warn!("Queue: Invalid queue record: {}", err);
+            Error::Corrupted
+        })?;
+        if record.checksum != Some(checksum(&record.queue).await) {
+            warn!("Queue: Queue record with invalid checksum");
+            return Err(Error::Corrupted);
+        }
+        Ok(Some(record))
+    }
+
+    /// Select the latest valid record of the given sectors' records. Returns the sector index
+    /// and the record, or `None` if all sectors are erased. Fails if no sector contains a valid
+    /// record, but at least one isn't erased.
+    fn latest(
+        records: impl IntoIterator<Item = Result<Option<Self>, Error>>,
+    ) -> Result<Option<(usize, Self)>, Error> {
+        let mut latest: Option<(usize, Self)> = None;
+        let mut corrupted = false;
+        for (sector, record) in records.into_iter().enumerate() {
+            match record {
+                Ok(Some(record)) if latest.as_ref().is_none_or(|(_, l)| record.seq > l.seq) => {
+                    latest = Some((sector, record));
+                }
+                Ok(_) => (),
+                Err(_) => corrupted = true,
+            }
+        }
+        match latest {
+            None if corrupted => Err(Error::Corrupted),
+            latest => Ok(latest),
+        }
+    }
+}
+
+/// Checksum of queue (FNV-1a of its JSON representation)
+async fn checksum(queue: &Queue) -> u32 {
+    let mut json = json::Writer::new(flash::Checksum::new());
+    // Writing to checksum can't fail
+    let _ = json.write(queue).await;
+    json.into_inner().value()
+}
+
+impl Queue {
+    /// Load purchase queue from `queue` flash data partition
+    ///
+    /// If there is no valid `queue` data partition, an empty queue is returned that only keeps
+    /// purchases in memory. If the partition is erased, an empty queue is returned as well. If
+    /// the partition contains data, but no valid queue, an error is returned, since pending
+    /// purchases would be lost otherwise.
+    pub async fn load() -> Result<Self, Error> {
+        // Look up queue data partition (custom partition type 0x54, subtype 0x51)
+        let mut partition = match flash::Partition::find(PARTITION_SUBTYPE) {
+            Ok(partition) => partition,
+            Err(err) => {
+                warn!("Queue: Unable to find queue partition: {}", err);
+                return Ok(Self::default());
+            }
+        };
+
+        // Read and parse both sectors (4 kb each) of queue data partition
+        let mut bytes = vec![0; SECTOR_SIZE];
+        let mut records = Vec::new();
+        for sector in 0..2 {
+            let record = match partition.read_at(sector * SECTOR_SIZE, &mut bytes) {
+                Ok(()) => Record::parse(&bytes).await,
+                Err(err) => {
+                    warn!("Queue: Unable to read queue partition: {}", err);
+                    Err(Error::Corrupted)
+                }
+            };
+            records.push(record);
+        }
+
+        let mut queue = match Record::latest(records) {
+            Ok(Some((sector, record))) => Self {
+                sector: Some(sector),
+                seq: record.seq,
+                ..record.queue
+            },
+            Ok(None) => {
+                debug!("Queue: No queue in queue partition");
+                Self::default()
+            }
+            Err(err) => {
+                error!("Queue: No valid queue in queue partition");
+                return Err(err);
+            }
+        };
+        queue.partition = Some(partition);
+
+        info!(
+            "Queue: Loaded {} pending purchases from queue partition",
+            queue.len()
+        );
+        Ok(queue)
+    }
+
+    /// Number of pending purchases
+    pub fn len(&self) -> usize {
+        self.purchases.len()
+    }
+
+    /// Returns true if there are no pending purchases
+    pub fn is_empty(&self) -> bool {
+        self.purchases.is_empty()
+    }
+
+    /// Oldest pending purchase
+    pub fn front(&self) -> Option<&Purchase> {
+        self.purchases.front()
+    }
+
+    /// Add a new purchase to the queue and store the queue to flash. The purchase is only kept if
+    /// it could be stored (or if there's no queue partition). Returns the stored purchase.
+    pub async fn push(
+        &mut self,
+        user_id: UserId,
+        article_id: ArticleId,
+        amount: f32,
+        total_price: f32,
+        salestax: Option<f32>,
+        callsign: Option<String>,
+    ) -> Result<Purchase, Error> {
+        if self.purchases.len() >= MAX_PURCHASES {
+            warn!("Queue: Queue full, rejecting purchase");
+            return Err(Error::Full);
+        }
+
+        let checkpoint = self.checkpoint();
+        let purchase = Purchase {
+            id: self.next_id,
+            user_id,
+            article_id,
+            amount,
+            total_price,
+            salestax,
+            callsign,
+            time: time::now(),
+        };
+        debug!("Queue: Adding purchase {:?}", purchase);
+        self.next_id = self.next_id.wrapping_add(1);
+        self.purchases.push_back(purchase.clone());
+        if self.max_record_size().await > SECTOR_SIZE {
+            warn!("Queue: Queue doesn't fit into flash sector, rejecting purchase");
+            self.rollback(checkpoint);
+            return Err(Error::Full);
+        }
+        if let Err(err) = self.save().await {
+            warn!("Queue: Discarding purchase: {}", err);
+            self.rollback(checkpoint);
+            return Err(err);
+        }
+        Ok(purchase)
+    }
+
+    /// Remove purchase with given id from the queue and store the queue to flash. Returns false
+    /// if there's no pending purchase with this id (e.g. because it was submitted already). If
+    /// storing fails, the purchase is submitted again after a restart (sales are tagged with the
+    /// purchase id to be able to spot this).
+    pub async fn remove(&mut self, id: u32) -> bool {
+        let len = self.purchases.len();
+        self.purchases.retain(|purchase| purchase.id != id);
+        if self.purchases.len() == len {
+            return false;
+        }
+        debug!("Queue: Removed purchase {}", id);
+        let _ = self.save().await;
+        true
+    }
+
+    /// Discard all pending purchases and store the queue to flash
+    pub async fn clear(&mut self) {
+        if !self.purchases.is_empty() {
+            warn!("Queue: Discarding {} pending purchases", self.len());
+            self.purchases.clear();
+            let _ = self.save().await;
+        }
+    }
+}
+
+/// State of the queue before adding a purchase, to undo adding it
+#[derive(Debug, Clone, Copy)]
+struct Checkpoint {
+    next_id: u32,
+    len: usize,
+}
+
+impl Queue {
+    /// Remember current state to be able to roll back to it
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            next_id: self.next_id,
+            len: self.purchases.len(),
+        }
+    }
+
+    /// Discard purchases added after the given checkpoint
+    fn rollback(&mut self, checkpoint: Checkpoint) {
+        self.purchases.truncate(checkpoint.len);
+        self.next_id = checkpoint.next_id;
+    }
+
+    /// JSON record of queue to store in a flash sector
+    async fn record(&self, seq: u32, checksum: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut json = json::Writer::new(&mut bytes);
+        // Serializing to a vector can't fail (except for out of memory, which panics anyway)
+        let _ = json
+            .write(&RecordRef {
+                seq,
+                checksum,
+                queue: self,
+            })
+            .await;
+        bytes
+    }
+
+    /// Max size of JSON record of queue (with the longest possible sequence number and checksum)
+    async fn max_record_size(&self) -> usize {
+        self.record(u32::MAX, u32::MAX).await.len()
+    }
+
+    /// Store queue to flash data partition (if any). The queue is written to the sector that
+    /// doesn't hold the latest stored queue and read back to verify it. Only then it becomes the
+    /// latest stored queue, so there's always a valid queue in flash.
+    async fn save(&mut self) -> Result<(), Error> {
+        if self.partition.is_none() {
+            return Ok(());
+        }
+
+        let seq = self.seq.wrapping_add(1);
+        let bytes = self.record(seq, checksum(self).await).await;
+        if bytes.len() > SECTOR_SIZE {
+            warn!("Queue: Queue too large to store ({} bytes)", bytes.len());
+            return Err(Error::Storage);
+        }
+
+        let Some(partition) = self.partition.as_mut() else {
+            return Ok(());
+        };
+        let sector = self.sector.map_or(0, |sector| 1 - sector);
+        if let Err(err) = partition.write_at(sector * SECTOR_SIZE, &bytes) {
+            warn!("Queue: Unable to write queue partition: {}", err);
+            return Err(Error::Storage);
+        }
+
+        // Read back and verify written queue
+        let mut bytes = vec![0; SECTOR_SIZE];
+        if let Err(err) = partition.read_at(sector * SECTOR_SIZE, &mut bytes) {
+            warn!("Queue: Unable to read queue partition: {}", err);
+            return Err(Error::Storage);
+        }
+        match Record::parse(&bytes).await {
+            Ok(Some(record)) if record.seq == seq => {
+                debug!("Queue: Stored queue to sector {} (seq {})", sector, seq);
+                self.sector = Some(sector);
+                self.seq = seq;
+                Ok(())
+            }
+            _ => {
+                warn!("Queue: Verifying stored queue failed");
+                Err(Error::Storage)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn purchase(id: u32, time: Option<i64>) -> Purchase {
+        Purchase {
+            id,
+            user_id: 12345,
+            article_id: "1234".to_string(),
+            amount: 2.0,
+            total_price: 3.5,
+            salestax: None,
+            callsign: None,
+            time: time.and_then(|t| DateTime::from_timestamp(t, 0)),
+        }
+    }
+
+    async fn to_json<T: ToJson>(value: &T) -> String {
+        let mut json = json::Writer::new(Vec::new());
+        json.write(value).await.unwrap();
+        String::from_utf8(json.into_inner()).unwrap()
+    }
+
+    async fn from_json(s: &str) -> Queue {
+        json::Reader::new(s.as_bytes()).read().await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn write_purchase() {
+        assert_eq!(
+            to_json(&purchase(1, Some(1_737_000_000))).await,
+            r#"{"id": 1, "user_id": 12345, "article_id": "1234", "amount": 2, "total_price": 3.5, "time": 1737000000}"#
+        );
+        assert_eq!(
+            to_json(&purchase(2, None)).await,
+            r#"{"id": 2, "user_id": 12345, "article_id": "1234", "amount": 2, "total_price": 3.5}"#
+        );
+    }
+
+    #[async_std::test]
+    async fn write_purchase_with_salestax_and_callsign() {
+        let purchase = Purchase {
+            salestax: Some(19.0),
+            callsign: Some("D-EABC".to_string()),
+            ..purchase(3, None)
+        };
+        assert_eq!(
+            to_json(&purchase).await,
+            r#"{"id": 3, "user_id": 12345, "article_id": "1234", "amount": 2, "total_price": 3.5, "salestax": 19, "callsign": "D-EABC"}"#
+        );
+    }
+
+    #[async_std::test]
+    async fn read_queue() {
+        let queue = from_json(
+            r#"{"next_id": 3, "purchases": [
+                {"id": 1, "user_id": 12345, "article_id": "1234", "amount": 2, "total_price": 3.5, "time": 1737000000},
+                {"id": 2, "user_id": 12345, "article_id": "1234", "amount": 2, "total_price": 3.5}
+            ]}"#,
+        )
+        .await;
+        assert_eq!(queue.next_id, 3);
+        assert_eq!(
+            queue.purchases,
+            [purchase(1, Some(1_737_000_000)), purchase(2, None)]
+        );
+    }
+
+    #[async_std::test]
+    async fn roundtrip() {
+        let queue = Queue {
+            next_id: 42,
+            purchases: VecDeque::from([
+                purchase(40, Some(1_737_000_000)),
+                purchase(41, None),
+                Purchase {
+                    salestax: Some(7.0),
+                    callsign: Some("D-EABC".to_string()),
+                    ..purchase(42, None)
+                },
+            ]),
+            ..Queue::default()
+        };
+        let json = to_json(&queue).await;
+        let read_queue = from_json(&json).await;
+        assert_eq!(read_queue.next_id, queue.next_id);
+        assert_eq!(read_queue.purchases, queue.purchases);
+    }
+
+    #[async_std::test]
+    async fn push() {
+        let mut queue = Queue::default();
+        queue
+            .push(12345, "1234".to_string(), 2.0, 3.5, None, None)
+            .await
+            .unwrap();
+        let purchase = queue
+            .push(12345, "1234".to_string(), 1.0, 1.75, None, None)
+            .await
+            .unwrap();
+        assert_eq!(purchase.id, 1);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.next_id, 2);
+        assert_eq!(queue.front().unwrap().id, 0);
+    }
+
+    #[async_std::test]
+    async fn remove() {
+        let mut queue = Queue {
+            next_id: 42,
+            purchases: VecDeque::from([purchase(40, None), purchase(41, None)]),
+            ..Queue::default()
+        };
+        assert!(queue.remove(40).await);
+        assert!(!queue.remove(40).await);
+        assert_eq!(queue.purchases, [purchase(41, None)]);
+    }
+
+    #[async_std::test]
+    async fn push_full() {
+        let mut queue = Queue::default();
+        for _ in 0..MAX_PURCHASES {
+            queue
+                .push(12345, "1234".to_string(), 1.0, 1.75, None, None)
+                .await
+                .unwrap();
+        }
+        let res = queue
+            .push(12345, "1234".to_string(), 1.0, 1.75, None, None)
+            .await;
+        assert!(matches!(res, Err(Error::Full)));
+        assert_eq!(queue.len(), MAX_PURCHASES);
+        assert_eq!(queue.next_id, 30);
+    }
+
+    #[async_std::test]
+    async fn push_full_sector() {
+        let mut queue = Queue::default();
+        let article_id = "1234567890".repeat(5);
+        let callsign = "D-EABC".repeat(10);
+        let res = loop {
+            let res = queue
+                .push(
+                    4_294_967,
+                    article_id.clone(),
+                    12.0,
+                    123.45,
+                    Some(19.0),
+                    Some(callsign.clone()),
+                )
+                .await;
+            if res.is_err() {
+                break res;
+            }
+        };
+        assert!(matches!(res, Err(Error::Full)));
+        assert!(queue.len() < MAX_PURCHASES);
+        assert_eq!(queue.next_id, u32::try_from(queue.len()).unwrap());
+        assert!(queue.max_record_size().await <= SECTOR_SIZE);
+    }
+
+    #[test]
+    fn rollback() {
+        let mut queue = Queue {
+            next_id: 42,
+            purchases: VecDeque::from([purchase(40, None), purchase(41, None)]),
+            ..Queue::default()
+        };
+        let checkpoint = queue.checkpoint();
+        queue.next_id = 44;
+        queue.purchases.push_back(purchase(42, None));
+        queue.purchases.push_back(purchase(43, None));
+        queue.rollback(checkpoint);
+        assert_eq!(queue.next_id, 42);
+        assert_eq!(queue.purchases, [purchase(40, None), purchase(41, None)]);
+    }
+
+    /// Sector data of a stored queue with given sequence number and pending purchases
+    async fn sector(seq: u32, ids: &[u32]) -> Vec<u8> {
+        let queue = Queue {
+            next_id: ids.last().map_or(0, |id| id + 1),
+            purchases: ids.iter().map(|id| purchase(*id, None)).collect(),
+            ..Queue::default()
+        };
+        let mut json = json::Writer::new(Vec::new());
+        json.write(&RecordRef {
+            seq,
+            checksum: checksum(&queue).await,
+            queue: &queue,
+        })
+        .await
+        .unwrap();
+        let mut bytes = json.into_inner();
+        bytes.resize(SECTOR_SIZE, 0xff);
+        bytes
+    }
+
+    #[async_std::test]
+    async fn parse_record() {
+        let bytes = sector(7, &[40, 41]).await;
+        let record = Record::parse(&bytes).await.unwrap().unwrap();
+        assert_eq!(record.seq, 7);
+        assert_eq!(record.queue.next_id, 42);
+        assert_eq!(
+            record.queue.purchases,
+            [purchase(40, None), purchase(41, None)]
+        );
+    }
+
+    #[async_std::test]
+    async fn parse_erased() {
+        let bytes = [0xff; SECTOR_SIZE];
+        assert!(matches!(Record::parse(&bytes).await, Ok(None)));
+    }
+
+    #[async_std::test]
+    async fn parse_interrupted_write() {
+        // Writing stopped halfway, rest of sector still erased
+        let mut bytes = sector(7, &[40, 41]).await;
+        bytes[60..].fill(0xff);
+        assert!(matches!(Record::parse(&bytes).await, Err(Error::Corrupted)));
+    }
+
+    #[async_std::test]
+    async fn parse_checksum_mismatch() {
+        let bytes = sector(7, &[40, 41]).await;
+        let json = String::from_utf8_lossy(&bytes).replace("\"amount\": 2", "\"amount\": 3");
+        assert!(matches!(
+            Record::parse(json.as_bytes()).await,
+            Err(Error::Corrupted)
+        ));
+    }
+
+    #[async_std::test]
+    async fn latest_record() {
+        let sector0 = Record::parse(&sector(7, &[40]).await).await;
+        let sector1 = Record::parse(&sector(8, &[40, 41]).await).await;
+        let (sector, record) = Record::latest([sector0, sector1]).unwrap().unwrap();
+        assert_eq!(sector, 1);
+        assert_eq!(record.seq, 8);
+        assert_eq!(record.queue.len(), 2);
+    }
+
+    #[async_std::test]
+    async fn latest_record_after_interrupted_write() {
+        // Adding a purchase was interrupted: the previous queue is still valid
+        let sector0 = Record::parse(&sector(7, &[40]).await).await;
+        let sector1 = Err(Error::Corrupted);
+        let (sector, record) = Record::latest([sector0, sector1]).unwrap().unwrap();
+        assert_eq!(sector, 0);
+        assert_eq!(record.queue.purchases, [purchase(40, None)]);
+    }
+
+    #[test]
+    fn latest_record_erased() {
+        assert!(matches!(Record::latest([Ok(None), Ok(None)]), Ok(None)));
+    }
+
+    #[test]
+    fn latest_record_corrupted() {
+        assert!(matches!(
+            Record::latest([Err(Error::Corrupted), Ok(None)]),
+            Err(Error::Corrupted)
+        ));
+    }
+}
@@ -1,3 +1,6 @@
+use crate::time;
+use alloc::vec::Vec;
+use chrono::{DateTime, FixedOffset, NaiveTime, TimeDelta, Utc};
 use core::fmt;
 use embassy_time::Timer;
 use embassy_time::{Duration, Instant};
@@ -9,6 +12,31 @@ const DAILY_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
 #[cfg(debug_assertions)]
 const DAILY_INTERVAL: Duration = Duration::from_secs(30 * 60);
 
+/// Simple time interval of 1h
+#[cfg(not(debug_assertions))]
+const HOURLY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+#[cfg(debug_assertions)]
+const HOURLY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Minimum time until the next event at a time of day. Prevents running twice if the timer fires
+/// slightly early.
+const MIN_TIME_OF_DAY_GAP: TimeDelta = TimeDelta::minutes(1);
+
+/// Parse time of day given as "HH:MM"
+pub fn parse_time_of_day(s: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(s, "%H:%M").ok()
+}
+
+/// Next occurrence of the given local time of day (with given UTC offset) after the given time
+fn next_occurrence(now: DateTime<Utc>, time: NaiveTime, offset: FixedOffset) -> DateTime<Utc> {
+    let now_local = now.with_timezone(&offset);
+    let mut next = now_local.date_naive().and_time(time) - offset;
+    while next.and_utc() < now + MIN_TIME_OF_DAY_GAP {
+        next += TimeDelta::days(1);
+    }
+    next.and_utc()
+}
+
 /// Duration display helper
 struct DisplayDuration(Duration);
 
@@ -21,20 +49,83 @@ impl fmt::Display for DisplayDuration {
     }
 }
 
-/// Scheduler for daily events
+/// Scheduled job
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Job {
+    /// Refresh article and user information
+    RefreshData,
+    /// Submit telemetry data, even if the buffer isn't filled yet
+    FlushTelemetry,
+}
+
+/// Recurring event
+/// Events are scheduled at a fixed interval from system start, or at a given time of day once the
+/// current time is known.
 #[derive(Debug)]
-pub struct Daily {
+pub struct Recurring {
     next: Instant,
+    interval: Duration,
+    time_of_day: Option<(NaiveTime, FixedOffset)>,
+    at_time_of_day: bool,
 }
 
-impl Daily {
-    /// Create new daily scheduler
-    pub fn new() -> Self {
-        let mut daily = Self {
-            next: Instant::now(),
-        };
-        daily.schedule_next();
-        daily
+impl Recurring {
+    /// Create new recurring event with given interval
+    pub fn every(interval: Duration) -> Self {
+        let mut recurring = Self::at(Instant::now(), interval);
+        recurring.schedule_next();
+        recurring
+    }
+
+    /// Create new daily event
+    pub fn daily() -> Self {
+        Self::every(DAILY_INTERVAL)
+    }
+
+    /// Create new hourly event
+    pub fn hourly() -> Self {
+        Self::every(HOURLY_INTERVAL)
+    }
+
+    /// Create new recurring event with given interval, first scheduled at the given time
+    fn at(next: Instant, interval: Duration) -> Self {
+        Self {
+            next,
+            interval,
+            time_of_day: None,
+            at_time_of_day: false,
+        }
+    }
+
+    /// Schedule events at given local time of day (with given UTC offset) instead of a fixed
+    /// interval. Takes effect as soon as the current time is known.
+    pub fn set_time_of_day(&mut self, time: NaiveTime, offset: FixedOffset) {
+        info!("Schedule: events at {} ({})", time, offset);
+        self.time_of_day = Some((time, offset));
+        self.at_time_of_day = false;
+        self.adjust();
+    }
+
+    /// Reschedule next event at time of day if it was scheduled by interval, but current time
+    /// became known in the meantime
+    pub fn adjust(&mut self) {
+        if !self.at_time_of_day {
+            if let Some(next) = self.next_at_time_of_day() {
+                self.next = next;
+                self.at_time_of_day = true;
+                info!(
+                    "Schedule: next event rescheduled in {} from now",
+                    DisplayDuration(self.time_left())
+                );
+            }
+        }
+    }
+
+    /// Expire schedule time immediately. The next event is scheduled as usual after running.
+    pub fn expire_now(&mut self) {
+        self.next = Instant::now();
+        // Prevent rescheduling at time of day before running
+        self.at_time_of_day = true;
     }
 
     /// Returns true when schedule time is expired
@@ -47,24 +138,240 @@ impl Daily {
         self.next.saturating_duration_since(Instant::now())
     }
 
-    /// Timer that can be awaited on to wait for schedule time
-    pub fn timer(&self) -> Timer {
-        Timer::at(self.next)
-    }
-
     /// After expiring, schedule next event
     pub fn schedule_next(&mut self) {
+        if let Some(next) = self.next_at_time_of_day() {
+            // Wall-clock schedule: run at next occurrence of time of day
+            self.next = next;
+            self.at_time_of_day = true;
+            info!(
+                "Schedule: next event scheduled in {} from now",
+                DisplayDuration(self.time_left())
+            );
+            return;
+        }
+        self.at_time_of_day = false;
         if self.is_expired() {
-            // Simple schedule: run again 24h later
-            self.next += DAILY_INTERVAL;
+            // Simple schedule: run again one interval later
+            self.next += self.interval;
         }
         if self.is_expired() {
-            // Simple schedule: run in 24h from now
-            self.next = Instant::now() + DAILY_INTERVAL;
+            // Simple schedule: run in one interval from now
+            self.next = Instant::now() + self.interval;
         }
         info!(
-            "Schedule: next daily event scheduled in {} from now",
+            "Schedule: next event scheduled in {} from now",
             DisplayDuration(self.time_left())
         );
     }
+
+    /// Time of next event at time of day, if time of day is set and current time is known
+    fn next_at_time_of_day(&self) -> Option<Instant> {
+        let (time, offset) = self.time_of_day?;
+        let now = time::now()?;
+        let delay = next_occurrence(now, time, offset) - now;
+        let delay = Duration::from_millis(u64::try_from(delay.num_milliseconds()).ok()?);
+        Some(Instant::now() + delay)
+    }
+}
+
+/// Scheduler for multiple recurring jobs
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    jobs: Vec<(Job, Recurring)>,
+}
+
+impl Scheduler {
+    /// Create new scheduler without any jobs
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add job with given recurrence
+    pub fn add(&mut self, job: Job, recurring: Recurring) {
+        self.jobs.push((job, recurring));
+    }
+
+    /// Reschedule jobs at time of day if current time became known in the meantime
+    pub fn adjust(&mut self) {
+        for (_job, recurring) in &mut self.jobs {
+            recurring.adjust();
+        }
+    }
+
+    /// Run given job as soon as possible
+    pub fn run_now(&mut self, job: Job) {
+        if let Some((_job, recurring)) = self.jobs.iter_mut().find(|(j, _)| *j == job) {
+            recurring.expire_now();
+        }
+    }
+
+    /// Job that is due next and its schedule time
+    fn next_due(&self) -> Option<(Job, Instant)> {
+        self.jobs
+            .iter()
+            .map(|(job, recurring)| (*job, recurring.next))
+            .min_by_key(|(_job, next)| *next)
+    }
+
+    /// Timer that can be awaited on to wait for the schedule time of the next due job
+    pub fn timer(&self) -> Timer {
+        match self.next_due() {
+            Some((_job, next)) => Timer::at(next),
+            None => Timer::at(Instant::MAX),
+        }
+    }
+
+    /// Returns the earliest expired job (if any) and schedules its next run
+    pub fn expired(&mut self, now: Instant) -> Option<Job> {
+        let (job, next) = self.next_due()?;
+        if next > now {
+            return None;
+        }
+        let (_job, recurring) = self.jobs.iter_mut().find(|(j, _)| *j == job)?;
+        info!("Schedule: {:?} job expired", job);
+        recurring.schedule_next();
+        Some(job)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn time(s: &str) -> NaiveTime {
+        parse_time_of_day(s).unwrap()
+    }
+
+    fn scheduler_with(jobs: &[(Job, u64)]) -> Scheduler {
+        let mut scheduler = Scheduler::new();
+        for (job, secs) in jobs {
+            let recurring = Recurring::at(Instant::from_secs(*secs), Duration::from_secs(3600));
+            scheduler.add(*job, recurring);
+        }
+        scheduler
+    }
+
+    #[test]
+    fn next_due_none() {
+        assert_eq!(Scheduler::new().next_due(), None);
+    }
+
+    #[test]
+    fn next_due_earliest() {
+        let scheduler = scheduler_with(&[(Job::RefreshData, 100), (Job::FlushTelemetry, 50)]);
+        assert_eq!(
+            scheduler.next_due(),
+            Some((Job::FlushTelemetry, Instant::from_secs(50)))
+        );
+        let scheduler = scheduler_with(&[(Job::RefreshData, 10), (Job::FlushTelemetry, 50)]);
+        assert_eq!(
+            scheduler.next_due(),
+            Some((Job::RefreshData, Instant::from_secs(10)))
+        );
+    }
+
+    #[test]
+    fn next_due_same_time() {
+        // First added job wins if several jobs are due at the same time
+        let scheduler = scheduler_with(&[(Job::RefreshData, 50), (Job::FlushTelemetry, 50)]);
+        assert_eq!(
+            scheduler.next_due(),
+            Some((Job::RefreshData, Instant::from_secs(50)))
+        );
+    }
+
+    #[test]
+    fn expired_earliest() {
+        let mut scheduler = scheduler_with(&[(Job::RefreshData, 100), (Job::FlushTelemetry, 50)]);
+        assert_eq!(scheduler.expired(Instant::from_secs(40)), None);
+        assert_eq!(
+            scheduler.expired(Instant::from_secs(60)),
+            Some(Job::FlushTelemetry)
+        );
+    }
+
+    #[test]
+    fn run_now() {
+        let mut scheduler = scheduler_with(&[(Job::RefreshData, 100), (Job::FlushTelemetry, 50)]);
+        scheduler.run_now(Job::RefreshData);
+        assert_eq!(scheduler.expired(Instant::now()), Some(Job::RefreshData));
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_time_of_day("04:00"), NaiveTime::from_hms_opt(4, 0, 0));
+        assert_eq!(
+            parse_time_of_day("23:59"),
+            NaiveTime::from_hms_opt(23, 59, 0)
+        );
+        assert_eq!(parse_time_of_day("24:00"), None);
+        assert_eq!(parse_time_of_day("4 Uhr"), None);
+    }
+
+    #[test]
+    fn next_occurrence_later_today() {
+        let utc0 = FixedOffset::east_opt(0).unwrap();
+        assert_eq!(
+            next_occurrence(utc("2025-01-15T01:30:00Z"), time("04:00"), utc0),
+            utc("2025-01-15T04:00:00Z")
+        );
+    }
+
+    #[test]
+    fn next_occurrence_tomorrow() {
+        let utc0 = FixedOffset::east_opt(0).unwrap();
+        assert_eq!(
+            next_occurrence(utc("2025-01-15T04:00:00Z"), time("04:00"), utc0),
+            utc("2025-01-16T04:00:00Z")
+        );
+        assert_eq!(
+            next_occurrence(utc("2025-01-15T23:30:00Z"), time("04:00"), utc0),
+            utc("2025-01-16T04:00:00Z")
+        );
+    }
+
+    #[test]
+    fn next_occurrence_across_midnight() {
+        let utc0 = FixedOffset::east_opt(0).unwrap();
+        assert_eq!(
+            next_occurrence(utc("2025-12-31T23:59:00Z"), time("00:30"), utc0),
+            utc("2026-01-01T00:30:00Z")
+        );
+    }
+
+    #[test]
+    fn next_occurrence_with_offset() {
+        // 04:00 in UTC+1 is 03:00 UTC
+        let cet = FixedOffset::east_opt(3600).unwrap();
+        assert_eq!(
+            next_occurrence(utc("2025-01-15T02:00:00Z"), time("04:00"), cet),
+            utc("2025-01-15T03:00:00Z")
+        );
+        // Local date is already the next day (00:30 in UTC+1)
+        assert_eq!(
+            next_occurrence(utc("2025-01-15T23:30:00Z"), time("04:00"), cet),
+            utc("2025-01-16T03:00:00Z")
+        );
+        // Local date is still the previous day (20:00 in UTC-5)
+        let est = FixedOffset::west_opt(5 * 3600).unwrap();
+        assert_eq!(
+            next_occurrence(utc("2025-01-16T01:00:00Z"), time("04:00"), est),
+            utc("2025-01-16T09:00:00Z")
+        );
+    }
+
+    #[test]
+    fn next_occurrence_not_immediately() {
+        let utc0 = FixedOffset::east_opt(0).unwrap();
+        // Timer fired a few seconds early, don't run again right away
+        assert_eq!(
+            next_occurrence(utc("2025-01-15T03:59:58Z"), time("04:00"), utc0),
+            utc("2025-01-16T04:00:00Z")
+        );
+    }
 }
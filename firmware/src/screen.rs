@@ -1,10 +1,18 @@
 use crate::article::{Article, Articles};
+use crate::language::Language;
 use crate::{GIT_SHA_STR, VERSION_STR};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use chrono::format::{DelayedFormat, StrftimeItems};
+use chrono::{DateTime, NaiveTime, TimeZone, Timelike};
 use core::fmt;
+use core::ops::Range;
 use embedded_graphics::draw_target::DrawTarget;
 use embedded_graphics::image::{Image, ImageRaw};
 use embedded_graphics::pixelcolor::BinaryColor;
 use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{PrimitiveStyle, Rectangle};
 use rand_core::RngCore;
 use u8g2_fonts::types::{FontColor, HorizontalAlignment, VerticalPosition};
 use u8g2_fonts::{fonts, Content, FontRenderer};
@@ -35,9 +43,14 @@ static LOGO: ImageRaw<BinaryColor> = ImageRaw::new(&[
     0b00011110, 0b00000111, 0b11100000, 0b11111100, 0b00011111, 0b10001111, 0b00011110, 0b00000001, 0b11100111, 0b11000011, 0b11111100, 0b01111001, 0b11101111, 0b01111001, 0b11110111, 0b10001111,
 ], 128);
 
-/// User greetings (chosen randomly)
-static GREETINGS: [&str; 9] = [
-    "Hi", "Hallo", "Hey", "Tach", "Servus", "Moin", "Hej", "Olá", "Ciao",
+/// Spinner animation frames (rotating bar), shown while waiting
+#[rustfmt::skip]
+#[allow(clippy::unreadable_literal)]
+static SPINNER: [ImageRaw<BinaryColor>; 4] = [
+    ImageRaw::new(&[0b00010000, 0b00010000, 0b00010000, 0b00010000, 0b00010000, 0b00010000, 0b00010000], 7),
+    ImageRaw::new(&[0b00000010, 0b00000100, 0b00001000, 0b00010000, 0b00100000, 0b01000000, 0b10000000], 7),
+    ImageRaw::new(&[0b00000000, 0b00000000, 0b00000000, 0b11111110, 0b00000000, 0b00000000, 0b00000000], 7),
+    ImageRaw::new(&[0b10000000, 0b01000000, 0b00100000, 0b00010000, 0b00001000, 0b00000100, 0b00000010], 7),
 ];
 
 const SPLASH_VERSION_FONT: FontRenderer = FontRenderer::new::<fonts::u8g2_font_profont10_tr>();
@@ -53,20 +66,77 @@ const WIDTH: i32 = 128;
 const HCENTER: i32 = WIDTH / 2;
 
 /// Screen height
+#[cfg(not(feature = "display-128x32"))]
 const HEIGHT: i32 = 64;
+#[cfg(feature = "display-128x32")]
+const HEIGHT: i32 = 32;
+
+/// Compact layout for small displays (128x32) that only fit two lines of text above the footer
+const COMPACT: bool = HEIGHT < 64;
+
+/// Height of common footer
+const FOOTER_HEIGHT: i32 = 7;
+
+/// Baseline of first line of text. Together with a second line, text is vertically centered
+/// above the footer.
+const LINE1: i32 = (HEIGHT - FOOTER_HEIGHT) / 2 - 2;
+
+/// Baseline of second line of text (in medium or small font)
+const LINE2: i32 = LINE1 + 12;
+
+/// Baseline of first article line and vertical shift per article to center article lines
+const ARTICLES_BASE: i32 = if COMPACT { 22 } else { 40 };
+const ARTICLES_SHIFT: i32 = if COMPACT { 6 } else { 5 };
+
+/// Vertical position of logo and baseline of version on splash screen
+const SPLASH_LOGO_Y: i32 = if COMPACT { 2 } else { 13 };
+const SPLASH_VERSION_Y: i32 = if COMPACT { HEIGHT - 1 } else { 13 + 29 };
 
 /// Number of characters that fit in a line
 const MEDIUM_CHARS_PER_LINE: usize = WIDTH as usize / 6;
 
+/// Max number of lines of a failure message below the title
+const FAILURE_LINES: usize = if COMPACT { 1 } else { 3 };
+
+/// Line spacing of small font
+const SMALL_LINE_HEIGHT: i32 = 8;
+
+/// Progress bar size (including 1px outline and 1px gap around the filled part)
+const PROGRESS_BAR_WIDTH: u32 = 100;
+const PROGRESS_BAR_HEIGHT: u32 = 7;
+
+/// Size of battery icon in footer (without tip)
+const BATTERY_ICON_SIZE: Size = Size::new(11, 6);
+
+/// Max number of characters of the machine name shown in footers (fits between battery
+/// indicator and clock)
+const MACHINE_NAME_MAX_CHARS: usize = 14;
+
+/// Number of articles that fit on a page
+pub const ARTICLES_PER_PAGE: usize = if COMPACT { 2 } else { 4 };
+
+/// Number of animation ticks to pause scrolling text at start and end
+const SCROLL_PAUSE: usize = 5;
+
+/// Size of spinner (square, shown at the right of the footer)
+const SPINNER_SIZE: i32 = 7;
+
 /// Screen display error
 pub type Error<E> = u8g2_fonts::Error<E>;
 
 /// Generic screen that can be displayed
 pub trait Screen {
+    /// Draw screen with texts in given language
     fn draw<D: DrawTarget<Color = BinaryColor>>(
         &self,
+        lang: Language,
         target: &mut D,
     ) -> Result<(), Error<D::Error>>;
+
+    /// Advance animation by one tick. Returns true if the screen needs to be redrawn.
+    fn tick(&mut self, _lang: Language) -> bool {
+        false
+    }
 }
 
 /// Draw left aligned text on given line
@@ -124,7 +194,7 @@ fn right<D: DrawTarget<Color = BinaryColor>>(
     Ok(())
 }
 
-/// Draw common footer (bottom 7 lines, 57..64)
+/// Draw common footer (bottom 7 lines)
 fn footer<D: DrawTarget<Color = BinaryColor>>(
     content_left: impl Content,
     content_right: impl Content,
@@ -135,13 +205,190 @@ fn footer<D: DrawTarget<Color = BinaryColor>>(
     Ok(())
 }
 
-/// Trim text if it's too long
+/// Machine name as shown in footers, shortened to fit next to other footer content
+fn machine_label(name: &str) -> &str {
+    let name = name.trim();
+    match name.char_indices().nth(MACHINE_NAME_MAX_CHARS) {
+        Some((idx, _ch)) => name[..idx].trim_end(),
+        None => name,
+    }
+}
+
+/// Spinner animation frame to show at given animation tick
+fn spinner_frame(tick: u32) -> usize {
+    tick as usize % SPINNER.len()
+}
+
+/// Draw spinner animation frame of given animation tick at the right of the footer
+fn spinner<D: DrawTarget<Color = BinaryColor>>(
+    tick: u32,
+    target: &mut D,
+) -> Result<(), Error<D::Error>> {
+    let position = Point::new(WIDTH - SPINNER_SIZE, HEIGHT - SPINNER_SIZE);
+    Image::new(&SPINNER[spinner_frame(tick)], position)
+        .draw(target)
+        .map_err(Error::DisplayError)?;
+    Ok(())
+}
+
+/// Width in pixels of the filled part of a progress bar with the given inner width
+fn progress_fill_width(width: u32, percent: u8) -> u32 {
+    width * u32::from(percent.min(100)) / 100
+}
+
+/// Draw centered progress bar with given vertical center, filled according to the given percentage
+fn progress_bar<D: DrawTarget<Color = BinaryColor>>(
+    y: i32,
+    percent: u8,
+    target: &mut D,
+) -> Result<(), Error<D::Error>> {
+    let outline = Rectangle::with_center(
+        Point::new(HCENTER, y),
+        Size::new(PROGRESS_BAR_WIDTH, PROGRESS_BAR_HEIGHT),
+    );
+    outline
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+        .draw(target)
+        .map_err(Error::DisplayError)?;
+    let inner_size = outline.size - Size::new(4, 4);
+    let fill = Rectangle::new(
+        outline.top_left + Point::new(2, 2),
+        Size::new(
+            progress_fill_width(inner_size.width, percent),
+            inner_size.height,
+        ),
+    );
+    fill.into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(target)
+        .map_err(Error::DisplayError)?;
+    Ok(())
+}
+
+/// Draw small battery icon in the left corner of the footer, filled according to the given
+/// charge (percentage, 0-100)
+fn battery_indicator<D: DrawTarget<Color = BinaryColor>>(
+    charge: u8,
+    target: &mut D,
+) -> Result<(), Error<D::Error>> {
+    let outline = Rectangle::new(Point::new(0, HEIGHT - 6), BATTERY_ICON_SIZE);
+    outline
+        .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+        .draw(target)
+        .map_err(Error::DisplayError)?;
+    let tip = Rectangle::new(
+        outline.top_left + Point::new(outline.size.width.try_into().unwrap_or(0), 2),
+        Size::new(1, 2),
+    );
+    let inner_size = outline.size - Size::new(4, 4);
+    let fill = Rectangle::new(
+        outline.top_left + Point::new(2, 2),
+        Size::new(
+            progress_fill_width(inner_size.width, charge),
+            inner_size.height,
+        ),
+    );
+    for rect in [tip, fill] {
+        rect.into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+            .draw(target)
+            .map_err(Error::DisplayError)?;
+    }
+    Ok(())
+}
+
+/// Trim text if it's too long (fallback for static screens that don't scroll). Length is
+/// counted in characters, which matches display width with the fixed-width fonts used.
 fn trim(text: &str, max_len: usize) -> &str {
-    if text.len() > max_len {
-        &text[..max_len]
-    } else {
-        text
+    match text.char_indices().nth(max_len) {
+        Some((index, _)) => &text[..index],
+        None => text,
+    }
+}
+
+/// Wrap text at word boundaries into lines of given max length (in characters). Words that are
+/// too long for a line are split. If there are more than the given max number of lines, the
+/// last line is truncated with an ellipsis.
+fn wrap(text: &str, max_len: usize, max_lines: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    let mut line_len = 0;
+    for mut word in text.split_whitespace() {
+        loop {
+            let word_len = word.chars().count();
+            let sep_len = usize::from(line_len > 0);
+            if line_len + sep_len + word_len <= max_len {
+                if sep_len > 0 {
+                    line.push(' ');
+                }
+                line.push_str(word);
+                line_len += sep_len + word_len;
+                break;
+            }
+            if line_len > 0 {
+                // Word doesn't fit, continue on next line
+                lines.push(core::mem::take(&mut line));
+                line_len = 0;
+            } else {
+                // Word doesn't fit on a line at all, split it
+                let head = trim(word, max_len);
+                lines.push(head.into());
+                word = &word[head.len()..];
+            }
+        }
+    }
+    if line_len > 0 {
+        lines.push(line);
+    }
+    if lines.len() > max_lines {
+        lines.truncate(max_lines);
+        if let Some(last) = lines.last_mut() {
+            *last = format!("{}...", trim(last, max_len.saturating_sub(3)));
+        }
+    }
+    lines
+}
+
+/// Returns true if text is too long and needs to be scrolled
+fn needs_scroll(text: &str, max_len: usize) -> bool {
+    text.chars().count() > max_len
+}
+
+/// Scroll offset (in characters) of text with given length at given animation tick. Text that
+/// is too long pauses at the start, scrolls to the end one character per tick, pauses at the
+/// end and starts over.
+fn scroll_offset(len: usize, max_len: usize, tick: u32) -> usize {
+    if len <= max_len {
+        return 0;
     }
+    let overflow = len - max_len;
+    let period = overflow + 2 * SCROLL_PAUSE;
+    let pos = tick as usize % period;
+    pos.saturating_sub(SCROLL_PAUSE).min(overflow)
+}
+
+/// Scroll text if it's too long, showing the part visible at given animation tick
+fn scroll(text: &str, max_len: usize, tick: u32) -> &str {
+    let offset = scroll_offset(text.chars().count(), max_len, tick);
+    let index = |n| text.char_indices().nth(n).map_or(text.len(), |(i, _)| i);
+    &text[index(offset)..index(offset + max_len)]
+}
+
+/// Number of pages needed to show given number of articles (at least one page)
+pub fn article_pages(num_articles: usize) -> usize {
+    num_articles.div_ceil(ARTICLES_PER_PAGE).max(1)
+}
+
+/// Range of article indexes shown on given page
+fn article_page_range(page: usize, num_articles: usize) -> Range<usize> {
+    let start = (page * ARTICLES_PER_PAGE).min(num_articles);
+    let end = (start + ARTICLES_PER_PAGE).min(num_articles);
+    start..end
+}
+
+/// Index of article selected by given digit (1-based) on given page
+pub fn article_index(page: usize, digit: u8, num_articles: usize) -> Option<usize> {
+    let range = article_page_range(page, num_articles);
+    let index = range.start + usize::from(digit).checked_sub(1)?;
+    range.contains(&index).then_some(index)
 }
 
 /// Trim prefixes from text
@@ -160,67 +407,182 @@ fn trim_prefixes<'a>(text: &'a str, prefixes: &[&str]) -> &'a str {
     }
 }
 
+/// User greeting chosen by given random number, either from the given custom greetings or (if
+/// there are none) from the greetings of the given language
+fn greeting_text(lang: Language, custom: Option<&[String]>, random: u32) -> &str {
+    match custom {
+        Some(greetings) if !greetings.is_empty() => &greetings[random as usize % greetings.len()],
+        _ => {
+            let greetings = lang.strings().greetings;
+            greetings[random as usize % greetings.len()]
+        }
+    }
+}
+
+/// Max length of name in user greeting
+fn greeting_name_len(greeting: &str) -> usize {
+    MEDIUM_CHARS_PER_LINE.saturating_sub(greeting.len() + 1)
+}
+
 /// Draw user greeting (top 10 lines, 0..10)
 fn greeting<D: DrawTarget<Color = BinaryColor>>(
+    lang: Language,
+    custom: Option<&[String]>,
     random: u32,
     name: &str,
+    tick: u32,
     target: &mut D,
 ) -> Result<(), Error<D::Error>> {
-    let greeting = GREETINGS[random as usize % GREETINGS.len()];
-    // Trim name if it's too long to display
-    let name = trim(name, MEDIUM_CHARS_PER_LINE - greeting.len() - 1);
+    let greeting = greeting_text(lang, custom, random);
+    // Scroll name if it's too long to display
+    let name = scroll(name, greeting_name_len(greeting), tick);
     centered(&MEDIUM_FONT, 8, format_args!("{greeting} {name}"), target)
 }
 
-/// Splash screen
-pub struct Splash;
+/// Splash screen, optionally showing the machine name
+pub struct Splash<'a> {
+    machine_name: &'a str,
+}
+
+impl<'a> Splash<'a> {
+    /// Create splash screen showing the given machine name (hidden if empty)
+    pub fn new(machine_name: &'a str) -> Self {
+        Self { machine_name }
+    }
+}
 
-impl Screen for Splash {
+impl Screen for Splash<'_> {
     fn draw<D: DrawTarget<Color = BinaryColor>>(
         &self,
+        _lang: Language,
         target: &mut D,
     ) -> Result<(), Error<D::Error>> {
-        Image::new(&LOGO, Point::new(0, 13))
+        Image::new(&LOGO, Point::new(0, SPLASH_LOGO_Y))
             .draw(target)
             .map_err(Error::DisplayError)?;
         centered(
             &SPLASH_VERSION_FONT,
-            13 + 29,
+            SPLASH_VERSION_Y,
             format_args!("v{VERSION_STR}"),
             target,
         )?;
-        #[cfg(not(debug_assertions))]
-        footer("", GIT_SHA_STR, target)?;
+        let label = machine_label(self.machine_name);
+        // Debug builds are marked unless the machine name takes the space
         #[cfg(debug_assertions)]
-        footer("(DEBUG)", GIT_SHA_STR, target)?;
+        let label = if label.is_empty() { "(DEBUG)" } else { label };
+        footer(label, GIT_SHA_STR, target)?;
         Ok(())
     }
 }
 
-/// Failure screen
+/// Failure screen, optionally showing an error code
 pub struct Failure<M> {
     message: M,
+    code: Option<u8>,
 }
 
 impl<M: fmt::Display> Failure<M> {
-    pub fn new(message: M) -> Self {
-        Self { message }
+    pub fn new(message: M, code: Option<u8>) -> Self {
+        Self { message, code }
     }
 }
 
 impl<M: fmt::Display> Screen for Failure<M> {
     fn draw<D: DrawTarget<Color = BinaryColor>>(
         &self,
+        lang: Language,
         target: &mut D,
     ) -> Result<(), Error<D::Error>> {
-        centered(&TITLE_FONT, 26, "FEHLER!", target)?;
+        let strings = lang.strings();
+        centered(&TITLE_FONT, LINE1, strings.error, target)?;
+        let message = self.message.to_string();
+        let lines = wrap(&message, MEDIUM_CHARS_PER_LINE, FAILURE_LINES);
+        for (y, line) in (LINE2..).step_by(SMALL_LINE_HEIGHT as usize).zip(lines) {
+            centered(&SMALL_FONT, y, line.as_str(), target)?;
+        }
+        match self.code {
+            Some(code) => footer(strings.cancel, format_args!("Err {code:#04x}"), target)?,
+            None => footer(strings.cancel, "", target)?,
+        }
+        Ok(())
+    }
+}
+
+/// Panic screen, shown after a fatal error before restarting
+pub struct Panic {
+    restart_delay_secs: u64,
+}
+
+impl Panic {
+    pub fn new(restart_delay_secs: u64) -> Self {
+        Self { restart_delay_secs }
+    }
+}
+
+impl Screen for Panic {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(
+        &self,
+        lang: Language,
+        target: &mut D,
+    ) -> Result<(), Error<D::Error>> {
+        let strings = lang.strings();
+        centered(&TITLE_FONT, LINE1, strings.panic, target)?;
         centered(
             &SMALL_FONT,
-            26 + 12,
-            format_args!("{}", self.message),
+            LINE2,
+            format_args!("{} {}s", strings.restarting, self.restart_delay_secs),
             target,
         )?;
-        footer("* Abbruch", "", target)?;
+        Ok(())
+    }
+}
+
+/// Brown-out screen, shown if the supply voltage drops before restarting
+pub struct BrownOut;
+
+impl Screen for BrownOut {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(
+        &self,
+        lang: Language,
+        target: &mut D,
+    ) -> Result<(), Error<D::Error>> {
+        let strings = lang.strings();
+        centered(&TITLE_FONT, LINE1, strings.brown_out, target)?;
+        centered(&SMALL_FONT, LINE2, strings.low_voltage, target)?;
+        Ok(())
+    }
+}
+
+/// Configuration missing screen, shown if the device can't work without configuration. Lists
+/// keys of missing settings if there's enough space.
+pub struct NotConfigured<'a> {
+    missing_keys: &'a [&'a str],
+}
+
+impl<'a> NotConfigured<'a> {
+    pub fn new(missing_keys: &'a [&'a str]) -> Self {
+        Self { missing_keys }
+    }
+}
+
+impl Screen for NotConfigured<'_> {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(
+        &self,
+        lang: Language,
+        target: &mut D,
+    ) -> Result<(), Error<D::Error>> {
+        let strings = lang.strings();
+        centered(&TITLE_FONT, LINE1, strings.not_configured, target)?;
+        centered(&SMALL_FONT, LINE2, strings.flash_config, target)?;
+        let keys = self.missing_keys.join(", ");
+        let lines = wrap(&keys, MEDIUM_CHARS_PER_LINE, FAILURE_LINES - 1);
+        let first_line = LINE2 + SMALL_LINE_HEIGHT;
+        for (y, line) in (first_line..)
+            .step_by(SMALL_LINE_HEIGHT as usize)
+            .zip(lines)
+        {
+            centered(&SMALL_FONT, y, line.as_str(), target)?;
+        }
         Ok(())
     }
 }
@@ -229,6 +591,9 @@ impl<M: fmt::Display> Screen for Failure<M> {
 pub enum PleaseWait {
     WifiConnecting,
     UpdatingData,
+    /// Updating data with known progress (percentage, 0-100)
+    UpdatingDataProgress(u8),
+    FetchingBalance,
     Purchasing,
     SubmittingTelemetry,
 }
@@ -236,119 +601,615 @@ pub enum PleaseWait {
 impl Screen for PleaseWait {
     fn draw<D: DrawTarget<Color = BinaryColor>>(
         &self,
+        lang: Language,
+        target: &mut D,
+    ) -> Result<(), Error<D::Error>> {
+        self.draw_with_spinner(lang, None, target)
+    }
+}
+
+impl PleaseWait {
+    /// Draw screen, with spinner at given animation tick (if any)
+    fn draw_with_spinner<D: DrawTarget<Color = BinaryColor>>(
+        &self,
+        lang: Language,
+        spinner_tick: Option<u32>,
         target: &mut D,
     ) -> Result<(), Error<D::Error>> {
-        centered(&TITLE_FONT, 26, "Stand By...", target)?;
+        let strings = lang.strings();
+        // Compact layout omits the title to fit (possibly multi-line) text and progress bar
+        if !COMPACT {
+            centered(&TITLE_FONT, LINE1, strings.stand_by, target)?;
+        }
         centered(
             &MEDIUM_FONT,
-            26 + 12,
+            if COMPACT { LINE1 } else { LINE2 },
             match self {
-                Self::WifiConnecting => "WLAN Verbindung\nwird aufgebaut",
-                Self::UpdatingData => "Daten-Aktualisierung",
-                Self::Purchasing => "Zahlung wird\nbearbeitet",
-                Self::SubmittingTelemetry => "Daten-Übertragung",
+                Self::WifiConnecting => strings.wifi_connecting,
+                Self::UpdatingData | Self::UpdatingDataProgress(_) => strings.updating_data,
+                Self::FetchingBalance => strings.fetching_balance,
+                Self::Purchasing => strings.purchasing,
+                Self::SubmittingTelemetry => strings.submitting_telemetry,
             },
             target,
         )?;
-        if let Self::WifiConnecting = self {
-            footer("* Abbruch", "", target)?;
+        if let Self::UpdatingDataProgress(percent) = self {
+            progress_bar(
+                if COMPACT { LINE2 - 2 } else { LINE2 + 8 },
+                *percent,
+                target,
+            )?;
+        }
+        if !matches!(self, Self::SubmittingTelemetry) {
+            footer(strings.cancel, "", target)?;
+        }
+        if let Some(tick) = spinner_tick {
+            spinner(tick, target)?;
+        }
+        Ok(())
+    }
+}
+
+/// Wait while a lengthy action is in progress, with animated spinner
+pub struct PleaseWaitAnimated {
+    please_wait: PleaseWait,
+    tick: u32,
+}
+
+impl PleaseWaitAnimated {
+    pub fn new(please_wait: PleaseWait) -> Self {
+        Self {
+            please_wait,
+            tick: 0,
+        }
+    }
+
+    /// Replace the wait screen (e.g. to show progress), keeping the spinner animation
+    pub fn set(&mut self, please_wait: PleaseWait) {
+        self.please_wait = please_wait;
+    }
+}
+
+impl Screen for PleaseWaitAnimated {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(
+        &self,
+        lang: Language,
+        target: &mut D,
+    ) -> Result<(), Error<D::Error>> {
+        self.please_wait
+            .draw_with_spinner(lang, Some(self.tick), target)
+    }
+
+    fn tick(&mut self, _lang: Language) -> bool {
+        self.tick = self.tick.wrapping_add(1);
+        true
+    }
+}
+
+/// Admin menu
+pub struct AdminMenu {
+    pending_purchases: usize,
+}
+
+impl AdminMenu {
+    pub fn new(pending_purchases: usize) -> Self {
+        Self { pending_purchases }
+    }
+}
+
+impl Screen for AdminMenu {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(
+        &self,
+        lang: Language,
+        target: &mut D,
+    ) -> Result<(), Error<D::Error>> {
+        let strings = lang.strings();
+        // Compact layout omits the title and uses a smaller font to fit all menu items
+        let (font, y0, step) = if COMPACT {
+            (&SMALL_FONT, 6, 6)
+        } else {
+            centered(&MEDIUM_FONT, 8, strings.admin_menu, target)?;
+            (&MEDIUM_FONT, 8 + 13, 10)
+        };
+        left(font, 0, y0, format_args!("1: {}", strings.refresh), target)?;
+        left(
+            font,
+            0,
+            y0 + step,
+            format_args!("2: {}", strings.device_id),
+            target,
+        )?;
+        left(
+            font,
+            0,
+            y0 + 2 * step,
+            format_args!("3: {} ({})", strings.clear_queue, self.pending_purchases),
+            target,
+        )?;
+        left(
+            font,
+            0,
+            y0 + 3 * step,
+            format_args!("4: {}", strings.reload_config),
+            target,
+        )?;
+        footer(strings.back, format_args!("1-4 {}", strings.next), target)?;
+        Ok(())
+    }
+}
+
+/// Device information
+pub struct DeviceInfo<'a, V> {
+    device_id: &'a str,
+    reader_firmware: Option<V>,
+}
+
+impl<'a, V: fmt::Display> DeviceInfo<'a, V> {
+    /// Device information with device id and NFC reader firmware version (if known)
+    pub fn new(device_id: &'a str, reader_firmware: Option<V>) -> Self {
+        Self {
+            device_id,
+            reader_firmware,
+        }
+    }
+}
+
+impl<V: fmt::Display> Screen for DeviceInfo<'_, V> {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(
+        &self,
+        lang: Language,
+        target: &mut D,
+    ) -> Result<(), Error<D::Error>> {
+        let strings = lang.strings();
+        centered(&MEDIUM_FONT, LINE1, strings.device_id, target)?;
+        centered(&TITLE_FONT, LINE1 + 14, self.device_id, target)?;
+        match self.reader_firmware {
+            Some(ref version) => footer(strings.back, format_args!("PN532 {version}"), target)?,
+            None => footer(strings.back, "", target)?,
+        }
+        Ok(())
+    }
+}
+
+/// Configuration reloaded
+pub struct ConfigReloaded {
+    restart_required: bool,
+}
+
+impl ConfigReloaded {
+    pub fn new(restart_required: bool) -> Self {
+        Self { restart_required }
+    }
+}
+
+impl Screen for ConfigReloaded {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(
+        &self,
+        lang: Language,
+        target: &mut D,
+    ) -> Result<(), Error<D::Error>> {
+        let strings = lang.strings();
+        if COMPACT && self.restart_required {
+            // Compact layout only fits the restart notice
+            centered(&MEDIUM_FONT, LINE1, strings.restart_required, target)?;
+        } else {
+            let y = if COMPACT { LINE1 - 2 } else { LINE1 - 8 };
+            centered(&MEDIUM_FONT, y, strings.config_reloaded, target)?;
+            if self.restart_required {
+                centered(&SMALL_FONT, LINE1 + 18, strings.restart_required, target)?;
+            }
+        }
+        footer(strings.back, "", target)?;
+        Ok(())
+    }
+}
+
+/// Default currency symbol shown with total prices
+pub const DEFAULT_CURRENCY: &str = "EUR";
+
+/// Default decimal separator of prices
+pub const DEFAULT_DECIMAL_SEPARATOR: &str = ".";
+
+/// Format of prices shown on screens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriceFormat<'a> {
+    currency: &'a str,
+    decimal_separator: &'a str,
+}
+
+impl Default for PriceFormat<'_> {
+    fn default() -> Self {
+        Self {
+            currency: DEFAULT_CURRENCY,
+            decimal_separator: DEFAULT_DECIMAL_SEPARATOR,
+        }
+    }
+}
+
+impl<'a> PriceFormat<'a> {
+    /// Create price format with given currency symbol and decimal separator (or defaults)
+    pub fn new(currency: Option<&'a str>, decimal_separator: Option<&'a str>) -> Self {
+        Self {
+            currency: currency.unwrap_or(DEFAULT_CURRENCY),
+            decimal_separator: decimal_separator.unwrap_or(DEFAULT_DECIMAL_SEPARATOR),
+        }
+    }
+
+    /// Price with two decimals, without currency symbol
+    fn price(&self, value: f32) -> Price<'_> {
+        Price {
+            value,
+            format: self,
+            currency: false,
+        }
+    }
+
+    /// Price with two decimals and currency symbol
+    fn total(&self, value: f32) -> Price<'_> {
+        Price {
+            value,
+            format: self,
+            currency: true,
+        }
+    }
+}
+
+/// Price formatted according to a price format
+struct Price<'a> {
+    value: f32,
+    format: &'a PriceFormat<'a>,
+    currency: bool,
+}
+
+impl fmt::Display for Price<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        /// Writer that replaces the decimal point with a different separator
+        struct Separated<'a, 'b> {
+            f: &'a mut fmt::Formatter<'b>,
+            separator: &'a str,
+        }
+
+        impl fmt::Write for Separated<'_, '_> {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                for (idx, part) in s.split('.').enumerate() {
+                    if idx > 0 {
+                        self.f.write_str(self.separator)?;
+                    }
+                    self.f.write_str(part)?;
+                }
+                Ok(())
+            }
+        }
+
+        let mut writer = Separated {
+            f,
+            separator: self.format.decimal_separator,
+        };
+        fmt::Write::write_fmt(&mut writer, format_args!("{:.02}", self.value))?;
+        if self.currency {
+            write!(f, " {}", self.format.currency)?;
+        }
+        Ok(())
+    }
+}
+
+/// Clock text (hours and minutes) of given time of day
+fn clock_text(time: NaiveTime) -> DelayedFormat<StrftimeItems<'static>> {
+    time.format("%H:%M")
+}
+
+/// Prompt to scan id card, with a clock in the corner if the current time is known and a
+/// battery indicator in the other corner if running on battery
+#[derive(Debug, PartialEq, Eq)]
+pub struct ScanId<'a> {
+    clock: Option<NaiveTime>,
+    battery: Option<u8>,
+    member_id_entry: bool,
+    demo: bool,
+    machine_name: &'a str,
+}
+
+impl<'a> ScanId<'a> {
+    /// Create screen showing the given local time (to the minute) and battery charge (percentage,
+    /// 0-100). Optionally hints that a member number can be entered instead and shows that demo
+    /// mode is active.
+    pub fn new<TZ: TimeZone>(
+        time: Option<DateTime<TZ>>,
+        battery: Option<u8>,
+        member_id_entry: bool,
+        demo: bool,
+    ) -> Self {
+        Self {
+            clock: time.and_then(|time| {
+                let time = time.naive_local().time();
+                NaiveTime::from_hms_opt(time.hour(), time.minute(), 0)
+            }),
+            battery,
+            member_id_entry,
+            demo,
+            machine_name: "",
+        }
+    }
+
+    /// Show the given machine name in the footer (hidden if empty or if the member number hint
+    /// is shown)
+    pub fn with_machine_name(self, machine_name: &'a str) -> Self {
+        Self {
+            machine_name,
+            ..self
+        }
+    }
+}
+
+impl Screen for ScanId<'_> {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(
+        &self,
+        lang: Language,
+        target: &mut D,
+    ) -> Result<(), Error<D::Error>> {
+        let strings = lang.strings();
+        centered(&TITLE_FONT, LINE1, strings.scan_id, target)?;
+        if self.demo {
+            centered(&MEDIUM_FONT, LINE2, strings.demo_mode, target)?;
+        }
+        if let Some(clock) = self.clock {
+            right(
+                &FOOTER_FONT,
+                HEIGHT - 1,
+                format_args!("{}", clock_text(clock)),
+                target,
+            )?;
+        }
+        if let Some(charge) = self.battery {
+            battery_indicator(charge, target)?;
+        }
+        if self.member_id_entry {
+            centered(&FOOTER_FONT, HEIGHT - 1, strings.enter_member_id, target)?;
+        } else if !self.machine_name.is_empty() {
+            let label = machine_label(self.machine_name);
+            centered(&FOOTER_FONT, HEIGHT - 1, label, target)?;
         }
         Ok(())
     }
 }
 
-/// Prompt to scan id card
-pub struct ScanId;
+/// Prompt to enter the admin PIN or a member's PIN. Entered digits are masked.
+pub struct EnterPin {
+    admin: bool,
+    len: usize,
+}
+
+impl EnterPin {
+    /// Create admin PIN prompt showing the number of digits entered so far
+    pub fn admin(len: usize) -> Self {
+        Self { admin: true, len }
+    }
+
+    /// Create member PIN prompt showing the number of digits entered so far
+    pub fn member(len: usize) -> Self {
+        Self { admin: false, len }
+    }
+}
 
-impl Screen for ScanId {
+impl Screen for EnterPin {
     fn draw<D: DrawTarget<Color = BinaryColor>>(
         &self,
+        lang: Language,
         target: &mut D,
     ) -> Result<(), Error<D::Error>> {
-        centered(&TITLE_FONT, 26, "Mitgliedsausweis\nscannen", target)?;
+        let strings = lang.strings();
+        let title = if self.admin {
+            strings.admin_pin
+        } else {
+            strings.pin
+        };
+        centered(&TITLE_FONT, LINE1, title, target)?;
+        centered(
+            &MEDIUM_FONT,
+            LINE2,
+            format_args!("{:*<len$}_", "", len = self.len),
+            target,
+        )?;
+        footer(strings.cancel, strings.ok, target)?;
+        Ok(())
+    }
+}
+
+/// Prompt to enter member number
+pub struct EnterMemberId<'a> {
+    digits: &'a str,
+}
+
+impl<'a> EnterMemberId<'a> {
+    /// Create screen showing the digits entered so far
+    pub fn new(digits: &'a str) -> Self {
+        Self { digits }
+    }
+}
+
+impl Screen for EnterMemberId<'_> {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(
+        &self,
+        lang: Language,
+        target: &mut D,
+    ) -> Result<(), Error<D::Error>> {
+        let strings = lang.strings();
+        centered(&TITLE_FONT, LINE1, strings.member_id, target)?;
+        centered(
+            &MEDIUM_FONT,
+            LINE2,
+            format_args!("{}_", self.digits),
+            target,
+        )?;
+        footer(strings.cancel, strings.ok, target)?;
         Ok(())
     }
 }
 
 /// Prompt to select article
 pub struct SelectArticle<'a> {
+    greetings: Option<&'a [String]>,
     greeting: u32,
     name: &'a str,
+    balance: Option<f32>,
     articles: &'a Articles,
+    price_format: PriceFormat<'a>,
+    page: usize,
+    tick: u32,
 }
 
 impl<'a> SelectArticle<'a> {
-    pub fn new<RNG: RngCore>(mut rng: RNG, name: &'a str, articles: &'a Articles) -> Self {
+    pub fn new<RNG: RngCore>(
+        mut rng: RNG,
+        name: &'a str,
+        balance: Option<f32>,
+        articles: &'a Articles,
+        price_format: PriceFormat<'a>,
+        greetings: Option<&'a [String]>,
+    ) -> Self {
         Self {
+            greetings,
             greeting: rng.next_u32(),
             name,
+            balance,
             articles,
+            price_format,
+            page: 0,
+            tick: 0,
         }
     }
+
+    /// Show given page of articles
+    pub fn set_page(&mut self, page: usize) {
+        self.page = page;
+    }
 }
 
 impl Screen for SelectArticle<'_> {
     fn draw<D: DrawTarget<Color = BinaryColor>>(
         &self,
+        lang: Language,
         target: &mut D,
     ) -> Result<(), Error<D::Error>> {
-        greeting(self.greeting, self.name, target)?;
+        let strings = lang.strings();
+        // Compact layout omits the greeting to fit article lines
+        if !COMPACT {
+            greeting(
+                lang,
+                self.greetings,
+                self.greeting,
+                self.name,
+                self.tick,
+                target,
+            )?;
+        }
 
+        let pages = article_pages(self.articles.count_ids());
+        let range = article_page_range(self.page, self.articles.count_ids());
         // Safe to unwrap since conversion always succeeds for these small numbers
-        let num_articles = i32::try_from(self.articles.count_ids()).unwrap();
-        let y0 = 40 + num_articles * -5;
+        let num_articles = i32::try_from(range.len()).unwrap();
+        let y0 = ARTICLES_BASE - num_articles * ARTICLES_SHIFT;
         for (idx, _article_id, article) in self.articles.iter() {
+            if !range.contains(&idx) {
+                continue;
+            }
+            let idx = idx - range.start;
             // Safe to unwrap since conversion always succeeds for these small numbers
             let y = y0 + i32::try_from(idx).unwrap() * 12;
             left(&TITLE_FONT, 0, y, format_args!("{}:", idx + 1), target)?;
             let article_name = trim_prefixes(&article.name, &["Getränke", "Getränk"]);
-            left(&TITLE_FONT, 16, y, trim(article_name, 13), target)?;
+            left(
+                &TITLE_FONT,
+                16,
+                y,
+                scroll(article_name, 13, self.tick),
+                target,
+            )?;
             right(
                 &SMALL_FONT,
                 y,
-                format_args!("{:.02}", article.price),
+                format_args!("{}", self.price_format.price(article.price)),
+                target,
+            )?;
+        }
+        // Balance (if known) takes the place of the cancel hint, there's no room for both next to
+        // the page hint
+        let balance = self.balance.map(|balance| self.price_format.total(balance));
+        let footer_left: &dyn fmt::Display = match &balance {
+            Some(balance) => balance,
+            None => &strings.cancel,
+        };
+        if pages > 1 {
+            footer(
+                format_args!("{footer_left}"),
+                format_args!("# {} {}/{}", strings.page, self.page + 1, pages),
+                target,
+            )?;
+        } else {
+            footer(
+                format_args!("{footer_left}"),
+                format_args!("1-{} {}", range.len(), strings.next),
                 target,
             )?;
         }
-        footer(
-            "* Abbruch",
-            format_args!("1-{} Weiter", self.articles.count_ids()),
-            target,
-        )?;
         Ok(())
     }
+
+    fn tick(&mut self, lang: Language) -> bool {
+        self.tick = self.tick.wrapping_add(1);
+        needs_scroll(
+            self.name,
+            greeting_name_len(greeting_text(lang, self.greetings, self.greeting)),
+        ) || self.articles.iter().any(|(_idx, _article_id, article)| {
+            let article_name = trim_prefixes(&article.name, &["Getränke", "Getränk"]);
+            needs_scroll(article_name, 13)
+        })
+    }
 }
 
 /// Prompt to enter amount
 pub struct EnterAmount<'a> {
     article: &'a Article,
+    price_format: PriceFormat<'a>,
+    tick: u32,
 }
 
 impl<'a> EnterAmount<'a> {
-    pub fn new(article: &'a Article) -> Self {
-        Self { article }
+    pub fn new(article: &'a Article, price_format: PriceFormat<'a>) -> Self {
+        Self {
+            article,
+            price_format,
+            tick: 0,
+        }
     }
 }
 
 impl Screen for EnterAmount<'_> {
     fn draw<D: DrawTarget<Color = BinaryColor>>(
         &self,
+        lang: Language,
         target: &mut D,
     ) -> Result<(), Error<D::Error>> {
+        let strings = lang.strings();
         centered(
             &MEDIUM_FONT,
-            23,
+            LINE1 - 3,
             format_args!(
-                "{} {:.02}",
-                trim(&self.article.name, MEDIUM_CHARS_PER_LINE - 3),
-                self.article.price
+                "{} {}",
+                scroll(&self.article.name, MEDIUM_CHARS_PER_LINE - 3, self.tick),
+                self.price_format.price(self.article.price)
             ),
             target,
         )?;
-        centered(&TITLE_FONT, 23 + 16, "Anzahl wählen", target)?;
-        footer("* Abbruch", "1-9 Weiter", target)?;
+        centered(&TITLE_FONT, LINE1 + 13, strings.select_amount, target)?;
+        footer(strings.cancel, format_args!("1-9 {}", strings.next), target)?;
         Ok(())
     }
+
+    fn tick(&mut self, _lang: Language) -> bool {
+        self.tick = self.tick.wrapping_add(1);
+        needs_scroll(&self.article.name, MEDIUM_CHARS_PER_LINE - 3)
+    }
 }
 
 /// Checkout (confirm purchase)
@@ -356,14 +1217,23 @@ pub struct Checkout<'a> {
     article: &'a Article,
     amount: usize,
     total_price: f32,
+    price_format: PriceFormat<'a>,
+    tick: u32,
 }
 
 impl<'a> Checkout<'a> {
-    pub fn new(article: &'a Article, amount: usize, total_price: f32) -> Self {
+    pub fn new(
+        article: &'a Article,
+        amount: usize,
+        total_price: f32,
+        price_format: PriceFormat<'a>,
+    ) -> Self {
         Self {
             article,
             amount,
             total_price,
+            price_format,
+            tick: 0,
         }
     }
 }
@@ -371,25 +1241,103 @@ impl<'a> Checkout<'a> {
 impl Screen for Checkout<'_> {
     fn draw<D: DrawTarget<Color = BinaryColor>>(
         &self,
+        lang: Language,
         target: &mut D,
     ) -> Result<(), Error<D::Error>> {
+        let strings = lang.strings();
         centered(
             &MEDIUM_FONT,
-            23,
+            LINE1 - 3,
             format_args!(
                 "{}x {}",
                 self.amount,
-                trim(&self.article.name, MEDIUM_CHARS_PER_LINE - 3)
+                scroll(&self.article.name, MEDIUM_CHARS_PER_LINE - 3, self.tick)
             ),
             target,
         )?;
         centered(
             &TITLE_FONT,
-            23 + 16,
-            format_args!("{:.02} EUR", self.total_price),
+            LINE1 + 13,
+            format_args!("{}", self.price_format.total(self.total_price)),
+            target,
+        )?;
+        footer(strings.cancel, strings.pay, target)?;
+        Ok(())
+    }
+
+    fn tick(&mut self, _lang: Language) -> bool {
+        self.tick = self.tick.wrapping_add(1);
+        needs_scroll(&self.article.name, MEDIUM_CHARS_PER_LINE - 3)
+    }
+}
+
+/// Confirmation of discarding pending purchases
+pub struct ConfirmClearQueue {
+    pending_purchases: usize,
+}
+
+impl ConfirmClearQueue {
+    pub fn new(pending_purchases: usize) -> Self {
+        Self { pending_purchases }
+    }
+}
+
+impl Screen for ConfirmClearQueue {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(
+        &self,
+        lang: Language,
+        target: &mut D,
+    ) -> Result<(), Error<D::Error>> {
+        let strings = lang.strings();
+        centered(&MEDIUM_FONT, LINE1 - 3, strings.really, target)?;
+        centered(
+            &MEDIUM_FONT,
+            LINE1 + 13,
+            format_args!("{} ({})?", strings.clear_queue, self.pending_purchases),
             target,
         )?;
-        footer("* Abbruch", "# BEZAHLEN", target)?;
+        footer(strings.cancel, strings.ok, target)?;
+        Ok(())
+    }
+}
+
+/// Extra confirmation of a purchase with an unusually large total price
+pub struct ConfirmLargePurchase<'a> {
+    amount: usize,
+    total_price: f32,
+    price_format: PriceFormat<'a>,
+}
+
+impl<'a> ConfirmLargePurchase<'a> {
+    pub fn new(amount: usize, total_price: f32, price_format: PriceFormat<'a>) -> Self {
+        Self {
+            amount,
+            total_price,
+            price_format,
+        }
+    }
+}
+
+impl Screen for ConfirmLargePurchase<'_> {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(
+        &self,
+        lang: Language,
+        target: &mut D,
+    ) -> Result<(), Error<D::Error>> {
+        let strings = lang.strings();
+        centered(
+            &MEDIUM_FONT,
+            LINE1 - 3,
+            format_args!("{} {}x?", strings.really, self.amount),
+            target,
+        )?;
+        centered(
+            &TITLE_FONT,
+            LINE1 + 13,
+            format_args!("{}", self.price_format.total(self.total_price)),
+            target,
+        )?;
+        footer(strings.cancel, strings.ok, target)?;
         Ok(())
     }
 }
@@ -397,27 +1345,588 @@ impl Screen for Checkout<'_> {
 /// Success screen
 pub struct Success {
     amount: usize,
+    undo: bool,
 }
 
 impl Success {
+    /// Success screen, optionally offering to undo the purchase
+    pub fn new(amount: usize, undo: bool) -> Self {
+        Self { amount, undo }
+    }
+}
+
+impl Screen for Success {
+    fn draw<D: DrawTarget<Color = BinaryColor>>(
+        &self,
+        lang: Language,
+        target: &mut D,
+    ) -> Result<(), Error<D::Error>> {
+        let strings = lang.strings();
+        centered(&TITLE_FONT, LINE1, "Affirm!", target)?;
+        centered(
+            &SMALL_FONT,
+            LINE2,
+            format_args!("{} {}", self.amount, strings.drinks_approved),
+            target,
+        )?;
+        footer(
+            if self.undo { strings.undo } else { "" },
+            strings.ok,
+            target,
+        )?;
+        Ok(())
+    }
+}
+
+/// Purchase undone screen
+pub struct Undone {
+    amount: usize,
+}
+
+impl Undone {
     pub fn new(amount: usize) -> Self {
         Self { amount }
     }
 }
 
-impl Screen for Success {
+impl Screen for Undone {
     fn draw<D: DrawTarget<Color = BinaryColor>>(
         &self,
+        lang: Language,
         target: &mut D,
     ) -> Result<(), Error<D::Error>> {
-        centered(&TITLE_FONT, 26, "Affirm!", target)?;
+        let strings = lang.strings();
+        centered(&TITLE_FONT, LINE1, strings.undone, target)?;
         centered(
             &SMALL_FONT,
-            26 + 12,
-            format_args!("{} Getränke genehmigt", self.amount),
+            LINE2,
+            format_args!("{} {}", self.amount, strings.drinks_cancelled),
             target,
         )?;
-        footer("", "# Ok", target)?;
+        footer("", strings.ok, target)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+    use chrono::{FixedOffset, Utc};
+    use core::convert::Infallible;
+    use embedded_graphics::primitives::Rectangle;
+
+    /// Draw target that counts pixels that are turned on and fails on out of bounds pixels
+    #[derive(Default)]
+    struct TestTarget {
+        pixels_on: usize,
+    }
+
+    impl OriginDimensions for TestTarget {
+        fn size(&self) -> Size {
+            #[allow(clippy::cast_sign_loss)]
+            Size::new(WIDTH as u32, HEIGHT as u32)
+        }
+    }
+
+    impl DrawTarget for TestTarget {
+        type Color = BinaryColor;
+        type Error = Infallible;
+
+        fn draw_iter<I: IntoIterator<Item = Pixel<BinaryColor>>>(
+            &mut self,
+            pixels: I,
+        ) -> Result<(), Infallible> {
+            let area = Rectangle::new(Point::zero(), self.size());
+            for Pixel(point, color) in pixels {
+                assert!(area.contains(point), "pixel {point} out of bounds");
+                if color.is_on() {
+                    self.pixels_on += 1;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn draw_panic() {
+        for lang in [Language::German, Language::English] {
+            let mut target = TestTarget::default();
+            assert!(Panic::new(10).draw(lang, &mut target).is_ok());
+            assert!(target.pixels_on > 0);
+        }
+    }
+
+    #[test]
+    fn draw_brown_out() {
+        for lang in [Language::German, Language::English] {
+            let mut target = TestTarget::default();
+            assert!(BrownOut.draw(lang, &mut target).is_ok());
+            assert!(target.pixels_on > 0);
+        }
+    }
+
+    #[test]
+    fn draw_battery_indicator() {
+        let mut empty = TestTarget::default();
+        assert!(battery_indicator(0, &mut empty).is_ok());
+        assert!(empty.pixels_on > 0);
+        let mut full = TestTarget::default();
+        assert!(battery_indicator(100, &mut full).is_ok());
+        // Inner 7x2 pixels filled
+        assert_eq!(full.pixels_on, empty.pixels_on + 14);
+    }
+
+    #[test]
+    fn draw_fits_display() {
+        fn draw<S: Screen>(screen: &S) {
+            for lang in [Language::German, Language::English] {
+                let mut target = TestTarget::default();
+                assert!(screen.draw(lang, &mut target).is_ok());
+                assert!(target.pixels_on > 0);
+            }
+        }
+        draw(&Splash::new(""));
+        draw(&Splash::new("Clubheim Nord"));
+        draw(&NotConfigured::new(&[]));
+        draw(&NotConfigured::new(&[
+            "wifi-ssid",
+            "vf-username",
+            "vf-password-md5",
+            "vf-appkey",
+            "vf-article-id",
+        ]));
+        draw(&Failure::new("Something went wrong", None));
+        draw(&Failure::new(
+            "Vereinsflieger: Fetch users failed (HTTP request failed: connection reset)",
+            Some(0x30),
+        ));
+        draw(&PleaseWait::WifiConnecting);
+        draw(&PleaseWait::UpdatingData);
+        draw(&PleaseWait::FetchingBalance);
+        draw(&PleaseWaitAnimated::new(PleaseWait::FetchingBalance));
+        draw(&AdminMenu::new(3));
+        draw(&ConfirmClearQueue::new(30));
+        draw(&EnterPin::admin(0));
+        draw(&EnterPin::admin(9));
+        draw(&EnterPin::member(4));
+        draw(&DeviceInfo::new("0123456789ab", Some("1.6")));
+        draw(&DeviceInfo::new("0123456789ab", None::<&str>));
+        draw(&ConfigReloaded::new(false));
+        draw(&ConfigReloaded::new(true));
+        draw(&ScanId::new(None::<DateTime<Utc>>, None, false, false));
+        draw(&ScanId::new(
+            DateTime::from_timestamp(1_737_000_000, 0),
+            Some(50),
+            true,
+            true,
+        ));
+        draw(
+            &ScanId::new(
+                DateTime::from_timestamp(1_737_000_000, 0),
+                Some(50),
+                false,
+                false,
+            )
+            .with_machine_name("Flugplatzgaststätte Nord"),
+        );
+        draw(&EnterMemberId::new(""));
+        draw(&EnterMemberId::new("123456789"));
+        draw(&Success::new(2, true));
+        draw(&Success::new(2, false));
+        draw(&Undone::new(2));
+    }
+
+    #[test]
+    fn select_article_footer_fits() {
+        // A 4-digit balance must leave a gap to the widest hint in the footer of article selection
+        // (with up to 9 pages)
+        let price_format = PriceFormat::default();
+        let balance = price_format.total(-1234.56);
+        let baseline = HEIGHT - 1;
+        for lang in [Language::German, Language::English] {
+            let strings = lang.strings();
+            let left = FOOTER_FONT
+                .get_rendered_dimensions_aligned(
+                    format_args!("{balance}"),
+                    Point::new(0, baseline),
+                    VerticalPosition::Baseline,
+                    HorizontalAlignment::Left,
+                )
+                .unwrap()
+                .unwrap();
+            for hint in [
+                format!("# {} {}/{}", strings.page, 9, 9),
+                format!("1-{} {}", ARTICLES_PER_PAGE, strings.next),
+            ] {
+                let right = FOOTER_FONT
+                    .get_rendered_dimensions_aligned(
+                        hint.as_str(),
+                        Point::new(WIDTH - 1, baseline),
+                        VerticalPosition::Baseline,
+                        HorizontalAlignment::Right,
+                    )
+                    .unwrap()
+                    .unwrap();
+                assert!(left.top_left.x >= 0);
+                assert!(
+                    left.bottom_right().unwrap().x + 5 < right.top_left.x,
+                    "balance {balance} overlaps {hint}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn greeting_from_language() {
+        assert_eq!(greeting_text(Language::German, None, 1), "Hallo");
+        assert_eq!(greeting_text(Language::English, None, 1), "Hello");
+        // Empty custom greetings fall back to the language's greetings
+        assert_eq!(greeting_text(Language::English, Some(&[]), 1), "Hello");
+    }
+
+    #[test]
+    fn greeting_custom() {
+        let greetings = ["Servus".to_string(), "Moin".to_string()];
+        assert_eq!(
+            greeting_text(Language::English, Some(&greetings), 0),
+            "Servus"
+        );
+        assert_eq!(
+            greeting_text(Language::English, Some(&greetings), 3),
+            "Moin"
+        );
+    }
+
+    #[test]
+    fn greeting_name_length() {
+        assert_eq!(greeting_name_len("Hi"), MEDIUM_CHARS_PER_LINE - 3);
+        // Overly long custom greeting leaves no room for the name
+        assert_eq!(greeting_name_len(&"x".repeat(MEDIUM_CHARS_PER_LINE)), 0);
+    }
+
+    #[test]
+    fn price_default_format() {
+        let format = PriceFormat::default();
+        assert_eq!(format.price(1.5).to_string(), "1.50");
+        assert_eq!(format.price(0.0).to_string(), "0.00");
+        assert_eq!(format.price(-12.345).to_string(), "-12.35");
+        assert_eq!(format.total(31.5).to_string(), "31.50 EUR");
+    }
+
+    #[test]
+    fn price_custom_format() {
+        let format = PriceFormat::new(Some("£"), Some(","));
+        assert_eq!(format.price(1.5).to_string(), "1,50");
+        assert_eq!(format.price(1234.0).to_string(), "1234,00");
+        assert_eq!(format.total(31.5).to_string(), "31,50 £");
+        let format = PriceFormat::new(Some("CHF"), None);
+        assert_eq!(format.total(2.25).to_string(), "2.25 CHF");
+        let format = PriceFormat::new(None, Some(","));
+        assert_eq!(format.total(2.25).to_string(), "2,25 EUR");
+    }
+
+    #[test]
+    fn clock() {
+        let time = |h, m| NaiveTime::from_hms_opt(h, m, 0).unwrap();
+        assert_eq!(clock_text(time(0, 0)).to_string(), "00:00");
+        assert_eq!(clock_text(time(4, 5)).to_string(), "04:05");
+        assert_eq!(clock_text(time(23, 59)).to_string(), "23:59");
+    }
+
+    #[test]
+    fn scan_id_clock() {
+        // 2025-01-16 03:59:59 UTC
+        let utc = DateTime::from_timestamp(1_736_999_999, 0).unwrap();
+        assert_eq!(
+            ScanId::new(Some(utc), None, false, false).clock,
+            NaiveTime::from_hms_opt(3, 59, 0)
+        );
+        let offset = FixedOffset::east_opt(3600).unwrap();
+        assert_eq!(
+            ScanId::new(Some(utc.with_timezone(&offset)), None, false, false).clock,
+            NaiveTime::from_hms_opt(4, 59, 0)
+        );
+        assert_eq!(
+            ScanId::new(None::<DateTime<Utc>>, None, false, false).clock,
+            None
+        );
+        // Same minute is the same screen
+        assert_eq!(
+            ScanId::new(
+                DateTime::from_timestamp(1_736_999_940, 0),
+                None,
+                false,
+                false
+            ),
+            ScanId::new(Some(utc), None, false, false)
+        );
+    }
+
+    #[test]
+    fn draw_confirm_large_purchase() {
+        for lang in [Language::German, Language::English] {
+            let mut target = TestTarget::default();
+            assert!(ConfirmLargePurchase::new(9, 31.5, PriceFormat::default())
+                .draw(lang, &mut target)
+                .is_ok());
+            assert!(target.pixels_on > 0);
+        }
+    }
+
+    #[test]
+    fn draw_progress() {
+        for percent in [0, 50, 100] {
+            let mut target = TestTarget::default();
+            assert!(PleaseWait::UpdatingDataProgress(percent)
+                .draw(Language::German, &mut target)
+                .is_ok());
+            assert!(target.pixels_on > 0);
+        }
+    }
+
+    #[test]
+    fn progress_width() {
+        assert_eq!(progress_fill_width(96, 0), 0);
+        assert_eq!(progress_fill_width(96, 50), 48);
+        assert_eq!(progress_fill_width(96, 100), 96);
+        assert_eq!(progress_fill_width(96, 33), 31);
+        // Percentage is clamped to 100
+        assert_eq!(progress_fill_width(96, 150), 96);
+    }
+
+    #[test]
+    #[cfg(not(feature = "display-128x32"))]
+    fn article_pages_count() {
+        assert_eq!(article_pages(0), 1);
+        assert_eq!(article_pages(1), 1);
+        assert_eq!(article_pages(4), 1);
+        assert_eq!(article_pages(5), 2);
+        assert_eq!(article_pages(8), 2);
+        assert_eq!(article_pages(9), 3);
+    }
+
+    #[test]
+    #[cfg(not(feature = "display-128x32"))]
+    fn article_index_single_page() {
+        assert_eq!(article_index(0, 1, 3), Some(0));
+        assert_eq!(article_index(0, 3, 3), Some(2));
+        assert_eq!(article_index(0, 4, 3), None);
+        assert_eq!(article_index(0, 0, 3), None);
+    }
+
+    #[test]
+    #[cfg(not(feature = "display-128x32"))]
+    fn article_index_multiple_pages() {
+        assert_eq!(article_index(0, 4, 6), Some(3));
+        assert_eq!(article_index(1, 1, 6), Some(4));
+        assert_eq!(article_index(1, 2, 6), Some(5));
+        assert_eq!(article_index(1, 3, 6), None);
+        assert_eq!(article_index(2, 1, 6), None);
+    }
+
+    #[test]
+    #[cfg(not(feature = "display-128x32"))]
+    fn article_index_full_pages() {
+        assert_eq!(article_index(0, 4, 8), Some(3));
+        assert_eq!(article_index(1, 1, 8), Some(4));
+        assert_eq!(article_index(1, 4, 8), Some(7));
+        assert_eq!(article_index(1, 5, 8), None);
+        assert_eq!(article_index(2, 1, 8), None);
+    }
+
+    #[test]
+    #[cfg(feature = "display-128x32")]
+    fn article_index_compact_pages() {
+        assert_eq!(article_pages(2), 1);
+        assert_eq!(article_pages(3), 2);
+        assert_eq!(article_index(0, 2, 3), Some(1));
+        assert_eq!(article_index(0, 3, 3), None);
+        assert_eq!(article_index(1, 1, 3), Some(2));
+        assert_eq!(article_index(1, 2, 3), None);
+    }
+
+    #[test]
+    #[cfg(not(feature = "display-128x32"))]
+    fn layout_128x64() {
+        assert!(!COMPACT);
+        assert_eq!(LINE1, 26);
+        assert_eq!(LINE2, 38);
+        assert_eq!(ARTICLES_PER_PAGE, 4);
+    }
+
+    #[test]
+    #[cfg(feature = "display-128x32")]
+    fn layout_128x32() {
+        assert!(COMPACT);
+        assert_eq!(LINE1, 10);
+        assert_eq!(LINE2, 22);
+        assert_eq!(ARTICLES_PER_PAGE, 2);
+    }
+
+    #[test]
+    fn layout_fits_above_footer() {
+        let footer_top = HEIGHT - FOOTER_HEIGHT;
+        // Title font is 10 pixels above baseline, small and medium fonts descend 2 pixels
+        assert!(LINE1 >= 10);
+        assert!(LINE2 + 2 <= footer_top);
+        // Article lines fit between greeting (if any) and footer on a full page
+        let num_articles = i32::try_from(ARTICLES_PER_PAGE).unwrap();
+        let first = ARTICLES_BASE - num_articles * ARTICLES_SHIFT;
+        let last = first + (num_articles - 1) * 12;
+        assert!(first - 10 >= if COMPACT { 0 } else { 10 });
+        assert!(last <= footer_top);
+    }
+
+    #[test]
+    fn machine_label_short() {
+        assert_eq!(machine_label(""), "");
+        assert_eq!(machine_label("Clubheim"), "Clubheim");
+        assert_eq!(machine_label("  Halle 2 "), "Halle 2");
+    }
+
+    #[test]
+    fn machine_label_shortened() {
+        assert_eq!(machine_label("Flugplatzgaststätte"), "Flugplatzgasts");
+        assert_eq!(machine_label("Werkstatt Süd Halle 3"), "Werkstatt Süd");
+        assert!(machine_label("ÄÖÜäöüßÄÖÜäöüßÄÖÜ").chars().count() <= MACHINE_NAME_MAX_CHARS);
+    }
+
+    #[test]
+    fn machine_label_fits_footer() {
+        // Fits between battery indicator and clock, which are at most 5 characters wide
+        let width = i32::try_from(MACHINE_NAME_MAX_CHARS).unwrap() * 5;
+        let battery = i32::try_from(BATTERY_ICON_SIZE.width).unwrap() + 1;
+        assert!(width + 2 * battery.max(5 * 5) <= WIDTH);
+    }
+
+    #[test]
+    fn scroll_offset_short_text() {
+        assert_eq!(scroll_offset(5, 10, 0), 0);
+        assert_eq!(scroll_offset(10, 10, 7), 0);
+        assert_eq!(scroll_offset(10, 10, 100), 0);
+    }
+
+    #[test]
+    fn scroll_offset_long_text() {
+        // 3 characters overflow, period of 3 + 2 * 5 ticks
+        let offsets: Vec<usize> = (0..14).map(|tick| scroll_offset(13, 10, tick)).collect();
+        assert_eq!(offsets, [0, 0, 0, 0, 0, 0, 1, 2, 3, 3, 3, 3, 3, 0]);
+    }
+
+    #[test]
+    fn spinner_frame_cycles() {
+        let frames: Vec<usize> = (0..9).map(spinner_frame).collect();
+        assert_eq!(frames, [0, 1, 2, 3, 0, 1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn spinner_frame_wraps_seamlessly() {
+        // Animation tick wraps around without skipping a frame
+        assert_eq!(spinner_frame(u32::MAX), SPINNER.len() - 1);
+        assert_eq!(spinner_frame(u32::MAX.wrapping_add(1)), 0);
+    }
+
+    #[test]
+    fn spinner_frames_fit() {
+        for (tick, frame) in SPINNER.iter().enumerate() {
+            assert_eq!(frame.size(), Size::new(7, 7));
+            let mut target = TestTarget::default();
+            assert!(spinner(u32::try_from(tick).unwrap(), &mut target).is_ok());
+            assert_eq!(target.pixels_on, 7);
+        }
+    }
+
+    #[test]
+    fn please_wait_animated_ticks() {
+        let lang = Language::English;
+        let mut screen = PleaseWaitAnimated::new(PleaseWait::FetchingBalance);
+        let mut static_target = TestTarget::default();
+        assert!(PleaseWait::FetchingBalance
+            .draw(lang, &mut static_target)
+            .is_ok());
+        let mut target = TestTarget::default();
+        assert!(screen.draw(lang, &mut target).is_ok());
+        // Spinner is drawn in addition to the static screen
+        assert_eq!(target.pixels_on, static_target.pixels_on + 7);
+        // Every tick shows the next frame
+        assert!(screen.tick(lang));
+        assert_eq!(screen.tick, 1);
+    }
+
+    #[test]
+    fn scroll_text() {
+        assert_eq!(scroll("Apfelschorle 0,5L", 10, 0), "Apfelschor");
+        assert_eq!(scroll("Apfelschorle 0,5L", 10, 7), "felschorle");
+        assert_eq!(scroll("Apfelschorle 0,5L", 10, 12), "horle 0,5L");
+        assert_eq!(scroll("Wasser", 10, 7), "Wasser");
+    }
+
+    #[test]
+    fn trim_text() {
+        assert_eq!(trim("Apfelschorle", 5), "Apfel");
+        assert_eq!(trim("Wasser", 6), "Wasser");
+        assert_eq!(trim("Wasser", 10), "Wasser");
+        assert_eq!(trim("Wasser", 0), "");
+    }
+
+    #[test]
+    fn trim_text_multibyte() {
+        // Cut right before, after and at multibyte characters
+        assert_eq!(trim("Müller", 1), "M");
+        assert_eq!(trim("Müller", 2), "Mü");
+        assert_eq!(trim("Müller", 3), "Mül");
+        assert_eq!(trim("Jürgen Müßig", 10), "Jürgen Müß");
+        assert_eq!(trim("Olá", 3), "Olá");
+        assert_eq!(trim("ÄÖÜ", 2), "ÄÖ");
+    }
+
+    #[test]
+    fn wrap_short_text() {
+        assert_eq!(
+            wrap("No network connection", 21, 3),
+            ["No network connection"]
+        );
+        assert!(wrap("", 21, 3).is_empty());
+    }
+
+    #[test]
+    fn wrap_long_text() {
+        assert_eq!(
+            wrap("Vereinsflieger: Fetch users failed (Timeout)", 21, 3),
+            ["Vereinsflieger: Fetch", "users failed", "(Timeout)"]
+        );
+        assert_eq!(
+            wrap("Queue: Flash storage   full", 12, 3),
+            ["Queue: Flash", "storage full"]
+        );
+    }
+
+    #[test]
+    fn wrap_long_word() {
+        assert_eq!(
+            wrap("Error: 0123456789abcdef", 10, 3),
+            ["Error:", "0123456789", "abcdef"]
+        );
+    }
+
+    #[test]
+    fn wrap_truncated() {
+        assert_eq!(
+            wrap(
+                "Vereinsflieger: Fetch users failed (HTTP request failed)",
+                21,
+                2
+            ),
+            ["Vereinsflieger: Fetch", "users failed (HTTP..."]
+        );
+        assert_eq!(wrap("one two three", 5, 1), ["on..."]);
+    }
+
+    #[test]
+    fn scroll_text_multibyte() {
+        assert_eq!(scroll("Schnäpse", 5, 0), "Schnä");
+        assert_eq!(scroll("Schnäpse", 5, 8), "näpse");
+    }
+}
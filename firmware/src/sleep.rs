@@ -0,0 +1,109 @@
+use crate::watchdog;
+use esp_hal::gpio::{Input, WakeEvent};
+use esp_hal::rtc_cntl::sleep::GpioWakeupSource;
+use esp_hal::rtc_cntl::Rtc;
+use log::{info, warn};
+
+/// Pin that can wake up the system from light sleep
+pub trait WakeupPin {
+    /// Enable or disable waking up the system if the pin is pulled low
+    fn set_wakeup(&mut self, enable: bool);
+}
+
+impl WakeupPin for Input<'_> {
+    fn set_wakeup(&mut self, enable: bool) {
+        if let Err(err) = self.wakeup_enable(enable, WakeEvent::LowLevel) {
+            warn!("Sleep: Failed to configure wakeup pin: {:?}", err);
+        }
+    }
+}
+
+/// Watchdog that needs to be paused while the system sleeps, since it can't be fed then
+trait PausableWatchdog {
+    /// Stop the watchdog
+    fn pause(&mut self);
+    /// Restart the watchdog
+    fn resume(&mut self);
+}
+
+/// RTC watchdog fed by the watchdog task
+struct RtcWatchdog;
+
+impl PausableWatchdog for RtcWatchdog {
+    fn pause(&mut self) {
+        watchdog::pause();
+    }
+
+    fn resume(&mut self) {
+        watchdog::resume();
+    }
+}
+
+/// Call the given sleep function with the watchdog paused
+fn with_watchdog_paused<W: PausableWatchdog, R>(watchdog: &mut W, sleep: impl FnOnce() -> R) -> R {
+    watchdog.pause();
+    let res = sleep();
+    watchdog.resume();
+    res
+}
+
+/// System sleep
+/// Uses light sleep, since deep sleep can only be woken up by RTC GPIOs (GPIO0-5 on ESP32-C3),
+/// which the NFC reader IRQ isn't connected to. Light sleep keeps memory and peripheral state,
+/// so execution simply continues after waking up.
+pub struct Sleep {
+    rtc: Rtc<'static>,
+}
+
+impl Sleep {
+    /// Create sleep controller using the given RTC. The RTC watchdog should have been moved out
+    /// already.
+    pub fn new(rtc: Rtc<'static>) -> Self {
+        Self { rtc }
+    }
+
+    /// Put the system into light sleep until any pin enabled with `WakeupPin::set_wakeup` is
+    /// pulled low. Other devices should be powered down before and must not be used by other
+    /// tasks, since the executor doesn't run while sleeping. Note that the NFC reader can only
+    /// wake up the system on an external RF field (e.g. a phone), not on passive id cards.
+    pub fn light_sleep(&mut self) {
+        info!("Sleep: Entering light sleep...");
+
+        // The watchdog would reset the system since the feed task can't run while sleeping
+        with_watchdog_paused(&mut RtcWatchdog, || {
+            self.rtc.sleep_light(&[&GpioWakeupSource::new()]);
+        });
+
+        info!("Sleep: Woke up");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use core::cell::RefCell;
+
+    struct WatchdogStub<'a>(&'a RefCell<Vec<&'static str>>);
+
+    impl PausableWatchdog for WatchdogStub<'_> {
+        fn pause(&mut self) {
+            self.0.borrow_mut().push("pause");
+        }
+
+        fn resume(&mut self) {
+            self.0.borrow_mut().push("resume");
+        }
+    }
+
+    #[test]
+    fn watchdog_paused_while_sleeping() {
+        let calls = RefCell::new(Vec::new());
+        let res = with_watchdog_paused(&mut WatchdogStub(&calls), || {
+            calls.borrow_mut().push("sleep");
+            42
+        });
+        assert_eq!(res, 42);
+        assert_eq!(*calls.borrow(), ["pause", "sleep", "resume"]);
+    }
+}
@@ -1,17 +1,25 @@
 use crate::http::Http;
 use crate::mixpanel::{self, Mixpanel};
-use crate::{article, json, nfc, user};
-use alloc::collections::VecDeque;
-use alloc::string::String;
+use crate::{article, heap, json, nfc, user};
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::{String, ToString};
+use core::mem;
 use embassy_time::{Duration, Instant};
 use embedded_io_async::Write;
 use log::{debug, info, warn};
 
-/// Time after which events are flushed even when queue isn't filled yet
-const MAX_BUFFER_DURATION: Duration = Duration::from_secs(30);
+/// Default time after which events are flushed even when queue isn't filled yet
+pub const MAX_BUFFER_DURATION: Duration = Duration::from_secs(30);
 
-/// Max number of events to buffer before flushing
-const MAX_BUFFER_EVENTS: usize = 10;
+/// Default max number of events to buffer before flushing
+pub const MAX_BUFFER_EVENTS: usize = 10;
+
+/// Minimum time to buffer events (to not flush too often)
+const MIN_BUFFER_DURATION: Duration = Duration::from_secs(5);
+
+/// Max number of events to keep queued. If exceeded (e.g. if network is unavailable for a long
+/// time), the oldest events are dropped to not run out of memory.
+const MAX_QUEUED_EVENTS: usize = 200;
 
 /// Telemetry error
 pub type Error = mixpanel::Error;
@@ -21,16 +29,30 @@ pub type Error = mixpanel::Error;
 pub enum Event {
     /// System start
     SystemStart,
+    /// NFC reader initialized (PN532 firmware version)
+    ReaderInitialized(nfc::FirmwareVersion),
     /// Articles and users refreshed (article count, NFC uid count, user count)
     DataRefreshed(usize, usize, usize),
-    /// User authentication failed (NFC uid)
-    AuthenticationFailed(nfc::Uid),
-    /// User authentication successful (user id, NFC uid)
-    UserAuthenticated(user::UserId, nfc::Uid),
+    /// Heap usage after articles and users were refreshed
+    HeapUsage(heap::Stats),
+    /// User authentication failed (NFC uid, none if a member number was entered)
+    AuthenticationFailed(Option<nfc::Uid>),
+    /// User authentication successful (user id, NFC uid, none if a member number was entered)
+    UserAuthenticated(user::UserId, Option<nfc::Uid>),
     /// Article purchased (user id, article id, amount, total price)
     ArticlePurchased(user::UserId, article::ArticleId, f32, f32),
-    /// Error occured (optional user id, error message)
-    Error(Option<user::UserId>, String),
+    /// Purchase undone (user id, article id, amount, total price)
+    PurchaseUndone(user::UserId, article::ArticleId, f32, f32),
+    /// Entered power saving (idle time in seconds)
+    PowerSaveEntered(u64),
+    /// Left power saving (time spent power saving in seconds)
+    PowerSaveLeft(u64),
+    /// Battery voltage dropped below low threshold (battery voltage in millivolts)
+    BatteryLow(u16),
+    /// Error occured (optional user id, optional error code, error message)
+    Error(Option<user::UserId>, Option<u8>, String),
+    /// Events were dropped because the queue was full (number of dropped events)
+    EventsDropped(usize),
 }
 
 impl Event {
@@ -38,11 +60,18 @@ impl Event {
     pub fn event_name(&self) -> &'static str {
         match self {
             Event::SystemStart => "system_start",
+            Event::ReaderInitialized(..) => "reader_initialized",
             Event::DataRefreshed(..) => "data_refreshed",
+            Event::HeapUsage(..) => "heap_usage",
             Event::AuthenticationFailed(..) => "authentication_failed",
             Event::UserAuthenticated(..) => "user_authenticated",
             Event::ArticlePurchased(..) => "article_purchased",
+            Event::PurchaseUndone(..) => "purchase_undone",
+            Event::PowerSaveEntered(..) => "power_save_entered",
+            Event::PowerSaveLeft(..) => "power_save_left",
+            Event::BatteryLow(..) => "battery_low",
             Event::Error(..) => "error",
+            Event::EventsDropped(..) => "events_dropped",
         }
     }
 
@@ -51,11 +80,18 @@ impl Event {
         #[allow(clippy::match_same_arms)]
         match self {
             Event::SystemStart => None,
+            Event::ReaderInitialized(..) => None,
             Event::DataRefreshed(..) => None,
+            Event::HeapUsage(..) => None,
             Event::AuthenticationFailed(..) => None,
             Event::UserAuthenticated(user_id, ..) => Some(*user_id),
             Event::ArticlePurchased(user_id, ..) => Some(*user_id),
+            Event::PurchaseUndone(user_id, ..) => Some(*user_id),
+            Event::PowerSaveEntered(..) => None,
+            Event::PowerSaveLeft(..) => None,
+            Event::BatteryLow(..) => None,
             Event::Error(user_id, ..) => *user_id,
+            Event::EventsDropped(..) => None,
         }
     }
 
@@ -66,6 +102,15 @@ impl Event {
     ) -> Result<(), json::Error<W::Error>> {
         match self {
             Event::SystemStart => (),
+            Event::ReaderInitialized(version) => {
+                object
+                    .field("ic_version", version.ic)
+                    .await?
+                    .field("firmware_version", version.to_string())
+                    .await?
+                    .field("support", version.support)
+                    .await?;
+            }
             Event::DataRefreshed(article_count, uid_count, user_count) => {
                 object
                     .field("article_count", article_count)
@@ -75,13 +120,20 @@ impl Event {
                     .field("user_count", user_count)
                     .await?;
             }
-            Event::AuthenticationFailed(uid) => {
-                object.field("uid", uid).await?;
+            Event::HeapUsage(stats) => {
+                object
+                    .field("heap_used", stats.used)
+                    .await?
+                    .field("heap_free", stats.free)
+                    .await?
+                    .field("heap_peak", stats.peak)
+                    .await?;
             }
-            Event::UserAuthenticated(_user_id, uid) => {
-                object.field("uid", uid).await?;
+            Event::AuthenticationFailed(uid) | Event::UserAuthenticated(_, uid) => {
+                object.field_opt("uid", uid.as_ref()).await?;
             }
-            Event::ArticlePurchased(_user_id, article_id, amount, total_price) => {
+            Event::ArticlePurchased(_user_id, article_id, amount, total_price)
+            | Event::PurchaseUndone(_user_id, article_id, amount, total_price) => {
                 object
                     .field("article_id", article_id)
                     .await?
@@ -90,71 +142,417 @@ impl Event {
                     .field("total_price", total_price)
                     .await?;
             }
-            Event::Error(_user_id, message) => {
-                object.field("error_message", message).await?;
+            Event::PowerSaveEntered(idle_secs) => {
+                object.field("idle_time", idle_secs).await?;
+            }
+            Event::PowerSaveLeft(power_save_secs) => {
+                object.field("power_save_time", power_save_secs).await?;
+            }
+            Event::BatteryLow(millivolts) => {
+                object.field("battery_voltage", millivolts).await?;
+            }
+            Event::Error(_user_id, code, message) => {
+                object
+                    .field_opt("error_code", code.as_ref())
+                    .await?
+                    .field("error_message", message)
+                    .await?;
+            }
+            Event::EventsDropped(count) => {
+                object.field("dropped_count", count).await?;
             }
         }
         Ok(())
     }
 }
 
+/// Queue of events with limited capacity. The oldest events are dropped if it's full.
+#[derive(Debug)]
+struct EventQueue {
+    events: VecDeque<(Instant, Event)>,
+    capacity: usize,
+    dropped: usize,
+}
+
+impl EventQueue {
+    /// Create new event queue with given capacity
+    fn new(capacity: usize) -> Self {
+        Self {
+            events: VecDeque::new(),
+            capacity,
+            dropped: 0,
+        }
+    }
+
+    /// Number of queued events
+    fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Returns true if no events are queued
+    fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Add event. If the queue is full, the oldest event is dropped.
+    fn push(&mut self, time: Instant, event: Event) {
+        // A dropped events report may exceed the capacity, so more than one event may need to go
+        while self.events.len() >= self.capacity {
+            self.events.pop_front();
+            self.dropped += 1;
+        }
+        self.events.push_back((time, event));
+    }
+
+    /// Number of dropped events since last call (resets the count)
+    fn take_dropped(&mut self) -> usize {
+        mem::take(&mut self.dropped)
+    }
+
+    /// Add event reporting the number of dropped events since last call (resets the count). The
+    /// report is added even if the queue is full, so it doesn't drop yet another event. Returns
+    /// the number of dropped events.
+    fn push_dropped(&mut self, time: Instant) -> usize {
+        let dropped = self.take_dropped();
+        if dropped > 0 {
+            self.events.push_back((time, Event::EventsDropped(dropped)));
+        }
+        dropped
+    }
+
+    /// All queued events in order
+    fn as_slice(&mut self) -> &[(Instant, Event)] {
+        self.events.make_contiguous()
+    }
+
+    /// Remove all queued events
+    fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+/// Thresholds that decide when to flush events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FlushPolicy {
+    max_duration: Duration,
+    max_events: usize,
+}
+
+impl FlushPolicy {
+    /// Create flush policy with given thresholds. Thresholds are limited to sane values, i.e.
+    /// duration is at least `MIN_BUFFER_DURATION` and number of events is between 1 and the max
+    /// number of queued events.
+    fn new(max_duration: Duration, max_events: usize) -> Self {
+        Self {
+            max_duration: max_duration.max(MIN_BUFFER_DURATION),
+            max_events: max_events.clamp(1, MAX_QUEUED_EVENTS),
+        }
+    }
+
+    /// Returns true if events should be flushed, given the time since the last flush and the
+    /// number of buffered events
+    fn needs_flush(self, since_flush: Duration, events: usize) -> bool {
+        (since_flush >= self.max_duration && events > 0) || events >= self.max_events
+    }
+}
+
+impl Default for FlushPolicy {
+    fn default() -> Self {
+        Self::new(MAX_BUFFER_DURATION, MAX_BUFFER_EVENTS)
+    }
+}
+
 /// Telemetry for tracking events
 #[derive(Debug)]
 pub struct Telemetry<'a> {
     mixpanel: Option<Mixpanel<'a>>,
-    events: VecDeque<(Instant, Event)>,
+    events: EventQueue,
+    profiles: BTreeMap<user::UserId, String>,
+    flush_policy: FlushPolicy,
     last_flush: Instant,
 }
 
 impl<'a> Telemetry<'a> {
     /// Create new telemetry
-    pub fn new(mp_token: Option<&'a str>, device_id: &'a str) -> Self {
+    pub fn new(mp_token: Option<&'a str>, mp_region: mixpanel::Region, device_id: &'a str) -> Self {
         let mixpanel = if let Some(token) = mp_token {
-            info!("Telemetry: Initialized with Mixpanel token {}", token);
-            Some(Mixpanel::new(token, device_id))
+            info!(
+                "Telemetry: Initialized with Mixpanel token {} ({:?})",
+                token, mp_region
+            );
+            Some(Mixpanel::new(token, mp_region, device_id))
         } else {
             warn!("Telemetry: Disabled! No Mixpanel token.");
             None
         };
         Self {
             mixpanel,
-            events: VecDeque::new(),
+            events: EventQueue::new(MAX_QUEUED_EVENTS),
+            profiles: BTreeMap::new(),
+            flush_policy: FlushPolicy::default(),
             last_flush: Instant::now(),
         }
     }
 
+    /// Set name of this machine, which is reported with every event
+    pub fn set_machine_name(&mut self, name: &'a str) {
+        debug!("Telemetry: Reporting machine name {:?}", name);
+        if let Some(ref mut mixpanel) = self.mixpanel {
+            mixpanel.set_machine_name(name);
+        }
+    }
+
+    /// Set time after which events are flushed and max number of events to buffer before
+    /// flushing
+    pub fn set_flush_limits(&mut self, max_duration: Duration, max_events: usize) {
+        self.flush_policy = FlushPolicy::new(max_duration, max_events);
+        debug!(
+            "Telemetry: Flushing after {}s or {} events",
+            self.flush_policy.max_duration.as_secs(),
+            self.flush_policy.max_events
+        );
+    }
+
     /// Track event
     pub fn track(&mut self, event: Event) {
         if self.mixpanel.is_some() {
             debug!("Telemetry: tracking event {:?}", event);
-            self.events.push_back((Instant::now(), event));
+            self.events.push(Instant::now(), event);
+        }
+    }
+
+    /// Set profile of given user (submitted with next flush)
+    pub fn set_profile(&mut self, user_id: user::UserId, name: String) {
+        if self.mixpanel.is_some() {
+            debug!("Telemetry: setting profile of user {}", user_id);
+            self.profiles.insert(user_id, name);
         }
     }
 
     /// Returns true if buffer has filled up or time has ran out and events should be submitted
     pub fn needs_flush(&mut self) -> bool {
-        (self.last_flush.elapsed() >= MAX_BUFFER_DURATION && !self.events.is_empty())
-            || self.events.len() >= MAX_BUFFER_EVENTS
+        self.flush_policy
+            .needs_flush(self.last_flush.elapsed(), self.events.len())
+    }
+
+    /// Returns true if there are tracked events or profiles waiting to be submitted. Always false
+    /// if telemetry is disabled.
+    pub fn is_pending(&self) -> bool {
+        !self.events.is_empty() || !self.profiles.is_empty()
     }
 
     /// Submit tracked events to server
     pub async fn flush(&mut self, http: &mut Http<'_>) -> Result<(), Error> {
-        if self.events.is_empty() {
+        if self.events.is_empty() && self.profiles.is_empty() {
             return Ok(());
         }
 
         if let Some(ref mut mixpanel) = self.mixpanel {
-            debug!("Telemetry: Flushing {} events...", self.events.len());
+            // Report number of events that were dropped since the last flush
+            let dropped = self.events.push_dropped(Instant::now());
+            if dropped > 0 {
+                warn!("Telemetry: {} events dropped, queue was full", dropped);
+            }
+
+            debug!(
+                "Telemetry: Flushing {} events, {} profiles...",
+                self.events.len(),
+                self.profiles.len()
+            );
 
             let mut mp = mixpanel.connect(http).await?;
-            let events = self.events.make_contiguous();
-            mp.submit(events).await?;
+            if !self.events.is_empty() {
+                let events = self.events.as_slice();
+                mp.submit(events).await?;
+                self.events.clear();
+            }
+            // Setting a profile is idempotent, so all profiles are simply set again on failure
+            for (user_id, name) in &self.profiles {
+                // Connect again if the server closed the connection
+                if !mp.is_reusable() {
+                    drop(mp);
+                    mp = mixpanel.connect(http).await?;
+                }
+                mp.set_profile(*user_id, name).await?;
+            }
+            self.profiles.clear();
 
             debug!("Telemetry: Flush successful");
-            self.events.clear();
             self.last_flush = Instant::now();
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    async fn event_attributes(event: &Event) -> String {
+        let mut json = json::Writer::new(Vec::new());
+        let mut object = json.write_object().await.unwrap();
+        event.add_event_attributes(&mut object).await.unwrap();
+        object.finish().await.unwrap();
+        String::from_utf8(json.into_inner()).unwrap()
+    }
+
+    fn push(queue: &mut EventQueue, secs: u64) {
+        queue.push(Instant::from_secs(secs), Event::DataRefreshed(0, 0, 0));
+    }
+
+    #[test]
+    fn flush_policy_default() {
+        let policy = FlushPolicy::default();
+        assert!(!policy.needs_flush(Duration::from_secs(10), 0));
+        assert!(!policy.needs_flush(Duration::from_secs(10), 9));
+        assert!(policy.needs_flush(Duration::from_secs(10), 10));
+        assert!(!policy.needs_flush(Duration::from_secs(30), 0));
+        assert!(policy.needs_flush(Duration::from_secs(30), 1));
+    }
+
+    #[test]
+    fn flush_policy_configured() {
+        let policy = FlushPolicy::new(Duration::from_secs(120), 50);
+        assert!(!policy.needs_flush(Duration::from_secs(30), 10));
+        assert!(!policy.needs_flush(Duration::from_secs(119), 49));
+        assert!(policy.needs_flush(Duration::from_secs(119), 50));
+        assert!(policy.needs_flush(Duration::from_secs(120), 1));
+    }
+
+    #[test]
+    fn flush_policy_limits() {
+        let policy = FlushPolicy::new(Duration::from_secs(0), 0);
+        assert_eq!(policy.max_duration, MIN_BUFFER_DURATION);
+        assert_eq!(policy.max_events, 1);
+        let policy = FlushPolicy::new(Duration::from_secs(3600), 10000);
+        assert_eq!(policy.max_events, MAX_QUEUED_EVENTS);
+    }
+
+    #[async_std::test]
+    async fn heap_usage_attributes() {
+        let event = Event::HeapUsage(heap::Stats {
+            used: 81_920,
+            free: 71_680,
+            peak: 102_400,
+        });
+        assert_eq!(event.event_name(), "heap_usage");
+        assert_eq!(event.user_id(), None);
+        assert_eq!(
+            event_attributes(&event).await,
+            r#"{"heap_used": 81920, "heap_free": 71680, "heap_peak": 102400}"#
+        );
+    }
+
+    #[async_std::test]
+    async fn power_save_attributes() {
+        let event = Event::PowerSaveEntered(300);
+        assert_eq!(event.event_name(), "power_save_entered");
+        assert_eq!(event.user_id(), None);
+        assert_eq!(event_attributes(&event).await, r#"{"idle_time": 300}"#);
+        let event = Event::PowerSaveLeft(7200);
+        assert_eq!(event.event_name(), "power_save_left");
+        assert_eq!(event.user_id(), None);
+        assert_eq!(
+            event_attributes(&event).await,
+            r#"{"power_save_time": 7200}"#
+        );
+    }
+
+    #[async_std::test]
+    async fn error_attributes() {
+        let event = Event::Error(Some(1234), Some(0x30), "Vereinsflieger: Timeout".into());
+        assert_eq!(event.event_name(), "error");
+        assert_eq!(event.user_id(), Some(1234));
+        assert_eq!(
+            event_attributes(&event).await,
+            r#"{"error_code": 48, "error_message": "Vereinsflieger: Timeout"}"#
+        );
+        let event = Event::Error(None, None, "Buzzer: Failed".into());
+        assert_eq!(
+            event_attributes(&event).await,
+            r#"{"error_message": "Buzzer: Failed"}"#
+        );
+    }
+
+    #[test]
+    fn queue_below_capacity() {
+        let mut queue = EventQueue::new(3);
+        push(&mut queue, 1);
+        push(&mut queue, 2);
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.take_dropped(), 0);
+    }
+
+    #[test]
+    fn queue_drops_oldest() {
+        let mut queue = EventQueue::new(3);
+        for secs in 1..=5 {
+            push(&mut queue, secs);
+        }
+        assert_eq!(queue.len(), 3);
+        let times: Vec<u64> = queue
+            .as_slice()
+            .iter()
+            .map(|(time, _event)| time.as_secs())
+            .collect();
+        assert_eq!(times, [3, 4, 5]);
+    }
+
+    #[test]
+    fn queue_counts_dropped() {
+        let mut queue = EventQueue::new(3);
+        for secs in 1..=5 {
+            push(&mut queue, secs);
+        }
+        assert_eq!(queue.take_dropped(), 2);
+        assert_eq!(queue.take_dropped(), 0);
+        push(&mut queue, 6);
+        assert_eq!(queue.take_dropped(), 1);
+    }
+
+    #[test]
+    fn queue_full_nothing_dropped() {
+        let mut queue = EventQueue::new(3);
+        for secs in 1..=3 {
+            push(&mut queue, secs);
+        }
+        assert_eq!(queue.push_dropped(Instant::from_secs(4)), 0);
+        assert_eq!(queue.len(), 3);
+    }
+
+    #[test]
+    fn queue_full_reports_dropped() {
+        let mut queue = EventQueue::new(3);
+        for secs in 1..=5 {
+            push(&mut queue, secs);
+        }
+        assert_eq!(queue.push_dropped(Instant::from_secs(6)), 2);
+        assert_eq!(queue.len(), 4);
+        let times: Vec<u64> = queue
+            .as_slice()
+            .iter()
+            .map(|(time, _event)| time.as_secs())
+            .collect();
+        assert_eq!(times, [3, 4, 5, 6]);
+        assert!(matches!(queue.as_slice()[3].1, Event::EventsDropped(2)));
+        // Adding the report must not drop another event
+        assert_eq!(queue.take_dropped(), 0);
+        assert_eq!(queue.push_dropped(Instant::from_secs(7)), 0);
+    }
+
+    #[test]
+    fn queue_shrinks_after_report() {
+        let mut queue = EventQueue::new(3);
+        for secs in 1..=4 {
+            push(&mut queue, secs);
+        }
+        queue.push_dropped(Instant::from_secs(5));
+        assert_eq!(queue.len(), 4);
+        // If the report wasn't flushed, the next event brings the queue back to capacity
+        push(&mut queue, 6);
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.take_dropped(), 2);
+    }
+}
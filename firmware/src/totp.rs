@@ -0,0 +1,149 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use chrono::{DateTime, Utc};
+use core::fmt;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+/// Time step of codes in seconds
+const TIME_STEP: u64 = 30;
+
+/// Number of digits of codes
+const DIGITS: u32 = 6;
+
+/// Time-based one-time password generator (TOTP, RFC 6238)
+///
+/// Generates codes for two-factor authentication from a shared secret and the current time, like
+/// authenticator apps do (HMAC-SHA1, 30 second time step, 6 digits).
+pub struct Totp {
+    key: Vec<u8>,
+}
+
+impl fmt::Debug for Totp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Totp").field("key", &"<redacted>").finish()
+    }
+}
+
+impl Totp {
+    /// Create TOTP generator from given shared secret (raw bytes)
+    pub fn new(key: Vec<u8>) -> Self {
+        Self { key }
+    }
+
+    /// Create TOTP generator from given shared secret in base32 encoding (as usually shown when
+    /// setting up two-factor authentication). Returns `None` if the secret isn't valid base32.
+    pub fn from_base32(secret: &str) -> Option<Self> {
+        let key = base32_decode(secret)?;
+        if key.is_empty() {
+            return None;
+        }
+        Some(Self::new(key))
+    }
+
+    /// Code for the given time
+    pub fn code(&self, time: DateTime<Utc>) -> String {
+        let secs = u64::try_from(time.timestamp()).unwrap_or(0);
+        format!(
+            "{:0width$}",
+            self.hotp(secs / TIME_STEP) % 10_u32.pow(DIGITS),
+            width = DIGITS as usize
+        )
+    }
+
+    /// HMAC-based one-time value for the given counter (RFC 4226), before reduction to digits
+    fn hotp(&self, counter: u64) -> u32 {
+        // HMAC-SHA1 accepts keys of any length
+        let mut mac = Hmac::<Sha1>::new_from_slice(&self.key).unwrap();
+        mac.update(&counter.to_be_bytes());
+        let hash = mac.finalize().into_bytes();
+        // Dynamic truncation
+        let offset = usize::from(hash[hash.len() - 1] & 0x0f);
+        let bytes = [
+            hash[offset],
+            hash[offset + 1],
+            hash[offset + 2],
+            hash[offset + 3],
+        ];
+        u32::from_be_bytes(bytes) & 0x7fff_ffff
+    }
+}
+
+/// Decode base32 (RFC 4648) string. Case-insensitive, ignores spaces and padding.
+fn base32_decode(s: &str) -> Option<Vec<u8>> {
+    let mut bytes = vec![];
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for ch in s.bytes() {
+        let value = match ch {
+            b'A'..=b'Z' => ch - b'A',
+            b'a'..=b'z' => ch - b'a',
+            b'2'..=b'7' => ch - b'2' + 26,
+            b' ' | b'=' => continue,
+            _ => return None,
+        };
+        buffer = (buffer << 5) | u32::from(value);
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            // Truncation intended, only the lowest 8 bits are taken
+            #[allow(clippy::cast_possible_truncation)]
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Shared secret of RFC 6238 test vectors (SHA1)
+    const RFC_KEY: &[u8] = b"12345678901234567890";
+
+    fn time(secs: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(secs, 0).unwrap()
+    }
+
+    #[test]
+    fn rfc6238_vectors() {
+        // RFC 6238 test vectors use 8 digits
+        let totp = Totp::new(RFC_KEY.to_vec());
+        let hotp = |secs: u64| totp.hotp(secs / TIME_STEP) % 100_000_000;
+        assert_eq!(hotp(59), 94_287_082);
+        assert_eq!(hotp(1_111_111_109), 7_081_804);
+        assert_eq!(hotp(1_111_111_111), 14_050_471);
+        assert_eq!(hotp(1_234_567_890), 89_005_924);
+        assert_eq!(hotp(2_000_000_000), 69_279_037);
+        assert_eq!(hotp(20_000_000_000), 65_353_130);
+    }
+
+    #[test]
+    fn code() {
+        let totp = Totp::new(RFC_KEY.to_vec());
+        assert_eq!(totp.code(time(59)), "287082");
+        assert_eq!(totp.code(time(1_111_111_109)), "081804");
+        assert_eq!(totp.code(time(1_234_567_890)), "005924");
+        // Same code within a time step
+        assert_eq!(totp.code(time(1_234_567_919)), "005924");
+    }
+
+    #[test]
+    fn from_base32() {
+        // Base32 encoding of the RFC 6238 shared secret
+        let totp = Totp::from_base32("GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ").unwrap();
+        assert_eq!(totp.key, RFC_KEY);
+        let totp = Totp::from_base32("gezd gnbv gy3t qojq gezd gnbv gy3t qojq").unwrap();
+        assert_eq!(totp.key, RFC_KEY);
+        assert!(Totp::from_base32("").is_none());
+        assert!(Totp::from_base32("GEZDGNBV1").is_none());
+    }
+
+    #[test]
+    fn base32() {
+        assert_eq!(base32_decode("MZXW6YQ=").unwrap(), b"foob");
+        assert_eq!(base32_decode("MZXW6YTBOI======").unwrap(), b"foobar");
+        assert_eq!(base32_decode("").unwrap(), b"");
+    }
+}
@@ -1,48 +1,106 @@
 use crate::article::{Article, ArticleId, Articles};
-use crate::buzzer::Buzzer;
+use crate::battery;
+use crate::buzzer::{self, Buzzer};
+use crate::cache::Cache;
+use crate::config::{Config, Settings};
+use crate::cooldown::{self, Cooldown};
+use crate::demo::{self, DemoLog};
 use crate::display::Display;
 use crate::error::{Error, ErrorKind};
+use crate::heap;
 use crate::http::Http;
-use crate::keypad::{Key, Keypad};
-use crate::nfc::Nfc;
-use crate::schedule::Daily;
-use crate::screen;
+use crate::idle::{self, Idle};
+use crate::keypad::{self, Entry, Key, KeyPress, KeySequence, Keypad, NumberEntry};
+use crate::led;
+use crate::nfc::{Nfc, Uid};
+use crate::queue::{Purchase, Queue};
+use crate::schedule::{Job, Scheduler};
+use crate::screen::{self, Screen};
+use crate::sleep::{Sleep, WakeupPin};
 use crate::telemetry::{Event, Telemetry};
+use crate::time;
+use crate::undo::{self, UndoWindow, UNDO_WINDOW};
 use crate::user::{UserId, Users};
 use crate::vereinsflieger::Vereinsflieger;
 use crate::wifi::Wifi;
+use alloc::format;
 use alloc::string::{String, ToString};
+use chrono::{DateTime, FixedOffset};
+use core::cell::Cell;
 use core::convert::Infallible;
-use embassy_futures::select::{select, Either};
-use embassy_time::{with_timeout, Duration, TimeoutError, Timer};
+use core::future::Future;
+use core::pin::pin;
+use embassy_futures::select::{select, select3, Either, Either3};
+use embassy_time::{with_deadline, with_timeout, Duration, Instant, TimeoutError, Timer};
 use embedded_hal_async::digital::Wait;
 use embedded_hal_async::i2c::I2c;
-use log::info;
+use log::{debug, error, info, warn};
 use rand_core::RngCore;
 
-/// How long to show the splash screen if no key is pressed
-const SPLASH_TIMEOUT: Duration = Duration::from_secs(5);
+/// Default time to show the splash screen if no key is pressed
+const DEFAULT_SPLASH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Key sequence to enter the admin menu while the splash screen is shown
+const ADMIN_KEY_SEQUENCE: [Key; 4] = [Key::Cancel, Key::Enter, Key::Cancel, Key::Enter];
+
+/// Max number of digits of the admin PIN and member PINs
+const PIN_MAX_DIGITS: usize = 9;
+
+/// How long to hold the cancel key to clear all digits of a PIN or member number
+const CLEAR_ENTRY_HOLD_TIME: Duration = Duration::from_secs(1);
+
+/// Delay after a wrong admin PIN to slow down guessing
+const ADMIN_PIN_FAILURE_DELAY: Duration = Duration::from_secs(5);
 
 /// How long to wait for network to become available
 const NETWORK_TIMEOUT: Duration = Duration::from_secs(30);
 
-/// Timeout for user input. Actions are cancelled if the user does nothing for this duration.
-#[cfg(not(debug_assertions))]
-const USER_TIMEOUT: Duration = Duration::from_secs(60);
-#[cfg(debug_assertions)]
-const USER_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often to advance animations of screens (e.g. scrolling text or wait spinners)
+const ANIMATION_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Default total price in EUR above which a purchase needs an extra confirmation
+const DEFAULT_LARGE_PURCHASE_THRESHOLD: f32 = 20.0;
+
+/// Max number of digits of member numbers entered instead of scanning an id card
+const MEMBER_ID_MAX_DIGITS: usize = 9;
+
+/// Delay after the first failed authentication by member number. Doubles with every further
+/// failure until a member authenticates successfully.
+const MEMBER_ID_FAILURE_DELAY: Duration = Duration::from_secs(2);
+
+/// Max delay after unknown member numbers
+const MEMBER_ID_MAX_FAILURE_DELAY: Duration = Duration::from_secs(60);
+
+/// How often to update the clock on the id card scan screen
+const CLOCK_UPDATE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long to show that a purchase was undone if no key is pressed
+const UNDONE_TIMEOUT: Duration = Duration::from_secs(5);
 
-/// Timeout for initial screen. Power saving is activated if no action is taken for this duration.
+/// How often to retry submitting pending purchases while the network is up
+const PURCHASE_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Default timeout for user input. Actions are cancelled if the user does nothing for this
+/// duration.
 #[cfg(not(debug_assertions))]
-const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+const DEFAULT_USER_TIMEOUT: Duration = Duration::from_secs(60);
 #[cfg(debug_assertions)]
-const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_USER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Minimum timeout for user input, shorter configured timeouts are raised to this
+const MIN_USER_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often to flash the display when showing an error (if enabled)
+const ERROR_FLASHES: usize = 2;
+
+/// How long to wait for Wifi to stop before going to sleep
+const WIFI_STOP_TIMEOUT: Duration = Duration::from_secs(2);
 
 /// User interface
 pub struct Ui<'a, RNG, I2C, IRQ> {
     rng: RNG,
     display: &'a mut Display<I2C>,
-    keypad: &'a mut Keypad<'a, 3, 4>,
+    keypad: &'a mut Keypad<'a, { keypad::COLS }, { keypad::ROWS }>,
     nfc: &'a mut Nfc<I2C, IRQ>,
     buzzer: &'a mut Buzzer<'a>,
     wifi: &'a Wifi,
@@ -50,17 +108,29 @@ pub struct Ui<'a, RNG, I2C, IRQ> {
     vereinsflieger: &'a mut Vereinsflieger<'a>,
     articles: &'a mut Articles,
     users: &'a mut Users,
+    queue: &'a mut Queue,
+    cache: &'a mut Cache,
     telemetry: &'a mut Telemetry<'a>,
-    schedule: &'a mut Daily,
+    schedule: &'a mut Scheduler,
+    sleep: &'a mut Sleep,
+    config: &'a Config,
+    device_id: &'a str,
+    idle: Idle,
+    user_timeout: Duration,
+    splash_timeout: Duration,
+    error_flash: bool,
+    member_id_failures: u32,
+    denials: Cooldown<Uid>,
+    demo: Option<DemoLog>,
 }
 
-impl<'a, RNG: RngCore, I2C: I2c, IRQ: Wait<Error = Infallible>> Ui<'a, RNG, I2C, IRQ> {
+impl<'a, RNG: RngCore, I2C: I2c, IRQ: Wait<Error = Infallible> + WakeupPin> Ui<'a, RNG, I2C, IRQ> {
     /// Create user interface with given human interface devices
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         rng: RNG,
         display: &'a mut Display<I2C>,
-        keypad: &'a mut Keypad<'a, 3, 4>,
+        keypad: &'a mut Keypad<'a, { keypad::COLS }, { keypad::ROWS }>,
         nfc: &'a mut Nfc<I2C, IRQ>,
         buzzer: &'a mut Buzzer<'a>,
         wifi: &'a Wifi,
@@ -68,8 +138,13 @@ impl<'a, RNG: RngCore, I2C: I2c, IRQ: Wait<Error = Infallible>> Ui<'a, RNG, I2C,
         vereinsflieger: &'a mut Vereinsflieger<'a>,
         articles: &'a mut Articles,
         users: &'a mut Users,
+        queue: &'a mut Queue,
+        cache: &'a mut Cache,
         telemetry: &'a mut Telemetry<'a>,
-        schedule: &'a mut Daily,
+        schedule: &'a mut Scheduler,
+        sleep: &'a mut Sleep,
+        config: &'a Config,
+        device_id: &'a str,
     ) -> Self {
         Self {
             rng,
@@ -82,49 +157,307 @@ impl<'a, RNG: RngCore, I2C: I2c, IRQ: Wait<Error = Infallible>> Ui<'a, RNG, I2C,
             vereinsflieger,
             articles,
             users,
+            queue,
+            cache,
             telemetry,
             schedule,
+            sleep,
+            config,
+            device_id,
+            idle: Idle::new(Instant::now()),
+            user_timeout: DEFAULT_USER_TIMEOUT,
+            splash_timeout: DEFAULT_SPLASH_TIMEOUT,
+            error_flash: false,
+            member_id_failures: 0,
+            denials: Cooldown::new(cooldown::DEFAULT_WINDOW),
+            demo: config.demo_mode.unwrap_or(false).then(DemoLog::new),
         }
     }
 
-    /// Save power by turning off devices not needed during idle
+    /// Apply settings that can be changed at runtime
+    pub async fn apply_settings(&mut self, settings: &Settings) -> Result<(), Error> {
+        self.display.set_language(settings.language);
+        self.display
+            .set_brightness(settings.display_brightness)
+            .await?;
+        self.buzzer
+            .set_volume(settings.buzzer_volume.unwrap_or(buzzer::DEFAULT_VOLUME));
+        self.buzzer
+            .set_muted(settings.buzzer_muted.unwrap_or(false));
+        self.buzzer
+            .set_key_feedback(settings.key_feedback.unwrap_or(false));
+        self.error_flash = settings.error_flash.unwrap_or(false);
+        self.idle.set_timeouts(
+            settings
+                .idle_dim_timeout
+                .map_or(idle::DEFAULT_DIM_TIMEOUT, |secs| {
+                    Duration::from_secs(secs.into())
+                }),
+            settings
+                .idle_power_save_timeout
+                .map_or(idle::DEFAULT_POWER_SAVE_TIMEOUT, |secs| {
+                    Duration::from_secs(secs.into())
+                }),
+        );
+        self.idle.set_sleep_timeout(
+            settings
+                .idle_sleep_timeout
+                .map(|secs| Duration::from_secs(secs.into())),
+        );
+        self.user_timeout = user_timeout(settings.user_timeout);
+        self.splash_timeout = settings
+            .splash_timeout
+            .map_or(DEFAULT_SPLASH_TIMEOUT, |secs| {
+                Duration::from_secs(secs.into())
+            });
+        Ok(())
+    }
+
+    /// Reload configuration and apply settings that can be changed at runtime. Shows whether a
+    /// restart is needed to apply other changed settings.
+    pub async fn reload_config(&mut self) -> Result<(), Error> {
+        info!("UI: Reloading configuration...");
+
+        let config = Config::read().await;
+        self.apply_settings(&config.settings()).await?;
+
+        let restart_required = self.config.requires_restart(&config);
+        if restart_required {
+            warn!("UI: Changed configuration requires a restart");
+        }
+
+        self.display
+            .screen(&screen::ConfigReloaded::new(restart_required))
+            .await?;
+        let _ = with_timeout(self.user_timeout, self.keypad.read()).await;
+        Ok(())
+    }
+
+    /// Save power by turning off devices not needed during idle. The NFC reader keeps polling for
+    /// cards at a low rate.
     pub async fn power_save(&mut self) -> Result<(), Error> {
         info!("UI: Power saving...");
 
         self.display.turn_off().await?;
+        self.nfc.set_low_power(true);
+        Ok(())
+    }
+
+    /// Leave power saving by turning on devices again
+    pub async fn power_up(&mut self) -> Result<(), Error> {
+        info!("UI: Leaving power saving...");
+
+        self.display.set_dimmed(false).await?;
+        self.nfc.set_low_power(false);
+        self.nfc.power_up().await?;
         Ok(())
     }
 
-    /// Show splash screen and wait for keypress or timeout
+    /// Go to sleep during power saving. Shuts down the remaining devices and Wifi and puts the
+    /// system into light sleep until a key is pressed or the NFC reader detects a RF field.
+    /// `power_up` needs to be called after waking up.
+    pub async fn sleep(&mut self) -> Result<(), Error> {
+        info!("UI: Going to sleep...");
+
+        let _ = self.buzzer.off();
+        // NFC reader stops polling and needs to wake up on RF field now
+        self.nfc.power_down_with_wakeup().await?;
+        // Wifi is never started in demo mode
+        if self.demo.is_none() {
+            self.wifi.stop();
+            if with_timeout(WIFI_STOP_TIMEOUT, self.wifi.wait_stopped())
+                .await
+                .is_err()
+            {
+                warn!("UI: Timeout waiting for Wifi to stop");
+            }
+        }
+
+        self.keypad.set_wakeup(true);
+        self.nfc.set_wakeup(true);
+        self.sleep.light_sleep();
+        self.keypad.set_wakeup(false);
+        self.nfc.set_wakeup(false);
+
+        info!("UI: Woke up from sleep");
+        if self.demo.is_none() {
+            self.wifi.reconnect();
+        }
+        Ok(())
+    }
+
+    /// Show splash screen and wait for keypress or timeout. Enter the admin menu if the admin
+    /// key sequence is pressed.
     pub async fn show_splash(&mut self) -> Result<(), Error> {
         info!("UI: Displaying splash screen");
 
-        self.display.screen(&screen::Splash).await?;
+        let machine_name = self.config.machine_name.as_deref().unwrap_or_default();
+        self.display
+            .screen(&screen::Splash::new(machine_name))
+            .await?;
+
+        let deadline = Instant::now() + self.splash_timeout;
+        let mut admin_sequence = KeySequence::new(ADMIN_KEY_SEQUENCE);
+        while let Ok(key) = with_deadline(deadline, self.keypad.read()).await {
+            if admin_sequence.feed(key) {
+                if self.authenticate_admin().await? {
+                    return self.admin_menu().await;
+                }
+                return Ok(());
+            }
+            // Any other key skips the splash screen
+            if admin_sequence.is_idle() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Ask for the admin PIN. Returns true if the configured admin PIN was entered. If no admin
+    /// PIN is configured, the admin menu can't be entered.
+    async fn authenticate_admin(&mut self) -> Result<bool, Error> {
+        let Some(pin) = self.config.admin_pin.as_deref() else {
+            warn!("UI: No admin PIN configured, admin menu disabled");
+            let _ = self.buzzer.deny().await;
+            return Ok(false);
+        };
+
+        info!("UI: Asking for admin PIN...");
+
+        let Some(digits) = self.read_pin(true).await? else {
+            return Ok(false);
+        };
+        if digits != pin {
+            warn!("UI: Wrong admin PIN");
+            let _ = self.buzzer.deny().await;
+            Timer::after(ADMIN_PIN_FAILURE_DELAY).await;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Read a PIN (admin PIN or member PIN). Returns the entered digits or `None` if cancelled
+    /// or timed out.
+    async fn read_pin(&mut self, admin: bool) -> Result<Option<String>, Error> {
+        let mut entry = NumberEntry::new(PIN_MAX_DIGITS);
+        loop {
+            let len = entry.digits().len();
+            let screen = if admin {
+                screen::EnterPin::admin(len)
+            } else {
+                screen::EnterPin::member(len)
+            };
+            self.display.screen(&screen).await?;
+            let Ok(press) = with_timeout(
+                self.user_timeout,
+                self.keypad.read_long_press(CLEAR_ENTRY_HOLD_TIME),
+            )
+            .await
+            else {
+                return Ok(None);
+            };
+            match feed_entry(self.buzzer, &mut entry, press).await {
+                Entry::Pending => (),
+                Entry::Done(_) => return Ok(Some(entry.digits().to_string())),
+                Entry::Cancelled => return Ok(None),
+            }
+        }
+    }
 
-        let _ = with_timeout(SPLASH_TIMEOUT, self.keypad.read()).await;
+    /// Ask to confirm discarding all pending purchases. Returns true if confirmed.
+    async fn confirm_clear_queue(&mut self) -> Result<bool, Error> {
+        self.display
+            .screen(&screen::ConfirmClearQueue::new(self.queue.len()))
+            .await?;
+        loop {
+            match with_timeout(self.user_timeout, self.keypad.read()).await {
+                // Enter key confirms
+                Ok(Key::Enter) => break Ok(true),
+                // Cancel key or timeout cancels
+                Ok(Key::Cancel) | Err(TimeoutError) => break Ok(false),
+                // Ignore any other key
+                Ok(_) => (),
+            }
+        }
+    }
+
+    /// Show admin menu and run selected actions until cancelled or timed out
+    pub async fn admin_menu(&mut self) -> Result<(), Error> {
+        info!("UI: Entering admin menu");
+
+        loop {
+            self.display
+                .screen(&screen::AdminMenu::new(self.queue.len()))
+                .await?;
+
+            #[allow(clippy::match_same_arms)]
+            match with_timeout(self.user_timeout, self.keypad.read()).await {
+                // Refresh article and user information
+                Ok(Key::Digit(1)) => self.refresh_articles_and_users().await?,
+                // Show device id
+                Ok(Key::Digit(2)) => {
+                    self.display
+                        .screen(&screen::DeviceInfo::new(
+                            self.device_id,
+                            self.nfc.firmware_version(),
+                        ))
+                        .await?;
+                    let _ = with_timeout(self.user_timeout, self.keypad.read()).await;
+                }
+                // Discard pending purchases (after confirmation)
+                Ok(Key::Digit(3)) => {
+                    if !self.queue.is_empty() && self.confirm_clear_queue().await? {
+                        self.queue.clear().await;
+                    }
+                }
+                // Reload configuration
+                Ok(Key::Digit(4)) => self.reload_config().await?,
+                // Cancel key leaves admin menu
+                Ok(Key::Cancel) => break,
+                // Ignore any other key
+                Ok(_) => (),
+                // User interaction timeout leaves admin menu
+                Err(TimeoutError) => break,
+            }
+        }
+
+        info!("UI: Leaving admin menu");
         Ok(())
     }
 
     /// Show error screen and wait for keypress or timeout
     pub async fn show_error(&mut self, error: &Error) -> Result<(), Error> {
         info!("UI: Displaying error: {}", error);
+        led::set(led::State::Error);
+
+        self.display
+            .screen(&screen::Failure::new(error, Some(error.code())))
+            .await?;
 
-        self.display.screen(&screen::Failure::new(error)).await?;
+        // Briefly flash the display to draw attention to the error
+        if self.error_flash {
+            if let Err(err) = self.display.flash(ERROR_FLASHES).await {
+                warn!("UI: Failed to flash display: {}", err);
+            }
+        }
 
         // Sound the error buzzer if the error was caused by a user's interaction
         if error.user_id().is_some() {
             let _ = self.buzzer.error().await;
         }
 
-        self.telemetry
-            .track(Event::Error(error.user_id(), error.to_string()));
+        self.telemetry.track(Event::Error(
+            error.user_id(),
+            Some(error.code()),
+            error.to_string(),
+        ));
 
         // Wait at least 1s without responding to keypad
         let min_time = Duration::from_secs(1);
         Timer::after(min_time).await;
 
         let wait_cancel = async { while self.keypad.read().await != Key::Cancel {} };
-        match with_timeout(USER_TIMEOUT - min_time, wait_cancel).await {
+        match with_timeout(self.user_timeout - min_time, wait_cancel).await {
             // Cancel key cancels
             Ok(()) => Ok(()),
             // User interaction timeout
@@ -140,13 +473,12 @@ impl<'a, RNG: RngCore, I2C: I2c, IRQ: Wait<Error = Infallible>> Ui<'a, RNG, I2C,
         }
 
         info!("UI: Waiting for network to become available...");
+        led::set(led::State::Connecting);
 
-        self.display
-            .screen(&screen::PleaseWait::WifiConnecting)
-            .await?;
-
-        let wait_cancel = async { while self.keypad.read().await != Key::Cancel {} };
-        match with_timeout(NETWORK_TIMEOUT, select(self.wifi.wait_up(), wait_cancel)).await {
+        let keypad = &mut *self.keypad;
+        let wait_cancel = async { while keypad.read().await != Key::Cancel {} };
+        let wait_up = with_timeout(NETWORK_TIMEOUT, select(self.wifi.wait_up(), wait_cancel));
+        match with_spinner(self.display, screen::PleaseWait::WifiConnecting, wait_up).await? {
             // Network has become available
             Ok(Either::First(())) => Ok(()),
             // Cancel key cancels
@@ -156,38 +488,77 @@ impl<'a, RNG: RngCore, I2C: I2c, IRQ: Wait<Error = Infallible>> Ui<'a, RNG, I2C,
         }
     }
 
-    /// Refresh article and user information
+    /// Refresh article and user information (not in demo mode)
     pub async fn refresh_articles_and_users(&mut self) -> Result<(), Error> {
+        if self.demo.is_some() {
+            return Ok(());
+        }
+
         // Wait for network to become available (if not already)
         self.wait_network_up().await?;
 
         info!("UI: Refreshing articles and users...");
 
-        self.display
-            .screen(&screen::PleaseWait::UpdatingData)
-            .await?;
-
-        // Connect to Vereinsflieger API
-        let mut vf = self.vereinsflieger.connect(self.http).await?;
+        let keypad = &mut *self.keypad;
+        let wait_cancel = async { while keypad.read().await != Key::Cancel {} };
+        let (vereinsflieger, http) = (&mut *self.vereinsflieger, &mut *self.http);
+        let (articles, users) = (&mut *self.articles, &mut *self.users);
+        // Previous number of users to estimate progress (best guess, total isn't known before)
+        let expected_users = users.count();
+        let parsed_users = Cell::new(0);
+        let refresh = async {
+            // Connect to Vereinsflieger API
+            let mut vf = vereinsflieger.connect(http).await?;
+
+            // Show authenticated user information when debugging
+            #[cfg(debug_assertions)]
+            vf.get_user_information().await?;
+
+            // Refresh article information
+            vf.refresh_articles(articles).await?;
+
+            // Connect again if the server closed the connection
+            if !vf.is_reusable() {
+                drop(vf);
+                vf = vereinsflieger.connect(http).await?;
+            }
 
-        // Show authenticated user information when debugging
-        #[cfg(debug_assertions)]
-        vf.get_user_information().await?;
+            // Refresh user information
+            vf.refresh_users(users, |count| parsed_users.set(count))
+                .await?;
 
-        // Refresh article information
-        vf.refresh_articles(self.articles).await?;
+            // Sample heap usage while refreshed information and the connection are in memory
+            heap::sample();
 
-        // Refresh user information
-        vf.refresh_users(self.users).await?;
+            Ok::<(), Error>(())
+        };
+        // Show estimated progress while refreshing users. If there's no estimate (no users known
+        // before), only the spinner is animated.
+        let progress = || {
+            let count = parsed_users.get();
+            (count > 0 && expected_users > 0).then(|| {
+                screen::PleaseWait::UpdatingDataProgress(refresh_progress(count, expected_users))
+            })
+        };
+        let refresh = with_spinner_update(
+            self.display,
+            screen::PleaseWait::UpdatingData,
+            refresh,
+            progress,
+        );
+        // Connection to Vereinsflieger API is closed when done or cancelled
+        #[allow(clippy::large_futures)]
+        cancellable(refresh, wait_cancel).await???;
 
-        // Close connection to Vereinsflieger API
-        drop(vf);
+        // Store refreshed information to cache (if changed)
+        self.cache.save(self.articles, self.users).await;
 
         self.telemetry.track(Event::DataRefreshed(
             self.articles.count(),
             self.users.count_uids(),
             self.users.count(),
         ));
+        self.telemetry.track(Event::HeapUsage(heap::stats()));
 
         // Submit telemetry data if needed
         self.submit_telemetry().await?;
@@ -195,23 +566,96 @@ impl<'a, RNG: RngCore, I2C: I2c, IRQ: Wait<Error = Infallible>> Ui<'a, RNG, I2C,
         Ok(())
     }
 
-    /// Submit telemetry data if needed
+    /// Submit pending purchases if network is available (not in demo mode)
+    pub async fn submit_purchases(&mut self) -> Result<(), Error> {
+        if self.queue.is_empty() || !self.wifi.is_up() || self.demo.is_some() {
+            return Ok(());
+        }
+
+        info!("UI: Submitting {} pending purchases...", self.queue.len());
+
+        self.display.screen(&screen::PleaseWait::Purchasing).await?;
+
+        let keypad = &mut *self.keypad;
+        let mut wait_cancel = pin!(async { while keypad.read().await != Key::Cancel {} });
+
+        // Submit pending purchases in the order they were made. Each purchase is removed from
+        // the queue right after it was stored, so it's never submitted twice. A purchase that
+        // the server rejects is dropped and reported, so it doesn't block subsequent purchases.
+        // On any other error, purchases are kept and submitting is retried later.
+        'connect: while !self.queue.is_empty() {
+            // Connect to Vereinsflieger API. On error, keep purchases and try again later.
+            let connect = self.vereinsflieger.connect(self.http);
+            #[allow(clippy::large_futures)]
+            let mut vf = match cancellable(connect, wait_cancel.as_mut()).await? {
+                Ok(vf) => vf,
+                Err(err) => {
+                    warn!("UI: Unable to submit pending purchases: {}", err);
+                    break;
+                }
+            };
+
+            while let Some(purchase) = self.queue.front().cloned() {
+                let article_name = self
+                    .articles
+                    .get(&purchase.article_id)
+                    .map(|article| article.name.as_str());
+                let submit = vf.purchase(&purchase, article_name);
+                #[allow(clippy::large_futures)]
+                match cancellable(submit, wait_cancel.as_mut()).await? {
+                    Ok(()) => (),
+                    Err(err) if err.is_rejected() => {
+                        error!("UI: Dropping rejected purchase {}: {}", purchase.id, err);
+                        self.telemetry.track(Event::Error(
+                            Some(purchase.user_id),
+                            None,
+                            format!("Purchase {} rejected ({err})", purchase.id),
+                        ));
+                    }
+                    Err(err) => {
+                        warn!("UI: Unable to submit purchase {}: {}", purchase.id, err);
+                        break 'connect;
+                    }
+                }
+                self.queue.remove(purchase.id).await;
+
+                // Connect again if the server closed the connection
+                if !vf.is_reusable() {
+                    continue 'connect;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Submit telemetry data if needed (not in demo mode)
     pub async fn submit_telemetry(&mut self) -> Result<(), Error> {
-        if !self.telemetry.needs_flush() {
+        if !self.telemetry.needs_flush() || self.demo.is_some() {
             return Ok(());
         }
 
-        // Wait for network to become available (if not already)
-        self.wait_network_up().await?;
+        self.flush_telemetry().await
+    }
 
-        info!("UI: Submitting telemetry data...");
+    /// Submit telemetry data, even if the buffer isn't filled yet (not in demo mode). Does nothing
+    /// if telemetry is disabled or there's nothing to submit. If the network isn't available,
+    /// telemetry data is kept and submitted next time.
+    async fn flush_telemetry(&mut self) -> Result<(), Error> {
+        if self.demo.is_some() || !self.telemetry.is_pending() {
+            return Ok(());
+        }
 
-        self.display
-            .screen(&screen::PleaseWait::SubmittingTelemetry)
-            .await?;
+        if !self.wifi.is_up() {
+            debug!("UI: Network not available, keeping telemetry data");
+            return Ok(());
+        }
+
+        info!("UI: Submitting telemetry data...");
 
         // Submit telemetry data, ignore any error
-        let _ = self.telemetry.flush(self.http).await;
+        let flush = self.telemetry.flush(self.http);
+        let _ = with_spinner(self.display, screen::PleaseWait::SubmittingTelemetry, flush).await?;
 
         Ok(())
     }
@@ -221,6 +665,27 @@ impl<'a, RNG: RngCore, I2C: I2c, IRQ: Wait<Error = Infallible>> Ui<'a, RNG, I2C,
         // Show splash screen for a while
         self.show_splash().await?;
 
+        // In demo mode, start right away with locally known articles and users. Unknown id cards
+        // are accepted as demo user.
+        if self.demo.is_some() {
+            let count = demo::add_placeholder_articles(self.articles);
+            demo::add_demo_user(self.users);
+            info!(
+                "UI: Demo mode, using {} articles ({} placeholders) and {} users",
+                self.articles.count(),
+                count,
+                self.users.count()
+            );
+            return Ok(());
+        }
+
+        // If cached articles and users were loaded, start right away and refresh when idle
+        if self.cache.is_valid() {
+            info!("UI: Using cached articles and users, refreshing later");
+            self.schedule.run_now(Job::RefreshData);
+            return Ok(());
+        }
+
         // Wait for network to become available (if not already)
         self.wait_network_up().await?;
 
@@ -232,28 +697,65 @@ impl<'a, RNG: RngCore, I2C: I2c, IRQ: Wait<Error = Infallible>> Ui<'a, RNG, I2C,
 
     /// Run the user interface flow
     pub async fn run(&mut self) -> Result<(), Error> {
+        // Submit pending purchases if possible
+        self.submit_purchases().await?;
+
+        // Report low battery (if running on battery)
+        if let Some(millivolts) = battery::take_low() {
+            self.telemetry.track(Event::BatteryLow(millivolts));
+        }
+
         // Submit telemetry data if needed
         self.submit_telemetry().await?;
 
-        // Either wait for id card read or schedule time
+        // Either wait for id card read, schedule time or for pending purchases to become
+        // submittable (network came back up or retry interval elapsed)
+        self.schedule.adjust();
         let schedule_timer = self.schedule.timer();
-        let user_id = match select(self.authenticate_user(), schedule_timer).await {
+        let wifi = self.wifi;
+        let has_pending_purchases = !self.queue.is_empty();
+        let purchase_retry = async {
+            if !has_pending_purchases {
+                return core::future::pending().await;
+            }
+            if wifi.is_up() {
+                // Submitting failed although the network is up, retry later
+                Timer::after(PURCHASE_RETRY_INTERVAL).await;
+            } else {
+                // Submit as soon as the network comes back up
+                wifi.wait_up().await;
+            }
+        };
+        led::set(if wifi.is_up() || self.demo.is_some() {
+            led::State::Ready
+        } else {
+            led::State::Connecting
+        });
+        let user_id = match select3(self.authenticate_user(), schedule_timer, purchase_retry).await
+        {
             // Id card read
-            Either::First(res) => res?,
+            Either3::First(res) => res?,
             // Schedule time
-            Either::Second(()) => {
+            Either3::Second(()) => {
+                led::set(led::State::Busy);
                 self.schedule().await?;
                 return Ok(());
             }
+            // Pending purchases can be submitted (will be done when starting over)
+            Either3::Third(()) => return Ok(()),
         };
 
-        Error::try_with_async(user_id, async {
+        led::set(led::State::Busy);
+        let res = Error::try_with_async(user_id, async {
             // Get user information
             let user = self.users.get(user_id);
             let user_name = user.map_or(String::new(), |u| u.name.clone());
 
+            // Get account balance (once per session, omitted if unavailable)
+            let balance = self.fetch_balance(user_id).await?;
+
             // Ask for article to purchase
-            let article_idx = self.select_article(&user_name).await?;
+            let article_idx = self.select_article(&user_name, balance).await?;
 
             // Get article information
             let article_id = self
@@ -277,108 +779,351 @@ impl<'a, RNG: RngCore, I2C: I2c, IRQ: Wait<Error = Infallible>> Ui<'a, RNG, I2C,
             // Show total price and ask for confirmation
             self.confirm_purchase(&article, amount, total_price).await?;
 
+            // Ask again if the total price is unusually large (e.g. mistyped amount)
+            if is_large_purchase(total_price, self.config.large_purchase_threshold) {
+                self.confirm_large_purchase(amount, total_price).await?;
+            }
+
             // Store purchase
             #[allow(clippy::cast_precision_loss)]
-            self.purchase(&article_id, amount as f32, user_id, total_price)
+            let purchase = self
+                .purchase(
+                    &article_id,
+                    amount as f32,
+                    user_id,
+                    total_price,
+                    article.salestax,
+                )
                 .await?;
 
-            // Show success and affirm to take items
-            self.show_success(amount).await?;
+            // Show success and affirm to take items (or undo purchase)
+            self.show_success(&purchase, amount).await?;
+
+            // Submit pending purchases if possible
+            self.submit_purchases().await?;
 
             // Submit telemetry data if needed
             self.submit_telemetry().await?;
 
             Ok(())
         })
-        .await
-    }
+        .await;
 
-    /// Run schedule
-    pub async fn schedule(&mut self) -> Result<(), Error> {
-        if self.schedule.is_expired() {
-            info!("UI: Running schedule...");
+        // Don't authenticate again with the same card unless it was removed in between
+        self.nfc.reset_same_card();
 
-            // Schedule next event
-            self.schedule.schedule_next();
+        res
+    }
 
-            // Refresh article and user information
-            self.refresh_articles_and_users().await?;
+    /// Run expired scheduled jobs
+    pub async fn schedule(&mut self) -> Result<(), Error> {
+        while let Some(job) = self.schedule.expired(Instant::now()) {
+            info!("UI: Running scheduled job {:?}...", job);
+
+            match job {
+                // Refresh article and user information
+                Job::RefreshData => self.refresh_articles_and_users().await?,
+                // Submit telemetry data
+                Job::FlushTelemetry => self.flush_telemetry().await?,
+            }
         }
         Ok(())
     }
 }
 
-impl<RNG: RngCore, I2C: I2c, IRQ: Wait<Error = Infallible>> Ui<'_, RNG, I2C, IRQ> {
-    /// Authentication: wait for id card, read it and look up the associated user. On idle timeout,
-    /// enter power saving (turn off display). Any key pressed leaves power saving (turn on
-    /// display).
+impl<RNG: RngCore, I2C: I2c, IRQ: Wait<Error = Infallible> + WakeupPin> Ui<'_, RNG, I2C, IRQ> {
+    /// Authentication: wait for id card, read it and look up the associated user. On short idle
+    /// timeout, dim the display. On long idle timeout, enter power saving (turn off display and
+    /// poll for cards at a low rate), and go to sleep if idle for even longer (if enabled). Any
+    /// key pressed or id card detected leaves power saving.
     async fn authenticate_user(&mut self) -> Result<UserId, Error> {
         info!("UI: Waiting for NFC card...");
 
         loop {
-            self.display.screen(&screen::ScanId).await?;
-
-            // Wait for id card read or timeout
-            #[allow(clippy::single_match_else)]
-            let uid = match with_timeout(IDLE_TIMEOUT, self.nfc.read()).await {
-                // Id card detected
-                Ok(res) => res?,
-                // Idle timeout, enter power saving
-                Err(TimeoutError) => {
-                    self.power_save().await?;
-                    // Wait for id card read or keypress
-                    match select(self.nfc.read(), self.keypad.read()).await {
-                        // Id card detected
-                        Either::First(res) => res?,
-                        // Key pressed while saving power, leave power saving
-                        Either::Second(_key) => continue,
-                    }
+            let res = self.wait_for_id().await;
+
+            // Restore full brightness if id card was detected while dimmed (also on errors)
+            if self.idle.state() == idle::State::Dimmed {
+                self.idle.reset(Instant::now());
+                self.display.set_dimmed(false).await?;
+            }
+
+            let Some(uid) = res? else {
+                if let Some(user_id) = self.authenticate_member_id().await? {
+                    self.denials.reset();
+                    break Ok(user_id);
                 }
+                continue;
             };
 
             // Look up user id by detected NFC uid
-            if let Some(user_id) = self.users.id(&uid) {
+            if let Some(user_id) = self.user_id_by_uid(&uid) {
                 // User found, authorized
                 info!("UI: NFC card {} identified as user {}", uid, user_id);
-                self.telemetry.track(Event::UserAuthenticated(user_id, uid));
+                self.telemetry
+                    .track(Event::UserAuthenticated(user_id, Some(uid)));
+                if let Some(user) = self.users.get(user_id) {
+                    self.telemetry.set_profile(user_id, user.name.clone());
+                }
                 let _ = self.buzzer.confirm().await;
+                self.denials.reset();
                 break Ok(user_id);
             }
 
-            // User not found, unauthorized
-            info!("UI: NFC card {} unknown, rejecting", uid);
-            self.telemetry.track(Event::AuthenticationFailed(uid));
-            let _ = self.buzzer.deny().await;
+            // User not found, unauthorized. Only report and sound the first of repeated attempts
+            // with the same card to not spam telemetry.
+            if self.denials.check(&uid, Instant::now()) {
+                info!("UI: NFC card {} unknown, rejecting", uid);
+                self.telemetry.track(Event::AuthenticationFailed(Some(uid)));
+                let _ = self.buzzer.deny().await;
+            } else {
+                debug!("UI: NFC card {} unknown, rejecting silently", uid);
+            }
+        }
+    }
+
+    /// Show scan id screen and wait for an id card or (if enabled) the enter key to enter a member
+    /// number instead. Meanwhile, the clock is updated and the display is dimmed and powered down
+    /// when idle. Returns the uid of the detected id card or `None` if the enter key was pressed.
+    async fn wait_for_id(&mut self) -> Result<Option<Uid>, Error> {
+        let member_id_entry = self.config.member_id_entry.unwrap_or(false);
+        let demo = self.demo.is_some();
+        let config = self.config;
+        let machine_name = config.machine_name.as_deref().unwrap_or_default();
+        let scan_id_screen = || {
+            screen::ScanId::new(local_time(config), battery::level(), member_id_entry, demo)
+                .with_machine_name(machine_name)
+        };
+
+        self.idle.reset(Instant::now());
+
+        loop {
+            let mut screen = scan_id_screen();
+            self.display.screen(&screen).await?;
+
+            // Wait for id card or keypress until idle for long enough to save power
+            {
+                // Keep reading the same id card and keypress while redrawing, so that no card read
+                // gets interrupted and no keypress gets lost
+                let keypad = &mut *self.keypad;
+                let mut wait_enter = pin!(async {
+                    if member_id_entry {
+                        while keypad.read().await != Key::Enter {}
+                    } else {
+                        core::future::pending::<()>().await;
+                    }
+                });
+                let mut read_id = pin!(self.nfc.read());
+
+                // Wait for id card read, member number entry, clock update or idle timeout
+                loop {
+                    let deadline = self
+                        .idle
+                        .deadline()
+                        .min(Instant::now() + CLOCK_UPDATE_INTERVAL);
+                    match select3(&mut read_id, &mut wait_enter, Timer::at(deadline)).await {
+                        // Id card detected
+                        Either3::First(res) => return Ok(Some(res?)),
+                        // Enter key pressed, enter member number instead
+                        Either3::Second(()) => return Ok(None),
+                        Either3::Third(()) => (),
+                    }
+                    // Redraw if the clock changed
+                    let updated_screen = scan_id_screen();
+                    if updated_screen != screen {
+                        screen = updated_screen;
+                        self.display.screen(&screen).await?;
+                    }
+                    match self.idle.update(Instant::now()) {
+                        // Short idle timeout, dim display and keep waiting for id card
+                        Some(idle::State::Dimmed) => self.display.set_dimmed(true).await?,
+                        // Long idle timeout, stop waiting to enter power saving
+                        Some(idle::State::PowerSave) => break,
+                        Some(idle::State::Active) | None => (),
+                    }
+                }
+            }
+
+            // Long idle timeout, enter power saving (which polls for id cards by itself)
+            let uid = self.save_power_until_woken().await?;
+            self.idle.reset(Instant::now());
+            // Id card detected while saving power, continue with it
+            if uid.is_some() {
+                return Ok(uid);
+            }
+        }
+    }
+
+    /// Look up user id by NFC uid. In demo mode, unknown cards belong to the demo user.
+    fn user_id_by_uid(&self, uid: &Uid) -> Option<UserId> {
+        self.users
+            .id(uid)
+            .or_else(|| self.demo.is_some().then_some(demo::DEMO_USER_ID))
+    }
+
+    /// Save power until a key is pressed or an id card is detected. If idle for even longer, go
+    /// to sleep until woken up. Returns the id card that was detected (if any).
+    async fn save_power_until_woken(&mut self) -> Result<Option<Uid>, Error> {
+        let power_save_start = Instant::now();
+        let idle_time = self.idle.idle_time(power_save_start);
+        self.telemetry
+            .track(Event::PowerSaveEntered(idle_time.as_secs()));
+        self.power_save().await?;
+
+        // Wait for keypress or id card (NFC reader polls at a low rate while saving power)
+        let wait_wakeup = select(self.keypad.read(), self.nfc.read());
+        let woken = match self.idle.sleep_deadline() {
+            Some(deadline) => with_deadline(deadline, wait_wakeup).await.ok(),
+            None => Some(wait_wakeup.await),
+        };
+        let uid = match woken {
+            // Key pressed
+            Some(Either::First(_key)) => Ok(None),
+            // Id card detected
+            Some(Either::Second(res)) => res.map(Some).map_err(Error::from),
+            // Idle for even longer, go to sleep until woken up
+            None => self.sleep().await.map(|()| None),
+        };
+
+        // Leave power saving, even if waiting failed
+        self.power_up().await?;
+        let power_save_time = power_save_start.elapsed();
+        self.telemetry
+            .track(Event::PowerSaveLeft(power_save_time.as_secs()));
+        uid
+    }
+
+    /// Authentication by member number: prompt for member number and the member's PIN and look
+    /// up the user. Since member numbers aren't secret, only members with a PIN (stored as a
+    /// Vereinsflieger user key) can authenticate this way. Returns `None` if cancelled, timed out,
+    /// the member number is unknown or the PIN is wrong. After a failure, waits for an increasing
+    /// delay to slow down guessing.
+    async fn authenticate_member_id(&mut self) -> Result<Option<UserId>, Error> {
+        info!("UI: Asking for member number...");
+
+        let mut entry = NumberEntry::new(MEMBER_ID_MAX_DIGITS);
+        let user_id = loop {
+            self.display
+                .screen(&screen::EnterMemberId::new(entry.digits()))
+                .await?;
+            let Ok(press) = with_timeout(
+                self.user_timeout,
+                self.keypad.read_long_press(CLEAR_ENTRY_HOLD_TIME),
+            )
+            .await
+            else {
+                return Ok(None);
+            };
+            match feed_entry(self.buzzer, &mut entry, press).await {
+                Entry::Pending => (),
+                Entry::Done(user_id) => break user_id,
+                Entry::Cancelled => return Ok(None),
+            }
+        };
+
+        // Look up user and PIN by member number. Ask for the PIN even if the member number is
+        // unknown, so that it can't be told whether a member number is valid.
+        let pin = self.users.pin(user_id).map(String::from);
+        let Some(digits) = self.read_pin(false).await? else {
+            return Ok(None);
+        };
+        let authorized = pin.is_some_and(|pin| pin == digits);
+        if let (true, Some(user)) = (authorized, self.users.get(user_id)) {
+            // User found and PIN correct, authorized
+            info!("UI: Member number identified as user {}", user_id);
+            self.member_id_failures = 0;
+            self.telemetry
+                .track(Event::UserAuthenticated(user_id, None));
+            self.telemetry.set_profile(user_id, user.name.clone());
+            let _ = self.buzzer.confirm().await;
+            return Ok(Some(user_id));
+        }
+
+        // User not found or wrong PIN, unauthorized
+        self.member_id_failures = self.member_id_failures.saturating_add(1);
+        info!(
+            "UI: Member number {} unknown or wrong PIN, rejecting ({} failures)",
+            user_id, self.member_id_failures
+        );
+        self.telemetry.track(Event::AuthenticationFailed(None));
+        let _ = self.buzzer.deny().await;
+        Timer::after(member_id_failure_delay(self.member_id_failures)).await;
+        Ok(None)
+    }
+
+    /// Fetch account balance of given user. Returns `None` if network is unavailable (or in demo
+    /// mode) or the balance couldn't be fetched.
+    async fn fetch_balance(&mut self, user_id: UserId) -> Result<Option<f32>, Error> {
+        if !self.wifi.is_up() || self.demo.is_some() {
+            return Ok(None);
+        }
+
+        info!("UI: Fetching balance of user {}...", user_id);
+
+        let keypad = &mut *self.keypad;
+        let wait_cancel = async { while keypad.read().await != Key::Cancel {} };
+        let (vereinsflieger, http) = (&mut *self.vereinsflieger, &mut *self.http);
+        let fetch = async {
+            let mut vf = vereinsflieger.connect(http).await?;
+            vf.get_balance(user_id).await
+        };
+        let fetch = cancellable(fetch, wait_cancel);
+        #[allow(clippy::large_futures)]
+        match with_spinner(self.display, screen::PleaseWait::FetchingBalance, fetch).await?? {
+            Ok(balance) => Ok(Some(balance)),
+            Err(err) => {
+                warn!("UI: Unable to fetch balance: {}", err);
+                Ok(None)
+            }
         }
     }
 
     /// Ask for article to purchase
-    async fn select_article(&mut self, name: &str) -> Result<usize, Error> {
+    async fn select_article(&mut self, name: &str, balance: Option<f32>) -> Result<usize, Error> {
         info!("UI: Asking to select article...");
 
-        self.display
-            .screen(&screen::SelectArticle::new(
-                &mut self.rng,
-                name,
-                self.articles,
-            ))
-            .await?;
-        let num_articles = self.articles.count_ids();
+        // Without articles, no digit would ever select one
+        let num_articles = num_selectable_articles(self.articles)?;
+
+        let mut screen = screen::SelectArticle::new(
+            &mut self.rng,
+            name,
+            balance,
+            self.articles,
+            price_format(self.config),
+            self.config.greetings.as_deref(),
+        );
+        self.display.screen(&screen).await?;
+        let pages = screen::article_pages(num_articles);
+        let mut page = 0;
         loop {
             #[allow(clippy::match_same_arms)]
-            match with_timeout(USER_TIMEOUT, self.keypad.read()).await {
-                // Any digit 1..=num_articles selects article
-                Ok(Key::Digit(n)) if n >= 1 && n as usize <= num_articles => {
-                    break Ok(n as usize - 1)
+            match read_key_animated(self.display, self.keypad, &mut screen, self.user_timeout)
+                .await?
+            {
+                // Any digit of an article on the current page selects article
+                Key::Digit(n) => {
+                    let idx = screen::article_index(page, n, num_articles);
+                    if let Some(idx) = accept_key(self.buzzer, idx).await {
+                        break Ok(idx);
+                    }
+                }
+                // Enter key (or A key) shows next page (if there's more than one)
+                Key::Enter | Key::Other('A') if pages > 1 => {
+                    page = (page + 1) % pages;
+                    screen.set_page(page);
+                    self.display.screen(&screen).await?;
+                }
+                // B key shows previous page (if there's more than one)
+                Key::Other('B') if pages > 1 => {
+                    page = (page + pages - 1) % pages;
+                    screen.set_page(page);
+                    self.display.screen(&screen).await?;
                 }
-                // Ignore any other digit
-                Ok(Key::Digit(_)) => (),
                 // Cancel key cancels
-                Ok(Key::Cancel) => Err(ErrorKind::Cancel)?,
+                Key::Cancel => Err(ErrorKind::Cancel)?,
                 // Ignore any other key
-                Ok(_) => (),
-                // User interaction timeout
-                Err(TimeoutError) => Err(ErrorKind::UserTimeout)?,
+                _ => (),
             }
         }
     }
@@ -390,22 +1135,23 @@ impl<RNG: RngCore, I2C: I2c, IRQ: Wait<Error = Infallible>> Ui<'_, RNG, I2C, IRQ
             article.name, article.price
         );
 
-        self.display
-            .screen(&screen::EnterAmount::new(article))
-            .await?;
+        let mut screen = screen::EnterAmount::new(article, price_format(self.config));
+        self.display.screen(&screen).await?;
         loop {
             #[allow(clippy::match_same_arms)]
-            match with_timeout(USER_TIMEOUT, self.keypad.read()).await {
+            match read_key_animated(self.display, self.keypad, &mut screen, self.user_timeout)
+                .await?
+            {
                 // Any digit 1..=9 selects amount
-                Ok(Key::Digit(n)) if (1..=9).contains(&n) => break Ok(n as usize),
-                // Ignore any other digit
-                Ok(Key::Digit(_)) => (),
+                key @ Key::Digit(_) => {
+                    if let Some(amount) = accept_key(self.buzzer, amount_from_key(key)).await {
+                        break Ok(amount);
+                    }
+                }
                 // Cancel key cancels
-                Ok(Key::Cancel) => Err(ErrorKind::Cancel)?,
+                Key::Cancel => Err(ErrorKind::Cancel)?,
                 // Ignore any other key
-                Ok(_) => (),
-                // User interaction timeout
-                Err(TimeoutError) => Err(ErrorKind::UserTimeout)?,
+                _ => (),
             }
         }
     }
@@ -422,47 +1168,91 @@ impl<RNG: RngCore, I2C: I2c, IRQ: Wait<Error = Infallible>> Ui<'_, RNG, I2C, IRQ
             amount, article.name, total_price
         );
 
-        self.display
-            .screen(&screen::Checkout::new(article, amount, total_price))
-            .await?;
+        let mut screen =
+            screen::Checkout::new(article, amount, total_price, price_format(self.config));
+        self.display.screen(&screen).await?;
         loop {
-            match with_timeout(USER_TIMEOUT, self.keypad.read()).await {
+            match read_key_animated(self.display, self.keypad, &mut screen, self.user_timeout)
+                .await?
+            {
                 // Enter key confirms purchase
-                Ok(Key::Enter) => break Ok(()),
+                Key::Enter => break Ok(()),
                 // Cancel key cancels
-                Ok(Key::Cancel) => Err(ErrorKind::Cancel)?,
+                Key::Cancel => Err(ErrorKind::Cancel)?,
                 // Ignore any other key
-                Ok(_) => (),
-                // User interaction timeout
-                Err(TimeoutError) => Err(ErrorKind::UserTimeout)?,
+                _ => (),
+            }
+        }
+    }
+
+    /// Ask for extra confirmation of an unusually large purchase
+    async fn confirm_large_purchase(
+        &mut self,
+        amount: usize,
+        total_price: f32,
+    ) -> Result<(), Error> {
+        info!(
+            "UI: Asking for extra confirmation of {}x, {:.02} EUR...",
+            amount, total_price
+        );
+
+        let mut screen =
+            screen::ConfirmLargePurchase::new(amount, total_price, price_format(self.config));
+        self.display.screen(&screen).await?;
+        let _ = self.buzzer.deny().await;
+        loop {
+            match read_key_animated(self.display, self.keypad, &mut screen, self.user_timeout)
+                .await?
+            {
+                // Enter key confirms purchase
+                Key::Enter => break Ok(()),
+                // Cancel key cancels
+                Key::Cancel => Err(ErrorKind::Cancel)?,
+                // Ignore any other key
+                _ => (),
             }
         }
     }
 
-    /// Purchase the given article
+    /// Purchase the given article. The purchase is added to the queue of pending purchases,
+    /// which is submitted to Vereinsflieger later on (as soon as network is available). In demo
+    /// mode, the purchase is only logged locally.
     async fn purchase(
         &mut self,
         article_id: &ArticleId,
         amount: f32,
         user_id: UserId,
         total_price: f32,
-    ) -> Result<(), Error> {
-        // Wait for network to become available (if not already)
-        self.wait_network_up().await?;
-
+        salestax: Option<f32>,
+    ) -> Result<Purchase, Error> {
         info!(
             "UI: Purchasing {}x {}, {:.02} EUR for user {}...",
             amount, article_id, total_price, user_id
         );
 
-        self.display.screen(&screen::PleaseWait::Purchasing).await?;
-
-        // Connect to Vereinsflieger API
-        let mut vf = self.vereinsflieger.connect(self.http).await?;
-
         // Store purchase
-        vf.purchase(article_id, amount, user_id, total_price)
-            .await?;
+        let callsign = self.config.vf_callsign.clone();
+        let purchase = if let Some(ref mut demo) = self.demo {
+            demo.push(
+                user_id,
+                article_id.clone(),
+                amount,
+                total_price,
+                salestax,
+                callsign,
+            )
+        } else {
+            self.queue
+                .push(
+                    user_id,
+                    article_id.clone(),
+                    amount,
+                    total_price,
+                    salestax,
+                    callsign,
+                )
+                .await?
+        };
         self.telemetry.track(Event::ArticlePurchased(
             user_id,
             article_id.clone(),
@@ -470,26 +1260,391 @@ impl<RNG: RngCore, I2C: I2c, IRQ: Wait<Error = Infallible>> Ui<'_, RNG, I2C, IRQ
             total_price,
         ));
 
-        Ok(())
+        Ok(purchase)
     }
 
-    /// Show success screen and wait for keypress or timeout
-    async fn show_success(&mut self, amount: usize) -> Result<(), Error> {
+    /// Show success screen and wait for keypress or timeout. Within the undo window, the cancel
+    /// key undoes the purchase.
+    async fn show_success(&mut self, purchase: &Purchase, amount: usize) -> Result<(), Error> {
         info!("UI: Displaying success, {} items", amount);
 
-        self.display.screen(&screen::Success::new(amount)).await?;
+        let start = Instant::now();
+        let mut undo = UndoWindow::new(UNDO_WINDOW);
+        let mut offering_undo = true;
+        self.display
+            .screen(&screen::Success::new(amount, true))
+            .await?;
         let _ = self.buzzer.confirm().await;
 
         // Wait at least 1s without responding to keypad
         let min_time = Duration::from_secs(1);
         Timer::after(min_time).await;
 
-        let wait_cancel = async { while self.keypad.read().await != Key::Enter {} };
-        match with_timeout(USER_TIMEOUT - min_time, wait_cancel).await {
-            // Enter key continues
-            Ok(()) => Ok(()),
+        loop {
+            // Wait for keypress until the undo window closes, afterwards until user timeout
+            let deadline = match undo.remaining(start.elapsed()) {
+                Some(remaining) => Instant::now() + remaining,
+                None => start + self.user_timeout,
+            };
+            match with_deadline(deadline, self.keypad.read()).await {
+                // Enter key continues
+                Ok(Key::Enter) => {
+                    undo.finalize();
+                    return Ok(());
+                }
+                // Cancel key undoes the purchase (if still possible)
+                Ok(Key::Cancel) => {
+                    if undo.request_undo(start.elapsed()) {
+                        break;
+                    }
+                }
+                // Ignore any other key
+                Ok(_) => (),
+                // Undo window elapsed, purchase is final
+                Err(TimeoutError) if undo.state() == undo::State::Open => undo.finalize(),
+                // User interaction timeout
+                Err(TimeoutError) => Err(ErrorKind::UserTimeout)?,
+            }
+            // Stop offering to undo once the purchase is final
+            if offering_undo && undo.state() == undo::State::Finalized {
+                info!("UI: Purchase {} finalized", purchase.id);
+                offering_undo = false;
+                self.display
+                    .screen(&screen::Success::new(amount, false))
+                    .await?;
+            }
+        }
+
+        // Undo purchase. On error, the purchase stays in effect and the error is shown.
+        self.undo_purchase(purchase).await?;
+
+        self.display.screen(&screen::Undone::new(amount)).await?;
+        let _ = self.buzzer.confirm().await;
+        let wait_enter = async { while self.keypad.read().await != Key::Enter {} };
+        let _ = with_timeout(UNDONE_TIMEOUT, wait_enter).await;
+        Ok(())
+    }
+
+    /// Undo the given purchase. Purchases are held in the queue of pending purchases until the
+    /// undo window closes, so undoing simply discards the pending purchase.
+    async fn undo_purchase(&mut self, purchase: &Purchase) -> Result<(), Error> {
+        info!("UI: Undoing purchase {}...", purchase.id);
+
+        if let Some(ref mut demo) = self.demo {
+            demo.remove(purchase.id);
+        } else if !self.queue.remove(purchase.id).await {
+            // Can't happen since purchases are only submitted after the undo window closed
+            warn!("UI: Purchase {} not pending, unable to undo", purchase.id);
+        }
+        self.telemetry.track(Event::PurchaseUndone(
+            purchase.user_id,
+            purchase.article_id.clone(),
+            purchase.amount,
+            purchase.total_price,
+        ));
+
+        Ok(())
+    }
+}
+
+/// Format of prices on screens as configured
+fn price_format(config: &Config) -> screen::PriceFormat<'_> {
+    screen::PriceFormat::new(
+        config.currency.as_deref(),
+        config.decimal_separator.as_deref(),
+    )
+}
+
+/// Current local time (if known) with the configured offset to UTC
+fn local_time(config: &Config) -> Option<DateTime<FixedOffset>> {
+    let offset = FixedOffset::east_opt(config.utc_offset.unwrap_or(0).saturating_mul(60))?;
+    time::now().map(|now| now.with_timezone(&offset))
+}
+
+/// Number of articles to select from. Fails if no articles are configured.
+fn num_selectable_articles(articles: &Articles) -> Result<usize, ErrorKind> {
+    match articles.count_ids() {
+        0 => Err(ErrorKind::NoArticles),
+        n => Ok(n),
+    }
+}
+
+/// Audible feedback on accepted keypresses
+trait KeyFeedback {
+    /// Give feedback for an accepted keypress
+    fn tick(&mut self) -> impl Future<Output = Result<(), buzzer::Error>>;
+}
+
+impl KeyFeedback for Buzzer<'_> {
+    fn tick(&mut self) -> impl Future<Output = Result<(), buzzer::Error>> {
+        self.key_tick()
+    }
+}
+
+/// Pass through the given selection. Plays a key tick if the keypress was accepted, i.e. if
+/// there's a selection.
+async fn accept_key<T>(feedback: &mut impl KeyFeedback, selection: Option<T>) -> Option<T> {
+    if selection.is_some() {
+        let _ = feedback.tick().await;
+    }
+    selection
+}
+
+/// Feed a key press to a number entry. Plays a key tick if a digit was accepted.
+async fn feed_entry(
+    feedback: &mut impl KeyFeedback,
+    entry: &mut NumberEntry,
+    press: KeyPress,
+) -> Entry {
+    let len = entry.digits().len();
+    let state = entry.feed_press(press);
+    if entry.digits().len() > len {
+        let _ = feedback.tick().await;
+    }
+    state
+}
+
+/// Amount selected by the given key. Digits 1..=9 select an amount, any other key doesn't.
+fn amount_from_key(key: Key) -> Option<usize> {
+    match key {
+        Key::Digit(n) if (1..=9).contains(&n) => Some(n as usize),
+        _ => None,
+    }
+}
+
+/// Estimated progress (percentage, 0-99) of fetching the given number of items if the given
+/// number is expected. Never reaches 100% since the actual number might be larger.
+fn refresh_progress(count: usize, expected: usize) -> u8 {
+    if expected == 0 {
+        return 0;
+    }
+    // Percentage is capped to 99, conversion can't fail
+    u8::try_from((count * 100 / expected).min(99)).unwrap_or(99)
+}
+
+/// Returns true if the given total price exceeds the given (or default) threshold, i.e. the
+/// purchase needs an extra confirmation
+fn is_large_purchase(total_price: f32, threshold: Option<f32>) -> bool {
+    total_price > threshold.unwrap_or(DEFAULT_LARGE_PURCHASE_THRESHOLD)
+}
+
+/// Run the given future, but abort it if the given cancel future completes first (e.g. if the
+/// cancel key is pressed). The aborted future is dropped, which e.g. closes any open connection.
+async fn cancellable<F: Future, C: Future>(fut: F, cancel: C) -> Result<F::Output, Error> {
+    match select(fut, cancel).await {
+        // Future completed
+        Either::First(res) => Ok(res),
+        // Cancelled
+        Either::Second(_) => Err(ErrorKind::Cancel)?,
+    }
+}
+
+/// Run the given future while showing the given wait screen. The spinner of the wait screen is
+/// animated periodically until the future completes.
+async fn with_spinner<I2C: I2c, F: Future>(
+    display: &mut Display<I2C>,
+    please_wait: screen::PleaseWait,
+    fut: F,
+) -> Result<F::Output, Error> {
+    with_spinner_update(display, please_wait, fut, || None).await
+}
+
+/// Like `with_spinner`, but before every animation tick, the given function can replace the wait
+/// screen (e.g. to show progress)
+async fn with_spinner_update<I2C: I2c, F: Future>(
+    display: &mut Display<I2C>,
+    please_wait: screen::PleaseWait,
+    fut: F,
+    mut update: impl FnMut() -> Option<screen::PleaseWait>,
+) -> Result<F::Output, Error> {
+    let mut screen = screen::PleaseWaitAnimated::new(please_wait);
+    display.screen(&screen).await?;
+    let mut fut = pin!(fut);
+    loop {
+        match select(&mut fut, Timer::after(ANIMATION_INTERVAL)).await {
+            // Future completed
+            Either::First(output) => break Ok(output),
+            // Animation tick. Failing to update the display must not abort the action.
+            Either::Second(()) => {
+                if let Some(please_wait) = update() {
+                    screen.set(please_wait);
+                }
+                if let Err(err) = display.animate(&mut screen).await {
+                    debug!("UI: Failed to animate wait screen: {}", err);
+                }
+            }
+        }
+    }
+}
+
+/// Timeout for user input with given configured seconds. Uses the default if not configured and
+/// never goes below the minimum timeout.
+fn user_timeout(secs: Option<u32>) -> Duration {
+    secs.map_or(DEFAULT_USER_TIMEOUT, |secs| {
+        Duration::from_secs(secs.into()).max(MIN_USER_TIMEOUT)
+    })
+}
+
+/// Delay after the given number of consecutive failed authentications by member number
+fn member_id_failure_delay(failures: u32) -> Duration {
+    let factor = 1_u64
+        .checked_shl(failures.saturating_sub(1))
+        .unwrap_or(u64::MAX);
+    Duration::from_ticks(MEMBER_ID_FAILURE_DELAY.as_ticks().saturating_mul(factor))
+        .min(MEMBER_ID_MAX_FAILURE_DELAY)
+}
+
+/// Wait for a keypress with given user interaction timeout. While waiting, the animation of the
+/// given screen is advanced periodically and the screen is redrawn if needed.
+async fn read_key_animated<I2C: I2c, S: Screen>(
+    display: &mut Display<I2C>,
+    keypad: &mut Keypad<'_, { keypad::COLS }, { keypad::ROWS }>,
+    screen: &mut S,
+    timeout: Duration,
+) -> Result<Key, Error> {
+    let deadline = Instant::now() + timeout;
+    // Keep reading the same keypress while redrawing, so that no keypress gets lost
+    let mut key = pin!(keypad.read());
+    loop {
+        match with_deadline(deadline, select(&mut key, Timer::after(ANIMATION_INTERVAL))).await {
+            // Key pressed
+            Ok(Either::First(key)) => break Ok(key),
+            // Animation tick, redraw screen if needed
+            Ok(Either::Second(())) => display.animate(screen).await?,
             // User interaction timeout
             Err(TimeoutError) => Err(ErrorKind::UserTimeout)?,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use core::future;
+
+    #[async_std::test]
+    async fn cancellable_completes() {
+        let res = cancellable(async { 42 }, core::future::pending::<()>()).await;
+        assert_eq!(res.ok(), Some(42));
+    }
+
+    #[async_std::test]
+    async fn cancellable_cancelled() {
+        let res = cancellable(core::future::pending::<()>(), async {}).await;
+        assert!(res.is_err_and(|err| err.is_cancel()));
+    }
+
+    #[test]
+    fn no_articles_configured() {
+        let articles = Articles::new(vec![]);
+        assert!(matches!(
+            num_selectable_articles(&articles),
+            Err(ErrorKind::NoArticles)
+        ));
+    }
+
+    #[test]
+    fn articles_configured() {
+        let articles = Articles::new(vec!["1".into(), "2".into()]);
+        assert_eq!(num_selectable_articles(&articles).ok(), Some(2));
+    }
+
+    #[test]
+    fn member_id_failure_delays() {
+        assert_eq!(member_id_failure_delay(0), Duration::from_secs(2));
+        assert_eq!(member_id_failure_delay(1), Duration::from_secs(2));
+        assert_eq!(member_id_failure_delay(2), Duration::from_secs(4));
+        assert_eq!(member_id_failure_delay(3), Duration::from_secs(8));
+        assert_eq!(member_id_failure_delay(6), MEMBER_ID_MAX_FAILURE_DELAY);
+        assert_eq!(member_id_failure_delay(100), MEMBER_ID_MAX_FAILURE_DELAY);
+    }
+
+    #[test]
+    fn user_timeout_default() {
+        assert_eq!(user_timeout(None), DEFAULT_USER_TIMEOUT);
+    }
+
+    #[test]
+    fn user_timeout_configured() {
+        assert_eq!(user_timeout(Some(30)), Duration::from_secs(30));
+        assert_eq!(user_timeout(Some(1)), MIN_USER_TIMEOUT);
+        assert_eq!(user_timeout(Some(0)), MIN_USER_TIMEOUT);
+    }
+
+    #[test]
+    fn amount_keys() {
+        assert_eq!(amount_from_key(Key::Digit(1)), Some(1));
+        assert_eq!(amount_from_key(Key::Digit(9)), Some(9));
+        assert_eq!(amount_from_key(Key::Digit(0)), None);
+        assert_eq!(amount_from_key(Key::Enter), None);
+        assert_eq!(amount_from_key(Key::Cancel), None);
+    }
+
+    /// Key feedback stub that counts ticks
+    #[derive(Default)]
+    struct CountingFeedback(usize);
+
+    impl KeyFeedback for CountingFeedback {
+        fn tick(&mut self) -> impl Future<Output = Result<(), buzzer::Error>> {
+            self.0 += 1;
+            future::ready(Ok(()))
+        }
+    }
+
+    #[async_std::test]
+    async fn amount_key_ticks_once_per_accepted_key() {
+        let mut feedback = CountingFeedback::default();
+        for n in 0..=9 {
+            accept_key(&mut feedback, amount_from_key(Key::Digit(n))).await;
+        }
+        accept_key(&mut feedback, amount_from_key(Key::Enter)).await;
+        accept_key(&mut feedback, amount_from_key(Key::Cancel)).await;
+        // Only digits 1..=9 are accepted
+        assert_eq!(feedback.0, 9);
+    }
+
+    #[async_std::test]
+    async fn entry_ticks_once_per_accepted_digit() {
+        let mut feedback = CountingFeedback::default();
+        let mut entry = NumberEntry::new(3);
+        for key in [Key::Digit(1), Key::Other('A'), Key::Digit(2), Key::Digit(3)] {
+            feed_entry(&mut feedback, &mut entry, KeyPress::Short(key)).await;
+        }
+        // Entry is full, further digits are not accepted
+        feed_entry(&mut feedback, &mut entry, KeyPress::Short(Key::Digit(4))).await;
+        assert_eq!(entry.digits(), "123");
+        assert_eq!(feedback.0, 3);
+        feed_entry(&mut feedback, &mut entry, KeyPress::Long(Key::Cancel)).await;
+        assert_eq!(entry.digits(), "");
+        assert_eq!(feedback.0, 3);
+    }
+
+    #[test]
+    fn refresh_progress_estimate() {
+        assert_eq!(refresh_progress(0, 200), 0);
+        assert_eq!(refresh_progress(100, 200), 50);
+        assert_eq!(refresh_progress(200, 200), 99);
+        assert_eq!(refresh_progress(300, 200), 99);
+        // Nothing to estimate from if no users were known before
+        assert_eq!(refresh_progress(100, 0), 0);
+    }
+
+    #[test]
+    fn large_purchase_default_threshold() {
+        assert!(!is_large_purchase(1.5, None));
+        assert!(!is_large_purchase(20.0, None));
+        assert!(is_large_purchase(20.01, None));
+        assert!(is_large_purchase(9.0 * 3.5, None));
+    }
+
+    #[test]
+    fn large_purchase_configured_threshold() {
+        assert!(!is_large_purchase(9.5, Some(10.0)));
+        assert!(!is_large_purchase(10.0, Some(10.0)));
+        assert!(is_large_purchase(10.5, Some(10.0)));
+        assert!(!is_large_purchase(40.0, Some(50.0)));
+        // A threshold of 0 asks for every purchase
+        assert!(is_large_purchase(0.5, Some(0.0)));
+    }
+}
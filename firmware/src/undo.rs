@@ -0,0 +1,120 @@
+use embassy_time::Duration;
+
+/// Time after a purchase during which it can be undone
+pub const UNDO_WINDOW: Duration = Duration::from_secs(5);
+
+/// State of the undo window that is offered after a purchase
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// Purchase can still be undone
+    Open,
+    /// User requested to undo the purchase, the purchase needs to be cancelled
+    Undoing,
+    /// Purchase is final and can't be undone anymore
+    Finalized,
+}
+
+/// Undo window state machine
+///
+/// Starts open when a purchase is made. Within the window, the user can request to undo the
+/// purchase. Once the window elapsed, the user confirmed or walked away, the purchase is final.
+/// The outcome of cancelling the purchase isn't tracked here, the caller shows it directly.
+#[derive(Debug)]
+pub struct UndoWindow {
+    window: Duration,
+    state: State,
+}
+
+impl UndoWindow {
+    /// Open undo window with given duration
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            state: State::Open,
+        }
+    }
+
+    /// Current state
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Time left to undo the purchase, given the time elapsed since the purchase. Returns `None`
+    /// if the purchase can't be undone anymore.
+    pub fn remaining(&self, elapsed: Duration) -> Option<Duration> {
+        match self.state {
+            State::Open if elapsed < self.window => Some(self.window - elapsed),
+            _ => None,
+        }
+    }
+
+    /// User requests to undo the purchase after the given time elapsed since the purchase.
+    /// Returns true if the purchase should be cancelled now. A late request finalizes the purchase.
+    pub fn request_undo(&mut self, elapsed: Duration) -> bool {
+        if self.state != State::Open {
+            return false;
+        }
+        if elapsed < self.window {
+            self.state = State::Undoing;
+            true
+        } else {
+            self.state = State::Finalized;
+            false
+        }
+    }
+
+    /// Window elapsed, user confirmed or walked away. The purchase is final unless a cancellation
+    /// is already in progress.
+    pub fn finalize(&mut self) {
+        if self.state == State::Open {
+            self.state = State::Finalized;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn open() {
+        let undo = UndoWindow::new(secs(5));
+        assert_eq!(undo.state(), State::Open);
+        assert_eq!(undo.remaining(secs(0)), Some(secs(5)));
+        assert_eq!(undo.remaining(secs(3)), Some(secs(2)));
+        assert_eq!(undo.remaining(secs(5)), None);
+    }
+
+    #[test]
+    fn undo_within_window() {
+        let mut undo = UndoWindow::new(secs(5));
+        assert!(undo.request_undo(secs(2)));
+        assert_eq!(undo.state(), State::Undoing);
+        assert_eq!(undo.remaining(secs(2)), None);
+        // Further requests and finalizing don't interfere with cancellation in progress
+        assert!(!undo.request_undo(secs(3)));
+        undo.finalize();
+        assert_eq!(undo.state(), State::Undoing);
+    }
+
+    #[test]
+    fn undo_after_window() {
+        let mut undo = UndoWindow::new(secs(5));
+        assert!(!undo.request_undo(secs(5)));
+        assert_eq!(undo.state(), State::Finalized);
+    }
+
+    #[test]
+    fn finalize() {
+        let mut undo = UndoWindow::new(secs(5));
+        undo.finalize();
+        assert_eq!(undo.state(), State::Finalized);
+        assert_eq!(undo.remaining(secs(1)), None);
+        assert!(!undo.request_undo(secs(1)));
+        assert_eq!(undo.state(), State::Finalized);
+    }
+}
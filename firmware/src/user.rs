@@ -2,13 +2,17 @@ use crate::nfc::Uid;
 use alloc::collections::BTreeMap;
 use alloc::string::String;
 
-/// Extra NFC card uids to add
+/// Extra NFC card uids to add. Test cards are only accepted by debug builds, so that they don't
+/// grant access to production devices.
+#[cfg(debug_assertions)]
 static EXTRA_UIDS: [(Uid, UserId); 2] = [
     // Test card #1 (Mifare Classic 1k)
     (Uid::Single([0x13, 0xbd, 0x5b, 0x2a]), 3),
     // Test token #1 (Mifare Classic 1k)
     (Uid::Single([0xb7, 0xd3, 0x65, 0x26]), 3),
 ];
+#[cfg(not(debug_assertions))]
+static EXTRA_UIDS: [(Uid, UserId); 0] = [];
 
 /// User id
 /// Equivalent to the Vereinsflieger `memberid` attribute
@@ -24,13 +28,24 @@ pub struct User {
 }
 
 /// User lookup table
-/// Provides a look up of user information (member id and name) by NFC uid.
+/// Provides a look up of user information (member id and name) by NFC uid, and of PINs by member
+/// id. Every entry is marked
+/// with the refresh in which it was last added or updated, so that entries not seen in the
+/// latest refresh can be pruned.
 #[derive(Debug)]
+#[allow(clippy::struct_field_names)]
 pub struct Users {
-    /// Look up NFC uid to user id
-    uids: BTreeMap<Uid, UserId>,
-    /// Look up user id to user information
-    users: BTreeMap<UserId, User>,
+    /// Look up NFC uid to user id (and refresh it was last seen in)
+    uids: BTreeMap<Uid, (UserId, u32)>,
+    /// Look up user id to user information (and refresh it was last seen in)
+    users: BTreeMap<UserId, (User, u32)>,
+    /// Look up user id to PIN for authenticating by member number (and refresh it was last seen
+    /// in)
+    pins: BTreeMap<UserId, (String, u32)>,
+    /// Current refresh
+    refresh: u32,
+    /// Whether user information changed since it was last marked clean
+    dirty: bool,
 }
 
 impl Users {
@@ -39,33 +54,112 @@ impl Users {
         let mut this = Self {
             uids: BTreeMap::new(),
             users: BTreeMap::new(),
+            pins: BTreeMap::new(),
+            refresh: 0,
+            dirty: false,
         };
         this.clear();
+        this.mark_clean();
         this
     }
 
     /// Clear all user information
     pub fn clear(&mut self) {
+        if !self.uids.is_empty() || !self.users.is_empty() || !self.pins.is_empty() {
+            self.dirty = true;
+        }
         self.uids.clear();
         self.users.clear();
+        self.pins.clear();
+        self.add_extra_uids();
+    }
 
-        // Add extra uids and user for testing
-        for (uid, id) in &EXTRA_UIDS {
-            self.uids.insert(uid.clone(), *id);
-            self.users.entry(*id).or_insert_with(|| User {
-                name: String::from("Test-User"),
-            });
+    /// Begin a refresh. Entries added or updated from now on are kept when pruning after the
+    /// refresh, all other entries are removed.
+    pub fn begin_refresh(&mut self) {
+        self.refresh = self.refresh.wrapping_add(1);
+        self.add_extra_uids();
+    }
+
+    /// Remove all entries that weren't added or updated since the refresh began. Returns the
+    /// number of removed users.
+    pub fn prune(&mut self) -> usize {
+        let refresh = self.refresh;
+        let uid_count = self.uids.len();
+        self.uids.retain(|_uid, (_id, seen)| *seen == refresh);
+        let count = self.users.len();
+        self.users.retain(|_id, (_user, seen)| *seen == refresh);
+        let pin_count = self.pins.len();
+        self.pins.retain(|_id, (_pin, seen)| *seen == refresh);
+        if self.uids.len() != uid_count || self.users.len() != count || self.pins.len() != pin_count
+        {
+            self.dirty = true;
         }
+        count - self.users.len()
     }
 
     /// Add/update NFC uid for given user id
     pub fn update_uid(&mut self, uid: Uid, id: UserId) {
-        self.uids.insert(uid, id);
+        let prev = self.uids.insert(uid, (id, self.refresh));
+        if prev.is_none_or(|(prev_id, _seen)| prev_id != id) {
+            self.dirty = true;
+        }
     }
 
     /// Add/update user with given user id
     pub fn update_user(&mut self, id: UserId, name: String) {
-        self.users.insert(id, User { name });
+        let user = User { name };
+        match self.users.get_mut(&id) {
+            Some((prev, seen)) if *prev == user => *seen = self.refresh,
+            _ => {
+                self.users.insert(id, (user, self.refresh));
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// Add/update PIN of user with given user id
+    pub fn update_pin(&mut self, id: UserId, pin: String) {
+        match self.pins.get_mut(&id) {
+            Some((prev, seen)) if *prev == pin => *seen = self.refresh,
+            _ => {
+                self.pins.insert(id, (pin, self.refresh));
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// Remove user with given user id and all of its NFC uids and its PIN
+    #[allow(dead_code)]
+    pub fn remove(&mut self, id: UserId) -> Option<User> {
+        let uid_count = self.uids.len();
+        self.uids.retain(|_uid, (uid_id, _seen)| *uid_id != id);
+        let user = self.users.remove(&id).map(|(user, _seen)| user);
+        let pin = self.pins.remove(&id);
+        if user.is_some() || pin.is_some() || self.uids.len() != uid_count {
+            self.dirty = true;
+        }
+        user
+    }
+
+    /// Remove given NFC uid. The user it belonged to is kept.
+    #[allow(dead_code)]
+    pub fn remove_uid(&mut self, uid: &Uid) -> Option<UserId> {
+        let id = self.uids.remove(uid).map(|(id, _seen)| id);
+        if id.is_some() {
+            self.dirty = true;
+        }
+        id
+    }
+
+    /// Returns true if user information changed since it was last marked clean
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Mark user information as clean, e.g. after storing it
+    pub fn mark_clean(&mut self) {
+        self.dirty = false;
     }
 
     /// Number of uids
@@ -78,13 +172,224 @@ impl Users {
         self.users.len()
     }
 
+    /// Iterate over NFC uids and their user ids
+    pub fn iter_uids(&self) -> impl Iterator<Item = (&Uid, UserId)> {
+        self.uids.iter().map(|(uid, (id, _seen))| (uid, *id))
+    }
+
+    /// Iterate over users
+    pub fn iter(&self) -> impl Iterator<Item = (UserId, &User)> {
+        self.users.iter().map(|(id, (user, _seen))| (*id, user))
+    }
+
     /// Look up user id by NFC uid
     pub fn id(&self, uid: &Uid) -> Option<UserId> {
-        self.uids.get(uid).copied()
+        self.uids.get(uid).map(|(id, _seen)| *id)
     }
 
     /// Look up user by user id
     pub fn get(&self, id: UserId) -> Option<&User> {
-        self.users.get(&id)
+        self.users.get(&id).map(|(user, _seen)| user)
+    }
+
+    /// Iterate over user ids and PINs
+    pub fn iter_pins(&self) -> impl Iterator<Item = (UserId, &str)> {
+        self.pins
+            .iter()
+            .map(|(id, (pin, _seen))| (*id, pin.as_str()))
+    }
+
+    /// Look up PIN of user by user id
+    pub fn pin(&self, id: UserId) -> Option<&str> {
+        self.pins.get(&id).map(|(pin, _seen)| pin.as_str())
+    }
+}
+
+impl Users {
+    /// Add extra uids and user for testing
+    fn add_extra_uids(&mut self) {
+        for (uid, id) in &EXTRA_UIDS {
+            self.update_uid(uid.clone(), *id);
+            self.update_user(*id, String::from("Test-User"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UID1: Uid = Uid::Single([0x12, 0x34, 0x56, 0x78]);
+    const UID2: Uid = Uid::Double([1, 2, 3, 4, 5, 6, 7]);
+
+    fn users() -> Users {
+        let mut users = Users::new();
+        users.update_uid(UID1, 1234);
+        users.update_uid(UID2, 1234);
+        users.update_user(1234, "Fred".into());
+        users.update_user(5678, "Wilma".into());
+        users
+    }
+
+    #[test]
+    fn extra_uids() {
+        let users = Users::new();
+        if cfg!(debug_assertions) {
+            assert_eq!(users.count_uids(), 2);
+            assert_eq!(users.id(&Uid::Single([0x13, 0xbd, 0x5b, 0x2a])), Some(3));
+            assert_eq!(users.get(3).map(|u| u.name.as_str()), Some("Test-User"));
+        } else {
+            assert_eq!(users.count_uids(), 0);
+            assert_eq!(users.count(), 0);
+        }
+    }
+
+    #[test]
+    fn add() {
+        let users = users();
+        assert_eq!(users.count(), Users::new().count() + 2);
+        assert_eq!(users.id(&UID1), Some(1234));
+        assert_eq!(users.id(&UID2), Some(1234));
+        assert_eq!(users.get(1234).map(|u| u.name.as_str()), Some("Fred"));
+        assert_eq!(users.get(5678).map(|u| u.name.as_str()), Some("Wilma"));
+    }
+
+    #[test]
+    fn remove() {
+        let mut users = users();
+        let count = users.count();
+        assert_eq!(users.remove(1234).map(|u| u.name), Some("Fred".into()));
+        assert_eq!(users.remove(1234), None);
+        assert_eq!(users.count(), count - 1);
+        assert_eq!(users.get(1234), None);
+        // NFC uids of removed user are removed as well
+        assert_eq!(users.id(&UID1), None);
+        assert_eq!(users.id(&UID2), None);
+    }
+
+    #[test]
+    fn remove_uid() {
+        let mut users = users();
+        assert_eq!(users.remove_uid(&UID1), Some(1234));
+        assert_eq!(users.remove_uid(&UID1), None);
+        assert_eq!(users.id(&UID1), None);
+        assert_eq!(users.id(&UID2), Some(1234));
+        assert!(users.get(1234).is_some());
+    }
+
+    #[test]
+    fn prune_after_refresh() {
+        let mut users = users();
+        users.begin_refresh();
+        users.update_uid(UID2, 1234);
+        users.update_user(1234, "Fred".into());
+        assert_eq!(users.prune(), 1);
+        assert_eq!(users.id(&UID1), None);
+        assert_eq!(users.id(&UID2), Some(1234));
+        assert!(users.get(1234).is_some());
+        assert_eq!(users.get(5678), None);
+    }
+
+    #[test]
+    fn prune_keeps_extra_uids() {
+        let mut users = users();
+        users.begin_refresh();
+        assert_eq!(users.prune(), 2);
+        assert_eq!(users.count_uids(), EXTRA_UIDS.len());
+        for (uid, id) in &EXTRA_UIDS {
+            assert_eq!(users.id(uid), Some(*id));
+        }
+    }
+
+    #[test]
+    fn prune_without_refresh() {
+        let mut users = users();
+        let count = users.count();
+        assert_eq!(users.prune(), 0);
+        assert_eq!(users.count(), count);
+    }
+
+    #[test]
+    fn new_is_clean() {
+        assert!(!Users::new().is_dirty());
+    }
+
+    #[test]
+    fn update_marks_dirty() {
+        let mut users = users();
+        assert!(users.is_dirty());
+        users.mark_clean();
+        users.update_user(1234, "Barney".into());
+        assert!(users.is_dirty());
+        users.mark_clean();
+        users.update_uid(UID1, 5678);
+        assert!(users.is_dirty());
+    }
+
+    #[test]
+    fn update_unchanged_keeps_clean() {
+        let mut users = users();
+        users.mark_clean();
+        users.begin_refresh();
+        users.update_uid(UID1, 1234);
+        users.update_uid(UID2, 1234);
+        users.update_user(1234, "Fred".into());
+        users.update_user(5678, "Wilma".into());
+        assert!(!users.is_dirty());
+        // Unchanged entries are still marked as seen in the refresh
+        assert_eq!(users.prune(), 0);
+        assert!(!users.is_dirty());
+    }
+
+    #[test]
+    fn remove_marks_dirty() {
+        let mut users = users();
+        users.mark_clean();
+        assert_eq!(users.remove_uid(&UID1), Some(1234));
+        assert!(users.is_dirty());
+        users.mark_clean();
+        assert_eq!(users.remove(5678).map(|u| u.name), Some("Wilma".into()));
+        assert!(users.is_dirty());
+        users.mark_clean();
+        assert_eq!(users.remove(5678), None);
+        assert!(!users.is_dirty());
+    }
+
+    #[test]
+    fn pins() {
+        let mut users = users();
+        users.mark_clean();
+        users.update_pin(1234, "0815".into());
+        assert!(users.is_dirty());
+        assert_eq!(users.pin(1234), Some("0815"));
+        assert_eq!(users.pin(5678), None);
+        users.mark_clean();
+        users.update_pin(1234, "0815".into());
+        assert!(!users.is_dirty());
+        // PIN of removed user is removed as well
+        users.remove(1234);
+        assert_eq!(users.pin(1234), None);
+    }
+
+    #[test]
+    fn prune_pins() {
+        let mut users = users();
+        users.update_pin(1234, "0815".into());
+        users.update_pin(5678, "4711".into());
+        users.begin_refresh();
+        users.update_user(5678, "Wilma".into());
+        users.update_pin(5678, "4711".into());
+        users.prune();
+        assert_eq!(users.pin(1234), None);
+        assert_eq!(users.pin(5678), Some("4711"));
+    }
+
+    #[test]
+    fn prune_marks_dirty() {
+        let mut users = users();
+        users.mark_clean();
+        users.begin_refresh();
+        assert_eq!(users.prune(), 2);
+        assert!(users.is_dirty());
     }
 }
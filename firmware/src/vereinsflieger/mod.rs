@@ -1,28 +1,107 @@
 mod proto_articles;
 mod proto_auth;
+mod proto_balance;
 mod proto_sale;
 mod proto_user;
 
-use crate::article::{ArticleId, Articles};
+use crate::article::Articles;
 use crate::http::{self, Http};
+use crate::queue::Purchase;
 use crate::time;
+use crate::totp::Totp;
 use crate::user::{UserId, Users};
 use alloc::format;
 use alloc::string::String;
-use core::cell::RefCell;
+use chrono::{DateTime, Utc};
+use core::cell::{Cell, RefCell};
 use core::fmt;
 use embassy_time::{with_timeout, Duration};
 use log::{debug, info, warn};
+use reqwless::response::StatusCode;
 
 /// Vereinsflieger API base URL
 const BASE_URL: &str = "https://www.vereinsflieger.de/interface/rest";
 
+/// Default label prefix of user keys that are NFC uids
+const DEFAULT_NFC_KEY_PREFIX: &str = "NFC Transponder";
+
+/// Label prefix of user keys that hold a PIN for authenticating by member number
+const PIN_KEY_PREFIX: &str = "Touch-n-Drink PIN";
+
 /// How long to wait for a server response
 const TIMEOUT: Duration = Duration::from_secs(10);
 
 /// How long to wait to finish streaming a server's response
 const FETCH_TIMEOUT: Duration = Duration::from_secs(60);
 
+/// Default comment for sales, tagged with the given device id
+fn sale_comment(device_id: &str) -> String {
+    format!("Touch-n-Drink {device_id}")
+}
+
+/// Comment for a sale of the purchase with the given id and article name (if known). Tagging sales
+/// with the purchase id allows to spot sales that were submitted twice (e.g. if a response got lost
+/// and submitting was retried).
+fn purchase_comment(sale_comment: &str, article_name: Option<&str>, purchase_id: u32) -> String {
+    match (sale_comment, article_name) {
+        ("", None) => format!("Purchase {purchase_id}"),
+        ("", Some(name)) => format!("{name}, purchase {purchase_id}"),
+        (comment, None) => format!("{comment}, purchase {purchase_id}"),
+        (comment, Some(name)) => format!("{comment}, {name}, purchase {purchase_id}"),
+    }
+}
+
+/// Check the HTTP status code that Vereinsflieger embeds in response bodies. Some failures are
+/// reported with a successful HTTP status, but an error status code in the body.
+fn check_status(httpstatuscode: Option<u16>) -> Result<(), http::Error> {
+    match httpstatuscode.map(StatusCode) {
+        Some(status) if !status.is_successful() => {
+            warn!("Vereinsflieger: Response reports status {}", status.0);
+            Err(http::Error::from_status(status))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Maximum number of pages to fetch for a paginated list request. Protects against servers that
+/// ignore the requested offset and return the same full page over and over again.
+const MAX_PAGES: u32 = 100;
+
+/// Page of a paginated list request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Page {
+    /// Number of entries to skip
+    offset: u32,
+    /// Max number of entries to return
+    limit: u32,
+}
+
+impl Page {
+    /// First page with given page size
+    fn first(limit: u32) -> Self {
+        Self { offset: 0, limit }
+    }
+
+    /// Page following this page, given the number of entries returned for this page. Returns
+    /// `None` if this was the last page, i.e. if fewer entries than requested were returned. If
+    /// more entries than requested were returned, the server didn't paginate and returned all
+    /// entries at once. Also returns `None` if the maximum number of pages was reached.
+    fn next(self, count: u32) -> Option<Self> {
+        if count != self.limit {
+            return None;
+        }
+        let offset = self.offset + self.limit;
+        if offset >= MAX_PAGES * self.limit {
+            warn!("Vereinsflieger: Stopping after {} pages", MAX_PAGES);
+            return None;
+        }
+        Some(Self {
+            offset,
+            limit: self.limit,
+        })
+    }
+}
+
 /// Vereinsflieger API error
 #[derive(Debug)]
 pub enum Error {
@@ -32,6 +111,8 @@ pub enum Error {
     FetchArticles(http::Error),
     /// Failed to fetch users
     FetchUsers(http::Error),
+    /// Failed to fetch account balance
+    FetchBalance(http::Error),
     /// Failed to purchase
     Purchase(http::Error),
     /// Failed to connect to API server
@@ -48,12 +129,24 @@ impl From<embassy_time::TimeoutError> for Error {
     }
 }
 
+impl Error {
+    /// Returns true if the server permanently rejected a purchase (e.g. because of an unknown
+    /// article or member). Submitting the same purchase again would fail again.
+    pub fn is_rejected(&self) -> bool {
+        matches!(
+            self,
+            Self::Purchase(http::Error::BadRequest(_) | http::Error::MalformedRequest(_))
+        )
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::FetchUserInformation(err) => write!(f, "Fetch user info failed ({err})"),
             Self::FetchArticles(err) => write!(f, "Fetch articles failed ({err})"),
             Self::FetchUsers(err) => write!(f, "Fetch users failed ({err})"),
+            Self::FetchBalance(err) => write!(f, "Fetch balance failed ({err})"),
             Self::Purchase(err) => write!(f, "Purchase failed ({err})"),
             Self::Connect(err) => write!(f, "Connect failed ({err})"),
             Self::SignIn(err) => write!(f, "Sign in failed ({err})"),
@@ -71,6 +164,10 @@ pub struct Vereinsflieger<'a> {
     password_md5: &'a str,
     appkey: &'a str,
     cid: Option<u32>,
+    page_size: Option<u32>,
+    totp: Option<Totp>,
+    sale_comment: String,
+    nfc_key_prefix: String,
     accesstoken: Option<AccessToken>,
 }
 
@@ -81,27 +178,63 @@ impl fmt::Debug for Vereinsflieger<'_> {
             .field("password_md5", &"<redacted>")
             .field("appkey", &"<redacted>")
             .field("cid", &self.cid)
+            .field("page_size", &self.page_size)
+            .field("totp", &self.totp)
+            .field("sale_comment", &self.sale_comment)
+            .field("nfc_key_prefix", &self.nfc_key_prefix)
             .finish()
     }
 }
 
 impl<'a> Vereinsflieger<'a> {
-    /// Create new Vereinsflieger API client using the given credentials
+    /// Create new Vereinsflieger API client using the given credentials. Sales are commented
+    /// with the given device id.
     pub fn new(
         username: &'a str,
         password_md5: &'a str,
         appkey: &'a str,
         cid: Option<u32>,
+        device_id: &str,
     ) -> Self {
         Self {
             username,
             password_md5,
             appkey,
             cid,
+            page_size: None,
+            totp: None,
+            sale_comment: sale_comment(device_id),
+            nfc_key_prefix: String::from(DEFAULT_NFC_KEY_PREFIX),
             accesstoken: None,
         }
     }
 
+    /// Set comment for sales instead of the default comment. An empty comment omits the comment.
+    pub fn set_sale_comment(&mut self, comment: String) {
+        debug!("Vereinsflieger: Setting sale comment to {:?}", comment);
+        self.sale_comment = comment;
+    }
+
+    /// Set label prefix of user keys that are NFC uids instead of the default prefix
+    pub fn set_nfc_key_prefix(&mut self, prefix: String) {
+        debug!("Vereinsflieger: Using NFC key prefix {:?}", prefix);
+        self.nfc_key_prefix = prefix;
+    }
+
+    /// Fetch lists of articles and users in pages of the given size instead of all at once.
+    /// Keeps single requests short, which is useful for clubs with many members.
+    pub fn set_page_size(&mut self, page_size: u32) {
+        debug!("Vereinsflieger: Using page size {}", page_size);
+        self.page_size = Some(page_size).filter(|size| *size > 0);
+    }
+
+    /// Set shared secret for two-factor authentication. A code generated from it is sent when
+    /// signing in.
+    pub fn set_auth_secret(&mut self, totp: Totp) {
+        debug!("Vereinsflieger: Using two-factor authentication");
+        self.totp = Some(totp);
+    }
+
     /// Connect to API server
     pub async fn connect<'conn>(
         &'conn mut self,
@@ -115,6 +248,9 @@ impl<'a> Vereinsflieger<'a> {
 pub struct Connection<'a> {
     http: http::Connection<'a>,
     accesstoken: &'a AccessToken,
+    page_size: Option<u32>,
+    sale_comment: &'a str,
+    nfc_key_prefix: &'a str,
 }
 
 impl fmt::Debug for Connection<'_> {
@@ -122,11 +258,20 @@ impl fmt::Debug for Connection<'_> {
         f.debug_struct("Connection")
             .field("http", &self.http)
             .field("accesstoken", &"<redacted>")
+            .field("page_size", &self.page_size)
+            .field("sale_comment", &self.sale_comment)
+            .field("nfc_key_prefix", &self.nfc_key_prefix)
             .finish()
     }
 }
 
 impl Connection<'_> {
+    /// Returns true if further requests can be sent over this connection. Otherwise, the
+    /// connection was closed and the caller needs to connect again.
+    pub fn is_reusable(&self) -> bool {
+        self.http.is_reusable()
+    }
+
     /// Fetch information about authenticated user
     #[allow(dead_code)]
     pub async fn get_user_information(&mut self) -> Result<(), Error> {
@@ -143,6 +288,7 @@ impl Connection<'_> {
         )
         .await?
         .map_err(Error::FetchUserInformation)?;
+        check_status(response.httpstatuscode).map_err(Error::FetchUserInformation)?;
         debug!("Vereinsflieger: Got user information: {:?}", response);
         Ok(())
     }
@@ -152,116 +298,180 @@ impl Connection<'_> {
         use proto_articles::{ArticleListRequest, ArticleListResponse};
 
         debug!("Vereinsflieger: Refreshing articles...");
-        let request_body = http::Connection::prepare_body(&ArticleListRequest {
-            accesstoken: self.accesstoken,
-        })
-        .await
-        .map_err(Error::FetchArticles)?;
-        let mut rx_buf = [0; 4096];
-        let mut json = with_timeout(
-            TIMEOUT,
-            self.http
-                .post_json("articles/list", &request_body, &mut rx_buf),
-        )
-        .await?
-        .map_err(Error::FetchArticles)?;
-
         articles.clear();
         let articles = RefCell::new(articles);
 
-        let response: ArticleListResponse =
-            with_timeout(FETCH_TIMEOUT, json.read_object_with_context(&articles))
-                .await?
-                .map_err(http::Error::MalformedResponse)
+        // Fetch pages (if paginated) and merge them into the article lookup table
+        let mut page = self.page_size.map(Page::first);
+        let mut total_articles = 0;
+        loop {
+            let request_body = http::Connection::prepare_body(&ArticleListRequest {
+                accesstoken: self.accesstoken,
+                page,
+            })
+            .await
+            .map_err(Error::FetchArticles)?;
+            let mut json =
+                with_timeout(TIMEOUT, self.http.post_json("articles/list", &request_body))
+                    .await?
+                    .map_err(Error::FetchArticles)?;
+
+            let response: ArticleListResponse =
+                with_timeout(FETCH_TIMEOUT, json.read_object_with_context(&articles))
+                    .await?
+                    .map_err(http::Error::from)
+                    .map_err(Error::FetchArticles)?;
+            check_status(response.httpstatuscode).map_err(Error::FetchArticles)?;
+            total_articles += response.total_articles;
+
+            // Discard remaining body (needed to make the next pipelined request work)
+            json.discard_to_end()
+                .await
+                .map_err(http::Error::from)
                 .map_err(Error::FetchArticles)?;
+
+            match page.and_then(|page| page.next(response.total_articles)) {
+                Some(next_page) => page = Some(next_page),
+                None => break,
+            }
+        }
         info!(
             "Vereinsflieger: Refreshed {} of {} articles",
             articles.borrow().count(),
-            response.total_articles
+            total_articles
         );
 
-        // Discard remaining body (needed to make the next pipelined request work)
-        json.discard_to_end()
-            .await
-            .map_err(http::Error::MalformedResponse)
-            .map_err(Error::FetchUsers)?;
-
         Ok(())
     }
 
-    /// Fetch list of users and update user lookup table
-    pub async fn refresh_users(&mut self, users: &mut Users) -> Result<(), Error> {
-        use proto_user::{UserListRequest, UserListResponse};
+    /// Fetch list of users and update user lookup table. The given progress function is called
+    /// with the number of users parsed so far after each parsed user (while still streaming the
+    /// response, so it must not block).
+    pub async fn refresh_users(
+        &mut self,
+        users: &mut Users,
+        progress: impl Fn(usize),
+    ) -> Result<(), Error> {
+        use proto_user::{UserListContext, UserListRequest, UserListResponse};
 
         debug!("Vereinsflieger: Refreshing users...");
-        let request_body = http::Connection::prepare_body(&UserListRequest {
-            accesstoken: self.accesstoken,
-        })
-        .await
-        .map_err(Error::FetchUsers)?;
-        let mut rx_buf = [0; 4096];
-        let mut json = with_timeout(
-            TIMEOUT,
-            self.http.post_json("user/list", &request_body, &mut rx_buf),
-        )
-        .await?
-        .map_err(Error::FetchUsers)?;
-
-        users.clear();
-        let users = RefCell::new(users);
-
-        let response: UserListResponse =
-            with_timeout(FETCH_TIMEOUT, json.read_object_with_context(&users))
+        // Keep known users until all pages are fetched, so that a failed refresh doesn't lose them
+        users.begin_refresh();
+        let parsed_users = Cell::new(0);
+        let on_user = || {
+            parsed_users.set(parsed_users.get() + 1);
+            progress(parsed_users.get());
+        };
+        let context = UserListContext {
+            users: RefCell::new(users),
+            key_prefix: self.nfc_key_prefix,
+            pin_key_prefix: PIN_KEY_PREFIX,
+            on_user: &on_user,
+        };
+
+        // Fetch pages (if paginated) and merge them into the user lookup table
+        let mut page = self.page_size.map(Page::first);
+        let mut total_users = 0;
+        loop {
+            let request_body = http::Connection::prepare_body(&UserListRequest {
+                accesstoken: self.accesstoken,
+                page,
+            })
+            .await
+            .map_err(Error::FetchUsers)?;
+            let mut json = with_timeout(TIMEOUT, self.http.post_json("user/list", &request_body))
                 .await?
-                .map_err(http::Error::MalformedResponse)
                 .map_err(Error::FetchUsers)?;
+
+            let response: UserListResponse =
+                with_timeout(FETCH_TIMEOUT, json.read_object_with_context(&context))
+                    .await?
+                    .map_err(http::Error::from)
+                    .map_err(Error::FetchUsers)?;
+            check_status(response.httpstatuscode).map_err(Error::FetchUsers)?;
+            total_users += response.total_users;
+
+            // Discard remaining body (needed to make the next pipelined request work)
+            json.discard_to_end()
+                .await
+                .map_err(http::Error::from)
+                .map_err(Error::FetchUsers)?;
+
+            match page.and_then(|page| page.next(response.total_users)) {
+                Some(next_page) => page = Some(next_page),
+                None => break,
+            }
+        }
+        let removed = context.users.borrow_mut().prune();
         info!(
-            "Vereinsflieger: Refreshed {} of {} users",
-            users.borrow().count(),
-            response.total_users
+            "Vereinsflieger: Refreshed {} of {} users ({} removed)",
+            context.users.borrow().count(),
+            total_users,
+            removed
         );
 
-        // Discard remaining body (needed to make the next pipelined request work)
-        json.discard_to_end()
-            .await
-            .map_err(http::Error::MalformedResponse)
-            .map_err(Error::FetchUsers)?;
-
         Ok(())
     }
 
+    /// Fetch account balance of given user
+    pub async fn get_balance(&mut self, user_id: UserId) -> Result<f32, Error> {
+        use proto_balance::{UserBalanceRequest, UserBalanceResponse};
+
+        debug!("Vereinsflieger: Fetching balance of user {}...", user_id);
+        let response: UserBalanceResponse = with_timeout(
+            TIMEOUT,
+            self.http.post(
+                "user/getbalance",
+                &UserBalanceRequest {
+                    accesstoken: self.accesstoken,
+                    memberid: user_id,
+                },
+            ),
+        )
+        .await?
+        .map_err(Error::FetchBalance)?;
+        check_status(response.httpstatuscode).map_err(Error::FetchBalance)?;
+        debug!(
+            "Vereinsflieger: Balance of user {}: {:.02} EUR",
+            user_id, response.balance
+        );
+        Ok(response.balance)
+    }
+
     /// Store a purchase
     pub async fn purchase(
         &mut self,
-        article_id: &ArticleId,
-        amount: f32,
-        user_id: UserId,
-        total_price: f32,
+        purchase: &Purchase,
+        article_name: Option<&str>,
     ) -> Result<(), Error> {
         use proto_sale::{SaleAddRequest, SaleAddResponse};
 
         debug!(
             "Vereinsflieger: Purchasing {}x {}, {:.02} EUR for user {}",
-            amount, article_id, total_price, user_id
+            purchase.amount, purchase.article_id, purchase.total_price, purchase.user_id
         );
 
-        let _response: SaleAddResponse = with_timeout(
+        let comment = purchase_comment(self.sale_comment, article_name, purchase.id);
+        let response: SaleAddResponse = with_timeout(
             TIMEOUT,
             self.http.post(
                 "sale/add",
                 &SaleAddRequest {
                     accesstoken: self.accesstoken,
-                    bookingdate: &Self::today(),
-                    articleid: article_id,
-                    amount,
-                    memberid: Some(user_id),
-                    totalprice: Some(total_price),
-                    comment: None,
+                    bookingdate: &Self::date(purchase.time),
+                    articleid: &purchase.article_id,
+                    amount: purchase.amount,
+                    memberid: Some(purchase.user_id),
+                    callsign: purchase.callsign.as_deref(),
+                    salestax: purchase.salestax,
+                    totalprice: Some(purchase.total_price),
+                    comment: Some(&comment),
                 },
             ),
         )
         .await?
         .map_err(Error::Purchase)?;
+        check_status(response.httpstatuscode).map_err(Error::Purchase)?;
         debug!("Vereinsflieger: Purchase successful");
         Ok(())
     }
@@ -285,6 +495,8 @@ impl<'a> Connection<'a> {
                 connection.post("auth/getuser", &UserInformationRequest { accesstoken }),
             )
             .await?;
+            let response = response
+                .and_then(|userinfo| check_status(userinfo.httpstatuscode).map(|()| userinfo));
             match response {
                 Ok(_userinfo) => debug!("Vereinsflieger: Access token valid"),
                 Err(http::Error::Unauthorized) => {
@@ -304,6 +516,7 @@ impl<'a> Connection<'a> {
                 with_timeout(TIMEOUT, connection.get("auth/accesstoken"))
                     .await?
                     .map_err(Error::SignIn)?;
+            check_status(response.httpstatuscode).map_err(Error::SignIn)?;
             let accesstoken = response.accesstoken;
             // debug!("Vereinsflieger: Got access token {}", accesstoken);
             debug!(
@@ -311,6 +524,14 @@ impl<'a> Connection<'a> {
                 accesstoken.len()
             );
 
+            // Generate two-factor auth code (if needed). Current time is usually known by now
+            // since it's taken from the server's response.
+            let now = time::now();
+            if vf.totp.is_some() && now.is_none() {
+                warn!("Vereinsflieger: Unable to generate two-factor auth code without time");
+            }
+            let auth_code = vf.totp.as_ref().zip(now).map(|(totp, now)| totp.code(now));
+
             // Use credentials to sign in
             let response: Result<SignInResponse, _> = with_timeout(
                 TIMEOUT,
@@ -322,11 +543,13 @@ impl<'a> Connection<'a> {
                         password_md5: vf.password_md5,
                         appkey: vf.appkey,
                         cid: vf.cid,
-                        auth_secret: None,
+                        auth_secret: auth_code.as_deref(),
                     },
                 ),
             )
             .await?;
+            let response =
+                response.and_then(|signin| check_status(signin.httpstatuscode).map(|()| signin));
             match response {
                 Ok(_signin) => {
                     vf.accesstoken = Some(accesstoken);
@@ -343,18 +566,134 @@ impl<'a> Connection<'a> {
             Some(ref accesstoken) => Ok(Self {
                 http: connection,
                 accesstoken,
+                page_size: vf.page_size,
+                sale_comment: &vf.sale_comment,
+                nfc_key_prefix: &vf.nfc_key_prefix,
             }),
             // Actually unreachable
             None => Err(Error::SignIn(http::Error::Unauthorized)),
         }
     }
 
-    /// Helper function to get today's date as "yyyy-mm-dd" string
-    fn today() -> String {
-        if let Some(now) = time::now() {
-            format!("{}", now.format("%Y-%m-%d"))
+    /// Helper function to get the given date (or today's date if not given) as "yyyy-mm-dd"
+    /// string
+    fn date(time: Option<DateTime<Utc>>) -> String {
+        if let Some(time) = time.or_else(time::now) {
+            format!("{}", time.format("%Y-%m-%d"))
         } else {
             String::new()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_ok() {
+        assert!(check_status(None).is_ok());
+        assert!(check_status(Some(200)).is_ok());
+        assert!(check_status(Some(204)).is_ok());
+    }
+
+    #[test]
+    fn status_error() {
+        assert!(matches!(
+            check_status(Some(401)),
+            Err(http::Error::Unauthorized)
+        ));
+        assert!(matches!(
+            check_status(Some(404)),
+            Err(http::Error::BadRequest(StatusCode(404)))
+        ));
+        assert!(matches!(
+            check_status(Some(500)),
+            Err(http::Error::ServerError(StatusCode(500)))
+        ));
+    }
+
+    #[test]
+    fn first_page() {
+        assert_eq!(
+            Page::first(100),
+            Page {
+                offset: 0,
+                limit: 100
+            }
+        );
+    }
+
+    #[test]
+    fn next_page() {
+        let page = Page::first(100);
+        let page = page.next(100).unwrap();
+        assert_eq!(
+            page,
+            Page {
+                offset: 100,
+                limit: 100
+            }
+        );
+        assert_eq!(
+            page.next(100),
+            Some(Page {
+                offset: 200,
+                limit: 100
+            })
+        );
+    }
+
+    #[test]
+    fn last_page() {
+        // Fewer entries than requested: last page
+        assert_eq!(Page::first(100).next(42), None);
+        assert_eq!(Page::first(100).next(0), None);
+        // More entries than requested: server doesn't paginate
+        assert_eq!(Page::first(100).next(250), None);
+    }
+
+    #[test]
+    fn max_pages() {
+        // Server always returns a full page: stop after the maximum number of pages
+        let mut page = Page::first(10);
+        let mut pages = 1;
+        while let Some(next_page) = page.next(10) {
+            page = next_page;
+            pages += 1;
+        }
+        assert_eq!(pages, MAX_PAGES);
+        assert_eq!(page.offset, (MAX_PAGES - 1) * 10);
+    }
+
+    #[test]
+    fn default_sale_comment() {
+        assert_eq!(sale_comment("0123456789ab"), "Touch-n-Drink 0123456789ab");
+    }
+
+    #[test]
+    fn purchase_sale_comment() {
+        assert_eq!(
+            purchase_comment("Touch-n-Drink 0123456789ab", Some("Coffee"), 42),
+            "Touch-n-Drink 0123456789ab, Coffee, purchase 42"
+        );
+        assert_eq!(
+            purchase_comment("Touch-n-Drink 0123456789ab", None, 42),
+            "Touch-n-Drink 0123456789ab, purchase 42"
+        );
+        assert_eq!(
+            purchase_comment("", Some("Coffee"), 42),
+            "Coffee, purchase 42"
+        );
+        assert_eq!(purchase_comment("", None, 42), "Purchase 42");
+    }
+
+    #[test]
+    fn rejected_purchase() {
+        let status = StatusCode(400);
+        assert!(Error::Purchase(http::Error::BadRequest(status)).is_rejected());
+        assert!(!Error::Purchase(http::Error::ServerError(StatusCode(503))).is_rejected());
+        assert!(!Error::Purchase(http::Error::Unauthorized).is_rejected());
+        assert!(!Error::Timeout.is_rejected());
+    }
+}
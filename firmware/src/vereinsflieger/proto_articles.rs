@@ -1,8 +1,10 @@
-use super::AccessToken;
+use super::{AccessToken, Page};
 use crate::article::Articles;
 use crate::json::{self, FromJsonObject, ToJson};
+use crate::time;
 use alloc::string::String;
 use alloc::vec::Vec;
+use chrono::NaiveDate;
 use core::cell::RefCell;
 use core::str::FromStr;
 use embedded_io_async::{BufRead, Write};
@@ -12,6 +14,7 @@ use log::warn;
 #[derive(Debug)]
 pub struct ArticleListRequest<'a> {
     pub accesstoken: &'a AccessToken,
+    pub page: Option<Page>,
 }
 
 impl ToJson for ArticleListRequest<'_> {
@@ -23,6 +26,10 @@ impl ToJson for ArticleListRequest<'_> {
             .await?
             .field("accesstoken", self.accesstoken)
             .await?
+            .field_opt("offset", self.page.map(|page| page.offset))
+            .await?
+            .field_opt("limit", self.page.map(|page| page.limit))
+            .await?
             .finish()
             .await
     }
@@ -32,9 +39,10 @@ impl ToJson for ArticleListRequest<'_> {
 #[derive(Debug, Default)]
 pub struct ArticleListResponse {
     // pub *: Article,
-    // pub httpstatuscode: u16,
     //
-    /// Total number of articles
+    /// Status code embedded in the response body (if any)
+    pub httpstatuscode: Option<u16>,
+    /// Number of articles in this response
     pub total_articles: u32,
 }
 
@@ -52,12 +60,14 @@ impl FromJsonObject for ArticleListResponse {
             Ok(_key) => {
                 let article: Article = json.read().await?;
                 self.total_articles += 1;
-                if let Some(price) = article.price() {
+                let today = time::now().map(|now| now.date_naive());
+                if let Some(price) = article.price(today) {
                     // Instead of reading all articles to a vector, this deserialization stores
                     // articles directly to the article lookup table and only keeps the articles
                     // needed, which heavily reduces memory consumption.
+                    let (unitprice, salestax) = (price.unitprice, price.salestax);
                     let mut articles = context.borrow_mut();
-                    articles.update(&article.articleid, article.designation, price);
+                    articles.update(&article.articleid, article.designation, unitprice, salestax);
                 } else {
                     warn!(
                         "Ignoring article with no valid price ({}): {}",
@@ -65,6 +75,9 @@ impl FromJsonObject for ArticleListResponse {
                     );
                 }
             }
+            Err(_) if key == "httpstatuscode" => {
+                self.httpstatuscode = Some(json.read_any().await?.try_into()?);
+            }
             _ => json.skip_any().await?,
         }
         Ok(())
@@ -101,30 +114,53 @@ impl FromJsonObject for Article {
 }
 
 impl Article {
-    /// Get today's price
-    fn price(&self) -> Option<f32> {
-        // TODO: Get a current date and do a real price selection based on validity dates.
-        // For now, we make sure to end up with the last entry valid until 9999-12-31, if any, or
-        // any last entry otherwise.
-        let price = self
-            .prices
+    /// Get price valid at the given date (usually today)
+    fn price(&self, today: Option<NaiveDate>) -> Option<&ArticlePrice> {
+        if let Some(today) = today {
+            // Of all prices valid today, choose the one that became valid most recently. If
+            // several prices became valid at the same date, the last one wins.
+            let price = self
+                .prices
+                .iter()
+                .filter(|p| p.is_valid_at(today))
+                .max_by_key(|p| parse_date(&p.validfrom));
+            if price.is_some() {
+                return price;
+            }
+        }
+        // If the current date is unknown or no price is valid today, use the last entry valid
+        // until 9999-12-31, if any, or any last entry otherwise.
+        self.prices
             .iter()
             .rev()
             .find(|p| p.validto == "9999-12-31")
-            .or(self.prices.last());
-        price.map(|p| p.unitprice)
+            .or(self.prices.last())
     }
 }
 
+/// Parse date given as "yyyy-mm-dd"
+fn parse_date(s: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()
+}
+
 /// Article price
 #[derive(Debug, Default)]
 struct ArticlePrice {
     validfrom: String, // "yyyy-mm-dd"
     validto: String,   // "yyyy-mm-dd"
-    salestax: f32,
+    salestax: Option<f32>,
     unitprice: f32,
 }
 
+impl ArticlePrice {
+    /// Returns true if price is valid at the given date. A missing or invalid date is treated as
+    /// an open-ended range.
+    fn is_valid_at(&self, date: NaiveDate) -> bool {
+        parse_date(&self.validfrom).is_none_or(|from| from <= date)
+            && parse_date(&self.validto).is_none_or(|to| date <= to)
+    }
+}
+
 impl FromJsonObject for ArticlePrice {
     type Context<'ctx> = ();
 
@@ -137,10 +173,101 @@ impl FromJsonObject for ArticlePrice {
         match &*key {
             "validfrom" => self.validfrom = json.read().await?,
             "validto" => self.validto = json.read().await?,
-            "salestax" => self.salestax = json.read_any().await?.try_into()?,
+            "salestax" => self.salestax = Some(json.read_any().await?.try_into()?),
             "unitprice" => self.unitprice = json.read_any().await?.try_into()?,
             _ => json.skip_any().await?,
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use alloc::vec;
+
+    fn price(validfrom: &str, validto: &str, unitprice: f32) -> ArticlePrice {
+        ArticlePrice {
+            validfrom: validfrom.to_string(),
+            validto: validto.to_string(),
+            salestax: None,
+            unitprice,
+        }
+    }
+
+    fn article_with(prices: Vec<ArticlePrice>) -> Article {
+        Article {
+            prices,
+            ..Article::default()
+        }
+    }
+
+    fn unitprice(article: &Article, today: Option<&str>) -> Option<f32> {
+        article
+            .price(today.and_then(parse_date))
+            .map(|p| p.unitprice)
+    }
+
+    #[test]
+    fn price_by_date() {
+        let article = article_with(vec![
+            price("2023-01-01", "2023-12-31", 1.0),
+            price("2024-01-01", "2024-12-31", 1.5),
+            price("2025-01-01", "9999-12-31", 2.0),
+        ]);
+        assert_eq!(unitprice(&article, Some("2023-06-15")), Some(1.0));
+        assert_eq!(unitprice(&article, Some("2024-01-01")), Some(1.5));
+        assert_eq!(unitprice(&article, Some("2024-12-31")), Some(1.5));
+        assert_eq!(unitprice(&article, Some("2025-06-15")), Some(2.0));
+    }
+
+    #[test]
+    fn price_overlapping() {
+        // Temporary price overlaps open-ended price, most recently started price wins
+        let article = article_with(vec![
+            price("2020-01-01", "9999-12-31", 2.0),
+            price("2025-06-01", "2025-06-30", 1.0),
+        ]);
+        assert_eq!(unitprice(&article, Some("2025-05-31")), Some(2.0));
+        assert_eq!(unitprice(&article, Some("2025-06-15")), Some(1.0));
+        assert_eq!(unitprice(&article, Some("2025-07-01")), Some(2.0));
+    }
+
+    #[test]
+    fn price_same_start() {
+        // Prices starting at the same date, last one wins
+        let article = article_with(vec![
+            price("2025-01-01", "9999-12-31", 2.0),
+            price("2025-01-01", "2025-12-31", 2.5),
+        ]);
+        assert_eq!(unitprice(&article, Some("2025-06-15")), Some(2.5));
+    }
+
+    #[test]
+    fn price_fallback() {
+        let article = article_with(vec![
+            price("2020-01-01", "9999-12-31", 2.0),
+            price("2030-01-01", "2030-12-31", 3.0),
+        ]);
+        // Unknown date uses open-ended price
+        assert_eq!(unitprice(&article, None), Some(2.0));
+        // No price valid yet uses open-ended price
+        assert_eq!(unitprice(&article, Some("2019-06-15")), Some(2.0));
+        // Without open-ended price, last price is used
+        let article = article_with(vec![price("2020-01-01", "2020-12-31", 1.0)]);
+        assert_eq!(unitprice(&article, Some("2025-06-15")), Some(1.0));
+        assert_eq!(unitprice(&article_with(vec![]), Some("2025-06-15")), None);
+    }
+
+    #[test]
+    fn price_invalid_dates() {
+        // Missing or invalid dates are treated as open-ended
+        let article = article_with(vec![
+            price("", "2025-12-31", 1.0),
+            price("2026-01-01", "xx", 2.0),
+        ]);
+        assert_eq!(unitprice(&article, Some("2025-06-15")), Some(1.0));
+        assert_eq!(unitprice(&article, Some("2026-06-15")), Some(2.0));
+    }
+}
@@ -9,7 +9,8 @@ use embedded_io_async::{BufRead, Write};
 pub struct AccessTokenResponse {
     pub accesstoken: AccessToken,
     // pub URL: String,
-    // pub httpstatuscode: u16,
+    /// Status code embedded in the response body (if any)
+    pub httpstatuscode: Option<u16>,
 }
 
 impl FromJsonObject for AccessTokenResponse {
@@ -23,6 +24,7 @@ impl FromJsonObject for AccessTokenResponse {
     ) -> Result<(), json::Error<R::Error>> {
         match &*key {
             "accesstoken" => self.accesstoken = json.read().await?,
+            "httpstatuscode" => self.httpstatuscode = Some(json.read_any().await?.try_into()?),
             _ => json.skip_any().await?,
         }
         Ok(())
@@ -45,8 +47,8 @@ impl ToJson for SignInRequest<'_> {
         &self,
         json: &mut json::Writer<W>,
     ) -> Result<(), json::Error<W::Error>> {
-        let mut object = json.write_object().await?;
-        let mut object = object
+        json.write_object()
+            .await?
             .field("accesstoken", self.accesstoken)
             .await?
             .field("username", self.username)
@@ -54,21 +56,21 @@ impl ToJson for SignInRequest<'_> {
             .field("password", self.password_md5)
             .await?
             .field("appkey", self.appkey)
-            .await?;
-        if let Some(cid) = self.cid {
-            object = object.field("cid", f64::from(cid)).await?;
-        }
-        if let Some(auth_secret) = self.auth_secret {
-            object = object.field("auth_secret", auth_secret).await?;
-        }
-        object.finish().await
+            .await?
+            .field_opt("cid", self.cid.map(f64::from))
+            .await?
+            .field_opt("auth_secret", self.auth_secret)
+            .await?
+            .finish()
+            .await
     }
 }
 
 /// `auth/signin` response
 #[derive(Debug, Default)]
 pub struct SignInResponse {
-    // pub httpstatuscode: u16,
+    /// Status code embedded in the response body (if any)
+    pub httpstatuscode: Option<u16>,
 }
 
 impl FromJsonObject for SignInResponse {
@@ -76,11 +78,15 @@ impl FromJsonObject for SignInResponse {
 
     async fn read_next<R: BufRead>(
         &mut self,
-        _key: String,
+        key: String,
         json: &mut json::Reader<R>,
         _context: &Self::Context<'_>,
     ) -> Result<(), json::Error<R::Error>> {
-        json.skip_any().await
+        match &*key {
+            "httpstatuscode" => self.httpstatuscode = Some(json.read_any().await?.try_into()?),
+            _ => json.skip_any().await?,
+        }
+        Ok(())
     }
 }
 
@@ -115,7 +121,8 @@ pub struct UserInformationResponse {
     // pub cid: u32, // undocumented
     pub roles: Vec<String>,
     pub email: String,
-    // pub httpstatuscode: u16,
+    /// Status code embedded in the response body (if any)
+    pub httpstatuscode: Option<u16>,
 }
 
 impl FromJsonObject for UserInformationResponse {
@@ -135,6 +142,7 @@ impl FromJsonObject for UserInformationResponse {
             "status" => self.status = json.read().await?,
             "roles" => self.roles = json.read().await?,
             "email" => self.email = json.read().await?,
+            "httpstatuscode" => self.httpstatuscode = Some(json.read_any().await?.try_into()?),
             _ => json.skip_any().await?,
         }
         Ok(())
@@ -0,0 +1,87 @@
+use super::AccessToken;
+use crate::json::{self, FromJsonObject, ToJson};
+use alloc::string::{String, ToString};
+use embedded_io_async::{BufRead, Write};
+
+/// `user/getbalance` request
+#[derive(Debug)]
+pub struct UserBalanceRequest<'a> {
+    pub accesstoken: &'a AccessToken,
+    pub memberid: u32,
+}
+
+impl ToJson for UserBalanceRequest<'_> {
+    async fn to_json<W: Write>(
+        &self,
+        json: &mut json::Writer<W>,
+    ) -> Result<(), json::Error<W::Error>> {
+        json.write_object()
+            .await?
+            .field("accesstoken", self.accesstoken)
+            .await?
+            .field("memberid", self.memberid.to_string())
+            .await?
+            .finish()
+            .await
+    }
+}
+
+/// `user/getbalance` response
+#[derive(Debug, Default)]
+pub struct UserBalanceResponse {
+    // pub memberid: u32,
+    /// Account balance in EUR
+    pub balance: f32,
+    /// Status code embedded in the response body (if any)
+    pub httpstatuscode: Option<u16>,
+}
+
+impl FromJsonObject for UserBalanceResponse {
+    type Context<'ctx> = ();
+
+    async fn read_next<R: BufRead>(
+        &mut self,
+        key: String,
+        json: &mut json::Reader<R>,
+        _context: &Self::Context<'_>,
+    ) -> Result<(), json::Error<R::Error>> {
+        match &*key {
+            "balance" => self.balance = json.read_any().await?.try_into()?,
+            "httpstatuscode" => self.httpstatuscode = Some(json.read_any().await?.try_into()?),
+            _ => json.skip_any().await?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read(s: &str) -> UserBalanceResponse {
+        json::Reader::new(s.as_bytes()).read().await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn read_response() {
+        let response =
+            read(r#"{"memberid": "12345", "balance": "-12.50", "httpstatuscode": 200}"#).await;
+        assert!((response.balance - -12.5).abs() < f32::EPSILON);
+    }
+
+    #[async_std::test]
+    async fn read_response_number() {
+        let response = read(r#"{"memberid": 12345, "balance": 3.2, "httpstatuscode": 200}"#).await;
+        assert!((response.balance - 3.2).abs() < f32::EPSILON);
+        assert_eq!(response.httpstatuscode, Some(200));
+    }
+
+    #[async_std::test]
+    async fn read_error_status() {
+        let response =
+            read(r#"{"error": "Mitglied nicht gefunden", "httpstatuscode": "404"}"#).await;
+        assert_eq!(response.httpstatuscode, Some(404));
+        let response = read(r#"{"httpstatuscode": 500}"#).await;
+        assert_eq!(response.httpstatuscode, Some(500));
+    }
+}
@@ -11,8 +11,8 @@ pub struct SaleAddRequest<'a> {
     pub articleid: &'a str,
     pub amount: f32,
     pub memberid: Option<u32>,
-    // pub callsign: Option<&'a str>,
-    // pub salestax: Option<f32>,
+    pub callsign: Option<&'a str>,
+    pub salestax: Option<f32>,
     pub totalprice: Option<f32>,
     // pub counter: Option<f32>,
     pub comment: Option<&'a str>,
@@ -25,8 +25,8 @@ impl ToJson for SaleAddRequest<'_> {
         &self,
         json: &mut json::Writer<W>,
     ) -> Result<(), json::Error<W::Error>> {
-        let mut object = json.write_object().await?;
-        let mut object = object
+        json.write_object()
+            .await?
             .field("accesstoken", self.accesstoken)
             .await?
             .field("bookingdate", self.bookingdate)
@@ -34,17 +34,19 @@ impl ToJson for SaleAddRequest<'_> {
             .field("articleid", self.articleid)
             .await?
             .field("amount", self.amount)
-            .await?;
-        if let Some(memberid) = self.memberid {
-            object = object.field("memberid", memberid.to_string()).await?;
-        }
-        if let Some(totalprice) = self.totalprice {
-            object = object.field("totalprice", totalprice.to_string()).await?;
-        }
-        if let Some(comment) = self.comment {
-            object = object.field("comment", comment).await?;
-        }
-        object.finish().await
+            .await?
+            .field_opt("memberid", self.memberid.map(|id| id.to_string()))
+            .await?
+            .field_opt("callsign", self.callsign)
+            .await?
+            .field_opt("salestax", self.salestax.map(|tax| tax.to_string()))
+            .await?
+            .field_opt("totalprice", self.totalprice.map(|price| price.to_string()))
+            .await?
+            .field_opt("comment", self.comment)
+            .await?
+            .finish()
+            .await
     }
 }
 
@@ -66,7 +68,8 @@ pub struct SaleAddResponse {
     // pub supid: u32,
     // pub articleid: String,
     // pub caid2: u32,
-    // pub httpstatuscode: u16,
+    /// Status code embedded in the response body (if any)
+    pub httpstatuscode: Option<u16>,
 }
 
 impl FromJsonObject for SaleAddResponse {
@@ -74,10 +77,76 @@ impl FromJsonObject for SaleAddResponse {
 
     async fn read_next<R: BufRead>(
         &mut self,
-        _key: String,
+        key: String,
         json: &mut json::Reader<R>,
         _context: &Self::Context<'_>,
     ) -> Result<(), json::Error<R::Error>> {
-        json.skip_any().await
+        match &*key {
+            "httpstatuscode" => self.httpstatuscode = Some(json.read_any().await?.try_into()?),
+            _ => json.skip_any().await?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    async fn to_json<T: ToJson>(value: &T) -> String {
+        let mut json = json::Writer::new(Vec::new());
+        json.write(value).await.unwrap();
+        String::from_utf8(json.into_inner()).unwrap()
+    }
+
+    fn request(accesstoken: &AccessToken) -> SaleAddRequest<'_> {
+        SaleAddRequest {
+            accesstoken,
+            bookingdate: "2025-01-15",
+            articleid: "1234",
+            amount: 2.0,
+            memberid: Some(12345),
+            callsign: None,
+            salestax: None,
+            totalprice: Some(3.5),
+            comment: None,
+        }
+    }
+
+    #[async_std::test]
+    async fn write_request() {
+        let accesstoken = AccessToken::from("secret");
+        assert_eq!(
+            to_json(&request(&accesstoken)).await,
+            r#"{"accesstoken": "secret", "bookingdate": "2025-01-15", "articleid": "1234", "amount": 2, "memberid": "12345", "totalprice": "3.5"}"#
+        );
+    }
+
+    #[async_std::test]
+    async fn write_request_with_salestax_and_callsign() {
+        let accesstoken = AccessToken::from("secret");
+        let request = SaleAddRequest {
+            callsign: Some("D-EABC"),
+            salestax: Some(19.0),
+            ..request(&accesstoken)
+        };
+        assert_eq!(
+            to_json(&request).await,
+            r#"{"accesstoken": "secret", "bookingdate": "2025-01-15", "articleid": "1234", "amount": 2, "memberid": "12345", "callsign": "D-EABC", "salestax": "19", "totalprice": "3.5"}"#
+        );
+    }
+
+    #[async_std::test]
+    async fn write_request_with_comment() {
+        let accesstoken = AccessToken::from("secret");
+        let request = SaleAddRequest {
+            comment: Some("Touch-n-Drink 0123456789ab"),
+            ..request(&accesstoken)
+        };
+        assert_eq!(
+            to_json(&request).await,
+            r#"{"accesstoken": "secret", "bookingdate": "2025-01-15", "articleid": "1234", "amount": 2, "memberid": "12345", "totalprice": "3.5", "comment": "Touch-n-Drink 0123456789ab"}"#
+        );
     }
 }
@@ -1,4 +1,4 @@
-use super::AccessToken;
+use super::{AccessToken, Page};
 use crate::json::{self, FromJsonObject, ToJson};
 use crate::nfc::Uid;
 use crate::user::Users;
@@ -13,6 +13,7 @@ use log::warn;
 #[derive(Debug)]
 pub struct UserListRequest<'a> {
     pub accesstoken: &'a AccessToken,
+    pub page: Option<Page>,
 }
 
 impl ToJson for UserListRequest<'_> {
@@ -24,24 +25,41 @@ impl ToJson for UserListRequest<'_> {
             .await?
             .field("accesstoken", self.accesstoken)
             .await?
+            .field_opt("offset", self.page.map(|page| page.offset))
+            .await?
+            .field_opt("limit", self.page.map(|page| page.limit))
+            .await?
             .finish()
             .await
     }
 }
 
+/// Context for reading a `user/list` response
+pub struct UserListContext<'a> {
+    /// User lookup table to store users to
+    pub users: RefCell<&'a mut Users>,
+    /// Label prefix of user keys that are NFC uids
+    pub key_prefix: &'a str,
+    /// Label prefix of user keys that are PINs
+    pub pin_key_prefix: &'a str,
+    /// Called for every parsed user (e.g. to report progress)
+    pub on_user: &'a dyn Fn(),
+}
+
 /// `user/list` response
 #[derive(Debug, Default)]
 pub struct UserListResponse {
     // pub *: User,
-    // pub httpstatuscode: u16,
     //
-    /// Total number of users
+    /// Status code embedded in the response body (if any)
+    pub httpstatuscode: Option<u16>,
+    /// Number of users in this response
     pub total_users: u32,
 }
 
 impl FromJsonObject for UserListResponse {
-    // Mutable reference to user lookup table
-    type Context<'ctx> = RefCell<&'ctx mut Users>;
+    // Mutable reference to user lookup table and NFC key label prefix
+    type Context<'ctx> = UserListContext<'ctx>;
 
     async fn read_next<R: BufRead>(
         &mut self,
@@ -51,15 +69,17 @@ impl FromJsonObject for UserListResponse {
     ) -> Result<(), json::Error<R::Error>> {
         match u32::from_str(&key) {
             Ok(_key) => {
-                let user: User = json.read().await?;
+                let user: User = json.read_object_with_context(context).await?;
                 self.total_users += 1;
+                (context.on_user)();
                 if !user.is_retired() {
-                    let keys = user.keys_named_with_prefix("NFC Transponder");
-                    if !keys.is_empty() {
+                    let keys = user.keys_named_with_prefix(context.key_prefix);
+                    let pin = user.pin(context.pin_key_prefix);
+                    if !keys.is_empty() || pin.is_some() {
                         // Instead of reading all users to a vector, this deserialization stores
                         // users directly to the user lookup table and only keeps the users needed,
                         // which heavily reduces memory consumption.
-                        let mut users = context.borrow_mut();
+                        let mut users = context.users.borrow_mut();
                         for key in keys {
                             if let Ok(uid) = Uid::from_str(key) {
                                 users.update_uid(uid, user.memberid);
@@ -70,10 +90,16 @@ impl FromJsonObject for UserListResponse {
                                 );
                             }
                         }
+                        if let Some(pin) = pin {
+                            users.update_pin(user.memberid, String::from(pin));
+                        }
                         users.update_user(user.memberid, user.firstname);
                     }
                 }
             }
+            Err(_) if key == "httpstatuscode" => {
+                self.httpstatuscode = Some(json.read_any().await?.try_into()?);
+            }
             _ => json.skip_any().await?,
         }
         Ok(())
@@ -149,20 +175,32 @@ struct User {
 }
 
 impl FromJsonObject for User {
-    type Context<'ctx> = ();
+    // Same context as the user list, for the NFC key label prefix
+    type Context<'ctx> = UserListContext<'ctx>;
 
     async fn read_next<R: BufRead>(
         &mut self,
         key: String,
         json: &mut json::Reader<R>,
-        _context: &Self::Context<'_>,
+        context: &Self::Context<'_>,
     ) -> Result<(), json::Error<R::Error>> {
         match &*key {
             "firstname" => self.firstname = json.read().await?,
             "lastname" => self.lastname = json.read().await?,
             "memberid" => self.memberid = json.read_any().await?.try_into()?,
             "memberstatus" => self.memberstatus = json.read().await?,
-            "keymanagement" => self.keymanagement = json.read().await?,
+            "keymanagement" => {
+                // Users can have many other keys, only keep the ones that may be NFC uids or PINs
+                json.read_array_streaming(async |json| {
+                    let key: Key = json.read().await?;
+                    if key.has_prefix(context.key_prefix) || key.has_prefix(context.pin_key_prefix)
+                    {
+                        self.keymanagement.push(key);
+                    }
+                    Ok(())
+                })
+                .await?;
+            }
             _ => json.skip_any().await?,
         }
         Ok(())
@@ -175,14 +213,34 @@ impl User {
         self.memberstatus.to_lowercase().contains("ausgeschieden")
     }
 
-    /// Get key numbers with the given label prefix
+    /// Get key numbers with the given label prefix (ignoring case and surrounding whitespace)
     fn keys_named_with_prefix(&self, prefix: &str) -> Vec<&str> {
         self.keymanagement
             .iter()
-            .filter(|key| key.title.starts_with(prefix))
+            .filter(|key| key.has_prefix(prefix))
             .map(|key| key.keyname.as_str())
             .collect()
     }
+
+    /// Get PIN from the first key with the given label prefix that holds a valid PIN (1 to 9
+    /// digits)
+    fn pin(&self, prefix: &str) -> Option<&str> {
+        self.keys_named_with_prefix(prefix)
+            .into_iter()
+            .map(str::trim)
+            .find(|pin| (1..=9).contains(&pin.len()) && pin.bytes().all(|b| b.is_ascii_digit()))
+    }
+}
+
+impl Key {
+    /// Whether the key label starts with the given prefix (ignoring case and surrounding
+    /// whitespace)
+    fn has_prefix(&self, prefix: &str) -> bool {
+        self.title
+            .trim()
+            .to_lowercase()
+            .starts_with(&prefix.trim().to_lowercase())
+    }
 }
 
 /// User keymanagement
@@ -212,3 +270,220 @@ impl FromJsonObject for Key {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use core::cell::Cell;
+
+    async fn to_json<T: ToJson>(value: &T) -> String {
+        let mut json = json::Writer::new(Vec::new());
+        json.write(value).await.unwrap();
+        String::from_utf8(json.into_inner()).unwrap()
+    }
+
+    async fn read_page(users: &mut Users, s: &str) -> UserListResponse {
+        let context = UserListContext {
+            users: RefCell::new(users),
+            key_prefix: "NFC Transponder",
+            pin_key_prefix: "Touch-n-Drink PIN",
+            on_user: &|| (),
+        };
+        json::Reader::new(s.as_bytes())
+            .read_object_with_context(&context)
+            .await
+            .unwrap()
+    }
+
+    fn user_with_keys(titles: &[&str]) -> User {
+        User {
+            keymanagement: titles
+                .iter()
+                .enumerate()
+                .map(|(i, title)| Key {
+                    title: (*title).to_string(),
+                    keyname: i.to_string(),
+                })
+                .collect(),
+            ..User::default()
+        }
+    }
+
+    #[test]
+    fn key_prefix() {
+        let user = user_with_keys(&[
+            "NFC Transponder",
+            "NFC Transponder 2",
+            "Schlüssel Clubheim",
+            "NFC",
+        ]);
+        assert_eq!(user.keys_named_with_prefix("NFC Transponder"), ["0", "1"]);
+        assert_eq!(user.keys_named_with_prefix("Schlüssel"), ["2"]);
+        assert!(user.keys_named_with_prefix("RFID").is_empty());
+    }
+
+    #[test]
+    fn key_prefix_ignores_case() {
+        let user = user_with_keys(&["nfc transponder", "NFC TRANSPONDER", "Nfc Transponder"]);
+        assert_eq!(
+            user.keys_named_with_prefix("NFC Transponder"),
+            ["0", "1", "2"]
+        );
+        assert_eq!(
+            user.keys_named_with_prefix("nfc transponder"),
+            ["0", "1", "2"]
+        );
+    }
+
+    #[test]
+    fn key_prefix_ignores_whitespace() {
+        let user = user_with_keys(&[" NFC Transponder", "NFC Transponder ", "\tNFC Transponder"]);
+        assert_eq!(
+            user.keys_named_with_prefix("NFC Transponder"),
+            ["0", "1", "2"]
+        );
+        assert_eq!(
+            user.keys_named_with_prefix(" NFC Transponder "),
+            ["0", "1", "2"]
+        );
+        // Whitespace within the label still matters
+        let user = user_with_keys(&["NFC  Transponder"]);
+        assert!(user.keys_named_with_prefix("NFC Transponder").is_empty());
+    }
+
+    #[async_std::test]
+    async fn write_request() {
+        let accesstoken = "0123456789abcdef".to_string();
+        let request = UserListRequest {
+            accesstoken: &accesstoken,
+            page: None,
+        };
+        assert_eq!(
+            to_json(&request).await,
+            r#"{"accesstoken": "0123456789abcdef"}"#
+        );
+    }
+
+    #[async_std::test]
+    async fn write_page_request() {
+        let accesstoken = "0123456789abcdef".to_string();
+        let request = UserListRequest {
+            accesstoken: &accesstoken,
+            page: Some(Page {
+                offset: 200,
+                limit: 100,
+            }),
+        };
+        assert_eq!(
+            to_json(&request).await,
+            r#"{"accesstoken": "0123456789abcdef", "offset": 200, "limit": 100}"#
+        );
+    }
+
+    #[async_std::test]
+    async fn read_user_keeps_prefixed_keys_only() {
+        let mut users = Users::new();
+        let context = UserListContext {
+            users: RefCell::new(&mut users),
+            key_prefix: "NFC Transponder",
+            pin_key_prefix: "Touch-n-Drink PIN",
+            on_user: &|| (),
+        };
+        let user: User = json::Reader::new(
+            r#"{"firstname": "Fred", "memberid": 1, "keymanagement": [{"title": "Schlüssel Clubheim", "keyname": "42"}, {"title": "NFC Transponder", "keyname": "12345678"}]}"#.as_bytes(),
+        )
+        .read_object_with_context(&context)
+        .await
+        .unwrap();
+        assert_eq!(user.keymanagement.len(), 1);
+        assert_eq!(user.keymanagement[0].keyname, "12345678");
+    }
+
+    #[test]
+    fn pin() {
+        let user = user_with_keys(&["NFC Transponder", "Touch-n-Drink PIN"]);
+        assert_eq!(user.pin("Touch-n-Drink PIN"), Some("1"));
+        let user = user_with_keys(&["NFC Transponder"]);
+        assert_eq!(user.pin("Touch-n-Drink PIN"), None);
+    }
+
+    #[test]
+    fn pin_must_be_digits() {
+        let mut user = user_with_keys(&["Touch-n-Drink PIN", "Touch-n-Drink PIN 2"]);
+        user.keymanagement[0].keyname = "12ab".to_string();
+        user.keymanagement[1].keyname = " 0815 ".to_string();
+        assert_eq!(user.pin("Touch-n-Drink PIN"), Some("0815"));
+        user.keymanagement[1].keyname = "1234567890".to_string();
+        assert_eq!(user.pin("Touch-n-Drink PIN"), None);
+    }
+
+    #[async_std::test]
+    async fn read_user_with_pin_only() {
+        let mut users = Users::new();
+        let count = users.count();
+        read_page(
+            &mut users,
+            r#"{"0": {"firstname": "Wilma", "memberid": 2, "memberstatus": "Aktiv", "keymanagement": [{"title": "Touch-n-Drink PIN", "keyname": "4711"}]}, "httpstatuscode": 200}"#,
+        )
+        .await;
+        assert_eq!(users.count(), count + 1);
+        assert_eq!(users.get(2).map(|u| u.name.as_str()), Some("Wilma"));
+        assert_eq!(users.pin(2), Some("4711"));
+    }
+
+    #[async_std::test]
+    async fn read_error_status() {
+        let mut users = Users::new();
+        let response = read_page(&mut users, r#"{"httpstatuscode": 403}"#).await;
+        assert_eq!(response.total_users, 0);
+        assert_eq!(response.httpstatuscode, Some(403));
+    }
+
+    #[async_std::test]
+    async fn merge_pages() {
+        let mut users = Users::new();
+        let count = users.count();
+
+        let response = read_page(
+            &mut users,
+            r#"{"0": {"firstname": "Fred", "memberid": 1, "memberstatus": "Aktiv", "keymanagement": [{"title": "NFC Transponder", "keyname": "12345678"}]}, "1": {"firstname": "Wilma", "memberid": 2, "memberstatus": "Aktiv", "keymanagement": []}, "httpstatuscode": 200}"#,
+        )
+        .await;
+        assert_eq!(response.total_users, 2);
+
+        let response = read_page(
+            &mut users,
+            r#"{"0": {"firstname": "Barney", "memberid": 30, "memberstatus": "Aktiv", "keymanagement": [{"title": "NFC Transponder", "keyname": "abcdef01"}]}, "httpstatuscode": 200}"#,
+        )
+        .await;
+        assert_eq!(response.total_users, 1);
+        assert_eq!(response.httpstatuscode, Some(200));
+
+        // Users with NFC keys of both pages are kept
+        assert_eq!(users.count(), count + 2);
+        assert_eq!(users.id(&Uid::Single([0x12, 0x34, 0x56, 0x78])), Some(1));
+        assert_eq!(users.id(&Uid::Single([0xab, 0xcd, 0xef, 0x01])), Some(30));
+        assert_eq!(users.get(30).map(|u| u.name.as_str()), Some("Barney"));
+    }
+
+    #[async_std::test]
+    async fn report_parsed_users() {
+        let mut users = Users::new();
+        let parsed = Cell::new(0);
+        let context = UserListContext {
+            users: RefCell::new(&mut users),
+            key_prefix: "NFC Transponder",
+            pin_key_prefix: "Touch-n-Drink PIN",
+            on_user: &|| parsed.set(parsed.get() + 1),
+        };
+        let _response: UserListResponse = json::Reader::new(
+            r#"{"0": {"firstname": "Fred", "memberid": 1, "memberstatus": "Aktiv", "keymanagement": [{"title": "NFC Transponder", "keyname": "12345678"}]}, "1": {"firstname": "Wilma", "memberid": 2, "memberstatus": "Aktiv", "keymanagement": []}, "httpstatuscode": 200}"#.as_bytes(),
+        )
+        .read_object_with_context(&context)
+        .await
+        .unwrap();
+        // Every parsed user is reported, even if not stored
+        assert_eq!(parsed.get(), 2);
+    }
+}
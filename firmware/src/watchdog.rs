@@ -0,0 +1,65 @@
+use embassy_executor::{task, Spawner};
+use embassy_time::{Duration, Timer};
+use esp_hal::rtc_cntl::Rwdt;
+use log::debug;
+
+/// Watchdog timeout. The system is reset if the watchdog isn't fed within this time.
+#[cfg(not(debug_assertions))]
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+#[cfg(debug_assertions)]
+pub const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How often to feed the watchdog. Must be well below the timeout, so that a busy executor
+/// doesn't cause a reset.
+const FEED_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Start feeding the (already enabled) RTC watchdog
+pub fn start(rwdt: Rwdt, spawner: Spawner) {
+    debug!(
+        "Watchdog: Spawning feed task, timeout {}s...",
+        TIMEOUT.as_secs()
+    );
+    spawner
+        .spawn(feed(rwdt))
+        // Panic on failure since failing to spawn a task indicates a serious error
+        .expect("Failed to spawn watchdog feed task");
+}
+
+/// Stop the watchdog, e.g. while the system sleeps and the feed task can't run
+pub fn pause() {
+    debug!("Watchdog: Pausing");
+    // The RTC watchdog is a singleton register block, so a new handle controls the same watchdog
+    // that the feed task uses
+    Rwdt.disable();
+}
+
+/// Restart the watchdog after `pause`
+pub fn resume() {
+    debug!("Watchdog: Resuming");
+    let mut rwdt = Rwdt;
+    rwdt.feed();
+    rwdt.enable();
+}
+
+/// Task for periodically feeding the watchdog. Since tasks are scheduled cooperatively, this task
+/// only gets to run if no other task blocks the executor. It doesn't depend on any other task
+/// or shared state, so it keeps running while the user interface is waiting for input. A future
+/// that spins without yielding (e.g. a stuck I2C transaction) starves it and triggers a reset.
+#[task]
+async fn feed(mut rwdt: Rwdt) {
+    loop {
+        rwdt.feed();
+        Timer::after(FEED_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn feed_interval_below_timeout() {
+        // Allow to miss at least two feeds before the watchdog resets the system
+        assert!(FEED_INTERVAL * 3 <= TIMEOUT);
+    }
+}
@@ -1,11 +1,19 @@
 use alloc::boxed::Box;
-use core::cell::Cell;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
 use core::fmt;
+use core::net::IpAddr;
 use embassy_executor::{task, Spawner};
+use embassy_futures::select::{select, Either};
 use embassy_net::dns::{self, DnsQueryType};
 use embassy_net::tcp::{self, client::TcpClientState};
 use embassy_net::{Config, DhcpConfig, IpAddress, Runner, Stack, StackResources, StaticConfigV4};
-use embassy_time::{Duration, Timer};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+use embedded_nal_async::{AddrType, Dns};
 use esp_hal::peripheral::Peripheral;
 use esp_hal::peripherals;
 use esp_hal::rng::Rng;
@@ -18,9 +26,20 @@ use esp_wifi::EspWifiTimerSource;
 use log::{debug, info, warn};
 use rand_core::RngCore;
 
-/// Delay after Wifi disconnect or connection failure before trying to reconnect
+/// Delay after Wifi disconnect or connection failure before trying to reconnect. Doubles with
+/// every further failed attempt until connected.
 const CONNECT_RETRY_DELAY: Duration = Duration::from_millis(5000);
 
+/// Max delay before trying to reconnect
+const CONNECT_RETRY_MAX_DELAY: Duration = Duration::from_secs(120);
+
+/// Max random jitter added to the reconnect delay (in percent of the delay), so that multiple
+/// devices don't retry in lockstep after an access point outage
+const CONNECT_RETRY_JITTER_PERCENT: u64 = 25;
+
+/// How often to check the controller state while waiting for it to stop
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
 /// Number of TCP sockets
 const NUM_TCP_SOCKETS: usize = 4;
 
@@ -30,8 +49,12 @@ const TX_BUFFER_SIZE: usize = 2048;
 /// Size of receive buffer (per TCP socket)
 const RX_BUFFER_SIZE: usize = 4096;
 
-/// Type of DNS socket
-pub type DnsSocket<'d> = dns::DnsSocket<'d>;
+/// Maximum number of cached DNS results
+const DNS_CACHE_SIZE: usize = 4;
+
+/// Fixed lifetime of cached DNS results. Record TTLs aren't exposed by the network stack, so every
+/// result is cached for the same time (or until Wifi disconnects).
+const DNS_CACHE_TTL: Duration = Duration::from_secs(300);
 
 /// Type of TCP client
 pub type TcpClient<'d> =
@@ -75,6 +98,127 @@ impl<T: fmt::Display> fmt::Display for DisplayList<'_, T> {
     }
 }
 
+/// Cache of DNS results, keyed by hostname
+#[derive(Debug)]
+struct DnsCache {
+    entries: Vec<DnsCacheEntry>,
+}
+
+/// Cached DNS result
+#[derive(Debug)]
+struct DnsCacheEntry {
+    host: String,
+    addr: IpAddr,
+    expires: Instant,
+}
+
+impl DnsCache {
+    /// Create empty cache
+    const fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    /// Look up cached address of given host that matches the given address type. Returns `None`
+    /// if not cached or expired at the given time.
+    fn get(&mut self, host: &str, addr_type: &AddrType, now: Instant) -> Option<IpAddr> {
+        self.entries.retain(|entry| entry.expires > now);
+        self.entries
+            .iter()
+            .find(|entry| entry.host == host && matches_addr_type(entry.addr, addr_type))
+            .map(|entry| entry.addr)
+    }
+
+    /// Cache address of given host, resolved at the given time, for the fixed cache lifetime. If
+    /// the cache is full, the entry that expires first is replaced.
+    fn insert(&mut self, host: &str, addr: IpAddr, now: Instant) {
+        let expires = now + DNS_CACHE_TTL;
+        self.entries.retain(|entry| entry.host != host);
+        if self.entries.len() >= DNS_CACHE_SIZE {
+            if let Some((i, _)) = self
+                .entries
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, entry)| entry.expires)
+            {
+                self.entries.swap_remove(i);
+            }
+        }
+        self.entries.push(DnsCacheEntry {
+            host: host.to_string(),
+            addr,
+            expires,
+        });
+    }
+
+    /// Remove all cached results
+    fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+/// Returns whether the given address matches the requested address type
+fn matches_addr_type(addr: IpAddr, addr_type: &AddrType) -> bool {
+    match addr_type {
+        AddrType::IPv4 => addr.is_ipv4(),
+        AddrType::IPv6 => addr.is_ipv6(),
+        AddrType::Either => true,
+    }
+}
+
+/// Cached DNS results. Shared with the connection task, which clears it when Wifi disconnects,
+/// since addresses may have changed when the network comes back up.
+static DNS_CACHE: Mutex<CriticalSectionRawMutex, RefCell<DnsCache>> =
+    Mutex::new(RefCell::new(DnsCache::new()));
+
+/// Remove all cached DNS results
+fn clear_dns_cache() {
+    DNS_CACHE.lock(|cache| cache.borrow_mut().clear());
+}
+
+/// Embedded-nal-async compatible DNS socket that caches resolved addresses
+pub struct DnsSocket<'d> {
+    socket: dns::DnsSocket<'d>,
+}
+
+impl<'d> DnsSocket<'d> {
+    /// Create caching DNS socket for given network stack
+    fn new(stack: Stack<'d>) -> Self {
+        Self {
+            socket: dns::DnsSocket::new(stack),
+        }
+    }
+}
+
+impl Dns for DnsSocket<'_> {
+    type Error = dns::Error;
+
+    async fn get_host_by_name(
+        &self,
+        host: &str,
+        addr_type: AddrType,
+    ) -> Result<IpAddr, Self::Error> {
+        if let Some(addr) =
+            DNS_CACHE.lock(|cache| cache.borrow_mut().get(host, &addr_type, Instant::now()))
+        {
+            debug!("Wifi: DNS cache hit {}: {}", host, addr);
+            return Ok(addr);
+        }
+        let addr = self.socket.get_host_by_name(host, addr_type).await?;
+        DNS_CACHE.lock(|cache| cache.borrow_mut().insert(host, addr, Instant::now()));
+        Ok(addr)
+    }
+
+    async fn get_host_by_address(
+        &self,
+        addr: IpAddr,
+        result: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.socket.get_host_by_address(addr, result).await
+    }
+}
+
 /// Wifi configuration display helper
 struct DisplayWifiConfig(WifiConfiguration);
 
@@ -113,6 +257,83 @@ impl fmt::Display for DisplayWifiConfig {
     }
 }
 
+/// Requested connection state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Target {
+    /// Keep connected, reconnect if disconnected
+    Connected,
+    /// Stay disconnected, but keep controller started
+    Disconnected,
+    /// Stop controller
+    Stopped,
+}
+
+/// Next action of the connection task
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    /// Start controller
+    Start,
+    /// Try to connect
+    Connect,
+    /// Connected, wait for disconnect
+    WaitForDisconnect,
+    /// Disconnect
+    Disconnect,
+    /// Stop controller
+    Stop,
+    /// Nothing to do, wait for a new requested state
+    Idle,
+}
+
+impl Target {
+    /// Next action to reach this state, given the current controller state
+    fn next_action(self, started: bool, connected: bool) -> Action {
+        match self {
+            Self::Connected if !started => Action::Start,
+            Self::Connected if connected => Action::WaitForDisconnect,
+            Self::Connected => Action::Connect,
+            Self::Disconnected if connected => Action::Disconnect,
+            Self::Stopped if started => Action::Stop,
+            Self::Disconnected | Self::Stopped => Action::Idle,
+        }
+    }
+}
+
+/// Exponential backoff with random jitter for reconnect attempts
+#[derive(Debug)]
+struct Backoff<R> {
+    rng: R,
+    delay: Duration,
+}
+
+impl<R: RngCore> Backoff<R> {
+    /// Create backoff using the given random number generator for jitter
+    fn new(rng: R) -> Self {
+        Self {
+            rng,
+            delay: CONNECT_RETRY_DELAY,
+        }
+    }
+
+    /// Delay before the next attempt. Every call doubles the delay of the following attempt
+    /// (up to the max delay).
+    fn next_delay(&mut self) -> Duration {
+        let delay = self.delay;
+        self.delay = (delay * 2).min(CONNECT_RETRY_MAX_DELAY);
+        let max_jitter = delay.as_millis() * CONNECT_RETRY_JITTER_PERCENT / 100;
+        let jitter = self.rng.next_u64() % (max_jitter + 1);
+        delay + Duration::from_millis(jitter)
+    }
+
+    /// Start over with the initial delay, e.g. after successfully connecting
+    fn reset(&mut self) {
+        self.delay = CONNECT_RETRY_DELAY;
+    }
+}
+
+/// Requested connection state, passed to the connection task
+static TARGET: Signal<CriticalSectionRawMutex, Target> = Signal::new();
+
 /// Network configuration display helper
 struct DisplayNetworkConfig(StaticConfigV4);
 
@@ -177,7 +398,7 @@ impl Wifi {
         // Spawn task for handling Wifi connection events
         debug!("Wifi: Spawning connection task...");
         spawner
-            .spawn(connection(controller))
+            .spawn(connection(controller, rng))
             // Panic on failure since failing to spawn a task indicates a serious error
             .expect("Failed to spawn Wifi connection task");
 
@@ -202,7 +423,7 @@ impl Wifi {
         let tcp_client_state = Box::new(TcpClientState::new());
         let tcp_client_state = Box::leak(tcp_client_state);
 
-        // Initialize embedded-nal-async compatible DNS socket and TCP client
+        // Initialize embedded-nal-async compatible (caching) DNS socket and TCP client
         let dns_socket = DnsSocket::new(stack);
         let tcp_client = TcpClient::new(stack, tcp_client_state);
 
@@ -274,48 +495,125 @@ impl Wifi {
         }
     }
 
+    /// Disconnect from Wifi and stop reconnecting (controller stays started)
+    #[allow(dead_code, clippy::unused_self)]
+    pub fn disconnect(&self) {
+        debug!("Wifi: Requesting disconnect");
+        TARGET.signal(Target::Disconnected);
+    }
+
+    /// Disconnect from Wifi and stop the controller, e.g. before going to sleep
+    #[allow(clippy::unused_self)]
+    pub fn stop(&self) {
+        debug!("Wifi: Requesting stop");
+        TARGET.signal(Target::Stopped);
+    }
+
+    /// Wait for the controller to be stopped after requesting `stop`
+    #[allow(clippy::unused_self)]
+    pub async fn wait_stopped(&self) {
+        while wifi::wifi_state() != WifiState::StaStopped {
+            Timer::after(STOP_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Resume connecting after `disconnect` or `stop` (starts the controller if needed)
+    #[allow(clippy::unused_self)]
+    pub fn reconnect(&self) {
+        debug!("Wifi: Requesting reconnect");
+        TARGET.signal(Target::Connected);
+    }
+
     /// Provide an embedded-nal-async compatible DNS socket
-    pub fn dns(&self) -> &'_ DnsSocket {
+    pub fn dns(&self) -> &'_ DnsSocket<'_> {
         &self.dns_socket
     }
 
     /// Provide an embedded-nal-async compatible TCP client
-    pub fn tcp(&self) -> &'_ TcpClient {
+    pub fn tcp(&self) -> &'_ TcpClient<'_> {
         &self.tcp_client
     }
 }
 
-/// Task for handling Wifi connection events
+/// Wait for the given retry delay to pass. Returns early with a new requested state if one is signaled.
+async fn retry_delay(delay: Duration) -> Option<Target> {
+    debug!("Wifi: Retrying in {}ms", delay.as_millis());
+    match select(Timer::after(delay), TARGET.wait()).await {
+        Either::First(()) => None,
+        Either::Second(target) => Some(target),
+    }
+}
+
+/// Task for handling Wifi connection events and requested connection state changes
 #[task]
-async fn connection(mut controller: WifiController<'static>) -> ! {
+async fn connection(mut controller: WifiController<'static>, rng: Rng) -> ! {
     debug!("Wifi: Start connection task");
 
+    // Requested state may have been set before the task started (e.g. stopped in demo mode)
+    let mut target = TARGET.try_take().unwrap_or(Target::Connected);
+    let mut backoff = Backoff::new(rng);
     loop {
-        // If connected, wait for disconnect
-        if wifi::wifi_state() == WifiState::StaConnected {
-            controller.wait_for_event(WifiEvent::StaDisconnected).await;
-            warn!("Wifi: Disconnected");
-            Timer::after(CONNECT_RETRY_DELAY).await;
-        }
-
-        // If needed, start controller
-        if !matches!(controller.is_started(), Ok(true)) {
-            debug!("Wifi: Starting controller...");
-            controller.start_async().await.unwrap();
-        }
-
-        // Try to connect
-        info!("Wifi: Connecting...");
-        match controller.connect_async().await {
-            Ok(()) => info!("Wifi: Connected"),
-            Err(err) => {
-                warn!(
-                    "Wifi: Failed to connect: {:?}, state {:?}",
-                    err,
-                    wifi::wifi_state()
-                );
-                Timer::after(CONNECT_RETRY_DELAY).await;
+        let started = matches!(controller.is_started(), Ok(true));
+        let connected = wifi::wifi_state() == WifiState::StaConnected;
+        let new_target = match target.next_action(started, connected) {
+            Action::Start => {
+                debug!("Wifi: Starting controller...");
+                controller.start_async().await.unwrap();
+                None
+            }
+            Action::Connect => {
+                info!("Wifi: Connecting...");
+                match controller.connect_async().await {
+                    Ok(()) => {
+                        info!("Wifi: Connected");
+                        backoff.reset();
+                        None
+                    }
+                    Err(err) => {
+                        warn!(
+                            "Wifi: Failed to connect: {:?}, state {:?}",
+                            err,
+                            wifi::wifi_state()
+                        );
+                        retry_delay(backoff.next_delay()).await
+                    }
+                }
+            }
+            Action::WaitForDisconnect => {
+                match select(
+                    controller.wait_for_event(WifiEvent::StaDisconnected),
+                    TARGET.wait(),
+                )
+                .await
+                {
+                    Either::First(()) => {
+                        warn!("Wifi: Disconnected");
+                        clear_dns_cache();
+                        retry_delay(backoff.next_delay()).await
+                    }
+                    Either::Second(target) => Some(target),
+                }
+            }
+            Action::Disconnect => {
+                info!("Wifi: Disconnecting...");
+                if let Err(err) = controller.disconnect_async().await {
+                    warn!("Wifi: Failed to disconnect: {:?}", err);
+                }
+                clear_dns_cache();
+                None
+            }
+            Action::Stop => {
+                info!("Wifi: Stopping controller...");
+                if let Err(err) = controller.stop_async().await {
+                    warn!("Wifi: Failed to stop controller: {:?}", err);
+                }
+                None
             }
+            Action::Idle => Some(TARGET.wait().await),
+        };
+        if let Some(new_target) = new_target {
+            debug!("Wifi: Requested state {:?}", new_target);
+            target = new_target;
         }
     }
 }
@@ -327,3 +625,169 @@ async fn network(mut runner: Runner<'static, WifiDevice<'static, WifiStaDevice>>
 
     runner.run().await;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::net::{Ipv4Addr, Ipv6Addr};
+
+    const ADDR: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+    #[test]
+    fn target_connected() {
+        let target = Target::Connected;
+        assert_eq!(target.next_action(false, false), Action::Start);
+        assert_eq!(target.next_action(true, false), Action::Connect);
+        assert_eq!(target.next_action(true, true), Action::WaitForDisconnect);
+    }
+
+    #[test]
+    fn target_disconnected() {
+        let target = Target::Disconnected;
+        assert_eq!(target.next_action(true, true), Action::Disconnect);
+        assert_eq!(target.next_action(true, false), Action::Idle);
+        // Doesn't start a stopped controller
+        assert_eq!(target.next_action(false, false), Action::Idle);
+    }
+
+    #[test]
+    fn target_stopped() {
+        let target = Target::Stopped;
+        assert_eq!(target.next_action(true, true), Action::Stop);
+        assert_eq!(target.next_action(true, false), Action::Stop);
+        assert_eq!(target.next_action(false, false), Action::Idle);
+    }
+
+    /// Simple deterministic random number generator (xorshift) for tests
+    struct TestRng(u64);
+
+    impl RngCore for TestRng {
+        fn next_u32(&mut self) -> u32 {
+            rand_core::impls::next_u32_via_fill(self)
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            rand_core::impls::fill_bytes_via_next(self, dest);
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    fn delays(backoff: &mut Backoff<TestRng>, count: usize) -> Vec<u64> {
+        (0..count)
+            .map(|_| backoff.next_delay().as_millis())
+            .collect()
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_max() {
+        let mut backoff = Backoff::new(TestRng(42));
+        let mut base = CONNECT_RETRY_DELAY;
+        for delay in delays(&mut backoff, 10) {
+            let max = base.as_millis() * (100 + CONNECT_RETRY_JITTER_PERCENT) / 100;
+            assert!(delay >= base.as_millis() && delay <= max);
+            base = (base * 2).min(CONNECT_RETRY_MAX_DELAY);
+        }
+        assert_eq!(base, CONNECT_RETRY_MAX_DELAY);
+    }
+
+    #[test]
+    fn backoff_jitter() {
+        // Same seed gives the same sequence, different seeds give different sequences
+        let sequence = delays(&mut Backoff::new(TestRng(42)), 8);
+        assert_eq!(delays(&mut Backoff::new(TestRng(42)), 8), sequence);
+        assert_ne!(delays(&mut Backoff::new(TestRng(7)), 8), sequence);
+        // Delays aren't exact multiples of the initial delay
+        assert!(sequence
+            .iter()
+            .any(|delay| delay % CONNECT_RETRY_DELAY.as_millis() != 0));
+    }
+
+    #[test]
+    fn backoff_reset() {
+        let mut backoff = Backoff::new(TestRng(42));
+        delays(&mut backoff, 5);
+        backoff.reset();
+        let delay = backoff.next_delay();
+        assert!(delay >= CONNECT_RETRY_DELAY && delay < CONNECT_RETRY_DELAY * 2);
+    }
+
+    fn secs(secs: u64) -> Instant {
+        Instant::from_secs(secs)
+    }
+
+    #[test]
+    fn dns_cache_hit() {
+        let mut cache = DnsCache::new();
+        assert_eq!(cache.get("example.com", &AddrType::Either, secs(0)), None);
+        cache.insert("example.com", ADDR, secs(0));
+        assert_eq!(
+            cache.get("example.com", &AddrType::Either, secs(1)),
+            Some(ADDR)
+        );
+        assert_eq!(
+            cache.get("example.com", &AddrType::IPv4, secs(1)),
+            Some(ADDR)
+        );
+        assert_eq!(cache.get("example.com", &AddrType::IPv6, secs(1)), None);
+        assert_eq!(cache.get("example.org", &AddrType::Either, secs(1)), None);
+    }
+
+    #[test]
+    fn dns_cache_expiry() {
+        let mut cache = DnsCache::new();
+        cache.insert("example.com", ADDR, secs(100));
+        assert_eq!(
+            cache.get("example.com", &AddrType::Either, secs(399)),
+            Some(ADDR)
+        );
+        assert_eq!(cache.get("example.com", &AddrType::Either, secs(400)), None);
+    }
+
+    #[test]
+    fn dns_cache_replace() {
+        let mut cache = DnsCache::new();
+        let addr = IpAddr::V6(Ipv6Addr::LOCALHOST);
+        cache.insert("example.com", ADDR, secs(0));
+        cache.insert("example.com", addr, secs(1));
+        assert_eq!(
+            cache.get("example.com", &AddrType::Either, secs(2)),
+            Some(addr)
+        );
+        assert_eq!(cache.entries.len(), 1);
+    }
+
+    #[test]
+    fn dns_cache_full() {
+        let mut cache = DnsCache::new();
+        for i in 0..=DNS_CACHE_SIZE {
+            let host = alloc::format!("{i}.example");
+            cache.insert(&host, ADDR, secs(i as u64));
+        }
+        assert_eq!(cache.entries.len(), DNS_CACHE_SIZE);
+        // Entry expiring first was replaced
+        assert_eq!(cache.get("0.example", &AddrType::Either, secs(10)), None);
+        assert_eq!(
+            cache.get("1.example", &AddrType::Either, secs(10)),
+            Some(ADDR)
+        );
+    }
+
+    #[test]
+    fn dns_cache_clear() {
+        let mut cache = DnsCache::new();
+        cache.insert("example.com", ADDR, secs(0));
+        cache.clear();
+        assert_eq!(cache.get("example.com", &AddrType::Either, secs(1)), None);
+    }
+}